@@ -0,0 +1,328 @@
+//! Backing off from repositories that keep failing.
+//!
+//! Each transport keeps a history of failed updates for all its
+//! repositories. If a repository could not be updated, the next update is
+//! only attempted after a delay which doubles with every consecutive
+//! failure up to a configured maximum. Until then, the data already stored
+//! for the repository is used as is.
+//!
+//! The failure histories are kept in a file in the cache directory so they
+//! survive restarts.
+
+use std::{cmp, fs, io};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use log::{error, warn};
+use crate::config::Config;
+use crate::error::Failed;
+use crate::metrics::BackoffMetrics;
+use crate::utils::binio::{Compose, Parse, ParseError};
+use crate::utils::sync::Mutex;
+
+
+//------------ Backoff -------------------------------------------------------
+
+/// The failure histories of the repositories of a transport.
+#[derive(Debug)]
+pub struct Backoff {
+    /// The path of the file the histories are kept in.
+    path: PathBuf,
+
+    /// The delay after the first failure.
+    ///
+    /// If this is `None`, updates are never skipped.
+    initial: Option<Duration>,
+
+    /// The maximum delay.
+    max: Duration,
+
+    /// The histories keyed by the repository identifier.
+    histories: Mutex<HashMap<String, FailureHistory>>,
+}
+
+impl Backoff {
+    /// The version of the file format.
+    const VERSION: u8 = 0;
+
+    /// Creates a new value for the transport with the given name.
+    ///
+    /// Loads the stored histories unless the config asks for a fresh
+    /// start in which case they are deleted.
+    pub fn new(config: &Config, name: &str) -> Result<Self, Failed> {
+        let path = config.cache_dir.join(format!("{}.backoff", name));
+        if config.fresh {
+            if let Err(err) = fs::remove_file(&path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    error!(
+                        "Failed to delete backoff state {}: {}",
+                        path.display(), err
+                    );
+                    return Err(Failed)
+                }
+            }
+        }
+        let histories = match fs::read(&path) {
+            Ok(data) => {
+                match Self::parse(&mut data.as_slice()) {
+                    Ok(histories) => histories,
+                    Err(err) => {
+                        warn!(
+                            "Ignoring broken backoff state {}: {}",
+                            path.display(), err
+                        );
+                        HashMap::new()
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                HashMap::new()
+            }
+            Err(err) => {
+                warn!(
+                    "Ignoring unreadable backoff state {}: {}",
+                    path.display(), err
+                );
+                HashMap::new()
+            }
+        };
+        Ok(Backoff {
+            path,
+            initial: config.repository_backoff,
+            max: config.repository_backoff_max,
+            histories: Mutex::new(histories),
+        })
+    }
+
+    /// Checks whether the update of a repository should be skipped.
+    ///
+    /// Returns the backoff metrics for the repository if so or `None` if
+    /// the update should go ahead.
+    pub fn check(&self, key: &str) -> Option<BackoffMetrics> {
+        self.initial?;
+        let histories = self.histories.lock();
+        let history = histories.get(key)?;
+        if history.next_attempt? > Utc::now().timestamp() {
+            Some(history.metrics(true))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Records a successful update of a repository.
+    pub fn success(&self, key: &str) -> BackoffMetrics {
+        let mut histories = self.histories.lock();
+        let history = histories.entry(key.into()).or_default();
+        history.failures = 0;
+        history.last_success = Some(Utc::now().timestamp());
+        history.next_attempt = None;
+        history.metrics(false)
+    }
+
+    /// Records a failed update of a repository.
+    pub fn failure(&self, key: &str) -> BackoffMetrics {
+        let mut histories = self.histories.lock();
+        let history = histories.entry(key.into()).or_default();
+        let now = Utc::now().timestamp();
+        history.failures = history.failures.saturating_add(1);
+        history.last_failure = Some(now);
+        history.next_attempt = self.initial.map(|initial| {
+            let delay = initial.checked_mul(
+                1u32.checked_shl(history.failures - 1).unwrap_or(u32::MAX)
+            ).unwrap_or(self.max);
+            now.saturating_add(
+                cmp::min(delay, self.max).as_secs().try_into()
+                    .unwrap_or(i64::MAX)
+            )
+        });
+        history.metrics(false)
+    }
+
+    /// Drops the histories of all repositories not accepted by `keep`.
+    pub fn retain(&self, mut keep: impl FnMut(&str) -> bool) {
+        self.histories.lock().retain(|key, _| keep(key))
+    }
+
+    /// Writes the histories to disk.
+    ///
+    /// Since the histories are merely an optimization, failing to write
+    /// them only results in a warning.
+    pub fn write(&self) {
+        let mut data = Vec::new();
+        if let Err(err) = self.compose(&mut data) {
+            warn!(
+                "Failed to encode backoff state {}: {}",
+                self.path.display(), err
+            );
+            return
+        }
+        let tmp_path = self.path.with_extension("backoff.tmp");
+        if let Err(err) = fs::write(&tmp_path, &data).and_then(|_| {
+            fs::rename(&tmp_path, &self.path)
+        }) {
+            warn!(
+                "Failed to write backoff state {}: {}",
+                self.path.display(), err
+            );
+        }
+    }
+
+    /// Parses the histories from a reader.
+    fn parse(
+        reader: &mut impl io::Read
+    ) -> Result<HashMap<String, FailureHistory>, ParseError> {
+        let version = u8::parse(reader)?;
+        if version != Self::VERSION {
+            return Err(ParseError::format(
+                format!("unexpected version {}", version)
+            ))
+        }
+        let len = u64::parse(reader)?;
+        let mut res = HashMap::new();
+        for _ in 0..len {
+            let key = String::from_utf8(
+                Bytes::parse(reader)?.to_vec()
+            ).map_err(|_| ParseError::format("invalid repository key"))?;
+            res.insert(key, FailureHistory::parse(reader)?);
+        }
+        Ok(res)
+    }
+
+    /// Composes the histories into a writer.
+    fn compose(&self, writer: &mut impl io::Write) -> Result<(), io::Error> {
+        let histories = self.histories.lock();
+        Self::VERSION.compose(writer)?;
+        (histories.len() as u64).compose(writer)?;
+        for (key, history) in histories.iter() {
+            Bytes::copy_from_slice(key.as_bytes()).compose(writer)?;
+            history.compose(writer)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ FailureHistory ------------------------------------------------
+
+/// The history of failed updates of a single repository.
+///
+/// All times are kept as Unix timestamps.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct FailureHistory {
+    /// The number of consecutive failed updates.
+    failures: u32,
+
+    /// The time of the last successful update.
+    last_success: Option<i64>,
+
+    /// The time of the last failed update.
+    last_failure: Option<i64>,
+
+    /// The earliest time of the next update attempt.
+    next_attempt: Option<i64>,
+}
+
+impl FailureHistory {
+    /// Returns the metrics for the history.
+    fn metrics(&self, skipped: bool) -> BackoffMetrics {
+        BackoffMetrics {
+            skipped,
+            failures: self.failures,
+            last_success: self.last_success.and_then(Self::time),
+            last_failure: self.last_failure.and_then(Self::time),
+            next_attempt: self.next_attempt.and_then(Self::time),
+        }
+    }
+
+    /// Converts a timestamp into a time.
+    fn time(ts: i64) -> Option<DateTime<Utc>> {
+        Utc.timestamp_opt(ts, 0).single()
+    }
+}
+
+impl<R: io::Read> Parse<R> for FailureHistory {
+    fn parse(source: &mut R) -> Result<Self, ParseError> {
+        Ok(FailureHistory {
+            failures: Parse::parse(source)?,
+            last_success: Parse::parse(source)?,
+            last_failure: Parse::parse(source)?,
+            next_attempt: Parse::parse(source)?,
+        })
+    }
+}
+
+impl<W: io::Write> Compose<W> for FailureHistory {
+    fn compose(&self, target: &mut W) -> Result<(), io::Error> {
+        self.failures.compose(target)?;
+        self.last_success.compose(target)?;
+        self.last_failure.compose(target)?;
+        self.next_attempt.compose(target)
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn backoff(initial: Option<u64>) -> Backoff {
+        Backoff {
+            path: PathBuf::new(),
+            initial: initial.map(Duration::from_secs),
+            max: Duration::from_secs(1000),
+            histories: Default::default(),
+        }
+    }
+
+    #[test]
+    fn delays() {
+        let backoff = backoff(Some(100));
+        let now = Utc::now().timestamp();
+        let delay = |metrics: BackoffMetrics| {
+            metrics.next_attempt.unwrap().timestamp() - now
+        };
+        assert!((100..102).contains(&delay(backoff.failure("a"))));
+        assert!((200..202).contains(&delay(backoff.failure("a"))));
+        assert!((400..402).contains(&delay(backoff.failure("a"))));
+        assert!((800..802).contains(&delay(backoff.failure("a"))));
+        assert!((1000..1002).contains(&delay(backoff.failure("a"))));
+        for _ in 0..40 {
+            backoff.failure("a");
+        }
+        assert!((1000..1002).contains(&delay(backoff.failure("a"))));
+        assert!(backoff.check("a").unwrap().skipped);
+        assert!(backoff.check("b").is_none());
+
+        let metrics = backoff.success("a");
+        assert_eq!(metrics.failures, 0);
+        assert!(metrics.next_attempt.is_none());
+        assert!(backoff.check("a").is_none());
+    }
+
+    #[test]
+    fn disabled() {
+        let backoff = backoff(None);
+        let metrics = backoff.failure("a");
+        assert_eq!(metrics.failures, 1);
+        assert!(metrics.next_attempt.is_none());
+        assert!(backoff.check("a").is_none());
+    }
+
+    #[test]
+    fn compose_parse() {
+        let backoff = backoff(Some(100));
+        backoff.failure("rsync://example.com/module/");
+        backoff.success("https://example.com/notification.xml");
+        let mut data = Vec::new();
+        backoff.compose(&mut data).unwrap();
+        assert_eq!(
+            Backoff::parse(&mut data.as_slice()).unwrap(),
+            *backoff.histories.lock()
+        );
+    }
+}
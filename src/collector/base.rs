@@ -9,11 +9,13 @@ use bytes::Bytes;
 use log::info;
 use rpki::repository::tal::TalUri;
 use rpki::uri;
-use crate::config::{Config, FallbackPolicy};
+use crate::config::{Config, ErikPolicy, FallbackPolicy, ValidationPolicy};
 use crate::error::{Failed, Fatal, RunFailed};
 use crate::metrics::Metrics;
 use crate::engine::CaCert;
-use super::{rrdp, rsync};
+use super::{erik, offline, rrdp, rsync};
+use super::rewrite::UriRewrites;
+use super::throttle::Bandwidth;
 
 
 //------------ Collector -----------------------------------------------------
@@ -40,8 +42,21 @@ pub struct Collector {
     /// If this is `None`, use of rsync has been disabled entirely.
     rsync: Option<rsync::Collector>,
 
+    /// The collector for Erik synchronisation.
+    ///
+    /// If this is `None`, no Erik relay has been configured.
+    erik: Option<erik::Collector>,
+
     /// The policy for falling back from RRDP to rsync.
     rrdp_fallback: FallbackPolicy,
+
+    /// The offline source replacing all transports.
+    ///
+    /// If this is `Some(_)`, `rrdp`, `rsync`, and `erik` are `None`.
+    offline: Option<offline::Collector>,
+
+    /// The URI rewrite table shared with the transports.
+    rewrites: Arc<UriRewrites>,
 }
 
 impl Collector {
@@ -54,6 +69,7 @@ impl Collector {
     pub fn init(config: &Config) -> Result<(), Failed> {
         rrdp::Collector::init(config)?;
         rsync::Collector::init(config)?;
+        erik::Collector::init(config)?;
         Ok(())
     }
 
@@ -64,10 +80,29 @@ impl Collector {
         config: &Config,
     ) -> Result<Self, Failed> {
         Self::init(config)?;
+        if let Some(offline) = offline::Collector::new(config)? {
+            return Ok(Collector {
+                rrdp: None,
+                rsync: None,
+                erik: None,
+                rrdp_fallback: config.rrdp_fallback,
+                offline: Some(offline),
+                rewrites: Default::default(),
+            })
+        }
+        let bandwidth = Arc::new(Bandwidth::new(config));
+        let rewrites = Arc::new(UriRewrites::new(config)?);
         Ok(Collector {
-            rrdp: rrdp::Collector::new(config)?,
-            rsync: rsync::Collector::new(config)?,
+            rrdp: rrdp::Collector::new(
+                config, bandwidth.clone(), rewrites.clone()
+            )?,
+            rsync: rsync::Collector::new(
+                config, bandwidth.clone(), rewrites.clone()
+            )?,
+            erik: erik::Collector::new(config, bandwidth)?,
             rrdp_fallback: config.rrdp_fallback,
+            offline: None,
+            rewrites,
         })
     }
 
@@ -78,6 +113,7 @@ impl Collector {
     pub fn ignite(&mut self) -> Result<(), Failed> {
         self.rrdp.as_mut().map_or(Ok(()), rrdp::Collector::ignite)?;
         self.rsync.as_mut().map_or(Ok(()), rsync::Collector::ignite)?;
+        self.erik.as_mut().map_or(Ok(()), erik::Collector::ignite)?;
         Ok(())
     }
 
@@ -107,6 +143,18 @@ impl Collector {
         }
         Ok(())
     }
+
+    /// Returns the state of all RRDP repositories of the collector.
+    ///
+    /// If RRDP is disabled, the list will be empty.
+    pub fn rrdp_states(
+        &self
+    ) -> Result<Vec<rrdp::RepositoryState>, Failed> {
+        match self.rrdp.as_ref() {
+            Some(rrdp) => Ok(rrdp.repository_states()?),
+            None => Ok(Vec::new())
+        }
+    }
 }
 
 
@@ -131,6 +179,12 @@ pub struct Run<'a> {
 
     /// The runner for RRDP if this transport is enabled.
     rrdp: Option<rrdp::Run<'a>>,
+
+    /// The runner for Erik if a relay has been configured.
+    erik: Option<erik::Run<'a>>,
+
+    /// The offline source if it replaces the transports.
+    offline: Option<&'a offline::Collector>,
 }
 
 impl<'a> Run<'a> {
@@ -140,6 +194,8 @@ impl<'a> Run<'a> {
             collector,
             rsync: collector.rsync.as_ref().map(|rsync| rsync.start()),
             rrdp: collector.rrdp.as_ref().map(|rrdp| rrdp.start()),
+            erik: collector.erik.as_ref().map(|erik| erik.start()),
+            offline: collector.offline.as_ref(),
         }
     }
 
@@ -156,6 +212,10 @@ impl<'a> Run<'a> {
         if let Some(rsync) = self.rsync {
             rsync.done(metrics)
         }
+        if let Some(erik) = self.erik {
+            erik.done(metrics)
+        }
+        metrics.uri_rewrites = self.collector.rewrites.take_metrics();
     }
 
     /// Loads the trust anchor certificate at the given URI.
@@ -167,7 +227,15 @@ impl<'a> Run<'a> {
     /// Trust anchor certificates referenced by a rsync URI will cause that
     /// module to be updated once, whereas those referenced via HTTPS URIs
     /// will be newly downloaded upon each call.
+    ///
+    /// With an offline source, the certificate is taken from there.
     pub fn load_ta(&self, uri: &TalUri) -> Option<Bytes> {
+        if let Some(offline) = self.offline {
+            return match *uri {
+                TalUri::Rsync(ref uri) => offline.load_file(uri),
+                TalUri::Https(ref uri) => offline.load_ta(uri),
+            }
+        }
         match *uri {
             TalUri::Rsync(ref uri) => {
                 self.rsync.as_ref().and_then(|rsync| {
@@ -186,14 +254,35 @@ impl<'a> Run<'a> {
     /// This method blocks if the repository is deemed to need updating until
     /// the update has finished.
     ///
+    /// Transports disabled by the validation policy of the CA’s TAL are
+    /// not used. Whether and when Erik is used depends on the Erik policy
+    /// for the host of the CA’s repository.
+    ///
     /// If no updated version of the repository is available, returns
     /// `Ok(None)`.
+    ///
+    /// With an offline source, all repositories are taken from there
+    /// irrespective of the transports allowed by the policy.
     pub fn repository<'s>(
-        &'s self, ca: &'s CaCert
+        &'s self, ca: &'s CaCert, policy: &ValidationPolicy,
     ) -> Result<Option<Repository<'s>>, RunFailed> {
+        if let Some(offline) = self.offline {
+            return Ok(Some(Repository::offline(offline)))
+        }
+
+        let rsync = self.rsync_for(policy);
+        let erik_host = ca.ca_repository().canonical_authority();
+
+        // See if Erik is preferred and works.
+        if let Some(erik) = self.erik_for(&erik_host, ErikPolicy::Preferred) {
+            if erik.load_host(&erik_host) {
+                return Ok(Some(Repository::erik(erik)))
+            }
+        }
+
         // See if we should and can use RRDP
         if let Some(rrdp_uri) = ca.rpki_notify() {
-            if let Some(ref rrdp) = self.rrdp {
+            if let Some(rrdp) = self.rrdp_for(policy) {
                 let (repo, first) = rrdp.load_repository(rrdp_uri)?;
                 match repo {
                     rrdp::LoadResult::Unavailable => {
@@ -229,14 +318,21 @@ impl<'a> Run<'a> {
                     }
                 }
 
-                if first && self.rsync.is_some() {
+                if first && rsync.is_some() {
                     info!("RRDP {}: Falling back to rsync.", rrdp_uri);
                 }
             }
         }
 
-        // Well, okay, then. How about rsync?
-        if let Some(ref rsync) = self.rsync {
+        // Well, okay, then. How about Erik as a fallback?
+        if let Some(erik) = self.erik_for(&erik_host, ErikPolicy::Fallback) {
+            if erik.load_host(&erik_host) {
+                return Ok(Some(Repository::erik(erik)))
+            }
+        }
+
+        // Or rsync?
+        if let Some(rsync) = rsync {
             rsync.load_module(ca.ca_repository());
             return Ok(Some(Repository::rsync(rsync)))
         }
@@ -246,22 +342,59 @@ impl<'a> Run<'a> {
     }
 
     /// Returns whether the repository for the PRKI CA has been updated.
-    pub fn was_updated(&self, ca: &CaCert) -> bool {
+    pub fn was_updated(
+        &self, ca: &CaCert, policy: &ValidationPolicy
+    ) -> bool {
+        let erik_host = ca.ca_repository().canonical_authority();
+        if let Some(erik) = self.erik_for(&erik_host, ErikPolicy::Preferred) {
+            return erik.was_updated(&erik_host)
+        }
         if let Some(rrdp_uri) = ca.rpki_notify() {
-            if let Some(ref rrdp) = self.rrdp {
+            if let Some(rrdp) = self.rrdp_for(policy) {
                 return rrdp.was_updated(rrdp_uri);
             }
         }
-        if let Some(ref rsync) = self.rsync {
+        if let Some(rsync) = self.rsync_for(policy) {
             return rsync.was_updated(ca.ca_repository());
         }
         true
     }
 
+    /// Returns the RRDP runner if RRDP may be used under `policy`.
+    fn rrdp_for(
+        &self, policy: &ValidationPolicy
+    ) -> Option<&rrdp::Run<'a>> {
+        if policy.disable_rrdp {
+            None
+        }
+        else {
+            self.rrdp.as_ref()
+        }
+    }
+
+    /// Returns the Erik runner if `host` has the given Erik policy.
+    fn erik_for(
+        &self, host: &str, policy: ErikPolicy
+    ) -> Option<&erik::Run<'a>> {
+        self.erik.as_ref().filter(|erik| erik.policy(host) == policy)
+    }
+
+    /// Returns the rsync runner if rsync may be used under `policy`.
+    fn rsync_for(
+        &self, policy: &ValidationPolicy
+    ) -> Option<&rsync::Run<'a>> {
+        if policy.disable_rsync {
+            None
+        }
+        else {
+            self.rsync.as_ref()
+        }
+    }
+
     /// Cleans the collector.
     ///
-    /// Any RRDP repository or rsync module not included in `retain` will
-    /// be deleted.
+    /// Any RRDP repository, rsync module, or Erik host not included in
+    /// `retain` will be deleted.
     pub fn cleanup(&self, retain: &mut Cleanup) -> Result<(), Failed> {
         if let Some(rsync) = self.rsync.as_ref() {
             rsync.cleanup(&mut retain.rsync)?;
@@ -269,6 +402,9 @@ impl<'a> Run<'a> {
         if let Some(rrdp) = self.rrdp.as_ref() {
             rrdp.cleanup(&mut retain.rrdp)?;
         }
+        if let Some(erik) = self.erik.as_ref() {
+            erik.cleanup(&mut retain.erik)?;
+        }
         Ok(())
     }
 }
@@ -292,6 +428,18 @@ enum RepoInner<'a> {
     Rsync {
         /// The rsync runner.
         rsync: &'a rsync::Run<'a>,
+    },
+
+    /// The repository is accessed via Erik.
+    Erik {
+        /// The Erik runner.
+        erik: &'a erik::Run<'a>,
+    },
+
+    /// The repository is read from an offline source.
+    Offline {
+        /// The offline collector.
+        offline: &'a offline::Collector,
     }
 }
 
@@ -308,6 +456,16 @@ impl<'a> Repository<'a> {
         )
     }
 
+    /// Creates an Erik repository.
+    fn erik(erik: &'a erik::Run<'a>) -> Self {
+        Repository(RepoInner::Erik { erik })
+    }
+
+    /// Creates a repository from an offline source.
+    fn offline(offline: &'a offline::Collector) -> Self {
+        Repository(RepoInner::Offline { offline })
+    }
+
     /// Returns whether the repository was accessed via RRDP.
     pub fn is_rrdp(&self) -> bool {
         matches!(self.0, RepoInner::Rrdp { .. })
@@ -327,6 +485,12 @@ impl<'a> Repository<'a> {
             RepoInner::Rsync { rsync } => {
                 Ok(rsync.load_file(uri))
             }
+            RepoInner::Erik { erik } => {
+                Ok(erik.load_file(uri))
+            }
+            RepoInner::Offline { offline } => {
+                Ok(offline.load_file(uri))
+            }
         }
     }
 }
@@ -342,6 +506,9 @@ pub struct Cleanup {
 
     /// The set of RRDP repositories to retain.
     rrdp: HashSet<uri::Https>,
+
+    /// The set of Erik hosts to retain.
+    erik: HashSet<String>,
 }
 
 impl Cleanup {
@@ -359,5 +526,13 @@ impl Cleanup {
     pub fn add_rsync_module(&mut self, uri: &uri::Rsync) {
         self.rsync.add_from_uri(uri);
     }
+
+    /// Registers the Erik host of a URI to be retained in cleanup.
+    pub fn add_erik_host(&mut self, uri: &uri::Rsync) {
+        let host = uri.canonical_authority();
+        if !self.erik.contains(host.as_ref()) {
+            self.erik.insert(host.into_owned());
+        }
+    }
 }
 
@@ -0,0 +1,1168 @@
+//! Local repository copy synchronized via Erik.
+//!
+//! Erik, described in draft-spaghetti-sidrops-rpki-erik-protocol, allows
+//! fetching the content of all RPKI repositories of a host from a relay
+//! via plain HTTP. The relay publishes an index for each host which lists
+//! a number of partitions by their hash. Each partition in turn lists the
+//! manifests published by the host, again by their hash. All objects,
+//! including the partitions, are fetched from the relay by their hash, so
+//! only what has changed since the last synchronisation needs to be
+//! transferred.
+//!
+//! Data is kept in the directory `erik` under the cache directory. Each
+//! host gets its own directory named after its canonical authority. In
+//! it, the partitions are kept under `partitions` using their identifier
+//! as the file name and the objects are kept under `objects` using the
+//! module name and path of their rsync URI.
+//!
+//! During a validation run, we keep track of the hosts we already have
+//! synchronised. When access to a host that has not yet been synchronised
+//! is requested, the synchronisation happens right away while other
+//! threads requesting access to the same host are blocked until it has
+//! finished.
+//!
+//! Which hosts are synchronised via Erik is determined by the configured
+//! [`ErikPolicy`] of a host. The parent module uses it to decide whether
+//! to try Erik before or after RRDP.
+
+use std::{fs, io};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use bcder::{decode, Ia5String, Mode, Oid, OctetString, Tag};
+use bcder::decode::DecodeError;
+use bytes::Bytes;
+use log::{error, info, warn};
+use reqwest::Url;
+use reqwest::blocking::{Client, ClientBuilder};
+use rpki::crypto::DigestAlgorithm;
+use rpki::repository::manifest::Manifest;
+use rpki::repository::x509::Time;
+use rpki::uri;
+use rpki::util::base64;
+use crate::config::{Config, ErikPolicy};
+use crate::error::{Failed, Fatal};
+use crate::metrics::{ErikHostMetrics, Metrics};
+use crate::utils::fatal;
+use crate::utils::sync::{Mutex, RwLock};
+use super::backoff::Backoff;
+use super::throttle::{Bandwidth, Limiter};
+
+
+//------------ Collector -----------------------------------------------------
+
+/// A local copy of repositories synchronized via Erik.
+#[derive(Debug)]
+pub struct Collector {
+    /// The base directory of the collector.
+    working_dir: PathBuf,
+
+    /// The base URI of the relay without a trailing slash.
+    relay: String,
+
+    /// The authority of the relay for limiting bandwidth.
+    relay_host: String,
+
+    /// The policy for hosts not listed in `hosts`.
+    policy: ErikPolicy,
+
+    /// The policies for individual hosts.
+    hosts: HashMap<String, ErikPolicy>,
+
+    /// The (blocking) reqwest client.
+    ///
+    /// This will be of the error variant until `ignite` has been called.
+    client: Result<Client, Option<ClientBuilder>>,
+
+    /// The timeout for requests.
+    timeout: Option<Duration>,
+
+    /// The maximum size of objects not listed with their size.
+    max_object_size: Option<u64>,
+
+    /// The failure histories of the hosts.
+    backoff: Backoff,
+
+    /// The bandwidth limits.
+    bandwidth: Arc<Bandwidth>,
+}
+
+impl Collector {
+    /// Initializes the Erik collector without creating a value.
+    ///
+    /// This function is called implicitely by [`new`][Collector::new].
+    pub fn init(config: &Config) -> Result<(), Failed> {
+        let _ = Self::create_working_dir(config)?;
+        Ok(())
+    }
+
+    /// Creates the working dir and returns its path.
+    fn create_working_dir(config: &Config) -> Result<PathBuf, Failed> {
+        let working_dir = config.cache_dir.join("erik");
+
+        if config.fresh {
+            if let Err(err) = fs::remove_dir_all(&working_dir) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    error!(
+                        "Failed to delete Erik working directory at {}: {}",
+                        working_dir.display(), err
+                    );
+                    return Err(Failed)
+                }
+            }
+        }
+
+        if let Err(err) = fs::create_dir_all(&working_dir) {
+            error!(
+                "Failed to create Erik working directory {}: {}.",
+                working_dir.display(), err
+            );
+            return Err(Failed);
+        }
+        Ok(working_dir)
+    }
+
+    /// Creates a new Erik collector.
+    ///
+    /// If no relay has been configured, returns `Ok(None)`.
+    pub fn new(
+        config: &Config, bandwidth: Arc<Bandwidth>,
+    ) -> Result<Option<Self>, Failed> {
+        let relay = match config.erik_relay.as_ref() {
+            Some(relay) => relay.trim_end_matches('/'),
+            None => return Ok(None)
+        };
+        let relay_host = match Url::parse(relay) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                match (url.host_str(), url.port()) {
+                    (Some(host), Some(port)) => format!("{}:{}", host, port),
+                    (Some(host), None) => host.to_string(),
+                    (None, _) => {
+                        error!("Invalid erik-relay '{}': no host.", relay);
+                        return Err(Failed)
+                    }
+                }
+            }
+            Ok(_) => {
+                error!(
+                    "Invalid erik-relay '{}': must be an HTTP URI.", relay
+                );
+                return Err(Failed)
+            }
+            Err(err) => {
+                error!("Invalid erik-relay '{}': {}", relay, err);
+                return Err(Failed)
+            }
+        };
+
+        // Deal with the reqwest’s TLS features by defining a creator
+        // function for the two cases.
+        #[cfg(not(feature = "native-tls"))]
+        fn create_builder() -> ClientBuilder {
+            Client::builder().use_rustls_tls()
+        }
+
+        #[cfg(feature = "native-tls")]
+        fn create_builder() -> ClientBuilder {
+            Client::builder().use_native_tls()
+        }
+
+        let mut builder = create_builder();
+        builder = builder.user_agent(&config.rrdp_user_agent);
+        builder = builder.timeout(None); // Set per request.
+        if let Some(timeout) = config.rrdp_connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(addr) = config.rrdp_local_addr {
+            builder = builder.local_address(addr)
+        }
+
+        Ok(Some(Collector {
+            working_dir: Self::create_working_dir(config)?,
+            relay: relay.into(),
+            relay_host,
+            policy: config.erik_policy,
+            hosts: config.erik_hosts.clone(),
+            client: Err(Some(builder)),
+            timeout: config.rrdp_timeout,
+            max_object_size: config.max_object_size,
+            backoff: Backoff::new(config, "erik")?,
+            bandwidth,
+        }))
+    }
+
+    /// Ignites the collector.
+    ///
+    /// This must be called after any potential fork on Unix systems
+    /// because the HTTP client spawns threads.
+    pub fn ignite(&mut self) -> Result<(), Fatal> {
+        let builder = match self.client.as_mut() {
+            Ok(_) => return Ok(()),
+            Err(builder) => match builder.take() {
+                Some(builder) => builder,
+                None => {
+                    error!(
+                        "Previously failed to initialize Erik HTTP client."
+                    );
+                    return Err(Fatal)
+                }
+            }
+        };
+        let client = match builder.build() {
+            Ok(client) => client,
+            Err(err) => {
+                error!("Failed to initialize Erik HTTP client: {}.", err);
+                return Err(Fatal)
+            }
+        };
+        self.client = Ok(client);
+        Ok(())
+    }
+
+    /// Returns the policy for using Erik with the given host.
+    pub fn policy(&self, host: &str) -> ErikPolicy {
+        self.hosts.get(host).copied().unwrap_or(self.policy)
+    }
+
+    /// Start a validation run on the collector.
+    pub fn start(&self) -> Run {
+        Run::new(self)
+    }
+
+    /// Returns a reference to the reqwest client.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if the client hasn’t been ignited yet.
+    fn client(&self) -> &Client {
+        self.client.as_ref().expect("Erik HTTP client has not been ignited")
+    }
+
+    /// Returns the directory for the given host.
+    fn host_dir(&self, host: &str) -> PathBuf {
+        self.working_dir.join(host)
+    }
+}
+
+
+//------------ Run -----------------------------------------------------------
+
+/// Using the Erik collector during a validation run.
+#[derive(Debug)]
+pub struct Run<'a> {
+    /// A reference to the underlying collector.
+    collector: &'a Collector,
+
+    /// The hosts that have been synchronised already.
+    ///
+    /// The value states whether synchronisation was successful.
+    updated: RwLock<HashMap<String, bool>>,
+
+    /// The hosts that are currently being synchronised.
+    ///
+    /// The value in the map is a mutex that is used to synchronize
+    /// competing attempts to update the host. Only the thread that has
+    /// the mutex is allowed to actually synchronise.
+    running: RwLock<HashMap<String, Arc<Mutex<()>>>>,
+
+    /// The metrics for synchronised hosts.
+    metrics: Mutex<Vec<ErikHostMetrics>>,
+}
+
+impl<'a> Run<'a> {
+    /// Creates a new runner from a collector.
+    fn new(collector: &'a Collector) -> Self {
+        Run {
+            collector,
+            updated: Default::default(),
+            running: Default::default(),
+            metrics: Default::default(),
+        }
+    }
+
+    /// Returns the policy for using Erik with the given host.
+    pub fn policy(&self, host: &str) -> ErikPolicy {
+        self.collector.policy(host)
+    }
+
+    /// Returns whether the host has been synchronised yet.
+    ///
+    /// This does not mean that synchronisation actually succeeded.
+    pub fn was_updated(&self, host: &str) -> bool {
+        self.updated.read().contains_key(host)
+    }
+
+    /// Synchronises the given host if necessary.
+    ///
+    /// If the host has not yet been synchronised during this run, may
+    /// block until synchronisation has finished.
+    ///
+    /// Returns whether the data of the host was successfully
+    /// synchronised and can be used.
+    pub fn load_host(&self, host: &str) -> bool {
+        if let Some(res) = self.updated.read().get(host) {
+            return *res
+        }
+
+        // Get a clone of the (arc-ed) mutex. Make a new one if there isn’t
+        // yet.
+        let mutex = {
+            self.running.write()
+            .entry(host.into()).or_default()
+            .clone()
+        };
+
+        // Acquire the mutex. Once we have it, see if the host is up-to-date
+        // which happens if someone else had it first.
+        let _lock = mutex.lock();
+        if let Some(res) = self.updated.read().get(host) {
+            return *res
+        }
+
+        // Check if the host has failed recently. If so, skip updating.
+        let success = if let Some(backoff) = self.collector.backoff.check(
+            host
+        ) {
+            info!(
+                "Erik {}: Skipping update after {} failed attempts.",
+                host, backoff.failures
+            );
+            self.metrics.lock().push(ErikHostMetrics {
+                host: host.into(),
+                success: false,
+                duration: Duration::ZERO,
+                requests: 0,
+                fetched: 0,
+                bytes: 0,
+                backoff: Some(backoff),
+            });
+            false
+        }
+        else {
+            let mut update = HostUpdate::new(self.collector, host);
+            let start = Instant::now();
+            let success = update.run().is_ok();
+            let backoff = if success {
+                self.collector.backoff.success(host)
+            }
+            else {
+                self.collector.backoff.failure(host)
+            };
+            self.metrics.lock().push(ErikHostMetrics {
+                host: host.into(),
+                success,
+                duration: start.elapsed(),
+                requests: update.requests,
+                fetched: update.fetched,
+                bytes: update.bytes,
+                backoff: Some(backoff),
+            });
+            success
+        };
+
+        // Remove from running.
+        self.running.write().remove(host);
+
+        // Insert into updated map no matter what.
+        self.updated.write().insert(host.into(), success);
+
+        success
+    }
+
+    /// Loads the file for the given URI.
+    ///
+    /// Does _not_ attempt to synchronise the host first. You need to
+    /// explicitely call [`load_host`][Run::load_host] for that.
+    ///
+    /// If the file is missing, returns `None`.
+    pub fn load_file(&self, uri: &uri::Rsync) -> Option<Bytes> {
+        let path = object_path(
+            &self.collector.host_dir(&uri.canonical_authority()), uri
+        );
+        match fs::read(&path) {
+            Ok(data) => Some(data.into()),
+            Err(err) => {
+                if err.kind() == io::ErrorKind::NotFound {
+                    info!("{}: not found in local Erik copy", uri);
+                }
+                else {
+                    error!(
+                        "Failed to read file '{}': {}",
+                        path.display(), err
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    /// Cleans the collector only keeping the hosts included in `retain`.
+    ///
+    /// Hosts synchronised during this run are always kept.
+    pub fn cleanup(
+        &self, retain: &mut HashSet<String>
+    ) -> Result<(), Failed> {
+        for host in self.updated.read().keys() {
+            retain.insert(host.clone());
+        }
+        self.collector.backoff.retain(|key| retain.contains(key));
+
+        for entry in fatal::read_dir(&self.collector.working_dir)? {
+            let entry = entry?;
+            let keep = match entry.file_name().to_str() {
+                Some(name) => entry.is_dir() && retain.contains(name),
+                None => false,
+            };
+            if !keep {
+                fatal::remove_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes the validation run.
+    ///
+    /// Updates `metrics` with the collector run’s metrics.
+    ///
+    /// If you are not interested in the metrics, you can simple drop the
+    /// value, instead.
+    pub fn done(self, metrics: &mut Metrics) {
+        self.collector.backoff.write();
+        metrics.erik = self.metrics.into_inner();
+    }
+}
+
+
+//------------ HostUpdate ----------------------------------------------------
+
+/// The synchronisation of a single host.
+struct HostUpdate<'a> {
+    /// The collector.
+    collector: &'a Collector,
+
+    /// The host to synchronise.
+    host: &'a str,
+
+    /// The directory for the host.
+    base: PathBuf,
+
+    /// The bandwidth limits for data received from the relay.
+    limiter: Limiter,
+
+    /// The number of requests sent.
+    requests: u64,
+
+    /// The number of objects fetched.
+    fetched: u64,
+
+    /// The number of bytes received.
+    bytes: u64,
+}
+
+impl<'a> HostUpdate<'a> {
+    /// Creates a new update for the given host.
+    fn new(collector: &'a Collector, host: &'a str) -> Self {
+        HostUpdate {
+            collector,
+            host,
+            base: collector.host_dir(host),
+            limiter: collector.bandwidth.limiter(&collector.relay_host),
+            requests: 0,
+            fetched: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Performs the synchronisation.
+    ///
+    /// Logs diagnostic information and returns an error if anything goes
+    /// wrong.
+    fn run(&mut self) -> Result<(), Failed> {
+        let index = self.fetch(
+            &format!(
+                "{}/.well-known/erik/index/{}", self.collector.relay,
+                self.host
+            ),
+            None
+        )?;
+        let index = ErikIndex::decode(index).map_err(|err| {
+            warn!("Erik {}: invalid index: {}", self.host, err);
+            Failed
+        })?;
+        if !index.scope.eq_ignore_ascii_case(self.host) {
+            warn!(
+                "Erik {}: index has wrong scope '{}'.",
+                self.host, index.scope
+            );
+            return Err(Failed)
+        }
+
+        let mut partitions = HashSet::new();
+        let mut objects = HashSet::new();
+        for item in &index.partitions {
+            let path = self.base.join("partitions").join(
+                item.identifier.to_string()
+            );
+            let data = self.load_or_fetch(&path, &item.hash, item.size)?;
+            let partition = ErikPartition::decode(data).map_err(|err| {
+                warn!(
+                    "Erik {}: invalid partition {}: {}",
+                    self.host, item.identifier, err
+                );
+                Failed
+            })?;
+            for manifest in &partition.manifests {
+                self.update_manifest(manifest, &mut objects)?;
+            }
+            partitions.insert(path);
+        }
+
+        cleanup_dir(&self.base.join("partitions"), &partitions)?;
+        cleanup_dir(&self.base.join("objects"), &objects)?;
+        Ok(())
+    }
+
+    /// Updates the manifest and all the objects it lists.
+    ///
+    /// Adds the paths of all objects to `keep`.
+    fn update_manifest(
+        &mut self, item: &ManifestRef, keep: &mut HashSet<PathBuf>,
+    ) -> Result<(), Failed> {
+        if item.location.canonical_authority() != self.host {
+            warn!(
+                "Erik {}: ignoring manifest {} for different host.",
+                self.host, item.location
+            );
+            return Ok(())
+        }
+        let base_uri = match item.location.parent() {
+            Some(uri) => uri,
+            None => {
+                warn!(
+                    "Erik {}: ignoring manifest with invalid location {}.",
+                    self.host, item.location
+                );
+                return Ok(())
+            }
+        };
+        let path = object_path(&self.base, &item.location);
+        let (data, fresh) = match self.load_existing(&path, &item.hash)? {
+            Some(data) => (data, false),
+            None => (self.fetch_object(&item.hash, Some(item.size))?, true),
+        };
+        let manifest = Manifest::decode(data.clone(), false).map_err(|err| {
+            warn!(
+                "Erik {}: invalid manifest {}: {}",
+                self.host, item.location, err
+            );
+            Failed
+        })?;
+
+        for (uri, hash) in manifest.content().iter_uris(&base_uri) {
+            let object_path = object_path(&self.base, &uri);
+            keep.insert(object_path.clone());
+
+            // If the manifest hasn’t changed, the objects are still the
+            // same, too, and we only need to check that they are present.
+            if !fresh {
+                if object_path.is_file() {
+                    continue
+                }
+            }
+            else if let Some(local) = fatal::read_existing_file(
+                &object_path
+            )? {
+                if hash.verify(&local).is_ok() {
+                    continue
+                }
+            }
+            let data = self.fetch_object(hash.as_slice(), None)?;
+            fatal::create_parent_all(&object_path)?;
+            fatal::write_file(&object_path, &data)?;
+        }
+
+        // Write the manifest last so an interrupted update will be
+        // repeated next time.
+        if fresh {
+            fatal::create_parent_all(&path)?;
+            fatal::write_file(&path, &data)?;
+        }
+        keep.insert(path);
+        Ok(())
+    }
+
+    /// Returns the content of a local file if it matches the hash.
+    fn load_existing(
+        &self, path: &Path, hash: &[u8]
+    ) -> Result<Option<Bytes>, Failed> {
+        Ok(fatal::read_existing_file(path)?.filter(|data| {
+            DigestAlgorithm::sha256().digest(data).as_ref() == hash
+        }).map(Into::into))
+    }
+
+    /// Returns the content of a local file or fetches it if necessary.
+    ///
+    /// If the file had to be fetched, it is written to `path`.
+    fn load_or_fetch(
+        &mut self, path: &Path, hash: &[u8], size: u64,
+    ) -> Result<Bytes, Failed> {
+        if let Some(data) = self.load_existing(path, hash)? {
+            return Ok(data)
+        }
+        let data = self.fetch_object(hash, Some(size))?;
+        fatal::create_parent_all(path)?;
+        fatal::write_file(path, &data)?;
+        Ok(data)
+    }
+
+    /// Fetches an object from the relay by its SHA-256 hash.
+    ///
+    /// Checks that the received object matches the hash.
+    fn fetch_object(
+        &mut self, hash: &[u8], size: Option<u64>
+    ) -> Result<Bytes, Failed> {
+        let data = self.fetch(
+            &format!(
+                "{}/.well-known/ni/sha-256/{}",
+                self.collector.relay, base64::Slurm.encode(hash)
+            ),
+            size.or(self.collector.max_object_size)
+        )?;
+        if DigestAlgorithm::sha256().digest(&data).as_ref() != hash {
+            warn!(
+                "Erik {}: object {} has wrong hash.",
+                self.host, base64::Slurm.encode(hash)
+            );
+            return Err(Failed)
+        }
+        self.fetched += 1;
+        Ok(data)
+    }
+
+    /// Fetches the resource at the given URI.
+    ///
+    /// Fails if the response is longer than `limit`.
+    fn fetch(
+        &mut self, uri: &str, limit: Option<u64>
+    ) -> Result<Bytes, Failed> {
+        let mut request = self.collector.client().get(uri);
+        if let Some(timeout) = self.collector.timeout {
+            request = request.timeout(timeout);
+        }
+        self.requests += 1;
+        let mut response = match request.send().and_then(|response| {
+            response.error_for_status()
+        }) {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("Erik {}: {}", self.host, err);
+                return Err(Failed)
+            }
+        };
+        let mut data = Vec::new();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let len = match response.read(&mut buf) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(err) => {
+                    warn!(
+                        "Erik {}: failed to read {}: {}", self.host, uri, err
+                    );
+                    return Err(Failed)
+                }
+            };
+            self.limiter.consume(len);
+            self.bytes += len as u64;
+            data.extend_from_slice(&buf[..len]);
+            if let Some(limit) = limit {
+                if data.len() as u64 > limit {
+                    warn!(
+                        "Erik {}: {} exceeds size limit of {} bytes.",
+                        self.host, uri, limit
+                    );
+                    return Err(Failed)
+                }
+            }
+        }
+        Ok(data.into())
+    }
+}
+
+
+//------------ ErikIndex -----------------------------------------------------
+
+/// The index of all partitions of a host.
+///
+/// ```text
+/// ErikIndex ::= SEQUENCE {
+///     version        [0] INTEGER DEFAULT 0,
+///     indexScope     IA5String,
+///     indexTime      GeneralizedTime,
+///     hashAlg        DigestAlgorithmIdentifier,
+///     partitionList  SEQUENCE SIZE (1..256) OF PartitionRef }
+/// ```
+#[derive(Clone, Debug)]
+struct ErikIndex {
+    /// The host the index is for.
+    scope: String,
+
+    /// The partitions.
+    partitions: Vec<PartitionRef>,
+}
+
+impl ErikIndex {
+    /// Decodes the index from its DER encoding.
+    fn decode(data: Bytes) -> Result<Self, DecodeError<Infallible>> {
+        Mode::Der.decode(data, |cons| {
+            cons.take_sequence(|cons| {
+                cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                    cons.skip_u8_if(0)
+                })?;
+                let scope = Ia5String::take_from(cons)?;
+                Time::take_from(cons)?; // indexTime
+                DigestAlgorithm::take_from(cons)?;
+                let partitions = cons.take_sequence(|cons| {
+                    let mut res = Vec::new();
+                    while let Some(item) = PartitionRef::take_opt_from(
+                        cons
+                    )? {
+                        res.push(item)
+                    }
+                    Ok(res)
+                })?;
+                Ok(ErikIndex { scope: scope.to_string(), partitions })
+            })
+        })
+    }
+}
+
+
+//------------ PartitionRef --------------------------------------------------
+
+/// A reference to a partition in the index.
+///
+/// ```text
+/// PartitionRef ::= SEQUENCE {
+///     identifier     INTEGER (0..255),
+///     hash           Digest,
+///     size           INTEGER (1..MAX) }
+/// ```
+#[derive(Clone, Debug)]
+struct PartitionRef {
+    /// The identifier of the partition.
+    identifier: u8,
+
+    /// The SHA-256 hash of the partition.
+    hash: Bytes,
+
+    /// The size of the partition in bytes.
+    size: u64,
+}
+
+impl PartitionRef {
+    /// Takes an optional reference from the beginning of a constructed value.
+    fn take_opt_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Option<Self>, DecodeError<S::Error>> {
+        cons.take_opt_sequence(|cons| {
+            Ok(PartitionRef {
+                identifier: cons.take_u8()?,
+                hash: OctetString::take_from(cons)?.into_bytes(),
+                size: cons.take_u64()?,
+            })
+        })
+    }
+}
+
+
+//------------ ErikPartition -------------------------------------------------
+
+/// A partition listing manifests of a host.
+///
+/// ```text
+/// ErikPartition ::= SEQUENCE {
+///     version        [0] INTEGER DEFAULT 0,
+///     partitionTime  GeneralizedTime,
+///     hashAlg        DigestAlgorithmIdentifier,
+///     manifestList   SEQUENCE SIZE (0..MAX) OF ManifestRef }
+/// ```
+#[derive(Clone, Debug)]
+struct ErikPartition {
+    /// The manifests.
+    manifests: Vec<ManifestRef>,
+}
+
+impl ErikPartition {
+    /// Decodes the partition from its DER encoding.
+    fn decode(data: Bytes) -> Result<Self, DecodeError<Infallible>> {
+        Mode::Der.decode(data, |cons| {
+            cons.take_sequence(|cons| {
+                cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                    cons.skip_u8_if(0)
+                })?;
+                Time::take_from(cons)?; // partitionTime
+                DigestAlgorithm::take_from(cons)?;
+                let manifests = cons.take_sequence(|cons| {
+                    let mut res = Vec::new();
+                    while let Some(item) = ManifestRef::take_opt_from(cons)? {
+                        res.push(item)
+                    }
+                    Ok(res)
+                })?;
+                Ok(ErikPartition { manifests })
+            })
+        })
+    }
+}
+
+
+//------------ ManifestRef ---------------------------------------------------
+
+/// A reference to a manifest in a partition.
+///
+/// ```text
+/// ManifestRef ::= SEQUENCE {
+///     hash           Digest,
+///     size           INTEGER (1..MAX),
+///     aki            KeyIdentifier,
+///     manifestNumber INTEGER (0..MAX),
+///     thisUpdate     GeneralizedTime,
+///     location       SEQUENCE SIZE (1..MAX) OF AccessDescription }
+/// ```
+///
+/// Only the first rsync URI of the location is used.
+#[derive(Clone, Debug)]
+struct ManifestRef {
+    /// The SHA-256 hash of the manifest.
+    hash: Bytes,
+
+    /// The size of the manifest in bytes.
+    size: u64,
+
+    /// The rsync URI of the manifest.
+    location: uri::Rsync,
+}
+
+impl ManifestRef {
+    /// Takes an optional reference from the beginning of a constructed value.
+    fn take_opt_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Option<Self>, DecodeError<S::Error>> {
+        cons.take_opt_sequence(|cons| {
+            let hash = OctetString::take_from(cons)?.into_bytes();
+            let size = cons.take_u64()?;
+            OctetString::take_from(cons)?; // aki
+            bcder::Unsigned::take_from(cons)?; // manifestNumber
+            Time::take_from(cons)?; // thisUpdate
+            let location = cons.take_sequence(|cons| {
+                let mut res = None;
+                while let Some(uri) = cons.take_opt_sequence(|cons| {
+                    Oid::take_from(cons)?;
+                    cons.take_value_if(Tag::CTX_6, Ia5String::from_content)
+                })? {
+                    if res.is_none() {
+                        res = uri::Rsync::from_bytes(uri.into_bytes()).ok();
+                    }
+                }
+                res.ok_or_else(|| cons.content_err("missing rsync URI"))
+            })?;
+            Ok(ManifestRef { hash, size, location })
+        })
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the path for an object within a host directory.
+fn object_path(base: &Path, uri: &uri::Rsync) -> PathBuf {
+    let mut res = base.join("objects");
+    res.push(uri.module_name());
+    res.push(uri.path());
+    res
+}
+
+/// Removes all files under `dir` not included in `keep`.
+///
+/// Also removes directories that end up empty. Returns whether the
+/// directory itself should be kept.
+fn cleanup_dir(dir: &Path, keep: &HashSet<PathBuf>) -> Result<bool, Failed> {
+    let entries = match fatal::read_existing_dir(dir)? {
+        Some(entries) => entries,
+        None => return Ok(false)
+    };
+    let mut keep_dir = false;
+    for entry in entries {
+        let entry = entry?;
+        if entry.is_dir() {
+            if cleanup_dir(entry.path(), keep)? {
+                keep_dir = true;
+            }
+            else {
+                fatal::remove_dir_all(entry.path())?;
+            }
+        }
+        else if keep.contains(entry.path()) {
+            keep_dir = true;
+        }
+        else {
+            fatal::remove_file(entry.path())?;
+        }
+    }
+    Ok(keep_dir)
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::str::FromStr;
+    use std::thread;
+    use bcder::encode;
+    use bcder::encode::{PrimitiveContent, Values};
+
+    const HOST: &str = "rpki.example.net";
+    const MFT_URI: &str = "rsync://rpki.example.net/repo/ta.mft";
+
+    /// A stand-in for an Erik relay serving fixed resources.
+    struct Relay {
+        addr: SocketAddr,
+        resources: Arc<Mutex<HashMap<String, Bytes>>>,
+    }
+
+    impl Relay {
+        fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let resources = Arc::new(
+                Mutex::new(HashMap::<String, Bytes>::new())
+            );
+            let served = resources.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+                    let mut reader = BufReader::new(
+                        stream.try_clone().unwrap()
+                    );
+                    let mut line = String::new();
+                    let _ = reader.read_line(&mut line);
+                    loop {
+                        let mut header = String::new();
+                        match reader.read_line(&mut header) {
+                            Ok(len) if len > 2 => { }
+                            _ => break
+                        }
+                    }
+                    let path = line.split_whitespace().nth(1).unwrap_or("");
+                    let _ = match served.lock().get(path) {
+                        Some(body) => {
+                            let _ = write!(
+                                stream,
+                                "HTTP/1.1 200 OK\r\n\
+                                 Content-Length: {}\r\n\
+                                 Connection: close\r\n\r\n",
+                                body.len()
+                            );
+                            stream.write_all(body)
+                        }
+                        None => {
+                            stream.write_all(
+                                b"HTTP/1.1 404 Not Found\r\n\
+                                  Content-Length: 0\r\n\
+                                  Connection: close\r\n\r\n"
+                            )
+                        }
+                    };
+                }
+            });
+            Relay { addr, resources }
+        }
+
+        fn set(&self, path: String, data: impl Into<Bytes>) {
+            self.resources.lock().insert(path, data.into());
+        }
+
+        fn set_object(&self, hash: &[u8], data: impl Into<Bytes>) {
+            self.set(
+                format!(
+                    "/.well-known/ni/sha-256/{}", base64::Slurm.encode(hash)
+                ),
+                data
+            )
+        }
+
+        fn collector(&self, cache_dir: &Path) -> Collector {
+            let mut config = Config::default_with_paths(
+                Default::default(), cache_dir.into()
+            );
+            config.erik_relay = Some(format!("http://{}/", self.addr));
+            config.erik_policy = ErikPolicy::Preferred;
+            let bandwidth = Arc::new(Bandwidth::new(&config));
+            let mut collector = Collector::new(
+                &config, bandwidth
+            ).unwrap().unwrap();
+            collector.ignite().unwrap();
+            collector
+        }
+    }
+
+    fn sha256(data: &[u8]) -> Bytes {
+        Bytes::copy_from_slice(
+            DigestAlgorithm::sha256().digest(data).as_ref()
+        )
+    }
+
+    fn encode_index(partition: &[u8]) -> Bytes {
+        encode::sequence((
+            OctetString::encode_slice_as(HOST, Tag::IA5_STRING),
+            Time::now().encode_generalized_time(),
+            DigestAlgorithm::sha256().encode(),
+            encode::sequence(
+                encode::sequence((
+                    0u8.encode(),
+                    OctetString::encode_slice(sha256(partition)),
+                    (partition.len() as u64).encode(),
+                ))
+            )
+        )).to_captured(Mode::Der).into_bytes()
+    }
+
+    fn encode_partition(manifest: &[u8]) -> Bytes {
+        encode::sequence((
+            Time::now().encode_generalized_time(),
+            DigestAlgorithm::sha256().encode(),
+            encode::sequence(
+                encode::sequence((
+                    OctetString::encode_slice(sha256(manifest)),
+                    (manifest.len() as u64).encode(),
+                    OctetString::encode_slice([0u8; 20]),
+                    1u8.encode(),
+                    Time::now().encode_generalized_time(),
+                    encode::sequence(
+                        encode::sequence((
+                            rpki::oid::AD_SIGNED_OBJECT.encode(),
+                            OctetString::encode_slice_as(
+                                MFT_URI, Tag::CTX_6
+                            ),
+                        ))
+                    )
+                ))
+            )
+        )).to_captured(Mode::Der).into_bytes()
+    }
+
+    /// Returns a manifest listing two objects and the objects.
+    ///
+    /// As we don’t have a signer, we take an existing manifest and replace
+    /// the hashes of the listed files. The result still decodes fine but
+    /// would fail validation which isn’t done by Erik.
+    fn manifest() -> (Bytes, Vec<(uri::Rsync, Bytes)>) {
+        let mut mft = include_bytes!("../../test/erik/ta.mft").to_vec();
+        let files = [
+            Bytes::from_static(include_bytes!("../../test/erik/ta.cer")),
+            Bytes::from_static(include_bytes!("../../test/erik/ta.crl")),
+        ];
+        let base = uri::Rsync::from_str(MFT_URI).unwrap().parent().unwrap();
+        let decoded = Manifest::decode(Bytes::from(mft.clone()), false);
+        let mut objects = Vec::new();
+        for (item, data) in decoded.unwrap().content().iter().zip(files) {
+            let (name, hash) = item.into_pair();
+            let pos = mft.windows(hash.len()).position(|window| {
+                window == hash.as_ref()
+            }).unwrap();
+            mft[pos..pos + hash.len()].copy_from_slice(&sha256(&data));
+            objects.push((base.join(&name).unwrap(), data));
+        }
+        (mft.into(), objects)
+    }
+
+    /// Sets up the relay with all the resources for `HOST`.
+    fn serve(relay: &Relay) -> Vec<(uri::Rsync, Bytes)> {
+        let (mft, mut objects) = manifest();
+        let partition = encode_partition(&mft);
+        relay.set(
+            format!("/.well-known/erik/index/{}", HOST),
+            encode_index(&partition)
+        );
+        relay.set_object(&sha256(&partition), partition);
+        relay.set_object(&sha256(&mft), mft.clone());
+        for (_, data) in &objects {
+            relay.set_object(&sha256(data), data.clone());
+        }
+        objects.push((uri::Rsync::from_str(MFT_URI).unwrap(), mft));
+        objects
+    }
+
+    #[test]
+    fn decode_index() {
+        let index = ErikIndex::decode(encode_index(b"foo")).unwrap();
+        assert_eq!(index.scope, HOST);
+        assert_eq!(index.partitions.len(), 1);
+        assert_eq!(index.partitions[0].identifier, 0);
+        assert_eq!(index.partitions[0].hash, sha256(b"foo"));
+        assert_eq!(index.partitions[0].size, 3);
+
+        let partition = ErikPartition::decode(
+            encode_partition(b"bar")
+        ).unwrap();
+        assert_eq!(partition.manifests.len(), 1);
+        assert_eq!(partition.manifests[0].hash, sha256(b"bar"));
+        assert_eq!(partition.manifests[0].size, 3);
+        assert_eq!(partition.manifests[0].location.as_str(), MFT_URI);
+    }
+
+    #[test]
+    fn synchronise() {
+        let relay = Relay::start();
+        let objects = serve(&relay);
+        let dir = tempfile::tempdir().unwrap();
+        let collector = relay.collector(dir.path());
+
+        let run = collector.start();
+        assert!(run.load_host(HOST));
+        assert!(run.was_updated(HOST));
+        for (uri, data) in &objects {
+            assert_eq!(run.load_file(uri).as_ref(), Some(data));
+        }
+        let mut metrics = Metrics::new();
+        run.done(&mut metrics);
+        assert_eq!(metrics.erik.len(), 1);
+        assert!(metrics.erik[0].success);
+        assert_eq!(metrics.erik[0].requests, 5);
+        assert_eq!(metrics.erik[0].fetched, 4);
+
+        // Nothing changed, so only the index is fetched.
+        let run = collector.start();
+        assert!(run.load_host(HOST));
+        let mut metrics = Metrics::new();
+        run.done(&mut metrics);
+        assert_eq!(metrics.erik[0].requests, 1);
+        assert_eq!(metrics.erik[0].fetched, 0);
+    }
+
+    #[test]
+    fn hash_mismatch() {
+        let relay = Relay::start();
+        let objects = serve(&relay);
+        relay.set_object(&sha256(&objects[0].1), &b"not the object"[..]);
+        let dir = tempfile::tempdir().unwrap();
+        let collector = relay.collector(dir.path());
+
+        let run = collector.start();
+        assert!(!run.load_host(HOST));
+        let mut metrics = Metrics::new();
+        run.done(&mut metrics);
+        assert!(!metrics.erik[0].success);
+    }
+}
@@ -18,16 +18,32 @@
 //! respository and subsequently provide access to the updated data via a
 //! [`Repository`] object.
 //
-//  Internally, the module is split up into three private sub-modules. The
+//  Internally, the module is split up into eight private sub-modules. The
 //  public types mentioned above live in the base module. In addition, the
 //  rsync and rrdp modules that implement those two transport protocols,
 //  mirroring the structure of the base module, i.e., they also have
-//  `Collector`, `Run`, and `Repository` types.
+//  `Collector`, `Run`, and `Repository` types. The rsync_native module
+//  provides a built-in rsync client the rsync module can use instead of
+//  the rsync command. The erik module implements synchronisation via an
+//  Erik relay as a third transport with the same structure. The backoff
+//  module keeps track of failing repositories for the transports while
+//  the throttle module limits concurrent updates and bandwidth per host.
+//  The rewrite module provides the URI rewrite table used by the
+//  transports.
+//  Finally, the offline module serves all data from a local copy instead
+//  of using the transports.
 //
 pub use self::base::{Collector, Cleanup, Run, Repository};
-pub use self::rrdp::{HttpStatus, SnapshotReason};
+pub use self::rrdp::{HttpStatus, RepositoryState, SnapshotReason};
+pub use self::rsync::RsyncCause;
 
+mod backoff;
 mod base;
+mod erik;
+mod offline;
+mod rewrite;
 mod rrdp;
 mod rsync;
+mod rsync_native;
+mod throttle;
 
@@ -0,0 +1,246 @@
+//! Local repository copy read from an offline source.
+//!
+//! The offline collector serves all RPKI objects from a frozen copy of the
+//! repositories instead of fetching them. The copy is a directory tree in
+//! rsync layout, i.e., the path of an object is its rsync URI without the
+//! scheme. This is the same layout the rsync collector uses and that the
+//! `rsync` directory produced by the `dump` command has. Trust anchor
+//! certificates referenced by HTTPS URIs are looked up in the same way
+//! using the HTTPS URI without the scheme.
+//!
+//! Instead of a directory, the source can also be a tarball of such a tree,
+//! optionally compressed with gzip. It is extracted into a temporary
+//! directory when the collector is created.
+
+use std::{fmt, io};
+use std::fs::File;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use bytes::Bytes;
+use flate2::bufread::GzDecoder;
+use log::{error, info, warn};
+use rpki::uri;
+use tempfile::TempDir;
+use crate::config::Config;
+use crate::error::Failed;
+use crate::utils::fatal;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The first bytes of a gzip-compressed file.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+
+//------------ Collector -----------------------------------------------------
+
+/// A local copy of repositories that is never updated.
+#[derive(Debug)]
+pub struct Collector {
+    /// The base directory of the tree.
+    base: PathBuf,
+
+    /// The temporary directory a tarball has been extracted to.
+    ///
+    /// We only keep this so the directory is removed when we are dropped.
+    _extracted: Option<TempDir>,
+}
+
+impl Collector {
+    /// Creates a new offline collector.
+    ///
+    /// Returns `Ok(None)` if no offline source has been configured.
+    pub fn new(config: &Config) -> Result<Option<Self>, Failed> {
+        let source = match config.offline_source.as_ref() {
+            Some(source) => source,
+            None => return Ok(None)
+        };
+        info!("Reading RPKI data from offline source {}.", source.display());
+        if source.is_dir() {
+            return Ok(Some(Collector {
+                base: Self::base_dir(source.clone()),
+                _extracted: None,
+            }))
+        }
+        let extracted = Self::extract(source, config)?;
+        Ok(Some(Collector {
+            base: Self::base_dir(extracted.path().into()),
+            _extracted: Some(extracted),
+        }))
+    }
+
+    /// Extracts a tarball into a temporary directory.
+    ///
+    /// The directory is created in the repository directory since the
+    /// tarball may well be rather large.
+    fn extract(source: &Path, config: &Config) -> Result<TempDir, Failed> {
+        let target = tempfile::Builder::new().prefix(
+            "offline-"
+        ).tempdir_in(&config.cache_dir).map_err(|err| {
+            error!(
+                "Failed to create directory for extracting offline \
+                 source {}: {}",
+                source.display(), err
+            );
+            Failed
+        })?;
+        info!(
+            "Extracting offline source {} to {}.",
+            source.display(), target.path().display()
+        );
+        let failed = |err: io::Error| {
+            error!(
+                "Failed to extract offline source {}: {}",
+                source.display(), err
+            );
+            Failed
+        };
+        let mut reader = io::BufReader::new(
+            File::open(source).map_err(failed)?
+        );
+        let res = if reader.fill_buf().map_err(failed)?.starts_with(
+            &GZIP_MAGIC
+        ) {
+            tar::Archive::new(GzDecoder::new(reader)).unpack(target.path())
+        }
+        else {
+            tar::Archive::new(reader).unpack(target.path())
+        };
+        res.map_err(failed)?;
+        Ok(target)
+    }
+
+    /// Returns the base directory of the tree at `path`.
+    ///
+    /// If `path` contains an `rsync` directory as produced by the `dump`
+    /// command, that directory is used. Otherwise `path` itself is.
+    fn base_dir(path: PathBuf) -> PathBuf {
+        let rsync = path.join("rsync");
+        if rsync.is_dir() {
+            rsync
+        }
+        else {
+            path
+        }
+    }
+
+    /// Loads the trust anchor certificate at the given HTTPS URI.
+    ///
+    /// If the file is missing or can’t be read, returns `None`.
+    pub fn load_ta(&self, uri: &uri::Https) -> Option<Bytes> {
+        let mut path = self.base.clone();
+        path.push(uri.canonical_authority().as_ref());
+        self.load_path(uri, path, uri.path())
+    }
+
+    /// Loads the file for the given rsync URI.
+    ///
+    /// If the file is missing or can’t be read, returns `None`.
+    pub fn load_file(&self, uri: &uri::Rsync) -> Option<Bytes> {
+        let mut path = self.base.clone();
+        path.push(uri.canonical_authority().as_ref());
+        path.push(uri.module_name());
+        self.load_path(uri, path, uri.path())
+    }
+
+    /// Loads the file at `uri_path` below `path` for the given URI.
+    ///
+    /// The URI path is always taken as relative to `path`. If it contains
+    /// `..` components, the file is not loaded as it could be outside the
+    /// tree.
+    fn load_path(
+        &self, uri: &impl fmt::Display, mut path: PathBuf, uri_path: &str
+    ) -> Option<Bytes> {
+        if uri_path.split('/').any(|item| item == "..") {
+            warn!(
+                "{}: path contains '..', not loading from offline source.",
+                uri
+            );
+            return None
+        }
+        path.push(uri_path.trim_start_matches('/'));
+        match fatal::read_existing_file(&path) {
+            Ok(Some(data)) => Some(data.into()),
+            Ok(None) => {
+                info!("{}: not found in offline source", uri);
+                None
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use std::str::FromStr;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use super::*;
+
+    /// Writes a tarball of a dump with an object and a TA certificate.
+    fn write_tarball(path: &Path, gzip: bool) {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [
+            ("rsync/example.test/module/some/object.roa", b"some object"),
+            ("rsync/example.test/ta/ta.cer", b"a ta cert!!"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(
+                &mut header, name, content.as_ref(),
+            ).unwrap();
+        }
+        let mut data = builder.into_inner().unwrap();
+        if gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+            encoder.write_all(&data).unwrap();
+            data = encoder.finish().unwrap();
+        }
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn extract_tarball() {
+        let _ = crate::process::Process::init(); // May be inited already.
+        let dir = tempfile::tempdir().unwrap();
+        let uri = uri::Rsync::from_str(
+            "rsync://example.test/module/some/object.roa"
+        ).unwrap();
+        let missing = uri::Rsync::from_str(
+            "rsync://example.test/module/some/other.roa"
+        ).unwrap();
+        let ta = uri::Https::from_str(
+            "https://example.test/ta/ta.cer"
+        ).unwrap();
+        let outside = uri::Https::from_str(
+            "https://example.test/../example.test/ta/ta.cer"
+        ).unwrap();
+        for gzip in [false, true] {
+            let source = dir.path().join("dump.tar");
+            write_tarball(&source, gzip);
+            let mut config = Config::default_with_paths(
+                Default::default(), dir.path().join("cache")
+            );
+            std::fs::create_dir_all(&config.cache_dir).unwrap();
+            config.offline_source = Some(source);
+            let collector = Collector::new(&config).unwrap().unwrap();
+            assert_eq!(
+                collector.load_file(&uri).unwrap().as_ref(),
+                b"some object"
+            );
+            assert!(collector.load_file(&missing).is_none());
+            assert_eq!(
+                collector.load_ta(&ta).unwrap().as_ref(),
+                b"a ta cert!!"
+            );
+            assert!(collector.load_ta(&outside).is_none());
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Rewriting the URIs data is fetched from.
+//!
+//! The URI rewrite table of the configuration maps URI prefixes to
+//! replacement prefixes. The transports apply it whenever they fetch data
+//! for a repository or trust anchor certificate, so that data can be taken
+//! from an internal mirror or, via a `file://` URI, from a local directory
+//! instead. Since the rewritten URI is only used for fetching, the original
+//! URI is kept for validation and reporting.
+//!
+//! RRDP applies the table to the URIs of all files it fetches, i.e., the
+//! notification, snapshot, and delta files. Rsync applies it to the URI of
+//! the module to be synchronised.
+
+use std::cmp;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use log::{debug, error, warn};
+use crate::config::Config;
+use crate::error::Failed;
+use crate::metrics::UriRewriteMetrics;
+
+
+//------------ UriRewrites ---------------------------------------------------
+
+/// The URI rewrite table.
+#[derive(Debug, Default)]
+pub struct UriRewrites {
+    /// The rules with the longest prefix first.
+    rules: Vec<Rule>,
+}
+
+impl UriRewrites {
+    /// Creates the rewrite table from the configuration.
+    ///
+    /// Only `https` and `rsync` URIs can be rewritten. The scheme of the
+    /// replacement must be the same as that of the prefix or `file`, and
+    /// it must be listed in the allowed schemes.
+    pub fn new(config: &Config) -> Result<Self, Failed> {
+        let mut rules = Vec::new();
+        for (from, to) in &config.uri_rewrites {
+            let from_scheme = scheme(from);
+            if !matches!(from_scheme.as_deref(), Some("https" | "rsync")) {
+                error!(
+                    "URI rewrite '{}': only https and rsync URIs can be \
+                     rewritten.",
+                    from
+                );
+                return Err(Failed)
+            }
+            let to_scheme = match scheme(to) {
+                Some(scheme) => scheme,
+                None => {
+                    error!(
+                        "URI rewrite '{}': replacement '{}' is not a URI.",
+                        from, to
+                    );
+                    return Err(Failed)
+                }
+            };
+            if !config.uri_rewrite_schemes.iter().any(|scheme| {
+                scheme.eq_ignore_ascii_case(&to_scheme)
+            }) {
+                error!(
+                    "URI rewrite '{}': scheme '{}' of replacement not \
+                     allowed via uri-rewrite-schemes.",
+                    from, to_scheme
+                );
+                return Err(Failed)
+            }
+            let target = if to_scheme == "file" {
+                Target::File(to["file://".len()..].into())
+            }
+            else if from_scheme.as_deref() == Some(to_scheme.as_str()) {
+                Target::Uri(to.clone())
+            }
+            else {
+                error!(
+                    "URI rewrite '{}': cannot rewrite to a {} URI.",
+                    from, to_scheme
+                );
+                return Err(Failed)
+            };
+            rules.push(Rule {
+                from: from.clone(),
+                to: to.clone(),
+                target,
+                count: AtomicU64::new(0),
+            });
+        }
+        rules.sort_by_key(|rule| cmp::Reverse(rule.from.len()));
+        Ok(UriRewrites { rules })
+    }
+
+    /// Rewrites a URI.
+    ///
+    /// A rule applies if its prefix is followed by a slash in the URI or
+    /// ends in one, so that a host name can only be matched in full.
+    ///
+    /// Returns `None` if no rule applies to the URI. Returns an error if
+    /// the URI would be rewritten to a path outside the directory given
+    /// by a `file` rule.
+    pub fn rewrite(&self, uri: &str) -> Option<Result<Rewritten, Failed>> {
+        let rule = self.rules.iter().find(|rule| rule.matches(uri))?;
+        rule.count.fetch_add(1, Ordering::Relaxed);
+        let tail = &uri[rule.from.len()..];
+        let res = match rule.target {
+            Target::Uri(ref prefix) => {
+                Rewritten::Uri(format!("{}{}", prefix, tail))
+            }
+            Target::File(ref prefix) => {
+                if tail.split('/').any(|item| item == "..") {
+                    warn!(
+                        "URI rewrite '{}': refusing to rewrite {} to a \
+                         path containing '..'.",
+                        rule.from, uri
+                    );
+                    return Some(Err(Failed))
+                }
+                Rewritten::File(format!("{}{}", prefix, tail).into())
+            }
+        };
+        debug!("Rewriting {} to {}.", uri, res);
+        Some(Ok(res))
+    }
+
+    /// Returns the metrics of the rules and resets the counters.
+    pub fn take_metrics(&self) -> Vec<UriRewriteMetrics> {
+        let mut res: Vec<_> = self.rules.iter().map(|rule| {
+            UriRewriteMetrics {
+                from: rule.from.clone(),
+                to: rule.to.clone(),
+                count: rule.count.swap(0, Ordering::Relaxed),
+            }
+        }).collect();
+        res.sort_by(|left, right| left.from.cmp(&right.from));
+        res
+    }
+}
+
+
+//------------ Rule ----------------------------------------------------------
+
+/// A single rule of the rewrite table.
+#[derive(Debug)]
+struct Rule {
+    /// The prefix to be replaced.
+    from: String,
+
+    /// The replacement prefix as configured.
+    to: String,
+
+    /// The replacement prefix to use.
+    target: Target,
+
+    /// The number of URIs rewritten since the metrics were last taken.
+    count: AtomicU64,
+}
+
+impl Rule {
+    /// Returns whether the rule applies to the given URI.
+    fn matches(&self, uri: &str) -> bool {
+        match uri.strip_prefix(self.from.as_str()) {
+            Some(tail) => {
+                self.from.ends_with('/')
+                    || tail.is_empty() || tail.starts_with('/')
+            }
+            None => false
+        }
+    }
+}
+
+/// The replacement prefix of a rule.
+#[derive(Debug)]
+enum Target {
+    /// The prefix of a URI to fetch instead.
+    Uri(String),
+
+    /// The prefix of a local path to read instead.
+    File(String),
+}
+
+
+//------------ Rewritten -----------------------------------------------------
+
+/// A rewritten URI.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Rewritten {
+    /// The URI to fetch instead.
+    ///
+    /// This is of the same scheme as the original URI.
+    Uri(String),
+
+    /// The local path to read instead.
+    File(PathBuf),
+}
+
+impl std::fmt::Display for Rewritten {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Rewritten::Uri(ref uri) => f.write_str(uri),
+            Rewritten::File(ref path) => {
+                write!(f, "file://{}", path.display())
+            }
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the lowercased scheme of a URI.
+fn scheme(uri: &str) -> Option<String> {
+    uri.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase())
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rewrites(
+        rules: &[(&str, &str)], schemes: &[&str]
+    ) -> Option<UriRewrites> {
+        let mut config = Config::default_with_paths(
+            Default::default(), Default::default()
+        );
+        config.uri_rewrites = rules.iter().map(|(from, to)| {
+            (String::from(*from), String::from(*to))
+        }).collect();
+        config.uri_rewrite_schemes = schemes.iter().map(|scheme| {
+            String::from(*scheme)
+        }).collect();
+        UriRewrites::new(&config).ok()
+    }
+
+    fn apply(rewrites: &UriRewrites, uri: &str) -> Option<Rewritten> {
+        rewrites.rewrite(uri).map(|res| res.ok().unwrap())
+    }
+
+    #[test]
+    fn rewrite() {
+        let rewrites = rewrites(
+            &[
+                ("https://rrdp.example.net/", "https://mirror.example.org/"),
+                ("https://rrdp.example.net/ta/", "file:///srv/ta/"),
+                ("rsync://rpki.example.net/", "rsync://mirror.example.org/"),
+            ],
+            &["https", "rsync", "file"]
+        ).unwrap();
+        assert_eq!(
+            apply(&rewrites, "https://rrdp.example.net/notification.xml"),
+            Some(Rewritten::Uri(
+                "https://mirror.example.org/notification.xml".into()
+            ))
+        );
+        assert_eq!(
+            apply(&rewrites, "https://rrdp.example.net/ta/ta.cer"),
+            Some(Rewritten::File("/srv/ta/ta.cer".into()))
+        );
+        assert_eq!(
+            apply(&rewrites, "rsync://rpki.example.net/repo/"),
+            Some(Rewritten::Uri("rsync://mirror.example.org/repo/".into()))
+        );
+        assert_eq!(
+            apply(&rewrites, "https://other.example.net/notification.xml"),
+            None
+        );
+        let metrics = rewrites.take_metrics();
+        assert_eq!(
+            metrics.iter().map(|item| item.count).collect::<Vec<_>>(),
+            [1, 1, 1]
+        );
+        assert!(rewrites.take_metrics().iter().all(|item| item.count == 0));
+    }
+
+    #[test]
+    fn schemes() {
+        // Replacement scheme must be allowed.
+        assert!(rewrites(
+            &[("https://rrdp.example.net/", "file:///srv/rrdp/")],
+            &["https"]
+        ).is_none());
+        assert!(rewrites(
+            &[("https://rrdp.example.net/", "file:///srv/rrdp/")],
+            &["FILE"]
+        ).is_some());
+
+        // Schemes cannot be mixed.
+        assert!(rewrites(
+            &[("https://rrdp.example.net/", "rsync://rpki.example.net/")],
+            &["https", "rsync"]
+        ).is_none());
+
+        // Only https and rsync can be rewritten.
+        assert!(rewrites(
+            &[("http://rrdp.example.net/", "https://rrdp.example.net/")],
+            &["http", "https"]
+        ).is_none());
+    }
+
+    #[test]
+    fn boundaries() {
+        let rewrites = rewrites(
+            &[
+                ("https://rrdp.example.net", "https://mirror.example.org"),
+                ("rsync://rpki.example.net/re", "file:///srv/rsync"),
+            ],
+            &["https", "file"]
+        ).unwrap();
+        assert_eq!(
+            apply(&rewrites, "https://rrdp.example.net/notification.xml"),
+            Some(Rewritten::Uri(
+                "https://mirror.example.org/notification.xml".into()
+            ))
+        );
+        assert_eq!(
+            apply(&rewrites, "https://rrdp.example.net.evil.org/x.xml"),
+            None
+        );
+        assert_eq!(
+            apply(&rewrites, "https://rrdp.example.net:8443/x.xml"),
+            None
+        );
+        assert_eq!(
+            apply(&rewrites, "rsync://rpki.example.net/re/"),
+            Some(Rewritten::File("/srv/rsync/".into()))
+        );
+        assert!(apply(&rewrites, "rsync://rpki.example.net/repo/").is_none());
+        assert!(matches!(
+            rewrites.rewrite("rsync://rpki.example.net/re/../etc/"),
+            Some(Err(Failed))
+        ));
+        assert_eq!(
+            apply(&rewrites, "rsync://rpki.example.net/re/a..b/"),
+            Some(Rewritten::File("/srv/rsync/a..b/".into()))
+        );
+    }
+}
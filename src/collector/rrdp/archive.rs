@@ -380,26 +380,35 @@ pub struct RepositoryState {
 
     /// Information of the deltas since in the last notificiation.
     pub delta_state: HashMap<u64, rrdp::Hash>,
+
+    /// The size in bytes of the last snapshot file received.
+    pub snapshot_size: Option<u64>,
+
+    /// The average size in bytes of the delta files received.
+    pub delta_size: Option<u64>,
 }
 
 impl RepositoryState {
     /// The current version of the data.
     ///
-    /// This is 1 since version 0 was in the main branch for quite some time.
-    const VERSION: u8 = 1;
+    /// This is 2 since version 0 was in the main branch for quite some time
+    /// and version 1 didn’t have the payload sizes yet.
+    const VERSION: u8 = 2;
 
     /// Reads the state from an IO reader.
+    ///
+    /// State of version 1 is still accepted with the payload sizes unknown.
     fn parse(reader: &mut impl io::Read) -> Result<Self, io::Error> {
         // Version number.
         let version = u8::parse(reader)?;
-        if version != Self::VERSION {
+        if version != Self::VERSION && version != 1 {
             return Err(io::Error::new(
                 io::ErrorKind::Other,
                 format!("unexpected version {}", version)
             ))
         }
 
-        Ok(RepositoryState {
+        let mut res = RepositoryState {
             rpki_notify: Parse::parse(reader)?,
             session: Parse::parse(reader)?,
             serial: Parse::parse(reader)?,
@@ -408,7 +417,14 @@ impl RepositoryState {
             last_modified_ts: Parse::parse(reader)?,
             etag: Parse::parse(reader)?,
             delta_state: Parse::parse(reader)?,
-        })
+            snapshot_size: None,
+            delta_size: None,
+        };
+        if version > 1 {
+            res.snapshot_size = Parse::parse(reader)?;
+            res.delta_size = Parse::parse(reader)?;
+        }
+        Ok(res)
     }
 
     /// Composes the encoded state.
@@ -422,6 +438,8 @@ impl RepositoryState {
         self.last_modified_ts.compose(writer)?;
         self.etag.compose(writer)?;
         self.delta_state.compose(writer)?;
+        self.snapshot_size.compose(writer)?;
+        self.delta_size.compose(writer)?;
         Ok(())
     }
 
@@ -464,6 +482,32 @@ impl RepositoryState {
     pub fn last_modified(&self) -> Option<DateTime<Utc>> {
         self.last_modified_ts.and_then(|ts| Utc.timestamp_opt(ts, 0).single())
     }
+
+    /// Returns the estimated number of bytes for fetching `count` deltas.
+    ///
+    /// Returns `None` if we haven’t received any deltas yet.
+    pub fn delta_estimate(&self, count: usize) -> Option<u64> {
+        self.delta_size.map(|size| {
+            size.saturating_mul(count.try_into().unwrap_or(u64::MAX))
+        })
+    }
+
+    /// Updates the average delta size with newly received deltas.
+    ///
+    /// The `len` bytes were received for `count` deltas. The new average
+    /// gives the older average three times the weight of the new deltas so
+    /// that a single unusual delta doesn’t change it too much.
+    pub fn update_delta_size(&mut self, count: usize, len: u64) {
+        let count = match u64::try_from(count) {
+            Ok(0) | Err(_) => return,
+            Ok(count) => count,
+        };
+        let size = len / count;
+        self.delta_size = Some(match self.delta_size {
+            Some(old) => old.saturating_mul(3).saturating_add(size) / 4,
+            None => size,
+        });
+    }
 }
 
 
@@ -554,11 +598,38 @@ mod test {
                 (18, rrdp::Hash::from_data(b"123")),
                 (19, rrdp::Hash::from_data(b"332")),
             ].iter().cloned().collect(),
+            snapshot_size: Some(1_000_000),
+            delta_size: None,
         };
         let mut buf = Vec::new();
         state.compose(&mut buf).unwrap();
         let parsed = RepositoryState::parse(&mut buf.as_slice()).unwrap();
         assert_eq!(state, parsed);
     }
+
+    #[test]
+    fn delta_size() {
+        let mut state = RepositoryState {
+            rpki_notify: uri::Https::from_str(
+                "https://foo.bar/baz"
+            ).unwrap(),
+            session: Uuid::from_u128(1),
+            serial: 12,
+            updated_ts: 0,
+            best_before_ts: 0,
+            last_modified_ts: None,
+            etag: None,
+            delta_state: HashMap::new(),
+            snapshot_size: None,
+            delta_size: None,
+        };
+        assert_eq!(state.delta_estimate(10), None);
+        state.update_delta_size(0, 1000);
+        assert_eq!(state.delta_size, None);
+        state.update_delta_size(4, 4000);
+        assert_eq!(state.delta_estimate(10), Some(10_000));
+        state.update_delta_size(1, 5000);
+        assert_eq!(state.delta_size, Some(2000));
+    }
 }
 
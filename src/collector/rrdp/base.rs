@@ -1,7 +1,9 @@
 use std::{cmp, fs, io};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Weak};
+use std::thread;
 use std::time::SystemTime;
 use bytes::Bytes;
 use log::{debug, error, info, warn};
@@ -11,17 +13,21 @@ use rpki::rrdp::{DeltaInfo, DeltaListError, NotificationFile};
 use tempfile::NamedTempFile;
 use crate::config::Config;
 use crate::error::{Fatal, RunFailed};
-use crate::metrics::{Metrics, RrdpRepositoryMetrics};
+use crate::metrics::{BackoffMetrics, Metrics, RrdpRepositoryMetrics};
 use crate::utils::fatal;
 use crate::utils::archive::{ArchiveError, OpenError};
 use crate::utils::dump::DumpRegistry;
 use crate::utils::json::JsonBuilder;
 use crate::utils::sync::{Mutex, RwLock};
 use crate::utils::uri::UriExt;
+use super::super::backoff::Backoff;
+use super::super::rewrite::UriRewrites;
+use super::super::throttle::{Bandwidth, HostSlots};
 use super::archive::{FallbackTime, RrdpArchive, RepositoryState};
 use super::http::{HttpClient, HttpStatus};
 use super::update::{
-    DeltaUpdate, Notification, SnapshotError, SnapshotReason, SnapshotUpdate
+    DeltaPrefetch, DeltaUpdate, Notification, SnapshotError, SnapshotReason,
+    SnapshotUpdate
 };
 
 
@@ -38,6 +44,12 @@ pub struct Collector {
 
     /// Various configuration options.
     config: RrdpConfig,
+
+    /// The failure histories of the repositories.
+    backoff: Backoff,
+
+    /// The slots for concurrent updates per host.
+    slots: HostSlots,
 }
 
 impl Collector {
@@ -78,14 +90,20 @@ impl Collector {
     /// Creates a new RRDP collector.
     ///
     /// Returns `Ok(None)` if RRDP was disabled.
-    pub fn new(config: &Config) -> Result<Option<Self>, Fatal> {
+    pub fn new(
+        config: &Config,
+        bandwidth: Arc<Bandwidth>,
+        rewrites: Arc<UriRewrites>,
+    ) -> Result<Option<Self>, Fatal> {
         if config.disable_rrdp {
             return Ok(None)
         }
         Ok(Some(Self {
             working_dir: Self::create_working_dir(config)?,
-            http: HttpClient::new(config)?,
+            http: HttpClient::new(config, bandwidth, rewrites)?,
             config: config.into(),
+            backoff: Backoff::new(config, "rrdp")?,
+            slots: HostSlots::new(config.rrdp_max_host_requests),
         }))
     }
 
@@ -175,6 +193,38 @@ impl Collector {
         Ok(())
     }
 
+    /// Returns the state of all repositories in the collector.
+    pub fn repository_states(&self) -> Result<Vec<RepositoryState>, Fatal> {
+        let mut res = Vec::new();
+        for entry in fatal::read_dir(&self.working_dir)? {
+            let entry = entry?;
+            if !entry.is_dir() || entry.file_name() == "tmp" {
+                continue;
+            }
+            for entry in fatal::read_dir(entry.path())? {
+                let entry = entry?;
+                if !entry.is_file() {
+                    continue
+                }
+                let state = RrdpArchive::try_open(
+                    entry.into_path().into()
+                ).and_then(|archive| {
+                    archive.map(|archive| archive.load_state()).transpose()
+                });
+                match state {
+                    Ok(Some(state)) => res.push(state),
+                    Ok(None) => { }
+                    Err(err) => {
+                        if err.is_fatal() {
+                            return Err(Fatal)
+                        }
+                    }
+                }
+            }
+        }
+        Ok(res)
+    }
+
     /// Dumps the content of an RRDP repository.
     #[allow(clippy::mutable_key_type)]
     fn dump_repository(
@@ -312,6 +362,13 @@ impl Collector {
         Ok((file, path.into()))
     }
 
+    /// Creates a temporary file that is deleted again when dropped.
+    pub(super) fn named_temp_file(&self) -> Result<NamedTempFile, io::Error> {
+        let base = self.working_dir.join("tmp");
+        fs::create_dir_all(&base)?;
+        NamedTempFile::new_in(&base)
+    }
+
     pub(super) fn http(&self) -> &HttpClient {
         &self.http
     }
@@ -360,7 +417,9 @@ impl<'a> Run<'a> {
     /// This just downloads the file. It is not cached since that is done
     /// by the store anyway.
     pub fn load_ta(&self, uri: &uri::Https) -> Option<Bytes> {
-        let mut response = match self.collector.http.response(uri, false) {
+        let mut response = match self.collector.http.response(
+            uri, false, &mut Default::default()
+        ) {
             Ok(response) => response,
             Err(_) => return None,
         };
@@ -433,10 +492,22 @@ impl<'a> Run<'a> {
             );
             (LoadResult::Unavailable, metrics)
         }
-        else {
+        else if let Some(backoff) = self.collector.backoff.check(
+            rpki_notify.as_str()
+        ) {
             RepositoryUpdate::new(
                 self.collector, rpki_notify
-            )?.try_update()?
+            )?.skip(backoff)?
+        }
+        else {
+            let slot = self.collector.slots.acquire(
+                &rpki_notify.canonical_authority()
+            );
+            let (repo, mut metrics) = RepositoryUpdate::new(
+                self.collector, rpki_notify
+            )?.try_update()?;
+            metrics.slot_wait = slot.wait();
+            (repo, metrics)
         };
 
         // Insert metrics.
@@ -463,6 +534,11 @@ impl<'a> Run<'a> {
         for uri in self.updated.read().keys() {
             retain.insert(uri.clone());
         }
+        self.collector.backoff.retain(|key| {
+            uri::Https::from_str(key).map(|uri| {
+                retain.contains(&uri)
+            }).unwrap_or(false)
+        });
 
         for entry in fatal::read_dir(&self.collector.working_dir)? {
             let entry = entry?;
@@ -576,7 +652,9 @@ impl<'a> Run<'a> {
     }
 
     pub fn done(self, metrics: &mut Metrics) {
-        metrics.rrdp = self.metrics.into_inner()
+        self.collector.backoff.write();
+        metrics.rrdp = self.metrics.into_inner();
+        metrics.rrdp_hosts = self.collector.http.take_host_metrics();
     }
 }
 
@@ -603,6 +681,11 @@ pub struct RrdpConfig {
 
     /// The maximum length of the delta list in a notification file.
     pub max_delta_list_len: usize,
+
+    /// The number of delta files to fetch concurrently.
+    ///
+    /// If this is 0, deltas are fetched one by one while being applied.
+    pub delta_prefetch: usize,
 }
 
 impl<'a> From<&'a Config> for RrdpConfig {
@@ -613,6 +696,7 @@ impl<'a> From<&'a Config> for RrdpConfig {
             max_object_size: config.max_object_size,
             max_delta_count: config.rrdp_max_delta_count,
             max_delta_list_len: config.rrdp_max_delta_list_len,
+            delta_prefetch: config.rrdp_delta_prefetch,
         }
     }
 }
@@ -737,24 +821,7 @@ impl<'a> RepositoryUpdate<'a> {
     fn try_update(
         mut self
     ) -> Result<(LoadResult<Repository>, RrdpRepositoryMetrics), RunFailed> {
-        let current = match RrdpArchive::try_open(self.path.clone()) {
-            Ok(Some(archive)) => {
-                let state = archive.load_state()?;
-                Some((archive, state))
-            }
-            Ok(None) => None,
-            Err(err) => {
-                if err.should_retry() {
-                    // RrdpArchive::try_open should already have deleted the
-                    // file, so we can happily pretend it never existed.
-                    None
-                }
-                else {
-                    return Err(err)
-                }
-            }
-        };
-
+        let current = self.open_current()?;
         let start_time = SystemTime::now();
         let is_current = match current.as_ref() {
             Some(current) => !current.1.is_expired(),
@@ -767,6 +834,14 @@ impl<'a> RepositoryUpdate<'a> {
         let is_updated = self.update(current)?;
 
         self.metrics.duration = SystemTime::now().duration_since(start_time);
+        self.metrics.backoff = Some(
+            if is_updated {
+                self.collector.backoff.success(self.rpki_notify.as_str())
+            }
+            else {
+                self.collector.backoff.failure(self.rpki_notify.as_str())
+            }
+        );
 
         let res = if is_updated {
             LoadResult::Updated(Repository::new(self.path))
@@ -792,6 +867,58 @@ impl<'a> RepositoryUpdate<'a> {
         Ok((res, self.metrics))
     }
 
+    /// Creates the repository from the current copy without updating it.
+    ///
+    /// This is used if the update is skipped because of earlier failures.
+    fn skip(
+        mut self, backoff: BackoffMetrics,
+    ) -> Result<(LoadResult<Repository>, RrdpRepositoryMetrics), RunFailed> {
+        info!(
+            "RRDP {}: Skipping update after {} failed attempts.",
+            self.rpki_notify, backoff.failures
+        );
+        self.metrics.notify_status = HttpStatus::Skipped;
+        self.metrics.backoff = Some(backoff);
+        let res = match self.open_current()? {
+            Some((_, state)) => {
+                if !state.is_expired() {
+                    LoadResult::Current
+                }
+                else if state.best_before().is_some() {
+                    LoadResult::Stale
+                }
+                else {
+                    LoadResult::Unavailable
+                }
+            }
+            None => LoadResult::Unavailable,
+        };
+        Ok((res, self.metrics))
+    }
+
+    /// Opens the current copy of the repository if there is one.
+    fn open_current(
+        &self
+    ) -> Result<Option<(RrdpArchive, RepositoryState)>, RunFailed> {
+        match RrdpArchive::try_open(self.path.clone()) {
+            Ok(Some(archive)) => {
+                let state = archive.load_state()?;
+                Ok(Some((archive, state)))
+            }
+            Ok(None) => Ok(None),
+            Err(err) => {
+                if err.should_retry() {
+                    // RrdpArchive::try_open should already have deleted the
+                    // file, so we can happily pretend it never existed.
+                    Ok(None)
+                }
+                else {
+                    Err(err)
+                }
+            }
+        }
+    }
+
     /// Performs the actual update.
     ///
     /// Returns `Ok(false)` if the update failed.
@@ -803,6 +930,7 @@ impl<'a> RepositoryUpdate<'a> {
             &self.collector.http, self.rpki_notify,
             current.as_ref().map(|x| &x.1),
             &mut self.metrics.notify_status,
+            &mut self.metrics.http,
             self.collector.config.max_delta_list_len,
         ) {
             Ok(Some(notify)) => notify,
@@ -816,6 +944,7 @@ impl<'a> RepositoryUpdate<'a> {
         self.metrics.serial = Some(notify.content().serial());
         self.metrics.session = Some(notify.content().session_id());
 
+        let delta_size = current.as_ref().and_then(|x| x.1.delta_size);
         if let Some((archive, state)) = current {
             match self.delta_update(&notify, archive, state)? {
                 None => {
@@ -829,7 +958,7 @@ impl<'a> RepositoryUpdate<'a> {
         else {
             self.metrics.snapshot_reason = Some(SnapshotReason::NewRepository);
         }
-        self.snapshot_update(&notify)
+        self.snapshot_update(&notify, delta_size)
     }
 
     /// Handle the case of a Not Modified response.
@@ -856,12 +985,14 @@ impl<'a> RepositoryUpdate<'a> {
     fn snapshot_update(
         &mut self,
         notify: &Notification,
+        delta_size: Option<u64>,
     ) -> Result<bool, RunFailed> {
         debug!("RRDP {}: updating from snapshot.", self.rpki_notify);
         let (file, path) = self.collector.temp_file()?;
         let mut archive = RrdpArchive::create_with_file(file, path.clone())?;
         if let Err(err) = SnapshotUpdate::new(
-            self.collector, &mut archive, notify, &mut self.metrics
+            self.collector, &mut archive, notify, delta_size,
+            &mut self.metrics
         ).try_update() {
             if let SnapshotError::RunFailed(err) = err {
                 debug!("RRDP {}: snapshot update failed.", self.rpki_notify);
@@ -936,7 +1067,9 @@ impl<'a> RepositoryUpdate<'a> {
             Err(reason) => return Ok(Some(reason)),
         };
 
-        if !deltas.is_empty() {
+        let start_bytes = self.metrics.payload_bytes;
+        let prefetch = self.collector.config.delta_prefetch;
+        if prefetch == 0 || deltas.len() < 2 {
             let count = deltas.len();
             for (i, info) in deltas.iter().enumerate() {
                 debug!(
@@ -956,13 +1089,24 @@ impl<'a> RepositoryUpdate<'a> {
                 }
             }
         }
+        else if let Some(reason) = self.prefetched_delta_update(
+            notify, &mut archive, deltas, prefetch
+        ) {
+            return Ok(Some(reason))
+        }
 
         // We are up-to-date now, so we can replace the state file with one
         // reflecting the notification we’ve got originally. This will update
         // the etag and last-modified data.
-        if let Err(err) = archive.update_state(
-            &notify.to_repository_state(self.collector.config.fallback_time)
-        ) {
+        let mut new_state = notify.to_repository_state(
+            self.collector.config.fallback_time
+        );
+        new_state.snapshot_size = state.snapshot_size;
+        new_state.delta_size = state.delta_size;
+        new_state.update_delta_size(
+            deltas.len(), self.metrics.payload_bytes - start_bytes
+        );
+        if let Err(err) = archive.update_state(&new_state) {
             if err.should_retry() {
                 return Ok(Some(SnapshotReason::CorruptArchive))
             }
@@ -975,12 +1119,62 @@ impl<'a> RepositoryUpdate<'a> {
         Ok(None)
     }
 
+    /// Applies the deltas while fetching them concurrently.
+    ///
+    /// Up to `prefetch` delta files are downloaded into temporary files at
+    /// the same time. They are still applied strictly in order. Returns
+    /// the reason for falling back to a snapshot if applying any of the
+    /// deltas failed.
+    fn prefetched_delta_update(
+        &mut self,
+        notify: &Notification,
+        archive: &mut RrdpArchive,
+        deltas: &[DeltaInfo],
+        prefetch: usize,
+    ) -> Option<SnapshotReason> {
+        let collector = self.collector;
+        let count = deltas.len();
+        thread::scope(|scope| {
+            let mut fetched = DeltaPrefetch::start(
+                scope, collector, deltas, prefetch
+            );
+            for (i, info) in deltas.iter().enumerate() {
+                debug!(
+                    "RRDP {}: Delta update step ({}/{}).",
+                    self.rpki_notify, i + 1, count
+                );
+                let delta = match fetched.next() {
+                    Some(delta) => delta,
+                    None => {
+                        warn!(
+                            "RRDP {}: failed to fetch delta {}.",
+                            self.rpki_notify, info.uri()
+                        );
+                        return Some(SnapshotReason::ConflictingDelta)
+                    }
+                };
+                if let Err(err) = DeltaUpdate::new(
+                    collector, archive,
+                    notify.content().session_id(),
+                    info, &mut self.metrics
+                ).try_update_prefetched(delta) {
+                    warn!(
+                        "RRDP {}: failed to process delta: {}",
+                        self.rpki_notify, err,
+                    );
+                    return Some(SnapshotReason::ConflictingDelta)
+                }
+            }
+            None
+        })
+    }
+
     /// Calculates the slice of deltas to follow for updating.
     ///
     /// Returns an empty slice if no update is necessary.
     /// Returns a non-empty slice of the sequence of deltas to be applied.
     fn calc_deltas<'b>(
-        &self,
+        &mut self,
         notify: &'b NotificationFile,
         state: &RepositoryState
     ) -> Result<&'b [DeltaInfo], SnapshotReason> {
@@ -1029,6 +1223,7 @@ impl<'a> RepositoryUpdate<'a> {
             }
         }
 
+        // We never apply more than the configured number of deltas.
         if deltas.len() > self.collector.config.max_delta_count {
             debug!(
                 "RRDP: {}: Too many delta steps required ({})",
@@ -1037,6 +1232,33 @@ impl<'a> RepositoryUpdate<'a> {
             return Err(SnapshotReason::TooManyDeltas)
         }
 
+        // If we know enough about the sizes of deltas and snapshot, we
+        // additionally pick whichever moves fewer bytes.
+        let delta_estimate = state.delta_estimate(deltas.len());
+        if let (Some(delta_estimate), Some(snapshot_size))
+            = (delta_estimate, state.snapshot_size)
+        {
+            // Only bother the server for the current size of the
+            // snapshot if our history suggests to use it.
+            let snapshot_estimate = if delta_estimate > snapshot_size {
+                self.collector.http.content_length(
+                    notify.snapshot().uri(), &mut self.metrics.http
+                ).unwrap_or(snapshot_size)
+            }
+            else {
+                snapshot_size
+            };
+            self.metrics.delta_estimate = Some(delta_estimate);
+            self.metrics.snapshot_estimate = Some(snapshot_estimate);
+            if delta_estimate > snapshot_estimate {
+                debug!(
+                    "RRDP: {}: Deltas larger than snapshot ({} vs {})",
+                    self.rpki_notify, delta_estimate, snapshot_estimate
+                );
+                return Err(SnapshotReason::LargerDeltas)
+            }
+        }
+
         Ok(deltas)
     }
 }
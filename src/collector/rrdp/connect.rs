@@ -0,0 +1,287 @@
+//! Measuring the phases of establishing HTTP connections.
+//!
+//! The reqwest client doesn’t tell how long it took to establish the
+//! connection a request was sent over. We therefore wrap its connector in
+//! a layer that measures how long establishing each connection takes. In
+//! addition, we hook into name resolution to learn the host name the
+//! connection is for and how long resolving it took. Since the resolver is
+//! called from within the connector, the two are linked through a
+//! task-local slot. The TCP connect and the TLS handshake both happen
+//! inside the connector and are measured together.
+//!
+//! Once a request has been answered over a new connection, the HTTP client
+//! claims the phases of the oldest connection established for the request’s
+//! host. Since connections to the same host are rarely established at the
+//! same time, this attributes the phases to the right request in most
+//! cases. The rest of the time until the response headers have been
+//! received is the time to first byte.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::net::ToSocketAddrs;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use tower_layer::Layer;
+use tower_service::Service;
+use crate::utils::sync::Mutex;
+
+
+//------------ ConnectPhases -------------------------------------------------
+
+/// The durations of the phases of establishing a connection.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ConnectPhases {
+    /// The time spent resolving the host name.
+    pub dns: Duration,
+
+    /// The time spent on the TCP connect and the TLS handshake.
+    pub connect: Duration,
+}
+
+
+//------------ ConnectTimes --------------------------------------------------
+
+/// The phases of connections not yet claimed by a request.
+#[derive(Debug, Default)]
+pub struct ConnectTimes {
+    /// The phases per host name, oldest first.
+    ///
+    /// Connections established without resolving a host name, e.g.,
+    /// because the host is given as an IP address, are kept under the
+    /// empty name.
+    hosts: Mutex<HashMap<String, VecDeque<ConnectPhases>>>,
+}
+
+impl ConnectTimes {
+    /// Records that a connection to `host` has been established.
+    fn connected(&self, host: String, phases: ConnectPhases) {
+        self.hosts.lock().entry(host).or_default().push_back(phases)
+    }
+
+    /// Claims the phases of the oldest connection established for `host`.
+    ///
+    /// Falls back to connections established without resolving a host
+    /// name. Returns zero durations if there are none either, which
+    /// happens when the connection was established via a proxy.
+    pub fn claim(&self, host: &str) -> ConnectPhases {
+        let mut hosts = self.hosts.lock();
+        for key in [host, ""] {
+            if let Some(phases) = hosts.get_mut(key) {
+                let res = phases.pop_front().unwrap_or_default();
+                if phases.is_empty() {
+                    hosts.remove(key);
+                }
+                return res
+            }
+        }
+        ConnectPhases::default()
+    }
+
+    /// Forgets all connections not yet claimed.
+    ///
+    /// This drops the leftovers of connections that were never used.
+    pub fn clear(&self) {
+        self.hosts.lock().clear()
+    }
+}
+
+
+//------------ Task-local Name Resolution ------------------------------------
+
+tokio::task_local! {
+    /// The host name resolved for the connection being established.
+    static RESOLVED: RefCell<Option<(String, Duration)>>;
+}
+
+
+//------------ TimingLayer ---------------------------------------------------
+
+/// A layer for the connector recording the phases of each connection.
+#[derive(Clone, Debug)]
+pub struct TimingLayer {
+    /// Where to record the phases.
+    times: Arc<ConnectTimes>,
+}
+
+impl TimingLayer {
+    /// Creates a new layer recording into `times`.
+    pub fn new(times: Arc<ConnectTimes>) -> Self {
+        TimingLayer { times }
+    }
+}
+
+impl<S> Layer<S> for TimingLayer {
+    type Service = TimingConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimingConnector { inner, times: self.times.clone() }
+    }
+}
+
+
+//------------ TimingConnector -----------------------------------------------
+
+/// A connector recording the phases of each connection it establishes.
+#[derive(Clone, Debug)]
+pub struct TimingConnector<S> {
+    /// The actual connector.
+    inner: S,
+
+    /// Where to record the phases.
+    times: Arc<ConnectTimes>,
+}
+
+impl<S, R> Service<R> for TimingConnector<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<
+        dyn Future<Output = Result<S::Response, S::Error>> + Send
+    >>;
+
+    fn poll_ready(
+        &mut self, cx: &mut Context
+    ) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let start = Instant::now();
+        let times = self.times.clone();
+        let connect = self.inner.call(req);
+        Box::pin(RESOLVED.scope(RefCell::new(None), async move {
+            let res = connect.await;
+            let elapsed = start.elapsed();
+            let (host, dns) = RESOLVED.with(|resolved| {
+                resolved.take()
+            }).unwrap_or_default();
+            times.connected(host, ConnectPhases {
+                dns, connect: elapsed.saturating_sub(dns)
+            });
+            res
+        }))
+    }
+}
+
+
+//------------ TimingResolver ------------------------------------------------
+
+/// A resolver using the system resolver that records its duration.
+///
+/// The host name and duration are recorded for the [`TimingConnector`]
+/// the resolver is called from.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimingResolver;
+
+impl Resolve for TimingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = String::from(name.as_str());
+        Box::pin(async move {
+            let start = Instant::now();
+            let addrs = tokio::task::spawn_blocking({
+                let host = host.clone();
+                move || (host.as_str(), 0).to_socket_addrs()
+            }).await;
+            let dns = start.elapsed();
+            // If we aren’t called from a timing connector, there is
+            // nothing to record.
+            let _ = RESOLVED.try_with(|resolved| {
+                *resolved.borrow_mut() = Some((host, dns))
+            });
+            Ok(Box::new(addrs??) as Addrs)
+        })
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+
+    #[test]
+    fn claim() {
+        let times = ConnectTimes::default();
+        let first = ConnectPhases {
+            dns: Duration::from_millis(20),
+            connect: Duration::from_millis(40),
+        };
+        let second = ConnectPhases {
+            dns: Duration::from_millis(30),
+            connect: Duration::from_millis(50),
+        };
+        let literal = ConnectPhases {
+            dns: Duration::ZERO,
+            connect: Duration::from_millis(60),
+        };
+
+        // Two connections to the same host are claimed oldest first.
+        times.connected("rrdp.example.net".into(), first);
+        times.connected("rrdp.example.net".into(), second);
+        assert_eq!(times.claim("rrdp.example.net"), first);
+        assert_eq!(times.claim("rrdp.example.net"), second);
+
+        // Unknown hosts and exhausted queues give nothing.
+        assert_eq!(times.claim("rrdp.example.net"), Default::default());
+        assert_eq!(times.claim("rrdp.example.org"), Default::default());
+
+        // Connections without a host name are claimed by anyone.
+        times.connected(String::new(), literal);
+        assert_eq!(times.claim("192.0.2.1"), literal);
+
+        // Clearing drops unclaimed connections.
+        times.connected("rrdp.example.net".into(), first);
+        times.clear();
+        assert_eq!(times.claim("rrdp.example.net"), Default::default());
+    }
+
+    /// A connector that resolves the host name and then takes a while.
+    #[derive(Clone)]
+    struct SlowConnector;
+
+    impl Service<&'static str> for SlowConnector {
+        type Response = ();
+        type Error = std::io::Error;
+        type Future = Pin<Box<
+            dyn Future<Output = Result<(), std::io::Error>> + Send
+        >>;
+
+        fn poll_ready(
+            &mut self, _cx: &mut Context
+        ) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, host: &'static str) -> Self::Future {
+            Box::pin(async move {
+                let name = Name::from_str(host).unwrap();
+                let _ = TimingResolver.resolve(name).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn timing_connector() {
+        let times = Arc::new(ConnectTimes::default());
+        let mut connector = TimingLayer::new(
+            times.clone()
+        ).layer(SlowConnector);
+        connector.call("localhost").await.unwrap();
+        let phases = times.claim("localhost");
+        assert!(phases.connect >= Duration::from_millis(20));
+        assert_eq!(times.claim("localhost"), Default::default());
+    }
+}
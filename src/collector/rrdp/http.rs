@@ -1,17 +1,27 @@
 use std::{fs, io};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
+use hyper_util::client::legacy::connect::HttpInfo;
 use log::{error, warn};
 use reqwest::{header, redirect};
-use reqwest::{Certificate, Proxy, StatusCode};
+use reqwest::{Certificate, Proxy, StatusCode, Url};
 use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
 use rpki::uri;
 use crate::config::Config;
 use crate::error::Fatal;
+use crate::metrics::{RrdpHostMetrics, RrdpHttpMetrics};
 use crate::utils::date::{format_http_date, parse_http_date};
+use crate::utils::sync::Mutex;
+use super::super::rewrite::{Rewritten, UriRewrites};
+use super::super::throttle::{Bandwidth, Limiter};
+use super::connect::{ConnectTimes, TimingLayer, TimingResolver};
 
 
 //------------ HttpClient ----------------------------------------------------
@@ -28,33 +38,81 @@ pub struct HttpClient {
     /// The base directory for storing copies of responses if that is enabled.
     response_dir: Option<PathBuf>,
 
+    /// The base directory of kept responses to replay if that is enabled.
+    ///
+    /// If this is present, no requests are sent at all and all responses
+    /// are taken from this directory instead.
+    replay_dir: Option<PathBuf>,
+
+    /// The number of times each URI with multiple responses was replayed.
+    replayed: Mutex<HashMap<String, usize>>,
+
     /// The timeout for requests.
     timeout: Option<Duration>,
+
+    /// The bandwidth limits for receiving responses.
+    bandwidth: Arc<Bandwidth>,
+
+    /// The connection usage per host since the metrics were last taken.
+    hosts: Mutex<HashMap<String, HostConnections>>,
+
+    /// The durations of name resolutions for new connections.
+    times: Arc<ConnectTimes>,
+
+    /// The number of redirects followed per URL of a pending request.
+    ///
+    /// This is filled by the redirect policy and drained once the client
+    /// returns. Concurrent requests for the same URL share an entry, so the
+    /// redirects may be attributed to the wrong one of them.
+    redirects: Arc<Mutex<HashMap<Url, u64>>>,
+
+    /// The URI rewrite table.
+    rewrites: Arc<UriRewrites>,
 }
 
 impl HttpClient {
     /// Creates a new, not-yet-ignited client based on the config.
-    pub fn new(config: &Config) -> Result<Self, Fatal> {
+    pub fn new(
+        config: &Config,
+        bandwidth: Arc<Bandwidth>,
+        rewrites: Arc<UriRewrites>,
+    ) -> Result<Self, Fatal> {
 
         // Deal with the reqwest’s TLS features by defining a creator
-        // function for the two cases.
+        // function for the two cases. Both use our own resolver so we can
+        // time it. It can only be set on the async builder.
         #[cfg(not(feature = "native-tls"))]
-        fn create_builder() -> ClientBuilder {
-            Client::builder().use_rustls_tls()
+        fn create_builder(times: &Arc<ConnectTimes>) -> ClientBuilder {
+            reqwest::Client::builder()
+                .use_rustls_tls()
+                .dns_resolver(Arc::new(TimingResolver))
+                .connector_layer(TimingLayer::new(times.clone()))
+                .into()
         }
 
         #[cfg(feature = "native-tls")]
-        fn create_builder() -> ClientBuilder {
-            Client::builder().use_native_tls()
+        fn create_builder(times: &Arc<ConnectTimes>) -> ClientBuilder {
+            reqwest::Client::builder()
+                .use_native_tls()
+                .dns_resolver(Arc::new(TimingResolver))
+                .connector_layer(TimingLayer::new(times.clone()))
+                .into()
         }
 
-        let mut builder = create_builder();
+        let times = Arc::new(ConnectTimes::default());
+        let mut builder = create_builder(&times);
         builder = builder.user_agent(&config.rrdp_user_agent);
         builder = builder.tcp_keepalive(config.rrdp_tcp_keepalive);
+        builder = match config.rrdp_idle_timeout {
+            Some(timeout) => builder.pool_idle_timeout(timeout),
+            None => builder.pool_max_idle_per_host(0),
+        };
         builder = builder.timeout(None); // Set per request.
-        builder = builder.redirect(
-            redirect::Policy::custom(Self::redirect_policy)
-        );
+        let redirects = Arc::new(Mutex::new(HashMap::new()));
+        builder = builder.redirect(redirect::Policy::custom({
+            let redirects = redirects.clone();
+            move |attempt| Self::redirect_policy(attempt, &redirects)
+        }));
         if let Some(timeout) = config.rrdp_connect_timeout {
             builder = builder.connect_timeout(timeout);
         }
@@ -81,7 +139,14 @@ impl HttpClient {
         Ok(HttpClient {
             client: Err(Some(builder)),
             response_dir: config.rrdp_keep_responses.clone(),
+            replay_dir: config.rrdp_replay_responses.clone(),
+            replayed: Default::default(),
             timeout: config.rrdp_timeout,
+            bandwidth,
+            hosts: Default::default(),
+            times,
+            redirects,
+            rewrites,
         })
     }
 
@@ -156,21 +221,56 @@ impl HttpClient {
     /// corresponding to the URI. If the resource behind the URI changes over
     /// time and this change should be tracked, set `multi` to `true` to
     /// include the current time in the file name.
+    ///
+    /// If replaying responses is enabled, the response is taken from the
+    /// kept responses instead. If `multi` is `true`, each request for the
+    /// same URI receives the next response in the order they were kept.
+    ///
+    /// Otherwise, the URI rewrite table is applied to the URI first.
+    ///
+    /// The metrics of the request up to receiving the response headers are
+    /// added to `metrics`, even if the request fails. The returned response
+    /// collects the metrics of receiving the body. They can be accessed via
+    /// [`HttpResponse::metrics`].
     pub fn response(
         &self,
         uri: &uri::Https,
         multi: bool,
+        metrics: &mut RrdpHttpMetrics,
     ) -> Result<HttpResponse, reqwest::Error> {
-        self._response(uri, self.client().get(uri.as_str()), multi)
+        if let Some(replay_dir) = self.replay_dir.as_ref() {
+            return Ok(self.replay_response(replay_dir, uri, multi))
+        }
+        let uri = match self.rewrite(uri) {
+            Ok(uri) => uri,
+            Err(file) => return Ok(HttpResponse::local(file))
+        };
+        self._response(&uri, self.client().get(uri.as_str()), multi, metrics)
     }
 
+    /// Performs an HTTP GET request with conditions.
+    ///
+    /// This is like [`response`][Self::response] but includes the given
+    /// ETag and last modified time as conditions. If the server answers
+    /// with status 304, this is noted in `metrics`.
     pub fn conditional_response(
         &self,
         uri: &uri::Https,
         etag: Option<&Bytes>,
         last_modified: Option<DateTime<Utc>>,
         multi: bool,
+        metrics: &mut RrdpHttpMetrics,
     ) -> Result<HttpResponse, reqwest::Error> {
+        if let Some(replay_dir) = self.replay_dir.as_ref() {
+            // Kept responses don’t know about conditions, so we always
+            // replay the next one.
+            return Ok(self.replay_response(replay_dir, uri, multi))
+        }
+        let uri = match self.rewrite(uri) {
+            Ok(uri) => uri,
+            Err(file) => return Ok(HttpResponse::local(file))
+        };
+        let conditional = etag.is_some() || last_modified.is_some();
         let mut request = self.client().get(uri.as_str());
         if let Some(etag) = etag {
             request = request.header(
@@ -183,24 +283,243 @@ impl HttpClient {
                 format_http_date(last_modified)
             );
         }
-        self._response(uri, request, multi)
+        let response = self._response(&uri, request, multi, metrics)?;
+        if conditional && response.status() == StatusCode::NOT_MODIFIED {
+            metrics.not_modified += 1;
+        }
+        Ok(response)
+    }
+
+    /// Asks the server for the size of the resource at the given URI.
+    ///
+    /// Sends a HEAD request and returns the content length given in the
+    /// response. Returns `None` if the request fails, the server doesn’t
+    /// provide the length, or responses are replayed. The metrics of the
+    /// request are added to `metrics`.
+    pub fn content_length(
+        &self, uri: &uri::Https, metrics: &mut RrdpHttpMetrics,
+    ) -> Option<u64> {
+        if self.replay_dir.is_some() {
+            return None
+        }
+        let uri = match self.rewrite(uri) {
+            Ok(uri) => uri,
+            Err(file) => return HttpResponse::local(file).content_length()
+        };
+        let response = self.send(
+            &uri, self.client().head(uri.as_str()), metrics
+        ).ok()?;
+        if response.status() != StatusCode::OK {
+            return None
+        }
+        response.headers().get(header::CONTENT_LENGTH)?
+            .to_str().ok()?.parse().ok()
+    }
+
+    /// Applies the URI rewrite table to a URI.
+    ///
+    /// Returns the URI to request or, if the URI was rewritten to a local
+    /// file, an error with the file to respond with. If the file doesn’t
+    /// exist or the rewritten URI is invalid or rejected, the error
+    /// contains `None` and the response should have status 404.
+    fn rewrite<'u>(
+        &self, uri: &'u uri::Https
+    ) -> Result<Cow<'u, uri::Https>, Option<fs::File>> {
+        match self.rewrites.rewrite(uri.as_str()) {
+            None => Ok(Cow::Borrowed(uri)),
+            Some(Err(_)) => Err(None),
+            Some(Ok(Rewritten::Uri(target))) => {
+                match uri::Https::from_string(target) {
+                    Ok(target) => Ok(Cow::Owned(target)),
+                    Err(err) => {
+                        warn!("RRDP {}: invalid rewritten URI: {}", uri, err);
+                        Err(None)
+                    }
+                }
+            }
+            Some(Ok(Rewritten::File(path))) => {
+                let file = fs::File::open(&path);
+                if let Err(ref err) = file {
+                    warn!(
+                        "RRDP {}: failed to open {}: {}",
+                        uri, path.display(), err
+                    );
+                }
+                Err(file.ok())
+            }
+        }
     }
 
     /// Creates a response from a request builder.
+    ///
+    /// Responses with a client or server error status are turned into an
+    /// error.
     fn _response(
         &self,
         uri: &uri::Https,
-        mut request: RequestBuilder,
-        multi: bool
+        request: RequestBuilder,
+        multi: bool,
+        metrics: &mut RrdpHttpMetrics,
     ) -> Result<HttpResponse, reqwest::Error> {
+        let response = self.send(uri, request, metrics)?.error_for_status();
+        if let Err(ref err) = response {
+            Self::count_error(err, metrics);
+        }
+        Ok(HttpResponse::create(
+            response?, uri, &self.response_dir, multi,
+            self.bandwidth.limiter(&uri.canonical_authority()),
+        ))
+    }
+
+    /// Sends a request.
+    ///
+    /// Redirects are followed by the client according to
+    /// [`redirect_policy`][Self::redirect_policy]. The timeout applies to
+    /// the request including all redirects.
+    ///
+    /// The number of redirects and the time spent on each phase of the
+    /// request until the response headers have been received are added to
+    /// `metrics`. If the request fails, the cause is counted, too.
+    fn send(
+        &self,
+        uri: &uri::Https,
+        mut request: RequestBuilder,
+        metrics: &mut RrdpHttpMetrics,
+    ) -> Result<Response, reqwest::Error> {
         if let Some(timeout) = self.timeout {
             request = request.timeout(timeout);
         }
-        request.send().and_then(|response| {
-            response.error_for_status()
-        }).map(|response| {
-            HttpResponse::create(response, uri, &self.response_dir, multi)
-        })
+        let request = request.build().map_err(|err| {
+            Self::count_error(&err, metrics);
+            err
+        })?;
+        let url = request.url().clone();
+        let name = url.host_str().unwrap_or_default();
+        metrics.requests += 1;
+        let start = Instant::now();
+        let response = self.client().execute(request);
+        let elapsed = start.elapsed();
+        metrics.redirects += self.redirects.lock().remove(&url).unwrap_or(0);
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                // We don’t know whether a connection was established, so
+                // we only account for connecting if that failed. If it
+                // timed out, the phases weren’t recorded.
+                if err.is_connect() {
+                    let dns = self.times.claim(name).dns;
+                    metrics.dns += dns;
+                    metrics.connect += elapsed.saturating_sub(dns);
+                }
+                else {
+                    metrics.ttfb += elapsed;
+                }
+                Self::count_error(&err, metrics);
+                return Err(err)
+            }
+        };
+        let new = self.hosts.lock().entry(
+            uri.canonical_authority().to_string()
+        ).or_default().add(response.extensions().get::<HttpInfo>());
+        if new {
+            metrics.connections += 1;
+            let phases = self.times.claim(name);
+            metrics.dns += phases.dns;
+            metrics.connect += phases.connect;
+            metrics.ttfb += elapsed.saturating_sub(
+                phases.dns + phases.connect
+            );
+        }
+        else {
+            metrics.ttfb += elapsed;
+        }
+
+        // A redirect the policy didn’t follow fails the request.
+        if response.status().is_redirection()
+            && response.status() != StatusCode::NOT_MODIFIED
+        {
+            metrics.errors.redirect += 1;
+        }
+        Ok(response)
+    }
+
+    /// Counts a failed request in `metrics` according to its cause.
+    fn count_error(err: &reqwest::Error, metrics: &mut RrdpHttpMetrics) {
+        let errors = &mut metrics.errors;
+        if err.is_timeout() {
+            errors.timeout += 1
+        }
+        else if err.is_connect() {
+            errors.connect += 1
+        }
+        else if err.is_status() {
+            errors.status += 1
+        }
+        else if err.is_redirect() {
+            errors.redirect += 1
+        }
+        else if err.is_body() || err.is_decode() {
+            errors.transfer += 1
+        }
+        else {
+            errors.other += 1
+        }
+    }
+
+    /// Returns the connection metrics per host and resets them.
+    ///
+    /// This also drops the phases of connections established but never
+    /// used by a request.
+    pub fn take_host_metrics(&self) -> Vec<RrdpHostMetrics> {
+        self.times.clear();
+        let mut res: Vec<_> = self.hosts.lock().drain().map(|(host, conn)| {
+            RrdpHostMetrics {
+                host,
+                requests: conn.requests,
+                connections: conn.connections,
+            }
+        }).collect();
+        res.sort_by(|left, right| left.host.cmp(&right.host));
+        res
+    }
+
+    /// Creates a response from the kept responses in `replay_dir`.
+    ///
+    /// The kept responses use the layout described with
+    /// [`HttpResponse::create`]. For a URI with multiple responses, the
+    /// n-th request receives the n-th response in the order of their
+    /// time stamps or the last one if there are fewer responses. If there
+    /// is no kept response, a response with status 404 is returned.
+    fn replay_response(
+        &self, replay_dir: &Path, uri: &uri::Https, multi: bool,
+    ) -> HttpResponse {
+        let path = replay_dir.join(&uri.as_str()[8..]);
+        let path = if multi {
+            let mut responses = match fs::read_dir(&path) {
+                Ok(dir) => {
+                    dir.filter_map(|entry| {
+                        entry.ok().map(|entry| entry.path())
+                    }).collect::<Vec<_>>()
+                }
+                Err(_) => Vec::new(),
+            };
+            responses.sort();
+            let mut replayed = self.replayed.lock();
+            let count = replayed.entry(uri.as_str().into()).or_default();
+            let path = responses.get(*count).or_else(|| {
+                responses.last()
+            }).cloned();
+            *count += 1;
+            path
+        }
+        else {
+            Some(path)
+        };
+        let file = path.and_then(|path| fs::File::open(path).ok());
+        if file.is_none() {
+            warn!("RRDP {}: no kept response to replay.", uri);
+        }
+        HttpResponse::local(file)
     }
 
     /*
@@ -263,8 +582,12 @@ impl HttpClient {
     /// The redirect policy.
     ///
     /// We allow up to 10 redirects (reqwest’s default policy) but only if
-    /// the origin stays the same.
-    fn redirect_policy(attempt: redirect::Attempt) -> redirect::Action {
+    /// the origin stays the same. Each redirect followed is counted in
+    /// `redirects` under the URL of the original request.
+    fn redirect_policy(
+        attempt: redirect::Attempt,
+        redirects: &Mutex<HashMap<Url, u64>>,
+    ) -> redirect::Action {
         if attempt.previous().len() > 9 {
             return attempt.stop();
         }
@@ -273,14 +596,49 @@ impl HttpClient {
             None => return attempt.follow() // Shouldn’t happen?
         };
         let new = attempt.url();
-        let orig = (orig.scheme(), orig.host(), orig.port());
-        let new = (new.scheme(), new.host(), new.port());
-        if orig == new {
-            attempt.follow()
+        if (orig.scheme(), orig.host(), orig.port())
+            != (new.scheme(), new.host(), new.port())
+        {
+            return attempt.stop()
         }
-        else {
-            attempt.stop()
+        *redirects.lock().entry(orig.clone()).or_default() += 1;
+        attempt.follow()
+    }
+}
+
+
+//------------ HostConnections -----------------------------------------------
+
+/// The connection usage for a single host.
+#[derive(Clone, Debug, Default)]
+struct HostConnections {
+    /// The number of requests sent.
+    requests: u64,
+
+    /// The number of distinct connections used.
+    connections: u64,
+
+    /// The local and remote addresses of the connections seen so far.
+    seen: HashSet<(SocketAddr, SocketAddr)>,
+}
+
+impl HostConnections {
+    /// Adds a request sent over the connection described by `info`.
+    ///
+    /// Returns whether the request used a new connection. If information
+    /// about the connection is missing, we have to assume it did.
+    fn add(&mut self, info: Option<&HttpInfo>) -> bool {
+        self.requests += 1;
+        let new = match info {
+            Some(info) => {
+                self.seen.insert((info.local_addr(), info.remote_addr()))
+            }
+            None => true
+        };
+        if new {
+            self.connections += 1;
         }
+        new
     }
 }
 
@@ -289,11 +647,28 @@ impl HttpClient {
 
 /// Wraps a reqwest response for added features.
 pub struct HttpResponse {
-    /// The wrapped reqwest response.
-    response: Response,
+    /// The wrapped response.
+    response: ResponseSource,
 
     /// A file to also store read data into.
     file: Option<fs::File>,
+
+    /// The bandwidth limits for reading the response.
+    limiter: Limiter,
+
+    /// The metrics of the request.
+    metrics: RrdpHttpMetrics,
+}
+
+/// Where the data of a response comes from.
+enum ResponseSource {
+    /// A response received from the server.
+    Reqwest(Response),
+
+    /// A local file, such as a replayed kept response.
+    ///
+    /// If this is `None`, there was no such file.
+    Local(Option<fs::File>),
 }
 
 impl HttpResponse {
@@ -305,17 +680,36 @@ impl HttpResponse {
     /// `false` the last component will be the file name. If `multi` is
     /// `true` the last component will be a directory, too, and the file name
     /// will be the ISO timestamp of the current time.
+    ///
+    /// Reading the response’s body will be throttled by `limiter`.
     pub fn create(
         response: Response,
         uri: &uri::Https,
         response_dir: &Option<PathBuf>,
-        multi: bool
+        multi: bool,
+        limiter: Limiter,
     ) -> Self {
         HttpResponse {
-            response,
+            response: ResponseSource::Reqwest(response),
             file: response_dir.as_ref().and_then(|base| {
                 Self::open_file(base, uri, multi)
-            })
+            }),
+            limiter,
+            metrics: Default::default(),
+        }
+    }
+
+    /// Creates a new response from a local file.
+    ///
+    /// This is used for replaying a kept response and for URIs rewritten to
+    /// local files. If `file` is `None`, the response will have status 404
+    /// and no content.
+    fn local(file: Option<fs::File>) -> Self {
+        HttpResponse {
+            response: ResponseSource::Local(file),
+            file: None,
+            limiter: Limiter::default(),
+            metrics: Default::default(),
         }
     }
 
@@ -367,7 +761,16 @@ impl HttpResponse {
 
     /// Returns the value of the content length header if present.
     pub fn content_length(&self) -> Option<u64> {
-        self.response.content_length()
+        match self.response {
+            ResponseSource::Reqwest(ref response) => {
+                response.content_length()
+            }
+            ResponseSource::Local(ref file) => {
+                file.as_ref().and_then(|file| file.metadata().ok()).map(
+                    |metadata| metadata.len()
+                )
+            }
+        }
     }
 
     /// Copies the full content of the response to the given writer.
@@ -380,9 +783,46 @@ impl HttpResponse {
         io::copy(self, w)
     }
 
+    /// Returns the metrics of the request.
+    ///
+    /// The time spent and bytes received while reading the body are
+    /// included for the part of the body read so far. A response from a
+    /// local file has no metrics.
+    pub fn metrics(&self) -> &RrdpHttpMetrics {
+        &self.metrics
+    }
+
+    /// Counts an error reading the body in `metrics`.
+    fn count_read_error(err: &io::Error, metrics: &mut RrdpHttpMetrics) {
+        let timeout = err.kind() == io::ErrorKind::TimedOut
+            || err.get_ref().and_then(|err| {
+                err.downcast_ref::<reqwest::Error>()
+            }).map(|err| err.is_timeout()).unwrap_or(false);
+        if timeout {
+            metrics.errors.timeout += 1
+        }
+        else {
+            metrics.errors.transfer += 1
+        }
+    }
+
     /// Returns the status code of the response.
     pub fn status(&self) -> StatusCode {
-        self.response.status()
+        match self.response {
+            ResponseSource::Reqwest(ref response) => response.status(),
+            ResponseSource::Local(Some(_)) => StatusCode::OK,
+            ResponseSource::Local(None) => StatusCode::NOT_FOUND,
+        }
+    }
+
+    /// Returns the headers of a received response.
+    fn headers(&self) -> Option<&header::HeaderMap> {
+        match self.response {
+            ResponseSource::Reqwest(ref response) => {
+                Some(response.headers())
+            }
+            ResponseSource::Local(_) => None
+        }
     }
 
     /// Returns the value of the ETag header if present.
@@ -396,7 +836,7 @@ impl HttpResponse {
     /// The method returns a `Bytes` value as there is a good chance the
     /// tag is short enough to be be inlined.
     pub fn etag(&self) -> Option<Bytes> {
-        let mut etags = self.response.headers()
+        let mut etags = self.headers()?
             .get_all(header::ETAG)
             .into_iter();
         let etag = etags.next()?;
@@ -440,7 +880,7 @@ impl HttpResponse {
     /// The method quietly returns `None` if the content of a header is
     /// malformed or if there is more than one occurence of the header.
     pub fn last_modified(&self) -> Option<DateTime<Utc>> {
-        let mut iter = self.response.headers()
+        let mut iter = self.headers()?
             .get_all(header::LAST_MODIFIED)
             .into_iter();
         let value = iter.next()?;
@@ -456,7 +896,25 @@ impl HttpResponse {
 
 impl io::Read for HttpResponse {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
-        let res = self.response.read(buf)?;
+        let res = match self.response {
+            ResponseSource::Reqwest(ref mut response) => {
+                let start = Instant::now();
+                let res = io::Read::read(response, buf);
+                if let Ok(len) = res {
+                    self.limiter.consume(len);
+                    self.metrics.bytes += len as u64;
+                }
+                self.metrics.transfer += start.elapsed();
+                if let Err(ref err) = res {
+                    Self::count_read_error(err, &mut self.metrics);
+                }
+                res?
+            }
+            ResponseSource::Local(Some(ref mut file)) => {
+                io::Read::read(file, buf)?
+            }
+            ResponseSource::Local(None) => 0,
+        };
         if let Some(file) = self.file.as_mut() {
             file.write_all(&buf[..res])?;
         }
@@ -476,6 +934,9 @@ pub enum HttpStatus {
     /// The repository URI was rejected.
     Rejected,
 
+    /// The update was skipped because of earlier failures.
+    Skipped,
+
     /// An error happened.
     Error
 }
@@ -485,6 +946,7 @@ impl HttpStatus {
         match self {
             HttpStatus::Response(code) => code.as_u16() as i16,
             HttpStatus::Rejected => -2,
+            HttpStatus::Skipped => -3,
             HttpStatus::Error => -1,
         }
     }
@@ -510,3 +972,131 @@ impl From<StatusCode> for HttpStatus {
     }
 }
 
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+    use std::str::FromStr;
+    use super::*;
+
+    /// Keeps `content` as a response for `uri` in `dir`.
+    ///
+    /// This reads a response in the same way as one received from a server
+    /// with keeping responses enabled.
+    fn keep(dir: &Path, uri: &uri::Https, multi: bool, content: &[u8]) {
+        let mut source = tempfile::tempfile().unwrap();
+        source.write_all(content).unwrap();
+        io::Seek::rewind(&mut source).unwrap();
+        let mut response = HttpResponse::local(Some(source));
+        response.file = HttpResponse::open_file(dir, uri, multi);
+        assert!(response.file.is_some());
+        response.copy_to(&mut io::sink()).unwrap();
+    }
+
+    /// Returns the status and content of a response.
+    fn read(
+        response: Result<HttpResponse, reqwest::Error>
+    ) -> (u16, Vec<u8>) {
+        let mut response = response.unwrap();
+        let mut content = Vec::new();
+        response.read_to_end(&mut content).unwrap();
+        (response.status().as_u16(), content)
+    }
+
+    #[test]
+    fn keep_and_replay_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let notify = uri::Https::from_str(
+            "https://rrdp.example.net/notification.xml"
+        ).unwrap();
+        let snapshot = uri::Https::from_str(
+            "https://rrdp.example.net/1/snapshot.xml"
+        ).unwrap();
+        let missing = uri::Https::from_str(
+            "https://rrdp.example.net/2/snapshot.xml"
+        ).unwrap();
+        keep(dir.path(), &notify, true, b"first");
+        keep(dir.path(), &notify, true, b"second");
+        keep(dir.path(), &snapshot, false, b"snapshot");
+
+        let mut config = Config::default_with_paths(
+            Default::default(), dir.path().join("cache")
+        );
+        config.rrdp_replay_responses = Some(dir.path().into());
+        let client = HttpClient::new(
+            &config,
+            Arc::new(Bandwidth::new(&config)),
+            Arc::new(UriRewrites::new(&config).unwrap()),
+        ).unwrap();
+
+        // Responses for the same URI are replayed in order with the last
+        // one repeated.
+        let mut metrics = RrdpHttpMetrics::default();
+        assert_eq!(
+            read(client.response(&notify, true, &mut metrics)),
+            (200, b"first".to_vec())
+        );
+        assert_eq!(
+            read(client.conditional_response(
+                &notify, None, None, true, &mut metrics
+            )),
+            (200, b"second".to_vec())
+        );
+        assert_eq!(
+            read(client.response(&notify, true, &mut metrics)),
+            (200, b"second".to_vec())
+        );
+        assert_eq!(
+            read(client.response(&snapshot, false, &mut metrics)),
+            (200, b"snapshot".to_vec())
+        );
+        assert_eq!(
+            read(client.response(&missing, false, &mut metrics)),
+            (404, Vec::new())
+        );
+        assert_eq!(metrics.requests, 0);
+    }
+
+    #[test]
+    fn failed_request_metrics() {
+        let mut config = Config::default_with_paths(
+            Default::default(), Default::default()
+        );
+        config.rrdp_timeout = Some(Duration::from_millis(200));
+        let mut client = HttpClient::new(
+            &config,
+            Arc::new(Bandwidth::new(&config)),
+            Arc::new(UriRewrites::new(&config).unwrap()),
+        ).unwrap();
+        client.ignite().unwrap();
+
+        // Nobody listens on the port.
+        let sock = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = uri::Https::from_string(format!(
+            "https://{}/notification.xml", sock.local_addr().unwrap()
+        )).unwrap();
+        drop(sock);
+        let mut metrics = RrdpHttpMetrics::default();
+        assert!(client.response(&uri, false, &mut metrics).is_err());
+        assert_eq!(metrics.requests, 1);
+        assert_eq!(metrics.errors.connect, 1);
+        assert_eq!(metrics.errors.iter().map(|item| item.1).sum::<u64>(), 1);
+
+        // The server accepts the connection but never responds.
+        let sock = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = uri::Https::from_string(format!(
+            "https://{}/notification.xml", sock.local_addr().unwrap()
+        )).unwrap();
+        let mut metrics = RrdpHttpMetrics::default();
+        assert!(client.response(&uri, false, &mut metrics).is_err());
+        assert_eq!(metrics.requests, 1);
+        assert_eq!(metrics.errors.timeout, 1);
+        assert!(
+            metrics.connect + metrics.ttfb >= Duration::from_millis(200)
+        );
+        drop(sock);
+    }
+}
@@ -1,11 +1,13 @@
 #![allow(dead_code)]
 
+pub use self::archive::RepositoryState;
 pub use self::base::{Collector, LoadResult, ReadRepository, Run};
 pub use self::http::HttpStatus;
 pub use self::update::SnapshotReason;
 
 mod archive;
 mod base;
+mod connect;
 mod http;
 mod update;
 
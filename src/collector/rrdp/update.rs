@@ -1,7 +1,9 @@
 
-use std::{error, fmt, io};
-use std::collections::HashSet;
-use std::io::Read;
+use std::{error, fmt, io, thread};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek};
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use log::{error, warn};
@@ -10,9 +12,10 @@ use ring::digest;
 use ring::constant_time::verify_slices_are_equal;
 use rpki::{rrdp, uri};
 use rpki::rrdp::{DeltaInfo, NotificationFile, ProcessDelta, ProcessSnapshot};
+use tempfile::NamedTempFile;
 use uuid::Uuid;
 use crate::error::{Failed, RunFailed};
-use crate::metrics::RrdpRepositoryMetrics;
+use crate::metrics::{RrdpHttpMetrics, RrdpRepositoryMetrics};
 use crate::utils::archive::{ArchiveError, PublishError};
 use super::archive::{AccessError, FallbackTime, RepositoryState, RrdpArchive};
 use super::base::Collector;
@@ -40,7 +43,8 @@ impl Notification {
     /// Requests, parses, and returns the given RRDP notification file.
     ///
     /// The value referred to by `status` will be updated to the received
-    /// status code or `HttpStatus::Error` if the request failed.
+    /// status code or `HttpStatus::Error` if the request failed. The
+    /// metrics of the request are added to `http_metrics`.
     ///
     /// Returns the notification file on success. Returns `Ok(None)` if a
     /// response was received successfully but indicated that the
@@ -50,13 +54,15 @@ impl Notification {
         uri: &uri::Https,
         state: Option<&RepositoryState>,
         status: &mut HttpStatus,
+        http_metrics: &mut RrdpHttpMetrics,
         delta_list_limit: usize,
     ) -> Result<Option<Self>, Failed> {
-        let response = match http.conditional_response(
+        let mut response = match http.conditional_response(
             uri,
             state.and_then(|state| state.etag.as_ref()),
             state.and_then(|state| state.last_modified()),
-            true
+            true,
+            http_metrics,
         ) {
             Ok(response) => {
                 *status = response.status().into();
@@ -69,7 +75,7 @@ impl Notification {
             }
         };
 
-        if response.status() == StatusCode::NOT_MODIFIED {
+        let res = if response.status() == StatusCode::NOT_MODIFIED {
             Ok(None)
         }
         else if response.status() != StatusCode::OK {
@@ -81,9 +87,11 @@ impl Notification {
         }
         else {
             Notification::from_response(
-                uri.clone(), response, delta_list_limit
+                uri.clone(), &mut response, delta_list_limit
             ).map(Some)
-        }
+        };
+        http_metrics.add(response.metrics());
+        res
     }
 
 
@@ -91,7 +99,7 @@ impl Notification {
     ///
     /// Assumes that the response status was 200 OK.
     fn from_response(
-        uri: uri::Https, response: HttpResponse, delta_list_limit: usize
+        uri: uri::Https, response: &mut HttpResponse, delta_list_limit: usize
     ) -> Result<Self, Failed> {
         let etag = response.etag();
         let last_modified = response.last_modified();
@@ -117,6 +125,9 @@ impl Notification {
     }
 
     /// Creates repository state for this notification.
+    ///
+    /// The payload sizes of the returned state are unknown and need to be
+    /// filled in by the caller.
     pub fn to_repository_state(
         &self, fallback: FallbackTime,
     ) -> RepositoryState {
@@ -131,6 +142,8 @@ impl Notification {
             delta_state: self.content.deltas().iter().map(|delta| {
                 (delta.serial(), delta.hash())
             }).collect(),
+            snapshot_size: None,
+            delta_size: None,
         }
     }
 
@@ -170,6 +183,9 @@ pub struct SnapshotUpdate<'a> {
     /// The notification file pointing to the snapshot.
     notify: &'a Notification,
 
+    /// The average delta size of the repository if known.
+    delta_size: Option<u64>,
+
     /// The metrics for the update.
     metrics: &'a mut RrdpRepositoryMetrics,
 }
@@ -179,44 +195,56 @@ impl<'a> SnapshotUpdate<'a> {
         collector: &'a Collector,
         archive: &'a mut RrdpArchive,
         notify: &'a Notification,
+        delta_size: Option<u64>,
         metrics: &'a mut RrdpRepositoryMetrics,
     ) -> Self {
-        SnapshotUpdate { collector, archive, notify, metrics }
+        SnapshotUpdate { collector, archive, notify, delta_size, metrics }
     }
 
     pub fn try_update(mut self) -> Result<(), SnapshotError> {
-        let response = match self.collector.http().response(
-            self.notify.content.snapshot().uri(), false
+        let mut response = match self.collector.http().response(
+            self.notify.content.snapshot().uri(), false,
+            &mut self.metrics.http,
         ) {
-            Ok(response) => {
-                self.metrics.payload_status = Some(response.status().into());
-                if response.status() != StatusCode::OK {
-                    return Err(response.status().into())
-                }
-                else {
-                    response
-                }
-            }
+            Ok(response) => response,
             Err(err) => {
                 self.metrics.payload_status = Some(HttpStatus::Error);
                 return Err(err.into())
             }
         };
+        self.metrics.payload_status = Some(response.status().into());
+        let res = if response.status() != StatusCode::OK {
+            Err(response.status().into())
+        }
+        else {
+            self.apply(&mut response)
+        };
+        self.metrics.http.add(response.metrics());
+        res
+    }
 
+    /// Applies the snapshot file received in `response`.
+    fn apply(
+        &mut self, response: &mut HttpResponse
+    ) -> Result<(), SnapshotError> {
         let mut reader = io::BufReader::new(HashRead::new(response));
         self.process(&mut reader)?;
-        let hash = reader.into_inner().into_hash();
+        let reader = reader.into_inner();
+        let len = reader.len();
+        self.metrics.payload_bytes += len;
+        let hash = reader.into_hash();
         if verify_slices_are_equal(
             hash.as_ref(),
             self.notify.content.snapshot().hash().as_ref()
         ).is_err() {
             return Err(SnapshotError::HashMismatch)
         }
-        self.archive.publish_state(
-            &self.notify.to_repository_state(
-                self.collector.config().fallback_time
-            )
-        )?;
+        let mut state = self.notify.to_repository_state(
+            self.collector.config().fallback_time
+        );
+        state.snapshot_size = Some(len);
+        state.delta_size = self.delta_size;
+        self.archive.publish_state(&state)?;
         Ok(())
     }
 }
@@ -322,27 +350,42 @@ impl<'a> DeltaUpdate<'a> {
     }
 
     pub fn try_update(mut self) -> Result<(), DeltaError> {
-        let response = match self.collector.http().response(
-            self.info.uri(), false
+        let mut response = match self.collector.http().response(
+            self.info.uri(), false, &mut self.metrics.http,
         ) {
-            Ok(response) => {
-                self.metrics.payload_status = Some(response.status().into());
-                if response.status() != StatusCode::OK {
-                    return Err(response.status().into())
-                }
-                else {
-                    response
-                }
-            }
+            Ok(response) => response,
             Err(err) => {
                 self.metrics.payload_status = Some(HttpStatus::Error);
                 return Err(err.into())
             }
         };
+        self.metrics.payload_status = Some(response.status().into());
+        let res = if response.status() != StatusCode::OK {
+            Err(response.status().into())
+        }
+        else {
+            self.apply(&mut response)
+        };
+        self.metrics.http.add(response.metrics());
+        res
+    }
 
-        let mut reader = io::BufReader::new(HashRead::new(response));
+    /// Applies a delta file that has been fetched already.
+    pub fn try_update_prefetched(
+        mut self, delta: PrefetchedDelta
+    ) -> Result<(), DeltaError> {
+        self.metrics.payload_status = Some(delta.status);
+        self.metrics.http.add(&delta.http);
+        self.apply(delta.file?)
+    }
+
+    /// Applies the delta file read from `reader`.
+    fn apply(&mut self, reader: impl io::Read) -> Result<(), DeltaError> {
+        let mut reader = io::BufReader::new(HashRead::new(reader));
         self.process(&mut reader)?;
-        let hash = reader.into_inner().into_hash();
+        let reader = reader.into_inner();
+        self.metrics.payload_bytes += reader.len();
+        let hash = reader.into_hash();
         if verify_slices_are_equal(
             hash.as_ref(),
             self.info.hash().as_ref()
@@ -438,6 +481,145 @@ impl<'a> ProcessDelta for DeltaUpdate<'a> {
 }
 
 
+//------------ DeltaPrefetch -------------------------------------------------
+
+/// Fetches delta files concurrently ahead of them being applied.
+///
+/// A number of worker threads download the delta files in order into
+/// temporary files. The fetched deltas are then taken in order via
+/// [`next`][Self::next] which blocks until the delta is available.
+/// Dropping the value stops the workers from starting to fetch further
+/// deltas.
+pub struct DeltaPrefetch {
+    /// The receiving end of the channel the workers send fetched deltas to.
+    rx: mpsc::Receiver<(usize, PrefetchedDelta)>,
+
+    /// Deltas that have been received but not yet taken.
+    pending: HashMap<usize, PrefetchedDelta>,
+
+    /// The index of the next delta to be taken.
+    next: usize,
+
+    /// Tells the workers to stop.
+    cancel: Arc<AtomicBool>,
+}
+
+impl DeltaPrefetch {
+    /// Starts fetching `deltas` using `workers` threads spawned in `scope`.
+    pub fn start<'scope, 'env>(
+        scope: &'scope thread::Scope<'scope, 'env>,
+        collector: &'env Collector,
+        deltas: &'env [DeltaInfo],
+        workers: usize,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let next = Arc::new(AtomicUsize::new(0));
+        for _ in 0..workers.min(deltas.len()) {
+            let tx = tx.clone();
+            let cancel = cancel.clone();
+            let next = next.clone();
+            scope.spawn(move || {
+                while !cancel.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let info = match deltas.get(index) {
+                        Some(info) => info,
+                        None => break,
+                    };
+                    let delta = PrefetchedDelta::fetch(collector, info);
+                    if tx.send((index, delta)).is_err() {
+                        break
+                    }
+                }
+            });
+        }
+        DeltaPrefetch {
+            rx,
+            pending: HashMap::new(),
+            next: 0,
+            cancel,
+        }
+    }
+
+    /// Returns the next delta in order.
+    ///
+    /// Blocks until the delta has been fetched. Returns `None` if all
+    /// workers have stopped without providing the delta.
+    pub fn next(&mut self) -> Option<PrefetchedDelta> {
+        let index = self.next;
+        self.next += 1;
+        loop {
+            if let Some(delta) = self.pending.remove(&index) {
+                return Some(delta)
+            }
+            let (received, delta) = self.rx.recv().ok()?;
+            self.pending.insert(received, delta);
+        }
+    }
+}
+
+impl Drop for DeltaPrefetch {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+
+//------------ PrefetchedDelta -----------------------------------------------
+
+/// A delta file fetched into a temporary file.
+pub struct PrefetchedDelta {
+    /// The status of the HTTP request for the delta file.
+    status: HttpStatus,
+
+    /// The metrics of the HTTP request for the delta file.
+    http: RrdpHttpMetrics,
+
+    /// The temporary file containing the delta or the error fetching it.
+    ///
+    /// The file is deleted when dropped.
+    file: Result<NamedTempFile, DeltaError>,
+}
+
+impl PrefetchedDelta {
+    /// Fetches the delta file described by `info`.
+    fn fetch(collector: &Collector, info: &DeltaInfo) -> Self {
+        let mut http = RrdpHttpMetrics::default();
+        let mut response = match collector.http().response(
+            info.uri(), false, &mut http
+        ) {
+            Ok(response) => response,
+            Err(err) => {
+                return PrefetchedDelta {
+                    status: HttpStatus::Error,
+                    http,
+                    file: Err(err.into()),
+                }
+            }
+        };
+        let status = response.status();
+        if status != StatusCode::OK {
+            return PrefetchedDelta {
+                status: status.into(),
+                http,
+                file: Err(status.into()),
+            }
+        }
+        let file = collector.named_temp_file().and_then(|mut file| {
+            response.copy_to(&mut file)?;
+            file.rewind()?;
+            Ok(file)
+        });
+        http.add(response.metrics());
+        PrefetchedDelta {
+            status: status.into(),
+            http,
+            file: file.map_err(Into::into),
+        }
+    }
+}
+
+
 //------------ HashRead ------------------------------------------------------
 
 /// A reader wrapper that calculates the SHA-256 hash of all read data.
@@ -447,6 +629,9 @@ struct HashRead<R> {
 
     /// The context for hash calculation.
     context: digest::Context,
+
+    /// The number of bytes read so far.
+    len: u64,
 }
 
 impl<R> HashRead<R> {
@@ -454,10 +639,16 @@ impl<R> HashRead<R> {
     pub fn new(reader: R) -> Self {
         HashRead {
             reader,
-            context: digest::Context::new(&digest::SHA256)
+            context: digest::Context::new(&digest::SHA256),
+            len: 0,
         }
     }
 
+    /// Returns the number of bytes read so far.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
     /// Converts the reader into the hash.
     pub fn into_hash(self) -> rrdp::Hash {
         // Unwrap should be safe: This can only fail if the slice has the
@@ -471,6 +662,7 @@ impl<R: io::Read> io::Read for HashRead<R> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
         let res = self.reader.read(buf)?;
         self.context.update(&buf[..res]);
+        self.len += res as u64;
         Ok(res)
     }
 }
@@ -618,6 +810,9 @@ pub enum SnapshotReason {
     /// There were too many deltas to process.
     TooManyDeltas,
 
+    /// The deltas were estimated to be larger than the snapshot.
+    LargerDeltas,
+
     /// The local copy was corrupt.
     CorruptArchive,
 }
@@ -637,6 +832,7 @@ impl SnapshotReason {
             OutdatedLocal => "outdate-local",
             ConflictingDelta => "conflicting-delta",
             TooManyDeltas => "too-many-deltas",
+            LargerDeltas => "larger-deltas",
             CorruptArchive => "corrupt-local-copy",
         }
     }
@@ -12,16 +12,28 @@
 //! requested, we spawn rsync and block until it returns. If during that time
 //! another thread requests access to the same module, that thread is blocked,
 //! too.
+//!
+//! If configured, a copy of each module is kept after it has been updated
+//! successfully. Instead of running rsync, these copies can also be replayed
+//! in the order they have been kept.
+//!
+//! Instead of spawning the rsync command, the built-in client from the
+//! [`rsync_native`][super::rsync_native] module can be used if configured.
+//!
+//! If the URI rewrite table has a rule for a module, the module is
+//! synchronised from the rewritten URI or copied from the local directory
+//! it was rewritten to. It is still stored under its original URI.
 
-use std::{fmt, fs, io, ops};
+use std::{cmp, fmt, fs, io, ops};
 use std::borrow::{Borrow, Cow};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::path::{Path, PathBuf};
 use std::process::{Command as StdCommand, ExitStatus, Stdio};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use bytes::Bytes;
+use chrono::Utc;
 use futures::{FutureExt, TryFutureExt};
 use futures::future::Either;
 use log::{debug, error, info, warn};
@@ -30,10 +42,16 @@ use tokio::io::AsyncBufReadExt;
 use tokio::process::Command as AsyncCommand;
 use crate::config::Config;
 use crate::error::{Failed, Fatal};
-use crate::metrics::{Metrics, RsyncModuleMetrics};
+use crate::metrics::{
+    Metrics, RsyncModuleMetrics, RsyncRunSummary, RsyncTransferStats
+};
 use crate::utils::fatal;
 use crate::utils::sync::{Mutex, RwLock};
 use crate::utils::uri::UriExt;
+use super::backoff::Backoff;
+use super::rewrite::{Rewritten, UriRewrites};
+use super::rsync_native;
+use super::throttle::{Bandwidth, HostSlots, Limiter};
 
 
 //------------ Collector -----------------------------------------------------
@@ -52,6 +70,23 @@ pub struct Collector {
 
     /// Whether to filter dubious authorities in rsync URIs.
     filter_dubious: bool,
+
+    /// The failure histories of the modules.
+    backoff: Backoff,
+
+    /// The slots for concurrent updates per host.
+    slots: HostSlots,
+
+    /// The bandwidth limits.
+    bandwidth: Arc<Bandwidth>,
+
+    /// The outcomes of the most recent updates of each module.
+    ///
+    /// The oldest update is at the front.
+    history: Mutex<HashMap<OwnedModule, VecDeque<RsyncRunSummary>>>,
+
+    /// The number of updates to keep in the history of each module.
+    history_size: usize,
 }
  
 
@@ -93,7 +128,11 @@ impl Collector {
     /// Creates a new rsync collector.
     ///
     /// If use of rsync is disabled via the config, returns `Ok(None)`.
-    pub fn new(config: &Config) -> Result<Option<Self>, Failed> {
+    pub fn new(
+        config: &Config,
+        bandwidth: Arc<Bandwidth>,
+        rewrites: Arc<UriRewrites>,
+    ) -> Result<Option<Self>, Failed> {
         if config.disable_rsync {
             Ok(None)
         }
@@ -102,8 +141,13 @@ impl Collector {
                 working_dir: WorkingDir::new(
                     Self::create_working_dir(config)?
                 ),
-                command: Some(RsyncCommand::new(config)?),
-                filter_dubious: !config.allow_dubious_hosts
+                command: Some(RsyncCommand::new(config, rewrites)?),
+                filter_dubious: !config.allow_dubious_hosts,
+                backoff: Backoff::new(config, "rsync")?,
+                slots: HostSlots::new(config.rsync_max_host_processes),
+                bandwidth,
+                history: Default::default(),
+                history_size: config.rsync_history_size,
             }))
         }
     }
@@ -127,6 +171,26 @@ impl Collector {
         Run::new(self)
     }
 
+    /// Returns the history of a module.
+    fn history(&self, module: &Module) -> Vec<RsyncRunSummary> {
+        self.history.lock().get(module).map(|history| {
+            history.iter().copied().collect()
+        }).unwrap_or_default()
+    }
+
+    /// Adds an update to the history of a module and returns the history.
+    fn add_history(
+        &self, module: &Module, summary: RsyncRunSummary
+    ) -> Vec<RsyncRunSummary> {
+        let mut history = self.history.lock();
+        let entry = history.entry(module.to_owned()).or_default();
+        entry.push_back(summary);
+        while entry.len() > self.history_size {
+            entry.pop_front();
+        }
+        entry.iter().copied().collect()
+    }
+
     /// Dumps the content of the rsync collector.
     pub fn dump(&self, dir: &Path) -> Result<(), Failed> {
         let target = dir.join("rsync");
@@ -287,11 +351,54 @@ impl<'a> Run<'a> {
                 module
             )
         }
+        // Check if the module has failed recently. If so, skip updating.
+        else if let Some(backoff) = self.collector.backoff.check(&module.0) {
+            info!(
+                "{}: Skipping update after {} failed attempts.",
+                module, backoff.failures
+            );
+            self.metrics.lock().push(RsyncModuleMetrics {
+                module: module.to_uri(),
+                status: Err(io::Error::new(
+                    io::ErrorKind::Other, "skipped due to backoff"
+                )),
+                cause: RsyncCause::Skipped,
+                duration: Ok(Duration::from_secs(0)),
+                stats: None,
+                backoff: Some(backoff),
+                slot_wait: None,
+                history: self.collector.history(module.as_ref()),
+            });
+        }
         else {
-            // Run the actual update.
-            let metrics = command.update(
+            // Wait for a slot for the host and run the actual update.
+            let authority = uri.canonical_authority();
+            let slot = self.collector.slots.acquire(&authority);
+            let mut metrics = command.update(
                 module.as_ref(),
-                &self.collector.working_dir.module_path(module.as_ref())
+                &self.collector.working_dir.module_path(module.as_ref()),
+                &self.collector.bandwidth.limiter(&authority),
+            );
+            metrics.slot_wait = slot.wait();
+            let success = matches!(
+                metrics.status, Ok(ref status) if status.success()
+            );
+            metrics.backoff = Some(
+                if success {
+                    self.collector.backoff.success(&module.0)
+                }
+                else {
+                    self.collector.backoff.failure(&module.0)
+                }
+            );
+            metrics.history = self.collector.add_history(
+                module.as_ref(),
+                RsyncRunSummary {
+                    time: Utc::now(),
+                    cause: metrics.cause,
+                    duration: metrics.duration.as_ref().ok().copied()
+                        .unwrap_or_default(),
+                }
             );
 
             // Insert into updated map and metrics.
@@ -358,6 +465,14 @@ impl<'a> Run<'a> {
         for module in self.updated.read().iter() {
             retain.add_from_uri(&module.to_uri());
         }
+        self.collector.backoff.retain(|key| {
+            uri::Rsync::from_str(key).map(|uri| {
+                retain.contains(&uri)
+            }).unwrap_or(false)
+        });
+        self.collector.history.lock().retain(|module, _| {
+            retain.contains(&module.to_uri())
+        });
 
         for entry in fatal::read_dir(
             &self.collector.working_dir.base
@@ -422,6 +537,7 @@ impl<'a> Run<'a> {
     /// If you are not interested in the metrics, you can simple drop the
     /// value, instead.
     pub fn done(self, metrics: &mut Metrics) {
+        self.collector.backoff.write();
         metrics.rsync = self.metrics.into_inner();
     }
 }
@@ -440,14 +556,58 @@ struct RsyncCommand {
     /// We will always add a few more when actually running.
     args: Vec<String>,
 
+    /// Whether the command supports the `--stats` option.
+    stats: bool,
+
     /// The rsync timeout.
     timeout: Option<Duration>,
+
+    /// The built-in client to use instead of the command if enabled.
+    native: Option<rsync_native::Client>,
+
+    /// The base directory for keeping updated modules if that is enabled.
+    keep_dir: Option<PathBuf>,
+
+    /// The base directory of kept modules to replay if that is enabled.
+    ///
+    /// If this is present, rsync is never run and the kept modules are
+    /// used instead.
+    replay_dir: Option<PathBuf>,
+
+    /// The number of times each module has been replayed.
+    replayed: Mutex<HashMap<OwnedModule, usize>>,
+
+    /// The URI rewrite table.
+    rewrites: Arc<UriRewrites>,
 }
 
 impl RsyncCommand {
     /// Creates a new rsync command from the config.
-    pub fn new(config: &Config) -> Result<Self, Failed> {
+    pub fn new(
+        config: &Config, rewrites: Arc<UriRewrites>,
+    ) -> Result<Self, Failed> {
         let command = config.rsync_command.clone();
+        if config.rsync_replay_responses.is_some() || config.rsync_native {
+            // We won’t run rsync, so we don’t need to check for it.
+            let replay_dir = config.rsync_replay_responses.clone();
+            let replaying = replay_dir.is_some();
+            return Ok(RsyncCommand {
+                command,
+                args: Vec::new(),
+                stats: false,
+                timeout: config.rsync_timeout,
+                native: (!replaying).then(|| {
+                    rsync_native::Client::new(config)
+                }),
+                keep_dir: {
+                    config.rsync_keep_responses.clone()
+                        .filter(|_| !replaying)
+                },
+                replay_dir,
+                replayed: Default::default(),
+                rewrites,
+            })
+        }
         let output = match StdCommand::new(&command).arg("-h").output() {
             Ok(output) => output,
             Err(err) => {
@@ -483,35 +643,249 @@ impl RsyncCommand {
                 args
             }
         };
+        let stats = output.stdout.windows(7).any(|window| {
+            window == b"--stats"
+        });
         Ok(RsyncCommand {
             command,
             args,
+            stats,
             timeout: config.rsync_timeout,
+            native: None,
+            keep_dir: config.rsync_keep_responses.clone(),
+            replay_dir: None,
+            replayed: Default::default(),
+            rewrites,
         })
     }
 
     /// Updates a module by running rsync.
+    ///
+    /// If replaying is enabled, replays the next kept copy of the module
+    /// instead. Otherwise, applies the URI rewrite table, uses the built-in
+    /// client instead of the command if that is enabled and keeps a copy of
+    /// the module after a successful update if that is enabled.
     pub fn update(
         &self,
         source: &Module,
-        destination: &Path
+        destination: &Path,
+        limiter: &Limiter,
     ) -> RsyncModuleMetrics {
         let start = SystemTime::now();
-        let status = self.command(
-            source, destination
-        ).and_then(|cmd| self.run(source, cmd));
+        let (status, stats, cause) = match self.replay_dir.as_ref() {
+            Some(replay_dir) => {
+                Self::outcome(self.replay(replay_dir, source, destination))
+            }
+            None => {
+                let (status, stats, cause) = match self.rewrites.rewrite(
+                    &source.0
+                ) {
+                    None => self.fetch(source, destination, limiter),
+                    Some(Err(_)) => {
+                        Self::outcome(Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "rejected rewritten URI"
+                        )))
+                    }
+                    Some(Ok(Rewritten::Uri(target))) => {
+                        match uri::Rsync::from_string(target) {
+                            Ok(target) => {
+                                self.fetch(
+                                    &Module::from_uri(&target),
+                                    destination, limiter
+                                )
+                            }
+                            Err(err) => {
+                                warn!(
+                                    "{}: invalid rewritten URI: {}",
+                                    source, err
+                                );
+                                Self::outcome(Err(io::Error::new(
+                                    io::ErrorKind::InvalidInput,
+                                    "invalid rewritten URI"
+                                )))
+                            }
+                        }
+                    }
+                    Some(Ok(Rewritten::File(path))) => {
+                        info!(
+                            "Copying rsync module {} from {}.",
+                            source, path.display()
+                        );
+                        Self::outcome(Self::copy_dir(&path, destination))
+                    }
+                };
+                if let Some(keep_dir) = self.keep_dir.as_ref() {
+                    if matches!(status, Ok(ref status) if status.success()) {
+                        Self::keep(keep_dir, source, destination)
+                    }
+                }
+                (status, stats, cause)
+            }
+        };
         RsyncModuleMetrics {
             module: source.to_uri(),
             status,
+            cause,
             duration: SystemTime::now().duration_since(start),
+            stats,
+            backoff: None,
+            slot_wait: None,
+            history: Vec::new(),
+        }
+    }
+
+    /// Fetches a module using either the command or the built-in client.
+    fn fetch(
+        &self,
+        source: &Module,
+        destination: &Path,
+        limiter: &Limiter,
+    ) -> (
+        Result<ExitStatus, io::Error>, Option<RsyncTransferStats>, RsyncCause
+    ) {
+        match self.native.as_ref() {
+            Some(native) => {
+                let (status, stats) = Self::run_native(
+                    native, source, destination, limiter
+                );
+                let cause = RsyncCause::from_status(&status, false);
+                (status, stats, cause)
+            }
+            None => {
+                let mut output = CommandOutput::default();
+                let status = self.command(
+                    source, destination, limiter
+                ).and_then(|cmd| self.run(source, cmd, &mut output));
+                let cause = RsyncCause::from_status(&status, output.refused);
+                (status, output.stats, cause)
+            }
+        }
+    }
+
+    /// Returns the outcome of an update that didn’t transfer anything.
+    fn outcome(
+        status: Result<ExitStatus, io::Error>
+    ) -> (
+        Result<ExitStatus, io::Error>, Option<RsyncTransferStats>, RsyncCause
+    ) {
+        let cause = RsyncCause::from_status(&status, false);
+        (status, None, cause)
+    }
+
+    /// Updates a module using the built-in client.
+    fn run_native(
+        native: &rsync_native::Client,
+        source: &Module,
+        destination: &Path,
+        limiter: &Limiter,
+    ) -> (Result<ExitStatus, io::Error>, Option<RsyncTransferStats>) {
+        info!("rsyncing from {} using the built-in client.", source);
+        match native.update(&source.to_uri(), destination, limiter) {
+            Ok(stats) => (Ok(Self::success_status()), Some(stats)),
+            Err(err) => {
+                warn!("{}: {}", source, err);
+                (Err(err), None)
+            }
         }
     }
 
+    /// Keeps a copy of an updated module.
+    ///
+    /// The copy is placed in a directory named after the current time
+    /// inside the module’s directory under `keep_dir`.
+    fn keep(keep_dir: &Path, source: &Module, destination: &Path) {
+        let target = keep_dir.join(&source.0[8..]).join(
+            Utc::now().to_rfc3339()
+        );
+        if let Err(err) = fs::create_dir_all(&target) {
+            warn!(
+                "Cannot keep rsync module {}; \
+                 creating directory {} failed: {}",
+                source, target.display(), err
+            );
+            return
+        }
+        if Collector::dump_dir(destination, &target).is_err() {
+            warn!("Cannot keep rsync module {}.", source);
+        }
+    }
+
+    /// Replays a kept copy of a module.
+    ///
+    /// The n-th update of a module replays the n-th kept copy in the order
+    /// they were kept or the last one if there are fewer copies.
+    fn replay(
+        &self,
+        replay_dir: &Path,
+        source: &Module,
+        destination: &Path
+    ) -> Result<ExitStatus, io::Error> {
+        info!("Replaying kept rsync module {}.", source);
+        let mut kept = fs::read_dir(replay_dir.join(&source.0[8..]))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>();
+        kept.sort();
+        let path = {
+            let mut replayed = self.replayed.lock();
+            let count = replayed.entry(source.to_owned()).or_default();
+            let path = kept.get(*count).or_else(|| kept.last()).cloned();
+            *count += 1;
+            path
+        };
+        let path = path.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound, "no kept module to replay"
+            )
+        })?;
+        Self::copy_dir(&path, destination)
+    }
+
+    /// Replaces the content of `destination` with a copy of `source`.
+    fn copy_dir(
+        source: &Path, destination: &Path
+    ) -> Result<ExitStatus, io::Error> {
+        if !source.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a directory", source.display())
+            ))
+        }
+        if let Err(err) = fs::remove_dir_all(destination) {
+            if err.kind() != io::ErrorKind::NotFound {
+                return Err(err)
+            }
+        }
+        fs::create_dir_all(destination)?;
+        Collector::dump_dir(source, destination).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::Other, "failed to copy module"
+            )
+        })?;
+        Ok(Self::success_status())
+    }
+
+    /// Returns the exit status for an update without the rsync command.
+    #[cfg(unix)]
+    fn success_status() -> ExitStatus {
+        std::os::unix::process::ExitStatusExt::from_raw(0)
+    }
+
+    /// Returns the exit status for an update without the rsync command.
+    #[cfg(windows)]
+    fn success_status() -> ExitStatus {
+        std::os::windows::process::ExitStatusExt::from_raw(0)
+    }
+
     /// Actually runs the rsync command.
+    ///
+    /// What could be learned from the command’s output is stored in
+    /// `output`.
     fn run(
         &self,
         source: &Module,
-        mut command: AsyncCommand
+        mut command: AsyncCommand,
+        output: &mut CommandOutput,
     ) -> Result<ExitStatus, io::Error> {
         // Because we can’t have a timeout on a child process with just std,
         // we resort to Tokio here: We fire up a current-thread runtime and
@@ -532,6 +906,7 @@ impl RsyncCommand {
                 tokio::io::BufReader::new
             );
             let mut stdout = Vec::new();
+            let mut refused = false;
             let res = tokio::try_join!(
                 match self.timeout {
                     None => Either::Left(child.wait().map(Ok)),
@@ -558,6 +933,9 @@ impl RsyncCommand {
                     if let Some(mut pipe) = stderr_pipe {
                         let mut line = Vec::new();
                         while pipe.read_until(b'\n', &mut line).await? != 0 {
+                            if Self::is_refused_line(&line) {
+                                refused = true;
+                            }
                             Self::log_err_line(source, &mut line);
                         }
                     }
@@ -583,10 +961,22 @@ impl RsyncCommand {
                     Err(err)
                 }
             };
+            output.refused = refused;
             if !stdout.is_empty() {
-                String::from_utf8_lossy(&stdout).lines().for_each(|l| {
-                    info!("{}: {}", source, l);
-                })
+                // The statistics are printed on every run, so they only
+                // go to the debug log.
+                let stdout = String::from_utf8_lossy(&stdout);
+                output.stats = Self::parse_stats(&stdout);
+                if output.stats.is_some() {
+                    stdout.lines().for_each(|l| {
+                        debug!("{}: {}", source, l);
+                    })
+                }
+                else {
+                    stdout.lines().for_each(|l| {
+                        info!("{}: {}", source, l);
+                    })
+                }
             }
             if let Err(ref err) = status {
                 warn!("{}: {}", source, err);
@@ -595,11 +985,71 @@ impl RsyncCommand {
         })
     }
 
+    /// Returns whether a line of error output reports a refused connection.
+    fn is_refused_line(line: &[u8]) -> bool {
+        line.windows(18).any(|window| window == b"Connection refused")
+    }
+
+    /// Parses the output of the `--stats` option.
+    ///
+    /// Returns `None` if the output doesn’t contain any statistics.
+    fn parse_stats(output: &str) -> Option<RsyncTransferStats> {
+        let mut res = RsyncTransferStats::default();
+        let mut found = false;
+        for line in output.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some(some) => some,
+                None => continue,
+            };
+            let (field, value) = match key.trim() {
+                // Since rsync 3.1, this line also contains the number of
+                // regular files, e.g., "1,234 (reg: 1,200, dir: 34)".
+                "Number of files" => (
+                    &mut res.files,
+                    value.split_once("reg:").map(|(_, reg)| {
+                        reg
+                    }).unwrap_or(value)
+                ),
+                "Number of regular files transferred"
+                | "Number of files transferred" => {
+                    (&mut res.transferred, value)
+                }
+                "Number of deleted files" => (&mut res.deleted, value),
+                "Total bytes received" => (&mut res.bytes_received, value),
+                "Total bytes sent" => (&mut res.bytes_sent, value),
+                _ => continue,
+            };
+            if let Some(value) = Self::parse_stats_number(value) {
+                *field = value;
+                found = true;
+            }
+        }
+        found.then_some(res)
+    }
+
+    /// Parses the number at the start of a value of the statistics.
+    ///
+    /// Depending on the locale, the number may contain thousands
+    /// separators which are ignored.
+    fn parse_stats_number(value: &str) -> Option<u64> {
+        let value = value.trim_start();
+        let end = value.find(|ch: char| {
+            !(ch.is_ascii_digit() || ch == ',' || ch == '.')
+        }).unwrap_or(value.len());
+        value[..end].chars().filter(char::is_ascii_digit)
+            .collect::<String>().parse().ok()
+    }
+
     /// Creates the rsync command.
+    ///
+    /// Since we can’t throttle the command ourselves, the lowest share of
+    /// the rates of `limiter` is passed via its `--bwlimit` option. The
+    /// share is fixed when the process starts.
     fn command(
         &self,
         source: &Module,
-        destination: &Path
+        destination: &Path,
+        limiter: &Limiter,
     ) -> Result<AsyncCommand, io::Error> {
         info!("rsyncing from {}.", source);
         fs::create_dir_all(destination)?;
@@ -620,10 +1070,15 @@ impl RsyncCommand {
         for item in &self.args {
             cmd.arg(item);
         }
-        cmd.arg("-rtO")
-           .arg("--delete")
-           .arg(source.to_string())
-           .arg(destination);
+        if let Some(rate) = limiter.rate() {
+            // The option takes kibibytes per second.
+            cmd.arg(format!("--bwlimit={}", cmp::max(rate / 1024, 1)));
+        }
+        cmd.arg("-rtO").arg("--delete");
+        if self.stats {
+            cmd.arg("--stats");
+        }
+        cmd.arg(source.to_string()).arg(destination);
         debug!("{}: Running command {:?}", source, cmd);
         Ok(cmd)
     }
@@ -726,6 +1181,141 @@ impl RsyncCommand {
 }
 
 
+//------------ CommandOutput -------------------------------------------------
+
+/// Information learned from the output of the rsync command.
+#[derive(Debug, Default)]
+struct CommandOutput {
+    /// The statistics printed by the command.
+    stats: Option<RsyncTransferStats>,
+
+    /// Whether the command reported that the connection was refused.
+    refused: bool,
+}
+
+
+//------------ RsyncCause ----------------------------------------------------
+
+/// The named cause of the outcome of an rsync update.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RsyncCause {
+    /// The update succeeded.
+    Success,
+
+    /// The update was skipped because of earlier failures.
+    Skipped,
+
+    /// The server didn’t respond in time.
+    Timeout,
+
+    /// The server refused the connection.
+    ConnectionRefused,
+
+    /// Some other error happened on the connection.
+    SocketError,
+
+    /// The server violated the rsync protocol or is incompatible.
+    ProtocolError,
+
+    /// Only some of the files could be transferred.
+    PartialTransfer,
+
+    /// Reading or writing local files failed.
+    FileError,
+
+    /// The command was invoked with unsupported options.
+    UsageError,
+
+    /// The command was killed by a signal.
+    Killed,
+
+    /// Something else went wrong.
+    Other,
+}
+
+impl RsyncCause {
+    /// Determines the cause from the status of an update.
+    ///
+    /// Exit codes are mapped as documented for the rsync command, errors
+    /// by their kind except for partial transfers of the built-in client.
+    /// If `refused` is `true`, a socket error is assumed to have been
+    /// caused by the server refusing the connection.
+    pub fn from_status(
+        status: &Result<ExitStatus, io::Error>, refused: bool
+    ) -> Self {
+        let code = match status {
+            Ok(status) => match status.code() {
+                Some(code) => code,
+                None => return RsyncCause::Killed,
+            },
+            Err(err) if rsync_native::PartialTransfer::is_cause(err) => {
+                return RsyncCause::PartialTransfer
+            }
+            Err(err) => {
+                return match err.kind() {
+                    io::ErrorKind::TimedOut => RsyncCause::Timeout,
+                    io::ErrorKind::ConnectionRefused => {
+                        RsyncCause::ConnectionRefused
+                    }
+                    io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::AddrNotAvailable => {
+                        RsyncCause::SocketError
+                    }
+                    io::ErrorKind::InvalidData
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::Unsupported => {
+                        RsyncCause::ProtocolError
+                    }
+                    _ => RsyncCause::Other,
+                }
+            }
+        };
+        match code {
+            0 => RsyncCause::Success,
+            1 | 4 => RsyncCause::UsageError,
+            2 | 5 | 12 => RsyncCause::ProtocolError,
+            3 | 11 => RsyncCause::FileError,
+            10 if refused => RsyncCause::ConnectionRefused,
+            10 => RsyncCause::SocketError,
+            20 => RsyncCause::Killed,
+            23..=25 => RsyncCause::PartialTransfer,
+            30 | 35 => RsyncCause::Timeout,
+            _ => RsyncCause::Other,
+        }
+    }
+
+    /// Returns whether the cause is a successful update.
+    pub fn is_success(self) -> bool {
+        matches!(self, RsyncCause::Success)
+    }
+
+    /// Returns the code used for the cause in metrics and status.
+    pub fn code(self) -> &'static str {
+        match self {
+            RsyncCause::Success => "success",
+            RsyncCause::Skipped => "skipped",
+            RsyncCause::Timeout => "timeout",
+            RsyncCause::ConnectionRefused => "connection-refused",
+            RsyncCause::SocketError => "socket-error",
+            RsyncCause::ProtocolError => "protocol-error",
+            RsyncCause::PartialTransfer => "partial-transfer",
+            RsyncCause::FileError => "file-error",
+            RsyncCause::UsageError => "usage-error",
+            RsyncCause::Killed => "killed",
+            RsyncCause::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for RsyncCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+
 //------------ WorkingDir ----------------------------------------------------
 
 /// The working directory of the rsync collector.
@@ -884,6 +1474,13 @@ impl ModuleSet {
         })
     }
 
+    /// Returns whether the module of a URI is included in the set.
+    pub fn contains(&self, uri: &uri::Rsync) -> bool {
+        self.authorities.get(uri.canonical_authority().as_ref()).map(|auth| {
+            auth.contains(uri.module_name())
+        }).unwrap_or(false)
+    }
+
     fn with_authority<F: FnOnce(&mut HashSet<String>) -> R, R>(
         &mut self, uri: &uri::Rsync, op: F,
     ) -> R {
@@ -901,3 +1498,143 @@ impl ModuleSet {
     }
 }
 
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_stats() {
+        let stats = RsyncCommand::parse_stats(
+            "\n\
+             Number of files: 1,234 (reg: 1,200, dir: 34)\n\
+             Number of created files: 3 (reg: 3)\n\
+             Number of deleted files: 2 (reg: 2)\n\
+             Number of regular files transferred: 5\n\
+             Total file size: 4,567,890 bytes\n\
+             Total transferred file size: 12,345 bytes\n\
+             Total bytes sent: 1.021\n\
+             Total bytes received: 23,456\n\
+             \n\
+             sent 1.021 bytes  received 23,456 bytes  1,234.50 bytes/sec\n"
+        ).unwrap();
+        assert_eq!(stats.files, 1200);
+        assert_eq!(stats.transferred, 5);
+        assert_eq!(stats.deleted, 2);
+        assert_eq!(stats.bytes_sent, 1021);
+        assert_eq!(stats.bytes_received, 23456);
+
+        // Older versions.
+        let stats = RsyncCommand::parse_stats(
+            "Number of files: 17\n\
+             Number of files transferred: 4\n"
+        ).unwrap();
+        assert_eq!(stats.files, 17);
+        assert_eq!(stats.transferred, 4);
+
+        assert!(RsyncCommand::parse_stats("some message\n").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cause_from_status() {
+        use std::os::unix::process::ExitStatusExt;
+
+        fn cause(code: i32, refused: bool) -> RsyncCause {
+            RsyncCause::from_status(
+                &Ok(ExitStatus::from_raw(code << 8)), refused
+            )
+        }
+
+        assert_eq!(cause(0, false), RsyncCause::Success);
+        assert_eq!(cause(10, false), RsyncCause::SocketError);
+        assert_eq!(cause(10, true), RsyncCause::ConnectionRefused);
+        assert_eq!(cause(23, false), RsyncCause::PartialTransfer);
+        assert_eq!(cause(30, false), RsyncCause::Timeout);
+        assert_eq!(cause(5, false), RsyncCause::ProtocolError);
+        assert_eq!(cause(99, false), RsyncCause::Other);
+        assert_eq!(
+            RsyncCause::from_status(&Ok(ExitStatus::from_raw(9)), false),
+            RsyncCause::Killed
+        );
+        assert_eq!(
+            RsyncCause::from_status(
+                &Err(io::ErrorKind::TimedOut.into()), false
+            ),
+            RsyncCause::Timeout
+        );
+        assert_eq!(
+            RsyncCause::from_status(
+                &Err(rsync_native::PartialTransfer.into()), false
+            ),
+            RsyncCause::PartialTransfer
+        );
+    }
+
+    #[test]
+    fn keep_and_replay_modules() {
+        /// Replaces the content of `dir` with a single file.
+        fn write(dir: &Path, name: &str, content: &[u8]) {
+            let _ = fs::remove_dir_all(dir);
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join(name), content).unwrap();
+        }
+
+        /// Returns the names and content of the files in `dir`.
+        fn read(dir: &Path) -> Vec<(String, Vec<u8>)> {
+            let mut res: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| {
+                let entry = entry.unwrap();
+                (
+                    entry.file_name().into_string().unwrap(),
+                    fs::read(entry.path()).unwrap()
+                )
+            }).collect();
+            res.sort();
+            res
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let keep_dir = dir.path().join("keep");
+        let module = Module::from_uri(
+            &uri::Rsync::from_str(
+                "rsync://rsync.example.net/module/"
+            ).unwrap()
+        ).into_owned();
+        let updated = dir.path().join("updated");
+        write(&updated, "first.roa", b"first");
+        RsyncCommand::keep(&keep_dir, &module, &updated);
+        write(&updated, "second.roa", b"second");
+        RsyncCommand::keep(&keep_dir, &module, &updated);
+
+        let mut config = Config::default_with_paths(
+            Default::default(), dir.path().join("cache")
+        );
+        config.rsync_replay_responses = Some(keep_dir);
+        let command = RsyncCommand::new(
+            &config, Arc::new(UriRewrites::new(&config).unwrap())
+        ).unwrap();
+
+        // Kept copies are replayed in order with the last one repeated.
+        let replayed = dir.path().join("replayed");
+        let first = vec![("first.roa".to_string(), b"first".to_vec())];
+        let second = vec![("second.roa".to_string(), b"second".to_vec())];
+        for expected in [first, second.clone(), second] {
+            let metrics = command.update(
+                &module, &replayed, &Limiter::default()
+            );
+            assert!(metrics.status.unwrap().success());
+            assert_eq!(read(&replayed), expected);
+        }
+
+        // A module without kept copies fails.
+        let other = Module::from_uri(
+            &uri::Rsync::from_str(
+                "rsync://rsync.example.net/other/"
+            ).unwrap()
+        ).into_owned();
+        let metrics = command.update(&other, &replayed, &Limiter::default());
+        assert!(metrics.status.is_err());
+    }
+}
@@ -0,0 +1,1566 @@
+//! A native client for the rsync daemon protocol.
+//!
+//! Instead of running an external rsync command, the rsync collector can
+//! use the client in this module to fetch modules directly from an rsync
+//! daemon. Only the part of the protocol we actually need is implemented:
+//! the client speaks protocol versions 30 and 31, always asks for a
+//! recursive transfer that preserves modification times, and fetches
+//! changed files in full rather than using the delta transfer algorithm.
+//! Since RPKI objects are small, the latter costs very little.
+//!
+//! Local files are considered current if their size and modification time
+//! match the file list sent by the server. Received files are checked
+//! against the MD5 checksum sent along with them, written to a temporary
+//! file first, and only then moved into place. Once a transfer has
+//! finished without errors, local files and directories that are not
+//! present in the file list are deleted.
+
+use std::{cmp, error, fmt, fs, io, str, thread};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use log::{debug, info, warn};
+use md5::{Digest, Md5};
+use rpki::uri;
+use crate::config::Config;
+use crate::metrics::RsyncTransferStats;
+use super::throttle::Limiter;
+
+
+//------------ Configuration Constants ---------------------------------------
+
+/// The port of the rsync daemon if the URI doesn’t provide one.
+const DEFAULT_PORT: u16 = 873;
+
+/// The timeout for establishing a TCP connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The protocol version we advertise.
+const PROTOCOL_VERSION: u32 = 31;
+
+/// The oldest protocol version we support.
+const MIN_PROTOCOL_VERSION: u32 = 30;
+
+/// The maximum length of a line during the initial exchange.
+const MAX_LINE_LEN: usize = 4096;
+
+/// The maximum length of a file name.
+const MAX_NAME_LEN: usize = 4096;
+
+/// The size of the buffer for outgoing data before it is sent.
+const WRITE_BUF_SIZE: usize = 32 * 1024;
+
+
+//------------ Client --------------------------------------------------------
+
+/// A client for updating modules from an rsync daemon.
+#[derive(Clone, Debug)]
+pub struct Client {
+    /// The time allowed for updating a module.
+    timeout: Option<Duration>,
+
+    /// The maximum size of files to be transferred.
+    max_object_size: Option<u64>,
+}
+
+impl Client {
+    /// Creates a new client from the config.
+    pub fn new(config: &Config) -> Self {
+        Client {
+            timeout: config.rsync_timeout,
+            max_object_size: config.max_object_size,
+        }
+    }
+
+    /// Updates the content of `destination` from the module at `source`.
+    ///
+    /// Returns the transfer statistics if the update succeeded.
+    ///
+    /// Receiving data is throttled by `limiter`.
+    pub fn update(
+        &self,
+        source: &uri::Rsync,
+        destination: &Path,
+        limiter: &Limiter,
+    ) -> Result<RsyncTransferStats, io::Error> {
+        fs::create_dir_all(destination)?;
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let stream = self.connect(source.authority())?;
+        let res = self.transfer(
+            source, destination, &stream, deadline, limiter
+        );
+        if res.is_err() {
+            // Make sure the request thread can’t get stuck.
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        res
+    }
+
+    /// Connects to the rsync daemon at the given authority.
+    fn connect(&self, authority: &str) -> Result<TcpStream, io::Error> {
+        let mut err = None;
+        for addr in socket_addrs(authority)? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_write_timeout(self.timeout)?;
+                    stream.set_nodelay(true)?;
+                    return Ok(stream)
+                }
+                Err(some) => err = Some(some)
+            }
+        }
+        Err(err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no address for host")
+        }))
+    }
+
+    /// Performs the transfer over an established connection.
+    fn transfer(
+        &self,
+        source: &uri::Rsync,
+        destination: &Path,
+        stream: &TcpStream,
+        deadline: Option<Instant>,
+        limiter: &Limiter,
+    ) -> Result<RsyncTransferStats, io::Error> {
+        let mut reader = Reader::new(
+            stream.try_clone()?, source, deadline, limiter.clone()
+        );
+        let mut writer = Writer::new(stream.try_clone()?);
+        let protocol = Self::handshake(source, &mut reader, &mut writer)?;
+
+        // From here on, data in both directions is multiplexed. We start
+        // by sending an empty filter list.
+        reader.multiplex = true;
+        writer.multiplex = true;
+        writer.write_i32(0)?;
+        writer.flush()?;
+
+        let (mut entries, io_error) = read_file_list(&mut reader)?;
+        reader.io_error |= io_error;
+        entries.sort_by_cached_key(Entry::sort_key);
+        let (requests, keep) = self.prepare(source, destination, &entries)?;
+        let files = entries.iter().filter(|entry| entry.is_file()).count();
+        let mut stats = RsyncTransferStats {
+            files: files as u64,
+            .. Default::default()
+        };
+
+        // The requests are sent by a separate thread so the server doesn’t
+        // stall on a full socket buffer while we are busy receiving.
+        let requester = thread::spawn(move || {
+            writer.request(requests, protocol)
+        });
+        stats.transferred = self.receive(
+            &mut reader, &entries, destination
+        )?;
+
+        // The final statistics. We don’t use them.
+        for _ in 0..5 {
+            read_varlong(&mut reader, 3)?;
+        }
+        if protocol >= 31 && reader.read_ndx()? != NDX_DONE {
+            return Err(invalid_data("missing final goodbye"))
+        }
+        stats.bytes_received = reader.bytes;
+        stats.bytes_sent = requester.join().map_err(|_| {
+            io::Error::new(io::ErrorKind::Other, "request thread panicked")
+        })??;
+
+        if reader.io_error != 0 {
+            warn!(
+                "{}: server reported I/O errors, skipping file deletion.",
+                source
+            );
+        }
+        else {
+            delete_extraneous(
+                destination, destination, &keep, &mut stats.deleted
+            )?;
+        }
+        if reader.io_error != 0 || reader.errors > 0 || reader.not_sent > 0 {
+            return Err(PartialTransfer.into())
+        }
+        Ok(stats)
+    }
+
+    /// Performs the initial exchange with the daemon.
+    ///
+    /// Returns the protocol version to use.
+    fn handshake(
+        source: &uri::Rsync,
+        reader: &mut Reader,
+        writer: &mut Writer,
+    ) -> Result<u32, io::Error> {
+        writer.write_raw(
+            format!("@RSYNCD: {}.0\n", PROTOCOL_VERSION).as_bytes()
+        )?;
+        writer.flush()?;
+        let greeting = reader.read_line()?;
+        let remote = parse_greeting(&greeting).ok_or_else(|| {
+            invalid_data(format!("unexpected greeting '{}'", greeting))
+        })?;
+        if remote < MIN_PROTOCOL_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported protocol version {}", remote)
+            ))
+        }
+        let protocol = cmp::min(remote, PROTOCOL_VERSION);
+
+        writer.write_raw(source.module_name().as_bytes())?;
+        writer.write_raw(b"\n")?;
+        writer.flush()?;
+        loop {
+            let line = reader.read_line()?;
+            if line == "@RSYNCD: OK" {
+                break
+            }
+            else if line.starts_with("@ERROR") {
+                return Err(io::Error::new(io::ErrorKind::Other, line))
+            }
+            else if line.starts_with("@RSYNCD: AUTHREQD") {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "module requires authentication"
+                ))
+            }
+            else if line.starts_with("@RSYNCD: EXIT") {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other, "server closed connection"
+                ))
+            }
+            else {
+                debug!("{}: {}", source, line);
+            }
+        }
+
+        let path = format!("{}/", source.module_name());
+        for arg in ["--server", "--sender", "-rt", ".", path.as_str()] {
+            writer.write_raw(arg.as_bytes())?;
+            writer.write_raw(b"\0")?;
+        }
+        writer.write_raw(b"\0")?;
+        writer.flush()?;
+
+        // If the server didn’t like our arguments, it sends an error line
+        // instead of the compatibility flags.
+        if reader.peek_u8()? == b'@' {
+            let line = reader.read_line()?;
+            return Err(io::Error::new(io::ErrorKind::Other, line))
+        }
+        let compat = read_varint(reader)?;
+        if compat & (CF_INC_RECURSE | CF_VARINT_FLIST_FLAGS) != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported compatibility flags {:#x}", compat)
+            ))
+        }
+        let _checksum_seed = read_i32(reader)?;
+        Ok(protocol)
+    }
+
+    /// Prepares the destination for the transfer.
+    ///
+    /// Creates all directories and determines which files need to be
+    /// requested. Returns the indexes of these files and the relative
+    /// paths of everything that should be kept.
+    fn prepare(
+        &self,
+        source: &uri::Rsync,
+        destination: &Path,
+        entries: &[Entry],
+    ) -> Result<(Vec<i32>, HashSet<PathBuf>), io::Error> {
+        let mut requests = Vec::new();
+        let mut keep = HashSet::new();
+        for (ndx, entry) in entries.iter().enumerate() {
+            let name = match entry.safe_name() {
+                Some(name) => name,
+                None => {
+                    warn!(
+                        "{}: skipping file with unsafe name '{}'.",
+                        source, String::from_utf8_lossy(&entry.name)
+                    );
+                    continue
+                }
+            };
+            if name == "." {
+                continue
+            }
+            let path = destination.join(name);
+            if entry.is_dir() {
+                keep.insert(PathBuf::from(name));
+                if let Ok(metadata) = fs::symlink_metadata(&path) {
+                    if !metadata.is_dir() {
+                        fs::remove_file(&path)?;
+                    }
+                }
+                fs::create_dir_all(&path)?;
+            }
+            else if entry.is_file() {
+                keep.insert(PathBuf::from(name));
+                if let Some(max_size) = self.max_object_size {
+                    if entry.size > max_size {
+                        warn!(
+                            "{}: skipping {} of size {} exceeding the \
+                             maximum object size.",
+                            source, name, entry.size
+                        );
+                        continue
+                    }
+                }
+                if !entry.is_current(&path) {
+                    requests.push(ndx as i32);
+                }
+            }
+        }
+        Ok((requests, keep))
+    }
+
+    /// Receives the requested files.
+    ///
+    /// Returns the number of files received.
+    fn receive(
+        &self,
+        reader: &mut Reader,
+        entries: &[Entry],
+        destination: &Path,
+    ) -> Result<u64, io::Error> {
+        let mut transferred = 0;
+        let mut done = 0;
+        while done < 3 {
+            let ndx = reader.read_ndx()?;
+            if ndx == NDX_DONE {
+                done += 1;
+                continue
+            }
+            let entry = usize::try_from(ndx).ok().and_then(|ndx| {
+                entries.get(ndx)
+            }).ok_or_else(|| invalid_data("invalid file index"))?;
+            let iflags = read_u16(reader)?;
+            if iflags & ITEM_BASIS_TYPE_FOLLOWS != 0 {
+                read_u8(reader)?;
+            }
+            if iflags & ITEM_XNAME_FOLLOWS != 0 {
+                read_vstring(reader)?;
+            }
+            if iflags & ITEM_TRANSFER == 0 {
+                continue
+            }
+            if self.receive_file(reader, entry, destination)? {
+                transferred += 1;
+            }
+        }
+        Ok(transferred)
+    }
+
+    /// Receives a single file.
+    ///
+    /// Returns whether the file was received correctly.
+    fn receive_file(
+        &self,
+        reader: &mut Reader,
+        entry: &Entry,
+        destination: &Path,
+    ) -> Result<bool, io::Error> {
+        let name = entry.safe_name().ok_or_else(|| {
+            invalid_data("received file with unsafe name")
+        })?;
+        let path = destination.join(name);
+        let parent = path.parent().unwrap_or(destination);
+        fs::create_dir_all(parent)?;
+        let mut tmp = tempfile::NamedTempFile::new_in(parent)?;
+
+        // The checksum header. Since we didn’t send any block checksums,
+        // it should be empty.
+        for _ in 0..4 {
+            read_i32(reader)?;
+        }
+
+        let mut md5 = Md5::new();
+        let mut buf = vec![0; WRITE_BUF_SIZE];
+        let mut size = 0u64;
+        loop {
+            let token = read_i32(reader)?;
+            if token == 0 {
+                break
+            }
+            let mut len = usize::try_from(token).map_err(|_| {
+                invalid_data("unexpected block match")
+            })?;
+            size += len as u64;
+            if matches!(self.max_object_size, Some(max) if size > max) {
+                return Err(invalid_data(
+                    format!("{} exceeds maximum object size", name)
+                ))
+            }
+            while len > 0 {
+                let chunk = cmp::min(len, buf.len());
+                reader.read_exact(&mut buf[..chunk])?;
+                md5.update(&buf[..chunk]);
+                tmp.write_all(&buf[..chunk])?;
+                len -= chunk;
+            }
+        }
+        let mut sum = [0u8; 16];
+        reader.read_exact(&mut sum)?;
+        if md5.finalize().as_slice() != sum {
+            warn!("{}: checksum mismatch for {}.", reader.source, name);
+            reader.errors += 1;
+            return Ok(false)
+        }
+
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if metadata.is_dir() {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        tmp.persist(&path).map_err(|err| err.error)?;
+        set_mtime(&path, entry.mtime)?;
+        Ok(true)
+    }
+}
+
+
+//------------ Reader --------------------------------------------------------
+
+/// The receiving side of a connection to an rsync daemon.
+///
+/// Once multiplexing has been enabled, the `Read` implementation only
+/// provides the data stream. All other messages are processed as they
+/// arrive.
+struct Reader<'a> {
+    /// The socket.
+    sock: BufReader<TcpStream>,
+
+    /// The module we are updating for log messages.
+    source: &'a uri::Rsync,
+
+    /// The time when the transfer has to be finished.
+    deadline: Option<Instant>,
+
+    /// The bandwidth limits for reading from the socket.
+    limiter: Limiter,
+
+    /// Whether the input is multiplexed.
+    multiplex: bool,
+
+    /// The data remaining in the current data message.
+    remaining: usize,
+
+    /// The state for reading file indexes.
+    ndx: NdxState,
+
+    /// The number of bytes read from the socket.
+    bytes: u64,
+
+    /// The I/O error flags reported by the server.
+    io_error: i32,
+
+    /// The number of error messages received from the server.
+    errors: usize,
+
+    /// The number of requested files the server didn’t send.
+    not_sent: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a new reader.
+    fn new(
+        sock: TcpStream,
+        source: &'a uri::Rsync,
+        deadline: Option<Instant>,
+        limiter: Limiter,
+    ) -> Self {
+        Reader {
+            sock: BufReader::new(sock),
+            source,
+            deadline,
+            limiter,
+            multiplex: false,
+            remaining: 0,
+            ndx: Default::default(),
+            bytes: 0,
+            io_error: 0,
+            errors: 0,
+            not_sent: 0,
+        }
+    }
+
+    /// Checks the deadline and updates the socket’s read timeout.
+    fn check_deadline(&mut self) -> Result<(), io::Error> {
+        if let Some(deadline) = self.deadline {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut, "rsync transfer reached time out"
+                ))
+            }
+            self.sock.get_ref().set_read_timeout(Some(deadline - now))?;
+        }
+        Ok(())
+    }
+
+    /// Accounts for `len` bytes read from the socket.
+    ///
+    /// Blocks if necessary to stay within the bandwidth limits.
+    fn account(&mut self, len: usize) {
+        self.bytes += len as u64;
+        self.limiter.consume(len);
+    }
+
+    /// Reads exactly enough bytes to fill `buf` from the socket.
+    fn read_raw(&mut self, buf: &mut [u8]) -> Result<(), io::Error> {
+        self.sock.read_exact(buf)?;
+        self.account(buf.len());
+        Ok(())
+    }
+
+    /// Returns the next byte from the socket without consuming it.
+    fn peek_u8(&mut self) -> Result<u8, io::Error> {
+        self.check_deadline()?;
+        self.sock.fill_buf()?.first().copied().ok_or_else(|| {
+            io::ErrorKind::UnexpectedEof.into()
+        })
+    }
+
+    /// Reads a line during the initial exchange.
+    fn read_line(&mut self) -> Result<String, io::Error> {
+        self.check_deadline()?;
+        let mut line = Vec::new();
+        loop {
+            let mut ch = [0u8];
+            self.read_raw(&mut ch)?;
+            match ch[0] {
+                b'\n' => break,
+                b'\r' => { }
+                ch => {
+                    if line.len() >= MAX_LINE_LEN {
+                        return Err(invalid_data("overlong line"))
+                    }
+                    line.push(ch)
+                }
+            }
+        }
+        Ok(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    /// Reads a file index.
+    fn read_ndx(&mut self) -> Result<i32, io::Error> {
+        let mut ndx = self.ndx;
+        let res = ndx.read(self);
+        self.ndx = ndx;
+        res
+    }
+
+    /// Reads the next message header and processes non-data messages.
+    fn read_message(&mut self) -> Result<(), io::Error> {
+        self.check_deadline()?;
+        let mut header = [0u8; 4];
+        self.read_raw(&mut header)?;
+        let header = u32::from_le_bytes(header);
+        let len = (header & 0xFF_FFFF) as usize;
+        let tag = (header >> 24).checked_sub(MPLEX_BASE).ok_or_else(|| {
+            invalid_data("invalid message header")
+        })?;
+        if tag == MSG_DATA {
+            self.remaining = len;
+            return Ok(())
+        }
+        let mut payload = vec![0; len];
+        self.read_raw(&mut payload)?;
+        match tag {
+            MSG_INFO | MSG_LOG | MSG_CLIENT => {
+                for line in String::from_utf8_lossy(&payload).lines() {
+                    if !line.is_empty() {
+                        info!("{}: {}", self.source, line);
+                    }
+                }
+            }
+            MSG_WARNING => {
+                for line in String::from_utf8_lossy(&payload).lines() {
+                    if !line.is_empty() {
+                        warn!("{}: {}", self.source, line);
+                    }
+                }
+            }
+            MSG_ERROR_XFER | MSG_ERROR | MSG_ERROR_SOCKET
+            | MSG_ERROR_UTF8 => {
+                for line in String::from_utf8_lossy(&payload).lines() {
+                    if !line.is_empty() {
+                        warn!("{}: {}", self.source, line);
+                    }
+                }
+                self.errors += 1;
+            }
+            MSG_IO_ERROR => {
+                self.io_error |= payload_int(&payload).unwrap_or(1);
+            }
+            MSG_NO_SEND => {
+                self.not_sent += 1;
+            }
+            MSG_ERROR_EXIT => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    match payload_int(&payload) {
+                        Some(code) => {
+                            format!("server exited with code {}", code)
+                        }
+                        None => "server exited with an error".into()
+                    }
+                ))
+            }
+            _ => { }
+        }
+        Ok(())
+    }
+}
+
+impl Read for Reader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        if !self.multiplex {
+            let len = self.sock.read(buf)?;
+            self.account(len);
+            return Ok(len)
+        }
+        while self.remaining == 0 {
+            self.read_message()?;
+        }
+        let len = cmp::min(buf.len(), self.remaining);
+        let len = self.sock.read(&mut buf[..len])?;
+        if len == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into())
+        }
+        self.remaining -= len;
+        self.account(len);
+        Ok(len)
+    }
+}
+
+
+//------------ Writer --------------------------------------------------------
+
+/// The sending side of a connection to an rsync daemon.
+struct Writer {
+    /// The socket.
+    sock: TcpStream,
+
+    /// Whether the output is multiplexed.
+    multiplex: bool,
+
+    /// Data not yet sent.
+    buf: Vec<u8>,
+
+    /// The state for writing file indexes.
+    ndx: NdxState,
+
+    /// The number of bytes written to the socket.
+    bytes: u64,
+}
+
+impl Writer {
+    /// Creates a new writer.
+    fn new(sock: TcpStream) -> Self {
+        Writer {
+            sock,
+            multiplex: false,
+            buf: Vec::new(),
+            ndx: Default::default(),
+            bytes: 0,
+        }
+    }
+
+    /// Adds data to the output.
+    fn write_raw(&mut self, data: &[u8]) -> Result<(), io::Error> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= WRITE_BUF_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Adds a 16 bit integer to the output.
+    fn write_u16(&mut self, value: u16) -> Result<(), io::Error> {
+        self.write_raw(&value.to_le_bytes())
+    }
+
+    /// Adds a 32 bit integer to the output.
+    fn write_i32(&mut self, value: i32) -> Result<(), io::Error> {
+        self.write_raw(&value.to_le_bytes())
+    }
+
+    /// Adds a file index to the output.
+    fn write_ndx(&mut self, ndx: i32) -> Result<(), io::Error> {
+        let mut buf = Vec::new();
+        self.ndx.write(ndx, &mut buf);
+        self.write_raw(&buf)
+    }
+
+    /// Sends all buffered data.
+    fn flush(&mut self) -> Result<(), io::Error> {
+        if self.multiplex {
+            for chunk in self.buf.chunks(0xFF_FFFF) {
+                let header = ((MPLEX_BASE + MSG_DATA) << 24)
+                    | chunk.len() as u32;
+                self.sock.write_all(&header.to_le_bytes())?;
+                self.sock.write_all(chunk)?;
+                self.bytes += 4;
+            }
+        }
+        else {
+            self.sock.write_all(&self.buf)?;
+        }
+        self.bytes += self.buf.len() as u64;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Requests the given files and finishes the session.
+    ///
+    /// Returns the number of bytes written to the socket.
+    fn request(
+        mut self, requests: Vec<i32>, protocol: u32
+    ) -> Result<u64, io::Error> {
+        for ndx in requests {
+            self.write_ndx(ndx)?;
+            self.write_u16(ITEM_TRANSFER)?;
+            // An empty checksum header requests the whole file.
+            for _ in 0..4 {
+                self.write_i32(0)?;
+            }
+        }
+
+        // End all three phases and then say goodbye. Protocol 31 adds
+        // another round of goodbyes.
+        for _ in 0..4 {
+            self.write_ndx(NDX_DONE)?;
+        }
+        if protocol >= 31 {
+            self.write_ndx(NDX_DONE)?;
+        }
+        self.flush()?;
+        Ok(self.bytes)
+    }
+}
+
+
+//------------ NdxState ------------------------------------------------------
+
+/// The state for encoding file indexes.
+///
+/// File indexes are encoded as the difference to the previous index of the
+/// same sign. Each direction keeps its own state.
+#[derive(Clone, Copy, Debug)]
+struct NdxState {
+    /// The previous non-negative index.
+    prev_positive: i32,
+
+    /// The absolute value of the previous negative index.
+    prev_negative: i32,
+}
+
+impl Default for NdxState {
+    fn default() -> Self {
+        NdxState { prev_positive: -1, prev_negative: 1 }
+    }
+}
+
+impl NdxState {
+    /// Reads a file index.
+    fn read(&mut self, source: &mut impl Read) -> Result<i32, io::Error> {
+        let mut first = read_u8(source)?;
+        let negative = match first {
+            0 => return Ok(NDX_DONE),
+            0xFF => {
+                first = read_u8(source)?;
+                true
+            }
+            _ => false
+        };
+        let prev = if negative {
+            &mut self.prev_negative
+        }
+        else {
+            &mut self.prev_positive
+        };
+        let num = if first == 0xFE {
+            let mut buf = [0u8; 2];
+            source.read_exact(&mut buf)?;
+            if buf[0] & 0x80 != 0 {
+                let mut rest = [0u8; 2];
+                source.read_exact(&mut rest)?;
+                i32::from_le_bytes([buf[1], rest[0], rest[1], buf[0] & 0x7F])
+            }
+            else {
+                (i32::from(buf[0]) << 8) + i32::from(buf[1]) + *prev
+            }
+        }
+        else {
+            i32::from(first) + *prev
+        };
+        *prev = num;
+        Ok(if negative { -num } else { num })
+    }
+
+    /// Appends the encoding of a file index to `target`.
+    fn write(&mut self, ndx: i32, target: &mut Vec<u8>) {
+        let diff = if ndx >= 0 {
+            let diff = ndx - self.prev_positive;
+            self.prev_positive = ndx;
+            diff
+        }
+        else if ndx == NDX_DONE {
+            target.push(0);
+            return
+        }
+        else {
+            target.push(0xFF);
+            let diff = -ndx - self.prev_negative;
+            self.prev_negative = -ndx;
+            diff
+        };
+        let ndx = ndx.abs();
+        if diff > 0 && diff < 0xFE {
+            target.push(diff as u8);
+        }
+        else if !(0..=0x7FFF).contains(&diff) {
+            let bytes = ndx.to_le_bytes();
+            target.extend_from_slice(&[
+                0xFE, bytes[3] | 0x80, bytes[0], bytes[1], bytes[2]
+            ]);
+        }
+        else {
+            target.extend_from_slice(&[0xFE, (diff >> 8) as u8, diff as u8]);
+        }
+    }
+}
+
+
+//------------ Entry ---------------------------------------------------------
+
+/// An entry of the file list.
+#[derive(Clone, Debug)]
+struct Entry {
+    /// The path of the entry relative to the module.
+    name: Vec<u8>,
+
+    /// The file mode.
+    mode: u32,
+
+    /// The size of the file.
+    size: u64,
+
+    /// The modification time as seconds since the Unix epoch.
+    mtime: i64,
+}
+
+impl Entry {
+    /// Returns whether the entry is a directory.
+    fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    /// Returns whether the entry is a regular file.
+    fn is_file(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    /// Returns the name if it is safe to use as a relative path.
+    fn safe_name(&self) -> Option<&str> {
+        let name = str::from_utf8(&self.name).ok()?;
+        if name == "." {
+            return Some(name)
+        }
+        if name.split('/').any(|item| {
+            item.is_empty() || item == "." || item == ".."
+            || item.contains('\\')
+        }) {
+            return None
+        }
+        if Path::new(name).components().any(|item| {
+            !matches!(item, Component::Normal(_))
+        }) {
+            return None
+        }
+        Some(name)
+    }
+
+    /// Returns whether the local file at `path` matches the entry.
+    fn is_current(&self, path: &Path) -> bool {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => return false,
+        };
+        if !metadata.is_file() || metadata.len() != self.size {
+            return false
+        }
+        let mtime = metadata.modified().ok().and_then(|mtime| {
+            mtime.duration_since(UNIX_EPOCH).ok()
+        });
+        matches!(mtime, Some(mtime) if mtime.as_secs() as i64 == self.mtime)
+    }
+
+    /// Returns the key for sorting the file list.
+    ///
+    /// The file list is sorted component by component. Within each
+    /// directory, files come before directories, and a directory sorts as
+    /// if its name ended in a slash. The top directory comes first.
+    fn sort_key(&self) -> Vec<(bool, Vec<u8>)> {
+        if self.name == b"." {
+            return Vec::new()
+        }
+        let count = self.name.split(|&ch| ch == b'/').count();
+        self.name.split(|&ch| ch == b'/').enumerate().map(|(i, item)| {
+            if i + 1 < count || self.is_dir() {
+                let mut item = item.to_vec();
+                item.push(b'/');
+                (true, item)
+            }
+            else {
+                (false, item.to_vec())
+            }
+        }).collect()
+    }
+}
+
+
+//------------ PartialTransfer -----------------------------------------------
+
+/// Only some of the files could be transferred.
+///
+/// This is the error returned in the cases the rsync command exits with
+/// code 23 or 24.
+#[derive(Clone, Copy, Debug)]
+pub struct PartialTransfer;
+
+impl PartialTransfer {
+    /// Returns whether `err` was caused by a partial transfer.
+    pub fn is_cause(err: &io::Error) -> bool {
+        matches!(err.get_ref(), Some(err) if err.is::<PartialTransfer>())
+    }
+}
+
+impl From<PartialTransfer> for io::Error {
+    fn from(err: PartialTransfer) -> Self {
+        io::Error::new(io::ErrorKind::Other, err)
+    }
+}
+
+impl fmt::Display for PartialTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("partial transfer due to errors")
+    }
+}
+
+impl error::Error for PartialTransfer { }
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Reads the file list.
+///
+/// Returns the unsorted entries and the I/O error flags sent at the end of
+/// the list.
+fn read_file_list(
+    source: &mut impl Read
+) -> Result<(Vec<Entry>, i32), io::Error> {
+    let mut res = Vec::new();
+    let mut io_error = 0;
+    let mut name = Vec::new();
+    let mut mode = 0;
+    let mut mtime = 0;
+    loop {
+        let mut flags = u32::from(read_u8(source)?);
+        if flags == 0 {
+            break
+        }
+        if flags & XMIT_EXTENDED_FLAGS != 0 {
+            flags |= u32::from(read_u8(source)?) << 8;
+        }
+        if flags == XMIT_EXTENDED_FLAGS | XMIT_IO_ERROR_ENDLIST {
+            io_error = read_varint(source)?;
+            break
+        }
+
+        let keep = if flags & XMIT_SAME_NAME != 0 {
+            usize::from(read_u8(source)?)
+        }
+        else {
+            0
+        };
+        let len = if flags & XMIT_LONG_NAME != 0 {
+            usize::try_from(read_varint(source)?).map_err(|_| {
+                invalid_data("invalid file name length")
+            })?
+        }
+        else {
+            usize::from(read_u8(source)?)
+        };
+        if keep > name.len() || keep + len > MAX_NAME_LEN {
+            return Err(invalid_data("invalid file name length"))
+        }
+        name.truncate(keep);
+        name.resize(keep + len, 0);
+        source.read_exact(&mut name[keep..])?;
+
+        let size = u64::try_from(read_varlong(source, 3)?).map_err(|_| {
+            invalid_data("invalid file size")
+        })?;
+        if flags & XMIT_SAME_TIME == 0 {
+            mtime = read_varlong(source, 4)?;
+        }
+        if flags & XMIT_MOD_NSEC != 0 {
+            read_varint(source)?;
+        }
+        if flags & XMIT_SAME_MODE == 0 {
+            mode = read_i32(source)? as u32;
+        }
+        res.push(Entry { name: name.clone(), mode, size, mtime });
+    }
+    Ok((res, io_error))
+}
+
+/// Deletes everything under `dir` that isn’t in `keep`.
+///
+/// The paths in `keep` are relative to `base`.
+fn delete_extraneous(
+    base: &Path,
+    dir: &Path,
+    keep: &HashSet<PathBuf>,
+    deleted: &mut u64,
+) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let relative = path.strip_prefix(base).unwrap_or(&path);
+        if keep.contains(relative) {
+            if file_type.is_dir() {
+                delete_extraneous(base, &path, keep, deleted)?;
+            }
+            continue
+        }
+        if file_type.is_dir() {
+            fs::remove_dir_all(&path)?;
+        }
+        else {
+            fs::remove_file(&path)?;
+        }
+        *deleted += 1;
+    }
+    Ok(())
+}
+
+/// Returns the socket addresses for an rsync URI authority.
+fn socket_addrs(authority: &str) -> Result<Vec<SocketAddr>, io::Error> {
+    let has_port = authority.rsplit_once(':').map(|(host, port)| {
+        !port.is_empty() && port.bytes().all(|ch| ch.is_ascii_digit())
+        && (!host.contains(':') || host.ends_with(']'))
+    }).unwrap_or(false);
+    if has_port {
+        Ok(authority.to_socket_addrs()?.collect())
+    }
+    else {
+        let host = authority.strip_prefix('[').and_then(|host| {
+            host.strip_suffix(']')
+        }).unwrap_or(authority);
+        Ok((host, DEFAULT_PORT).to_socket_addrs()?.collect())
+    }
+}
+
+/// Returns the protocol version from the server’s greeting.
+fn parse_greeting(line: &str) -> Option<u32> {
+    line.strip_prefix("@RSYNCD: ")?.split(['.', ' ']).next()?.parse().ok()
+}
+
+/// Sets the modification time of a file.
+#[cfg(unix)]
+fn set_mtime(path: &Path, mtime: i64) -> Result<(), io::Error> {
+    use nix::sys::time::{TimeVal, TimeValLike};
+
+    let time = TimeVal::seconds(mtime);
+    nix::sys::stat::utimes(path, &time, &time).map_err(Into::into)
+}
+
+/// Sets the modification time of a file.
+///
+/// This isn’t supported on this platform, so files will always be
+/// transferred again.
+#[cfg(not(unix))]
+fn set_mtime(_path: &Path, _mtime: i64) -> Result<(), io::Error> {
+    Ok(())
+}
+
+/// Returns the integer contained in a message payload.
+fn payload_int(payload: &[u8]) -> Option<i32> {
+    <[u8; 4]>::try_from(payload).ok().map(i32::from_le_bytes)
+}
+
+/// Creates an invalid data error.
+fn invalid_data(
+    msg: impl Into<Box<dyn std::error::Error + Send + Sync>>
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u8(source: &mut impl Read) -> Result<u8, io::Error> {
+    let mut buf = [0u8; 1];
+    source.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16(source: &mut impl Read) -> Result<u16, io::Error> {
+    let mut buf = [0u8; 2];
+    source.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_i32(source: &mut impl Read) -> Result<i32, io::Error> {
+    let mut buf = [0u8; 4];
+    source.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+/// Reads a variable length 32 bit integer.
+///
+/// The number of extra bytes is encoded in the high bits of the first
+/// byte which also provides the most significant bits of the value.
+fn read_varint(source: &mut impl Read) -> Result<i32, io::Error> {
+    let first = read_u8(source)?;
+    let extra = INT_BYTE_EXTRA[usize::from(first / 4)];
+    if extra == 0 {
+        return Ok(i32::from(first))
+    }
+    if extra > 4 {
+        return Err(invalid_data("overflow in varint"))
+    }
+    let mut buf = [0u8; 5];
+    source.read_exact(&mut buf[..extra])?;
+    buf[extra] = first & ((1u8 << (8 - extra)) - 1);
+    Ok(i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+}
+
+/// Reads a variable length 64 bit integer.
+///
+/// At least `min_bytes` bytes are always present.
+fn read_varlong(
+    source: &mut impl Read, min_bytes: usize
+) -> Result<i64, io::Error> {
+    let mut head = [0u8; 8];
+    source.read_exact(&mut head[..min_bytes])?;
+    let mut buf = [0u8; 9];
+    buf[..min_bytes - 1].copy_from_slice(&head[1..min_bytes]);
+    let extra = INT_BYTE_EXTRA[usize::from(head[0] / 4)];
+    if extra == 0 {
+        buf[min_bytes - 1] = head[0];
+    }
+    else {
+        if min_bytes + extra > 9 {
+            return Err(invalid_data("overflow in varlong"))
+        }
+        source.read_exact(&mut buf[min_bytes - 1..min_bytes - 1 + extra])?;
+        buf[min_bytes + extra - 1] = head[0] & ((1u8 << (8 - extra)) - 1);
+    }
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&buf[..8]);
+    Ok(i64::from_le_bytes(value))
+}
+
+/// Reads a string with a one or two byte length prefix.
+fn read_vstring(source: &mut impl Read) -> Result<Vec<u8>, io::Error> {
+    let mut len = usize::from(read_u8(source)?);
+    if len & 0x80 != 0 {
+        len = (len & 0x7F) * 0x100 + usize::from(read_u8(source)?);
+    }
+    let mut res = vec![0; len];
+    source.read_exact(&mut res)?;
+    Ok(res)
+}
+
+
+//------------ Protocol Constants --------------------------------------------
+
+/// The file index signalling the end of a phase.
+const NDX_DONE: i32 = -1;
+
+/// The number of extra bytes in a varint by the first byte divided by 4.
+const INT_BYTE_EXTRA: [usize; 64] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 5, 6,
+];
+
+// Compatibility flags.
+const CF_INC_RECURSE: i32 = 1 << 0;
+const CF_VARINT_FLIST_FLAGS: i32 = 1 << 7;
+
+// Multiplexed message tags.
+const MPLEX_BASE: u32 = 7;
+const MSG_DATA: u32 = 0;
+const MSG_ERROR_XFER: u32 = 1;
+const MSG_INFO: u32 = 2;
+const MSG_ERROR: u32 = 3;
+const MSG_WARNING: u32 = 4;
+const MSG_ERROR_SOCKET: u32 = 5;
+const MSG_LOG: u32 = 6;
+const MSG_CLIENT: u32 = 7;
+const MSG_ERROR_UTF8: u32 = 8;
+const MSG_IO_ERROR: u32 = 22;
+const MSG_ERROR_EXIT: u32 = 86;
+const MSG_NO_SEND: u32 = 102;
+
+// File list flags.
+const XMIT_SAME_MODE: u32 = 1 << 1;
+const XMIT_EXTENDED_FLAGS: u32 = 1 << 2;
+const XMIT_SAME_NAME: u32 = 1 << 5;
+const XMIT_LONG_NAME: u32 = 1 << 6;
+const XMIT_SAME_TIME: u32 = 1 << 7;
+const XMIT_IO_ERROR_ENDLIST: u32 = 1 << 12;
+const XMIT_MOD_NSEC: u32 = 1 << 13;
+
+// Item flags.
+const ITEM_BASIS_TYPE_FOLLOWS: u16 = 1 << 11;
+const ITEM_XNAME_FOLLOWS: u16 = 1 << 12;
+const ITEM_TRANSFER: u16 = 1 << 15;
+
+// File modes.
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(name: &str, mode: u32) -> Entry {
+        Entry { name: name.into(), mode, size: 0, mtime: 0 }
+    }
+
+    #[test]
+    fn varints() {
+        assert_eq!(read_varint(&mut b"\x7f".as_ref()).unwrap(), 0x7f);
+        assert_eq!(read_varint(&mut b"\x80\x80".as_ref()).unwrap(), 0x80);
+        assert_eq!(
+            read_varint(&mut b"\x92\x34".as_ref()).unwrap(), 0x1234
+        );
+        assert_eq!(
+            read_varlong(&mut b"\x00\x05\x00".as_ref(), 3).unwrap(), 5
+        );
+        assert_eq!(
+            read_varlong(&mut b"\x81\x04\x03\x02".as_ref(), 3).unwrap(),
+            0x01020304
+        );
+        assert_eq!(
+            read_varlong(&mut b"\x00\xe8\x03\x00".as_ref(), 4).unwrap(),
+            1000
+        );
+    }
+
+    #[test]
+    fn ndx_round_trip() {
+        let ndxs = [0, 1, 5, 300, 70000, 2, NDX_DONE, 3, -2, -101, 4];
+        let mut buf = Vec::new();
+        let mut state = NdxState::default();
+        for ndx in ndxs {
+            state.write(ndx, &mut buf);
+        }
+        let mut state = NdxState::default();
+        let mut source = buf.as_slice();
+        for ndx in ndxs {
+            assert_eq!(state.read(&mut source).unwrap(), ndx);
+        }
+        assert!(source.is_empty());
+    }
+
+    #[test]
+    fn file_list() {
+        let data = b"\
+            \x01\x01.\x00\x00\x00\x00\xe8\x03\x00\xed\x41\x00\x00\
+            \x80\x05a.roa\x00\x03\x00\xa4\x81\x00\x00\
+            \xa2\x02\x03cer\x00\x07\x00\
+            \x00";
+        let (entries, io_error) = read_file_list(
+            &mut data.as_ref()
+        ).unwrap();
+        assert_eq!(io_error, 0);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, b".");
+        assert!(entries[0].is_dir());
+        assert_eq!(entries[0].mtime, 1000);
+        assert_eq!(entries[1].name, b"a.roa");
+        assert!(entries[1].is_file());
+        assert_eq!(entries[1].size, 3);
+        assert_eq!(entries[1].mtime, 1000);
+        assert_eq!(entries[2].name, b"a.cer");
+        assert!(entries[2].is_file());
+        assert_eq!(entries[2].size, 7);
+    }
+
+    #[test]
+    fn sort_order() {
+        let mut entries = [
+            entry("a/y/z", S_IFREG),
+            entry("b", S_IFREG),
+            entry("a/y", S_IFDIR),
+            entry("a", S_IFDIR),
+            entry("a/x", S_IFREG),
+            entry(".", S_IFDIR),
+            entry("a.b", S_IFREG),
+        ];
+        entries.sort_by_cached_key(Entry::sort_key);
+        assert_eq!(
+            entries.iter().map(|entry| {
+                str::from_utf8(&entry.name).unwrap()
+            }).collect::<Vec<_>>(),
+            [".", "a.b", "b", "a", "a/x", "a/y", "a/y/z"]
+        );
+    }
+
+    #[test]
+    fn safe_names() {
+        for name in [".", "a", "a/b.cer"] {
+            assert!(entry(name, S_IFREG).safe_name().is_some(), "{}", name);
+        }
+        for name in ["", "/a", "../a", "a/../b", "a//b", "a/./b", "a\\b"] {
+            assert!(entry(name, S_IFREG).safe_name().is_none(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn authorities() {
+        assert_eq!(
+            socket_addrs("127.0.0.1").unwrap(),
+            ["127.0.0.1:873".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            socket_addrs("127.0.0.1:1873").unwrap(),
+            ["127.0.0.1:1873".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            socket_addrs("[::1]").unwrap(),
+            ["[::1]:873".parse::<SocketAddr>().unwrap()]
+        );
+        assert_eq!(
+            socket_addrs("[::1]:1873").unwrap(),
+            ["[::1]:1873".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn greetings() {
+        assert_eq!(parse_greeting("@RSYNCD: 31.0"), Some(31));
+        assert_eq!(
+            parse_greeting("@RSYNCD: 31.0 sha512 sha256 sha1 md5 md4"),
+            Some(31)
+        );
+        assert_eq!(parse_greeting("@RSYNCD: 30"), Some(30));
+        assert_eq!(parse_greeting("@ERROR: go away"), None);
+    }
+
+    /// The files served by the stub daemon.
+    ///
+    /// These are sorted in file list order. Directories have no content.
+    const STUB_FILES: [(&str, Option<&[u8]>); 4] = [
+        (".", None),
+        ("a.roa", Some(b"first")),
+        ("sub", None),
+        ("sub/b.cer", Some(b"second")),
+    ];
+
+    /// The modification time of all files served by the stub daemon.
+    const STUB_MTIME: i64 = 1_700_000_000;
+
+    /// Encodes a variable length 64 bit integer.
+    fn varlong(value: i64, min_bytes: usize) -> Vec<u8> {
+        let bytes = value.to_le_bytes();
+        assert!(bytes[min_bytes - 1] < 0x80);
+        assert!(bytes[min_bytes..].iter().all(|&byte| byte == 0));
+        let mut res = vec![bytes[min_bytes - 1]];
+        res.extend_from_slice(&bytes[..min_bytes - 1]);
+        res
+    }
+
+    /// Reads a multiplexed data message.
+    fn read_data(source: &mut impl Read) -> Vec<u8> {
+        let header = read_i32(source).unwrap() as u32;
+        assert_eq!(header >> 24, MPLEX_BASE + MSG_DATA);
+        let mut res = vec![0; (header & 0xFF_FFFF) as usize];
+        source.read_exact(&mut res).unwrap();
+        res
+    }
+
+    /// Sends a multiplexed data message.
+    fn write_data(target: &mut impl Write, data: &[u8]) {
+        let header = ((MPLEX_BASE + MSG_DATA) << 24) | data.len() as u32;
+        target.write_all(&header.to_le_bytes()).unwrap();
+        target.write_all(data).unwrap();
+    }
+
+    /// Serves a single connection as a minimal rsync daemon.
+    ///
+    /// The daemon speaks protocol version 30 and serves [`STUB_FILES`]. If
+    /// `corrupt` is `true`, it sends wrong checksums.
+    fn serve_stub(sock: TcpStream, corrupt: bool) {
+        let mut reader = BufReader::new(sock.try_clone().unwrap());
+        let mut sock = sock;
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "@RSYNCD: 31.0\n");
+        sock.write_all(b"@RSYNCD: 30.0\n").unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "module\n");
+        sock.write_all(b"@RSYNCD: OK\n").unwrap();
+
+        // The arguments end with an empty one.
+        let mut args = Vec::new();
+        while !args.ends_with(b"\0\0") {
+            args.push(read_u8(&mut reader).unwrap());
+        }
+        assert_eq!(args, b"--server\0--sender\0-rt\0.\0module/\0\0");
+
+        // No compatibility flags and a zero checksum seed. From here on,
+        // everything is multiplexed, starting with the empty filter list.
+        sock.write_all(&[0; 5]).unwrap();
+        assert_eq!(read_data(&mut reader), [0; 4]);
+
+        let mut list = Vec::new();
+        for (name, content) in STUB_FILES {
+            list.push(XMIT_LONG_NAME as u8);
+            list.push(name.len() as u8);
+            list.extend_from_slice(name.as_bytes());
+            list.extend_from_slice(&varlong(
+                content.map(|content| content.len() as i64).unwrap_or(0), 3
+            ));
+            list.extend_from_slice(&varlong(STUB_MTIME, 4));
+            let mode = match content {
+                Some(_) => S_IFREG | 0o644,
+                None => S_IFDIR | 0o755,
+            };
+            list.extend_from_slice(&mode.to_le_bytes());
+        }
+        list.push(0);
+        write_data(&mut sock, &list);
+
+        // The client sends all its requests at once.
+        let requests = read_data(&mut reader);
+        let mut requests = requests.as_slice();
+        let mut ndx_in = NdxState::default();
+        let mut ndx_out = NdxState::default();
+        let mut files = Vec::new();
+        loop {
+            let ndx = ndx_in.read(&mut requests).unwrap();
+            if ndx == NDX_DONE {
+                break
+            }
+            assert_eq!(read_u16(&mut requests).unwrap(), ITEM_TRANSFER);
+            for _ in 0..4 {
+                assert_eq!(read_i32(&mut requests).unwrap(), 0);
+            }
+            let content = STUB_FILES[ndx as usize].1.unwrap();
+            ndx_out.write(ndx, &mut files);
+            files.extend_from_slice(&ITEM_TRANSFER.to_le_bytes());
+            files.extend_from_slice(&[0; 16]);
+            files.extend_from_slice(&(content.len() as i32).to_le_bytes());
+            files.extend_from_slice(content);
+            files.extend_from_slice(&0i32.to_le_bytes());
+            let mut sum: [u8; 16] = Md5::digest(content).into();
+            if corrupt {
+                sum[0] ^= 0xFF;
+            }
+            files.extend_from_slice(&sum);
+        }
+        for _ in 0..3 {
+            ndx_out.write(NDX_DONE, &mut files);
+        }
+        for _ in 0..5 {
+            files.extend_from_slice(&varlong(0, 3));
+        }
+        write_data(&mut sock, &files);
+
+        // Wait for the client to hang up.
+        let _ = io::copy(&mut reader, &mut io::sink());
+    }
+
+    #[test]
+    fn update_from_stub() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = uri::Rsync::from_string(format!(
+            "rsync://{}/module/", listener.local_addr().unwrap()
+        )).unwrap();
+        let server = thread::spawn(move || {
+            for corrupt in [false, false, true] {
+                serve_stub(listener.accept().unwrap().0, corrupt);
+            }
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let client = Client {
+            timeout: Some(Duration::from_secs(10)),
+            max_object_size: None,
+        };
+
+        // A fresh update transfers everything.
+        let stats = client.update(
+            &uri, dir.path(), &Limiter::default()
+        ).unwrap();
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.transferred, 2);
+        assert_eq!(fs::read(dir.path().join("a.roa")).unwrap(), b"first");
+        assert_eq!(
+            fs::read(dir.path().join("sub/b.cer")).unwrap(), b"second"
+        );
+
+        // Only changed files are transferred and extra files deleted.
+        fs::write(dir.path().join("a.roa"), b"changed").unwrap();
+        fs::write(dir.path().join("extra.roa"), b"extra").unwrap();
+        let stats = client.update(
+            &uri, dir.path(), &Limiter::default()
+        ).unwrap();
+        assert_eq!(stats.transferred, 1);
+        assert_eq!(stats.deleted, 1);
+        assert_eq!(fs::read(dir.path().join("a.roa")).unwrap(), b"first");
+        assert!(!dir.path().join("extra.roa").exists());
+
+        // Files with a wrong checksum fail the update.
+        fs::write(dir.path().join("a.roa"), b"changed").unwrap();
+        assert!(
+            client.update(&uri, dir.path(), &Limiter::default()).is_err()
+        );
+        assert_eq!(fs::read(dir.path().join("a.roa")).unwrap(), b"changed");
+
+        server.join().unwrap();
+    }
+
+    /// Updates a module from a real rsync daemon.
+    ///
+    /// Set `ROUTINATOR_TEST_RSYNCD` to the URI of a module to run.
+    #[test]
+    #[ignore]
+    fn update_from_daemon() {
+        let uri = std::env::var("ROUTINATOR_TEST_RSYNCD").unwrap();
+        let uri = uri::Rsync::from_string(uri).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let client = Client {
+            timeout: Some(Duration::from_secs(60)),
+            max_object_size: None,
+        };
+        let stats = client.update(
+            &uri, dir.path(), &Limiter::default()
+        ).unwrap();
+        assert_eq!(stats.transferred, stats.files);
+        let stats = client.update(
+            &uri, dir.path(), &Limiter::default()
+        ).unwrap();
+        assert_eq!(stats.transferred, 0);
+        assert_eq!(stats.deleted, 0);
+    }
+}
@@ -0,0 +1,331 @@
+//! Limiting the load we put on repository servers.
+//!
+//! This module provides two mechanisms. [`HostSlots`] limits the number of
+//! concurrent updates from a single host, making threads wait for a free
+//! slot if necessary. [`Bandwidth`] limits the rate of data received
+//! globally and per host.
+
+use std::{cmp, thread};
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex as StdMutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use crate::config::Config;
+use crate::utils::sync::Mutex;
+
+
+//------------ HostSlots -----------------------------------------------------
+
+/// A limit on the number of concurrent updates per host.
+#[derive(Debug)]
+pub struct HostSlots {
+    /// The maximum number of concurrent updates per host.
+    ///
+    /// If this is `None`, there is no limit.
+    max: Option<usize>,
+
+    /// The number of currently active updates per host.
+    active: StdMutex<HashMap<String, usize>>,
+
+    /// Signals that a slot has been released.
+    released: Condvar,
+}
+
+impl HostSlots {
+    /// Creates a new value with the given limit.
+    ///
+    /// A limit of 0 means there is no limit.
+    pub fn new(max: usize) -> Self {
+        HostSlots {
+            max: (max != 0).then_some(max),
+            active: Default::default(),
+            released: Default::default(),
+        }
+    }
+
+    /// Acquires a slot for the given host.
+    ///
+    /// Blocks until a slot becomes available. The slot is released when
+    /// the returned value is dropped.
+    pub fn acquire(&self, host: &str) -> HostSlot {
+        let start = Instant::now();
+        let max = match self.max {
+            Some(max) => max,
+            None => {
+                return HostSlot { slots: None, host: host.into(), wait: None }
+            }
+        };
+        let mut active = self.active.lock().expect(
+            "acquiring a poisoned mutex"
+        );
+        loop {
+            let count = active.entry(host.into()).or_default();
+            if *count < max {
+                *count += 1;
+                break
+            }
+            active = self.released.wait(active).expect(
+                "acquiring a poisoned mutex"
+            );
+        }
+        HostSlot {
+            slots: Some(self),
+            host: host.into(),
+            wait: Some(start.elapsed()),
+        }
+    }
+
+    /// Releases a slot for the given host.
+    fn release(&self, host: &str) {
+        let mut active = self.active.lock().expect(
+            "acquiring a poisoned mutex"
+        );
+        if let Some(count) = active.get_mut(host) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(host);
+            }
+        }
+        self.released.notify_all();
+    }
+}
+
+
+//------------ HostSlot ------------------------------------------------------
+
+/// A slot for updating from a host.
+#[derive(Debug)]
+pub struct HostSlot<'a> {
+    /// The slots we have been acquired from if they are limited.
+    slots: Option<&'a HostSlots>,
+
+    /// The host we have been acquired for.
+    host: String,
+
+    /// The time spent waiting for the slot if slots are limited.
+    wait: Option<Duration>,
+}
+
+impl HostSlot<'_> {
+    /// Returns the time spent waiting for the slot.
+    ///
+    /// Returns `None` if the number of slots is not limited.
+    pub fn wait(&self) -> Option<Duration> {
+        self.wait
+    }
+}
+
+impl Drop for HostSlot<'_> {
+    fn drop(&mut self) {
+        if let Some(slots) = self.slots {
+            slots.release(&self.host)
+        }
+    }
+}
+
+
+//------------ Bandwidth -----------------------------------------------------
+
+/// The bandwidth limits for receiving data.
+#[derive(Debug)]
+pub struct Bandwidth {
+    /// The limit for all data received.
+    global: Option<Arc<RateLimit>>,
+
+    /// The rate in bytes per second for data received from a single host.
+    host_rate: Option<u64>,
+
+    /// The limits for the individual hosts.
+    hosts: Mutex<HashMap<String, Arc<RateLimit>>>,
+}
+
+impl Bandwidth {
+    /// Creates the bandwidth limits from the config.
+    pub fn new(config: &Config) -> Self {
+        Bandwidth {
+            global: config.bandwidth_limit.map(|rate| {
+                Arc::new(RateLimit::new(rate))
+            }),
+            host_rate: config.host_bandwidth_limit,
+            hosts: Default::default(),
+        }
+    }
+
+    /// Returns the limiter for data received from the given host.
+    ///
+    /// The limiter counts as an active transfer for each of its limits
+    /// until it is dropped.
+    pub fn limiter(&self, host: &str) -> Limiter {
+        let mut res = Vec::new();
+        if let Some(global) = self.global.as_ref() {
+            res.push(global.clone())
+        }
+        if let Some(rate) = self.host_rate {
+            res.push(
+                self.hosts.lock().entry(host.into()).or_insert_with(|| {
+                    Arc::new(RateLimit::new(rate))
+                }).clone()
+            )
+        }
+        Limiter::new(res)
+    }
+}
+
+
+//------------ Limiter -------------------------------------------------------
+
+/// The bandwidth limits applying to a single transfer.
+#[derive(Debug, Default)]
+pub struct Limiter(Vec<Arc<RateLimit>>);
+
+impl Limiter {
+    /// Creates a new limiter registering with the given limits.
+    fn new(limits: Vec<Arc<RateLimit>>) -> Self {
+        for limit in &limits {
+            limit.active.fetch_add(1, Ordering::Relaxed);
+        }
+        Limiter(limits)
+    }
+
+    /// Returns the smallest share of a rate if there is a limit.
+    ///
+    /// The share is the rate in bytes per second divided by the number of
+    /// transfers currently active under the limit. This is used for
+    /// processes that can only be given a fixed rate when they are started.
+    pub fn rate(&self) -> Option<u64> {
+        self.0.iter().map(|limit| {
+            let active = limit.active.load(Ordering::Relaxed);
+            cmp::max(limit.rate / cmp::max(active, 1) as u64, 1)
+        }).min()
+    }
+
+    /// Accounts for `len` bytes received.
+    ///
+    /// Blocks for as long as necessary to stay within the limits.
+    pub fn consume(&self, len: usize) {
+        let until = self.0.iter().map(|limit| limit.consume(len)).max();
+        if let Some(until) = until {
+            let now = Instant::now();
+            if until > now {
+                thread::sleep(until - now)
+            }
+        }
+    }
+}
+
+
+impl Clone for Limiter {
+    fn clone(&self) -> Self {
+        Limiter::new(self.0.clone())
+    }
+}
+
+impl Drop for Limiter {
+    fn drop(&mut self) {
+        for limit in &self.0 {
+            limit.active.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+
+//------------ RateLimit -----------------------------------------------------
+
+/// A single bandwidth limit.
+#[derive(Debug)]
+struct RateLimit {
+    /// The rate in bytes per second.
+    rate: u64,
+
+    /// The time when all data accounted for so far may have been received.
+    next: Mutex<Instant>,
+
+    /// The number of transfers currently sharing the limit.
+    active: AtomicUsize,
+}
+
+impl RateLimit {
+    /// Creates a new limit for the given rate in bytes per second.
+    fn new(rate: u64) -> Self {
+        RateLimit {
+            rate: cmp::max(rate, 1),
+            next: Mutex::new(Instant::now()),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Accounts for `len` bytes received.
+    ///
+    /// Returns the time until which the receiver has to wait.
+    fn consume(&self, len: usize) -> Instant {
+        let mut next = self.next.lock();
+        let now = Instant::now();
+        if *next < now {
+            *next = now;
+        }
+        let until = *next;
+        *next += Duration::from_secs_f64(len as f64 / self.rate as f64);
+        until
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_slots() {
+        let slots = HostSlots::new(1);
+        let first = slots.acquire("a.example");
+        let other = slots.acquire("b.example");
+        assert!(first.wait().is_some());
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                slots.acquire("a.example").wait().unwrap()
+            });
+            thread::sleep(Duration::from_millis(50));
+            drop(first);
+            assert!(waiter.join().unwrap() >= Duration::from_millis(50));
+        });
+        drop(other);
+        assert!(slots.active.lock().unwrap().is_empty());
+
+        assert!(HostSlots::new(0).acquire("a.example").wait().is_none());
+    }
+
+    #[test]
+    fn rate_limit() {
+        let limit = RateLimit::new(1000);
+        let start = Instant::now();
+        assert!(limit.consume(500) <= Instant::now());
+        let until = limit.consume(500);
+        assert!(until >= start + Duration::from_millis(500));
+        assert!(until < start + Duration::from_millis(600));
+    }
+
+    #[test]
+    fn limiter_rate_shares() {
+        let mut config = Config::default_with_paths(
+            Default::default(), Default::default()
+        );
+        config.bandwidth_limit = Some(8192);
+        config.host_bandwidth_limit = Some(6144);
+        let bandwidth = Bandwidth::new(&config);
+        let first = bandwidth.limiter("a.example");
+        assert_eq!(first.rate(), Some(6144));
+        let second = bandwidth.limiter("a.example");
+        assert_eq!(first.rate(), Some(3072));
+        let other = bandwidth.limiter("b.example");
+        assert_eq!(other.rate(), Some(8192 / 3));
+        drop(second);
+        drop(other);
+        let clone = first.clone();
+        assert_eq!(clone.rate(), Some(3072));
+        drop(clone);
+        assert_eq!(first.rate(), Some(6144));
+        assert_eq!(Limiter::default().rate(), None);
+    }
+}
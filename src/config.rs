@@ -6,7 +6,7 @@
 //!
 //! [`Config`]: struct.Config.html
 
-use std::{env, fmt, fs, process};
+use std::{env, fmt, fs, hash, process};
 use std::collections::HashMap;
 use std::io::Read;
 use std::net::{IpAddr, SocketAddr};
@@ -20,6 +20,8 @@ use clap::{
 };
 use dirs::home_dir;
 use log::{LevelFilter, error, warn};
+use rpki::repository::x509::Time;
+use rpki::uri;
 #[cfg(unix)] use syslog::Facility;
 use toml_edit as toml;
 use crate::tals;
@@ -34,6 +36,9 @@ const DEFAULT_STRICT: bool = false;
 /// The default timeout for running rsync commands in seconds.
 const DEFAULT_RSYNC_TIMEOUT: Duration = Duration::from_secs(300);
 
+/// The default number of updates per rsync module kept in the history.
+const DEFAULT_RSYNC_HISTORY_SIZE: usize = 10;
+
 /// Are we leaving the repository dirty by default?
 const DEFAULT_DIRTY_REPOSITORY: bool = false;
 
@@ -58,6 +63,9 @@ const DEFAULT_RRDP_TCP_KEEPALIVE: Duration = Duration::from_secs(60);
 /// The default for the RRDP fallback policy.
 const DEFAULT_RRDP_FALLBACK: FallbackPolicy = FallbackPolicy::Stale;
 
+/// The default policy for using Erik synchronisation.
+const DEFAULT_ERIK_POLICY: ErikPolicy = ErikPolicy::Disabled;
+
 /// The default for the RRDP fallback time.
 const DEFAULT_RRDP_FALLBACK_TIME: Duration = Duration::from_secs(3600);
 
@@ -67,13 +75,31 @@ const DEFAULT_RRDP_MAX_DELTA_COUNT: usize = 100;
 /// The default for the maximum number of deltas parsed.
 const DEFAULT_RRDP_MAX_DELTA_LIST_LEN: usize = 500;
 
+/// The default number of RRDP delta files fetched concurrently.
+const DEFAULT_RRDP_DELTA_PREFETCH: usize = 4;
+
+/// The default time idle RRDP connections are kept for reuse.
+const DEFAULT_RRDP_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
 /// The default RRDP HTTP User Agent header value to send.
 const DEFAULT_RRDP_USER_AGENT: &str = concat!("Routinator/", crate_version!());
 
+/// The default for the maximum repository backoff.
+const DEFAULT_REPOSITORY_BACKOFF_MAX: Duration = Duration::from_secs(14400);
+
 /// The default RTR TCP keepalive.
 const DEFAULT_RTR_TCP_KEEPALIVE: Option<Duration>
     = Some(Duration::from_secs(60));
 
+/// The default size of the RTR audit log before it is rotated.
+const DEFAULT_RTR_AUDIT_LOG_SIZE: u64 = 100_000_000;
+
+/// The default number of rotated RTR audit log files to keep.
+const DEFAULT_RTR_AUDIT_LOG_FILES: usize = 10;
+
+/// The default time to wait for RTR connections to close during shutdown.
+const DEFAULT_RTR_SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
 /// The default stale policy.
 const DEFAULT_STALE_POLICY: FilterPolicy = FilterPolicy::Reject;
 
@@ -83,6 +109,9 @@ const DEFAULT_UNSAFE_VRPS_POLICY: FilterPolicy = FilterPolicy::Accept;
 /// The default unknown-objects policy.
 const DEFAULT_UNKNOWN_OBJECTS_POLICY: FilterPolicy = FilterPolicy::Warn;
 
+/// The default manifest-replay policy.
+const DEFAULT_MANIFEST_REPLAY_POLICY: FilterPolicy = FilterPolicy::Reject;
+
 /// The default maximum object size.
 const DEFAULT_MAX_OBJECT_SIZE: u64 = 20_000_000;
 
@@ -179,6 +208,14 @@ pub struct Config {
     /// How to deal with unknown RPKI object types.
     pub unknown_objects: FilterPolicy,
 
+    /// How should we deal with replayed manifests?
+    ///
+    /// A freshly collected manifest is considered replayed if its manifest
+    /// number isn’t greater or its thisUpdate time isn’t later than that of
+    /// the stored manifest for the publication point. If such a manifest is
+    /// rejected, the stored publication point is used instead.
+    pub manifest_replay: FilterPolicy,
+
     /// The maximum length of IPv4 prefixes included in the VRP set.
     pub limit_v4_len: Option<u8>,
 
@@ -193,12 +230,33 @@ pub struct Config {
     /// (This option is only available on command line.)
     pub fresh: bool,
 
+    /// The time to validate at instead of the current time.
+    ///
+    /// If this is set, the local repository is not updated and all data is
+    /// taken from the store.
+    ///
+    /// (This option is only available on command line.)
+    pub as_of: Option<Time>,
+
+    /// A local copy of the repositories to use instead of fetching data.
+    ///
+    /// This is either a directory tree in rsync layout, i.e., with the
+    /// authority and module name as the first two levels, or a tarball of
+    /// such a tree. If present, all RPKI data is read from this copy and
+    /// no network access happens.
+    ///
+    /// (This option is only available on command line.)
+    pub offline_source: Option<PathBuf>,
+
     /// Whether to disable rsync.
     pub disable_rsync: bool,
 
     /// The command to run for rsync.
     pub rsync_command: String,
 
+    /// Whether to use the built-in rsync client instead of the command.
+    pub rsync_native: bool,
+
     /// Optional arguments passed to rsync.
     ///
     /// If these are present, they overide the arguments automatically
@@ -211,6 +269,20 @@ pub struct Config {
     /// If this is None, no timeout is set.
     pub rsync_timeout: Option<Duration>,
 
+    /// The maximum number of concurrent rsync updates per host.
+    ///
+    /// A value of 0 means there is no limit.
+    pub rsync_max_host_processes: usize,
+
+    /// The number of updates per rsync module kept in the history.
+    pub rsync_history_size: usize,
+
+    /// Should we keep updated rsync modules and if so where?
+    pub rsync_keep_responses: Option<PathBuf>,
+
+    /// Should we replay kept rsync modules instead of running rsync?
+    pub rsync_replay_responses: Option<PathBuf>,
+
     /// Whether to disable RRDP.
     pub disable_rrdp: bool,
 
@@ -221,6 +293,9 @@ pub struct Config {
     pub rrdp_fallback_time: Duration,
 
     /// The maxmimm number of deltas we allow before using snapshot.
+    ///
+    /// This is only used for repositories where the sizes of snapshot and
+    /// deltas are not yet known.
     pub rrdp_max_delta_count: usize,
 
     /// The maximum allowd length of the delta list in an RRDP notification.
@@ -229,6 +304,17 @@ pub struct Config {
     /// the snapshot will be used.
     pub rrdp_max_delta_list_len: usize,
 
+    /// The maximum number of concurrent RRDP updates per host.
+    ///
+    /// A value of 0 means there is no limit.
+    pub rrdp_max_host_requests: usize,
+
+    /// The number of delta files of a repository to fetch concurrently.
+    ///
+    /// A value of 0 means deltas are fetched one by one while being
+    /// applied.
+    pub rrdp_delta_prefetch: usize,
+
     /// RRDP timeout in seconds.
     ///
     /// If this is None, no timeout is set.
@@ -240,6 +326,11 @@ pub struct Config {
     /// Optional TCP keepalive duration for RRDP connections.
     pub rrdp_tcp_keepalive: Option<Duration>,
 
+    /// How long idle RRDP connections are kept open for reuse.
+    ///
+    /// If this is None, connections are not reused.
+    pub rrdp_idle_timeout: Option<Duration>,
+
     /// Optional RRDP local address to bind to when doing requests.
     pub rrdp_local_addr: Option<IpAddr>,
 
@@ -257,12 +348,62 @@ pub struct Config {
     /// Should we keep RRDP responses and if so where?
     pub rrdp_keep_responses: Option<PathBuf>,
 
+    /// Should we replay kept RRDP responses instead of fetching them?
+    pub rrdp_replay_responses: Option<PathBuf>,
+
+    /// The base URI of the Erik relay to use.
+    ///
+    /// If this is `None`, Erik synchronisation is disabled.
+    pub erik_relay: Option<String>,
+
+    /// The policy for using Erik for hosts not listed in `erik_hosts`.
+    pub erik_policy: ErikPolicy,
+
+    /// The policy for using Erik for individual repository hosts.
+    pub erik_hosts: HashMap<String, ErikPolicy>,
+
+    /// A mapping of URI prefixes to replacement prefixes for fetching data.
+    pub uri_rewrites: HashMap<String, String>,
+
+    /// The URI schemes allowed in the replacement prefixes of rewrites.
+    pub uri_rewrite_schemes: Vec<String>,
+
+    /// The initial backoff after a failed repository update.
+    ///
+    /// If this is `None`, failed repositories are retried in every
+    /// validation run.
+    pub repository_backoff: Option<Duration>,
+
+    /// The maximum backoff after failed repository updates.
+    pub repository_backoff_max: Duration,
+
+    /// Optional limit for receiving data in bytes per second.
+    pub bandwidth_limit: Option<u64>,
+
+    /// Optional limit for receiving data from a host in bytes per second.
+    pub host_bandwidth_limit: Option<u64>,
+
     /// Optional size limit for objects.
     pub max_object_size: Option<u64>,
 
     /// Maxium length of the CA chain.
     pub max_ca_depth: usize,
 
+    /// Optional limit for the number of objects per repository.
+    pub max_repository_objects: Option<u64>,
+
+    /// Optional limit for the total size of objects per repository.
+    pub max_repository_size: Option<u64>,
+
+    /// Optional limit for the number of CA certificates per repository.
+    pub max_repository_cas: Option<u64>,
+
+    /// Optional limit for the number of publication points per repository.
+    pub max_repository_points: Option<u64>,
+
+    /// Optional limit for the time spent on a repository per run.
+    pub max_repository_time: Option<Duration>,
+
     /// Whether to process BGPsec router keys.
     pub enable_bgpsec: bool,
 
@@ -277,6 +418,21 @@ pub struct Config {
     /// deleted.
     pub dirty_repository: bool,
 
+    /// Whether to reuse the results of unchanged publication points.
+    ///
+    /// If this is `true`, the engine keeps the outcome of validating each
+    /// publication point in memory and reuses it in the next run if neither
+    /// the manifest nor the CA certificate have changed.
+    pub incremental_validation: bool,
+
+    /// The resource shrinkage in percent allowed for TA certificates.
+    ///
+    /// If this is some value, a new trust anchor certificate whose
+    /// resources shrank by more than this percentage compared to the
+    /// previously accepted certificate is refused until an operator has
+    /// accepted it.
+    pub ta_pinning: Option<u8>,
+
     /// Number of threads used during validation.
     pub validation_threads: usize,
 
@@ -315,6 +471,31 @@ pub struct Config {
     /// Should we publish detailed RTR client statistics?
     pub rtr_client_metrics: bool,
 
+    /// A mapping of RTR client addresses to names used in client metrics.
+    pub rtr_client_labels: HashMap<IpAddr, String>,
+
+    /// Path to the RTR audit log.
+    ///
+    /// If this is `None`, no audit log is written.
+    pub rtr_audit_log: Option<PathBuf>,
+
+    /// The size in bytes beyond which the RTR audit log is rotated.
+    pub rtr_audit_log_size: u64,
+
+    /// The number of rotated RTR audit log files to keep.
+    pub rtr_audit_log_files: usize,
+
+    /// The time to wait for RTR connections to close during shutdown.
+    pub rtr_shutdown_grace: Duration,
+
+    /// Should we keep a report on all objects of a validation run?
+    pub object_report: bool,
+
+    /// The base URI for re-publishing the store via RRDP.
+    ///
+    /// If this is `None`, the store is not re-published.
+    pub rrdp_publish_uri: Option<uri::Https>,
+
     /// Path to the RTR TLS private key.
     pub rtr_tls_key: Option<PathBuf>,
 
@@ -350,6 +531,12 @@ pub struct Config {
 
     /// A mapping of TAL file names to TAL labels.
     pub tal_labels: HashMap<String, String>,
+
+    /// Overrides of the validation policy per TAL.
+    ///
+    /// The keys are the TAL names, i.e., the TAL label if one is configured
+    /// or the file name of the TAL without the extension.
+    pub tal_policies: HashMap<String, TalPolicy>,
 }
 
 
@@ -484,6 +671,11 @@ impl Config {
             self.unknown_objects = value
         }
 
+        // manifest_replay
+        if let Some(value) = args.manifest_replay {
+            self.manifest_replay = value
+        }
+
         // limit_v4_len
         if let Some(value) = args.limit_v4_len {
             self.limit_v4_len = Some(value)
@@ -504,6 +696,16 @@ impl Config {
             self.fresh = true
         }
 
+        // as_of
+        if let Some(value) = args.as_of {
+            self.as_of = Some(value)
+        }
+
+        // offline_source
+        if let Some(path) = args.offline_source {
+            self.offline_source = Some(cur_dir.join(path))
+        }
+
         // disable_rsync
         if args.disable_rsync {
             self.disable_rsync = true
@@ -514,6 +716,11 @@ impl Config {
             self.rsync_command = value
         }
 
+        // rsync_native
+        if args.rsync_native {
+            self.rsync_native = true
+        }
+
         // rsync_timeout
         if let Some(value) = args.rsync_timeout {
             self.rsync_timeout = if value == 0 {
@@ -524,6 +731,26 @@ impl Config {
             };
         }
 
+        // rsync_max_host_processes
+        if let Some(value) = args.rsync_max_host_processes {
+            self.rsync_max_host_processes = value
+        }
+
+        // rsync_history_size
+        if let Some(value) = args.rsync_history_size {
+            self.rsync_history_size = value
+        }
+
+        // rsync_keep_responses
+        if let Some(path) = args.rsync_keep_responses {
+            self.rsync_keep_responses = Some(path)
+        }
+
+        // rsync_replay_responses
+        if let Some(path) = args.rsync_replay_responses {
+            self.rsync_replay_responses = Some(path)
+        }
+
         // disable_rrdp
         if args.disable_rrdp {
             self.disable_rrdp = true
@@ -549,6 +776,16 @@ impl Config {
             self.rrdp_max_delta_list_len = value
         }
 
+        // rrdp_max_host_requests
+        if let Some(value) = args.rrdp_max_host_requests {
+            self.rrdp_max_host_requests = value
+        }
+
+        // rrdp_delta_prefetch
+        if let Some(value) = args.rrdp_delta_prefetch {
+            self.rrdp_delta_prefetch = value
+        }
+
         // rrdp_timeout
         if let Some(value) = args.rrdp_timeout {
             self.rrdp_timeout = if value == 0 {
@@ -574,6 +811,16 @@ impl Config {
             };
         }
 
+        // rrdp_idle_timeout
+        if let Some(value) = args.rrdp_idle_timeout {
+            self.rrdp_idle_timeout = if value == 0 {
+                None
+            }
+            else {
+                Some(Duration::from_secs(value))
+            };
+        }
+
         // rrdp_local_addr
         if let Some(value) = args.rrdp_local_addr {
             self.rrdp_local_addr = Some(value)
@@ -596,6 +843,94 @@ impl Config {
             self.rrdp_keep_responses = Some(path)
         }
 
+        // rrdp_replay_responses
+        if let Some(path) = args.rrdp_replay_responses {
+            self.rrdp_replay_responses = Some(path)
+        }
+
+        // erik_relay
+        if let Some(value) = args.erik_relay {
+            self.erik_relay = Some(value)
+        }
+
+        // erik_policy
+        if let Some(value) = args.erik_policy {
+            self.erik_policy = value
+        }
+
+        // erik_hosts
+        if let Some(list) = args.erik_host {
+            for item in list {
+                let (host, policy) = match item.split_once('=') {
+                    Some(pair) => pair,
+                    None => {
+                        error!(
+                            "Invalid value '{}' for --erik-host: \
+                             expected HOST=POLICY.",
+                            item
+                        );
+                        return Err(Failed)
+                    }
+                };
+                match ErikPolicy::from_str(policy) {
+                    Ok(policy) => {
+                        self.erik_hosts.insert(
+                            host.to_ascii_lowercase(), policy
+                        );
+                    }
+                    Err(err) => {
+                        error!("Invalid value for --erik-host: {}.", err);
+                        return Err(Failed)
+                    }
+                }
+            }
+        }
+
+        // uri_rewrites
+        if let Some(list) = args.uri_rewrite {
+            for item in list {
+                let (from, to) = match item.split_once('=') {
+                    Some(pair) => pair,
+                    None => {
+                        error!(
+                            "Invalid value '{}' for --uri-rewrite: \
+                             expected FROM=TO.",
+                            item
+                        );
+                        return Err(Failed)
+                    }
+                };
+                self.uri_rewrites.insert(from.into(), to.into());
+            }
+        }
+
+        // uri_rewrite_schemes
+        if let Some(list) = args.uri_rewrite_scheme {
+            self.uri_rewrite_schemes = list
+        }
+
+        // repository_backoff
+        if let Some(value) = args.repository_backoff {
+            self.repository_backoff = (value != 0).then(|| {
+                Duration::from_secs(value)
+            })
+        }
+
+        // repository_backoff_max
+        if let Some(value) = args.repository_backoff_max {
+            self.repository_backoff_max = Duration::from_secs(value)
+        }
+
+        // bandwidth_limit
+        if let Some(value) = args.bandwidth_limit {
+            self.bandwidth_limit = (value != 0).then_some(value)
+        }
+
+        // host_bandwidth_limit
+        if let Some(value) = args.host_bandwidth_limit {
+            self.host_bandwidth_limit = (value != 0).then_some(value)
+        }
+
         // max_object_size
         if let Some(value) = args.max_object_size {
             if value == 0 {
@@ -611,6 +946,33 @@ impl Config {
             self.max_ca_depth = value;
         }
 
+        // max_repository_objects
+        if let Some(value) = args.max_repository_objects {
+            self.max_repository_objects = (value != 0).then_some(value)
+        }
+
+        // max_repository_size
+        if let Some(value) = args.max_repository_size {
+            self.max_repository_size = (value != 0).then_some(value)
+        }
+
+        // max_repository_cas
+        if let Some(value) = args.max_repository_cas {
+            self.max_repository_cas = (value != 0).then_some(value)
+        }
+
+        // max_repository_points
+        if let Some(value) = args.max_repository_points {
+            self.max_repository_points = (value != 0).then_some(value)
+        }
+
+        // max_repository_time
+        if let Some(value) = args.max_repository_time {
+            self.max_repository_time = {
+                (value != 0).then(|| Duration::from_secs(value))
+            }
+        }
+
         // enable_bgpsec
         if args.enable_bgpsec {
             self.enable_bgpsec = true
@@ -627,6 +989,16 @@ impl Config {
             self.dirty_repository = true
         }
 
+        // incremental_validation
+        if args.incremental_validation {
+            self.incremental_validation = true
+        }
+
+        // ta_pinning
+        if let Some(value) = args.ta_pinning {
+            self.ta_pinning = Some(value)
+        }
+
         // validation_threads
         if let Some(value) = args.validation_threads {
             self.validation_threads = value
@@ -781,6 +1153,36 @@ impl Config {
             self.rtr_client_metrics = true
         }
 
+        // rtr_audit_log
+        if let Some(path) = args.rtr_audit_log {
+            self.rtr_audit_log = Some(cur_dir.join(path))
+        }
+
+        // rtr_audit_log_size
+        if let Some(value) = args.rtr_audit_log_size {
+            self.rtr_audit_log_size = value
+        }
+
+        // rtr_audit_log_files
+        if let Some(value) = args.rtr_audit_log_files {
+            self.rtr_audit_log_files = value
+        }
+
+        // rtr_shutdown_grace
+        if let Some(value) = args.rtr_shutdown_grace {
+            self.rtr_shutdown_grace = Duration::from_secs(value)
+        }
+
+        // object_report
+        if args.object_report {
+            self.object_report = true
+        }
+
+        // rrdp_publish_uri
+        if let Some(uri) = args.rrdp_publish_uri {
+            self.rrdp_publish_uri = Some(uri)
+        }
+
         // rtr_tls_key
         if let Some(path) = args.rtr_tls_key {
             self.rtr_tls_key = Some(cur_dir.join(path))
@@ -897,16 +1299,23 @@ impl Config {
                 file.take_from_str("unknown-objects")?
                     .unwrap_or(DEFAULT_UNKNOWN_OBJECTS_POLICY)
             },
+            manifest_replay: {
+                file.take_from_str("manifest-replay")?
+                    .unwrap_or(DEFAULT_MANIFEST_REPLAY_POLICY)
+            },
             limit_v4_len: file.take_limited_u8("limit-v4-len", 32)?,
             limit_v6_len: file.take_limited_u8("limit-v6-len", 128)?,
             allow_dubious_hosts:
                 file.take_bool("allow-dubious-hosts")?.unwrap_or(false),
             fresh: false,
+            as_of: None,
+            offline_source: None,
             disable_rsync: file.take_bool("disable-rsync")?.unwrap_or(false),
             rsync_command: {
                 file.take_string("rsync-command")?
                     .unwrap_or_else(|| "rsync".into())
             },
+            rsync_native: file.take_bool("rsync-native")?.unwrap_or(false),
             rsync_args: file.take_string_array("rsync-args")?,
             rsync_timeout: {
                 match file.take_u64("rsync-timeout")? {
@@ -915,6 +1324,17 @@ impl Config {
                     None => Some(DEFAULT_RSYNC_TIMEOUT)
                 }
             },
+            rsync_max_host_processes: {
+                file.take_usize("rsync-max-host-processes")?.unwrap_or(0)
+            },
+            rsync_history_size: {
+                file.take_usize("rsync-history-size")?
+                    .unwrap_or(DEFAULT_RSYNC_HISTORY_SIZE)
+            },
+            rsync_keep_responses: file.take_path("rsync-keep-responses")?,
+            rsync_replay_responses: {
+                file.take_path("rsync-replay-responses")?
+            },
             disable_rrdp: file.take_bool("disable-rrdp")?.unwrap_or(false),
             rrdp_fallback: {
                 file.take_from_str("rrdp-fallback")?
@@ -933,6 +1353,13 @@ impl Config {
                 file.take_usize("rrdp-max-delta-list-len")?
                 .unwrap_or(DEFAULT_RRDP_MAX_DELTA_LIST_LEN)
             },
+            rrdp_max_host_requests: {
+                file.take_usize("rrdp-max-host-requests")?.unwrap_or(0)
+            },
+            rrdp_delta_prefetch: {
+                file.take_usize("rrdp-delta-prefetch")?
+                .unwrap_or(DEFAULT_RRDP_DELTA_PREFETCH)
+            },
             rrdp_timeout: {
                 match file.take_u64("rrdp-timeout")? {
                     Some(0) => None,
@@ -950,6 +1377,13 @@ impl Config {
                     None => Some(DEFAULT_RRDP_TCP_KEEPALIVE)
                 }
             },
+            rrdp_idle_timeout: {
+                match file.take_u64("rrdp-idle-timeout")? {
+                    Some(0) => None,
+                    Some(value) => Some(Duration::from_secs(value)),
+                    None => Some(DEFAULT_RRDP_IDLE_TIMEOUT)
+                }
+            },
             rrdp_local_addr: file.take_from_str("rrdp-local-addr")?,
             rrdp_root_certs: {
                 file.take_from_str_array("rrdp-root-certs")?
@@ -960,6 +1394,39 @@ impl Config {
             },
             rrdp_user_agent: DEFAULT_RRDP_USER_AGENT.to_string(),
             rrdp_keep_responses: file.take_path("rrdp-keep-responses")?,
+            rrdp_replay_responses: {
+                file.take_path("rrdp-replay-responses")?
+            },
+            erik_relay: file.take_string("erik-relay")?,
+            erik_policy: {
+                file.take_from_str("erik-policy")?
+                    .unwrap_or(DEFAULT_ERIK_POLICY)
+            },
+            erik_hosts: Self::erik_hosts_from_config_file(&mut file)?,
+            uri_rewrites: {
+                file.take_string_map("uri-rewrites")?.unwrap_or_default()
+            },
+            uri_rewrite_schemes: {
+                file.take_string_array("uri-rewrite-schemes")?
+                    .unwrap_or_default()
+            },
+            repository_backoff: {
+                file.take_u64("repository-backoff")?
+                .filter(|value| *value != 0)
+                .map(Duration::from_secs)
+            },
+            repository_backoff_max: {
+                file.take_u64("repository-backoff-max")?
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_REPOSITORY_BACKOFF_MAX)
+            },
+            bandwidth_limit: {
+                file.take_u64("bandwidth-limit")?.filter(|value| *value != 0)
+            },
+            host_bandwidth_limit: {
+                file.take_u64("host-bandwidth-limit")?
+                .filter(|value| *value != 0)
+            },
             max_object_size: {
                 match file.take_u64("max-object-size")? {
                     Some(0) => None,
@@ -971,6 +1438,27 @@ impl Config {
                 file.take_usize("max-ca-depth")?
                     .unwrap_or(DEFAULT_MAX_CA_DEPTH)
             },
+            max_repository_objects: {
+                file.take_u64("max-repository-objects")?
+                    .filter(|value| *value != 0)
+            },
+            max_repository_size: {
+                file.take_u64("max-repository-size")?
+                    .filter(|value| *value != 0)
+            },
+            max_repository_cas: {
+                file.take_u64("max-repository-cas")?
+                    .filter(|value| *value != 0)
+            },
+            max_repository_points: {
+                file.take_u64("max-repository-points")?
+                    .filter(|value| *value != 0)
+            },
+            max_repository_time: {
+                file.take_u64("max-repository-time")?
+                    .filter(|value| *value != 0)
+                    .map(Duration::from_secs)
+            },
             enable_bgpsec: file.take_bool("enable-bgpsec")?.unwrap_or(false),
 
             #[cfg(feature = "aspa")]
@@ -980,6 +1468,10 @@ impl Config {
             enable_aspa: false,
 
             dirty_repository: file.take_bool("dirty")?.unwrap_or(false),
+            incremental_validation: {
+                file.take_bool("incremental-validation")?.unwrap_or(false)
+            },
+            ta_pinning: file.take_limited_u8("ta-pinning", 100)?,
             validation_threads: {
                 file.take_small_usize(
                     "validation-threads"
@@ -1031,6 +1523,28 @@ impl Config {
             rtr_client_metrics: {
                 file.take_bool("rtr-client-metrics")?.unwrap_or(false)
             },
+            rtr_client_labels: {
+                file.take_from_str_map("rtr-client-labels")?
+                    .unwrap_or_default()
+            },
+            rtr_audit_log: file.take_path("rtr-audit-log")?,
+            rtr_audit_log_size: {
+                file.take_u64("rtr-audit-log-size")?
+                    .unwrap_or(DEFAULT_RTR_AUDIT_LOG_SIZE)
+            },
+            rtr_audit_log_files: {
+                file.take_usize("rtr-audit-log-files")?
+                    .unwrap_or(DEFAULT_RTR_AUDIT_LOG_FILES)
+            },
+            rtr_shutdown_grace: {
+                file.take_u64("rtr-shutdown-grace")?
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_RTR_SHUTDOWN_GRACE)
+            },
+            object_report: {
+                file.take_bool("object-report")?.unwrap_or(false)
+            },
+            rrdp_publish_uri: file.take_from_str("rrdp-publish-uri")?,
             rtr_tls_key: file.take_path("rtr-tls-key")?,
             rtr_tls_cert: file.take_path("rtr-tls-cert")?,
             http_tls_key: file.take_path("http-tls-key")?,
@@ -1045,6 +1559,7 @@ impl Config {
             user: file.take_string("user")?,
             group: file.take_string("group")?,
             tal_labels: file.take_string_map("tal-labels")?.unwrap_or_default(),
+            tal_policies: Self::tal_policies_from_config_file(&mut file)?,
         };
 
         if file.take_path("tal-dir")?.is_some() {
@@ -1144,6 +1659,56 @@ impl Config {
         }
     }
 
+    /// Determines the per-TAL policy overrides from the config file.
+    ///
+    /// These are given as tables under the `tal-policy` key, one for each
+    /// TAL name.
+    fn tal_policies_from_config_file(
+        file: &mut ConfigFile
+    ) -> Result<HashMap<String, TalPolicy>, Failed> {
+        let mut res = HashMap::new();
+        for (name, mut table) in file.take_tables("tal-policy")? {
+            let policy = TalPolicy {
+                strict: table.take_bool("strict")?,
+                stale: table.take_from_str("stale")?,
+                unsafe_vrps: table.take_from_str("unsafe-vrps")?,
+                unknown_objects: table.take_from_str("unknown-objects")?,
+                manifest_replay: table.take_from_str("manifest-replay")?,
+                max_ca_depth: table.take_usize("max-ca-depth")?,
+                disable_rsync: table.take_bool("disable-rsync")?,
+                disable_rrdp: table.take_bool("disable-rrdp")?,
+            };
+            table.check_exhausted()?;
+            res.insert(name, policy);
+        }
+        Ok(res)
+    }
+
+    /// Takes the Erik policies for individual hosts from the config file.
+    fn erik_hosts_from_config_file(
+        file: &mut ConfigFile
+    ) -> Result<HashMap<String, ErikPolicy>, Failed> {
+        let mut res = HashMap::new();
+        for (host, policy) in file.take_string_map("erik-hosts")?
+            .unwrap_or_default()
+        {
+            match ErikPolicy::from_str(&policy) {
+                Ok(policy) => {
+                    res.insert(host.to_ascii_lowercase(), policy);
+                }
+                Err(err) => {
+                    error!(
+                        "Failed in config file {}: \
+                         illegal value in 'erik-hosts': {}.",
+                        file.path.display(), err
+                    );
+                    return Err(Failed)
+                }
+            }
+        }
+        Ok(res)
+    }
+
     /// Creates a default config with the given paths.
     ///
     /// Uses default values for everything except for the config file path
@@ -1163,32 +1728,60 @@ impl Config {
             stale: DEFAULT_STALE_POLICY,
             unsafe_vrps: DEFAULT_UNSAFE_VRPS_POLICY,
             unknown_objects: DEFAULT_UNKNOWN_OBJECTS_POLICY,
+            manifest_replay: DEFAULT_MANIFEST_REPLAY_POLICY,
             limit_v4_len: None,
             limit_v6_len: None,
             allow_dubious_hosts: false,
             fresh: false,
+            as_of: None,
+            offline_source: None,
             disable_rsync: false,
             rsync_command: "rsync".into(),
+            rsync_native: false,
             rsync_args: None,
             rsync_timeout: Some(DEFAULT_RSYNC_TIMEOUT),
+            rsync_max_host_processes: 0,
+            rsync_history_size: DEFAULT_RSYNC_HISTORY_SIZE,
+            rsync_keep_responses: None,
+            rsync_replay_responses: None,
             disable_rrdp: false,
             rrdp_fallback: DEFAULT_RRDP_FALLBACK,
             rrdp_fallback_time: DEFAULT_RRDP_FALLBACK_TIME,
             rrdp_max_delta_count: DEFAULT_RRDP_MAX_DELTA_COUNT,
             rrdp_max_delta_list_len: DEFAULT_RRDP_MAX_DELTA_LIST_LEN,
+            rrdp_max_host_requests: 0,
+            rrdp_delta_prefetch: DEFAULT_RRDP_DELTA_PREFETCH,
             rrdp_timeout: Some(DEFAULT_RRDP_TIMEOUT), 
             rrdp_connect_timeout: None,
             rrdp_tcp_keepalive: Some(DEFAULT_RRDP_TCP_KEEPALIVE),
+            rrdp_idle_timeout: Some(DEFAULT_RRDP_IDLE_TIMEOUT),
             rrdp_local_addr: None,
             rrdp_root_certs: Vec::new(),
             rrdp_proxies: Vec::new(),
             rrdp_user_agent: DEFAULT_RRDP_USER_AGENT.to_string(),
             rrdp_keep_responses: None,
+            rrdp_replay_responses: None,
+            erik_relay: None,
+            erik_policy: DEFAULT_ERIK_POLICY,
+            erik_hosts: HashMap::new(),
+            uri_rewrites: HashMap::new(),
+            uri_rewrite_schemes: Vec::new(),
+            repository_backoff: None,
+            repository_backoff_max: DEFAULT_REPOSITORY_BACKOFF_MAX,
+            bandwidth_limit: None,
+            host_bandwidth_limit: None,
             max_object_size: Some(DEFAULT_MAX_OBJECT_SIZE),
             max_ca_depth: DEFAULT_MAX_CA_DEPTH,
+            max_repository_objects: None,
+            max_repository_size: None,
+            max_repository_cas: None,
+            max_repository_points: None,
+            max_repository_time: None,
             enable_bgpsec: false,
             enable_aspa: false,
             dirty_repository: DEFAULT_DIRTY_REPOSITORY,
+            incremental_validation: false,
+            ta_pinning: None,
             validation_threads: Config::default_validation_threads(),
             refresh: Duration::from_secs(DEFAULT_REFRESH),
             retry: Duration::from_secs(DEFAULT_RETRY),
@@ -1201,6 +1794,13 @@ impl Config {
             systemd_listen: false,
             rtr_tcp_keepalive: DEFAULT_RTR_TCP_KEEPALIVE,
             rtr_client_metrics: false,
+            rtr_client_labels: HashMap::new(),
+            rtr_audit_log: None,
+            rtr_audit_log_size: DEFAULT_RTR_AUDIT_LOG_SIZE,
+            rtr_audit_log_files: DEFAULT_RTR_AUDIT_LOG_FILES,
+            rtr_shutdown_grace: DEFAULT_RTR_SHUTDOWN_GRACE,
+            object_report: false,
+            rrdp_publish_uri: None,
             rtr_tls_key: None,
             rtr_tls_cert: None,
             http_tls_key: None,
@@ -1213,6 +1813,7 @@ impl Config {
             user: None,
             group: None,
             tal_labels: HashMap::new(),
+            tal_policies: HashMap::new(),
         }
     }
 
@@ -1289,6 +1890,28 @@ impl Config {
         Ok(())
     }
 
+    /// Returns the global validation policy.
+    pub fn validation_policy(&self) -> ValidationPolicy {
+        ValidationPolicy {
+            strict: self.strict,
+            stale: self.stale,
+            unsafe_vrps: self.unsafe_vrps,
+            unknown_objects: self.unknown_objects,
+            manifest_replay: self.manifest_replay,
+            max_ca_depth: self.max_ca_depth,
+            disable_rsync: self.disable_rsync,
+            disable_rrdp: self.disable_rrdp,
+        }
+    }
+
+    /// Returns the effective validation policy for the TAL with the name.
+    ///
+    /// This is the global policy with the overrides configured for the TAL
+    /// applied.
+    pub fn tal_policy(&self, name: &str) -> ValidationPolicy {
+        self.validation_policy().with_overrides(self.tal_policies.get(name))
+    }
+
     /// Returns a TOML representation of the config.
     pub fn to_toml(&self) -> toml::Table {
         fn insert(
@@ -1331,6 +1954,9 @@ impl Config {
         insert(
             &mut res, "unknown-objects", format!("{}", self.unknown_objects)
         );
+        insert(
+            &mut res, "manifest-replay", format!("{}", self.manifest_replay)
+        );
         if let Some(value) = self.limit_v4_len {
             insert(&mut res, "limit-v4-len", i64::from(value));
         }
@@ -1340,6 +1966,7 @@ impl Config {
         insert(&mut res, "allow-dubious-hosts", self.allow_dubious_hosts);
         insert(&mut res, "disable-rsync", self.disable_rsync);
         insert(&mut res, "rsync-command", self.rsync_command.clone());
+        insert(&mut res, "rsync-native", self.rsync_native);
         if let Some(ref args) = self.rsync_args {
             insert(
                 &mut res, "rsync-args",
@@ -1355,6 +1982,25 @@ impl Config {
                 Some(value) => value.as_secs(),
             }
         );
+        insert_int(
+            &mut res, "rsync-max-host-processes",
+            self.rsync_max_host_processes
+        );
+        insert_int(
+            &mut res, "rsync-history-size", self.rsync_history_size
+        );
+        if let Some(path) = self.rsync_keep_responses.as_ref() {
+            insert(
+                &mut res, "rsync-keep-responses",
+                format!("{}", path.display())
+            );
+        }
+        if let Some(path) = self.rsync_replay_responses.as_ref() {
+            insert(
+                &mut res, "rsync-replay-responses",
+                format!("{}", path.display())
+            );
+        }
         insert(&mut res, "disable-rrdp", self.disable_rrdp);
         insert(&mut res, "rrdp-fallback", self.rrdp_fallback.to_string());
         insert_int(
@@ -1366,6 +2012,12 @@ impl Config {
         insert_int(
             &mut res, "rrdp-max-delta-list-len", self.rrdp_max_delta_list_len
         );
+        insert_int(
+            &mut res, "rrdp-max-host-requests", self.rrdp_max_host_requests
+        );
+        insert_int(
+            &mut res, "rrdp-delta-prefetch", self.rrdp_delta_prefetch
+        );
         insert_int(
             &mut res, "rrdp-timeout",
             match self.rrdp_timeout {
@@ -1383,6 +2035,13 @@ impl Config {
                 Some(value) => value.as_secs(),
             }
         );
+        insert_int(
+            &mut res, "rrdp-idle-timeout",
+            match self.rrdp_idle_timeout {
+                None => 0,
+                Some(value) => value.as_secs(),
+            }
+        );
         if let Some(addr) = self.rrdp_local_addr {
             insert(&mut res, "rrdp-local-addr", addr.to_string());
         }
@@ -1407,24 +2066,109 @@ impl Config {
                 &mut res,"rrdp-keep-responses", format!("{}", path.display())
             );
         }
-        insert_int(
-            &mut res, "max-object-size",
-            self.max_object_size.unwrap_or(0),
-        );
-        insert_int(&mut res, "max-ca-depth", self.max_ca_depth);
-        insert(&mut res, "enable-bgpsec", self.enable_bgpsec);
-        #[cfg(feature = "aspa")]
-        insert(&mut res, "enable-aspa", self.enable_aspa);
-        insert(&mut res, "dirty", self.dirty_repository);
-        insert_int(&mut res, "validation-threads", self.validation_threads);
-        insert_int(&mut res, "refresh", self.refresh.as_secs());
-        insert_int(&mut res, "retry", self.retry.as_secs());
-        insert_int(&mut res, "expire", self.expire.as_secs());
-        insert_int(&mut res, "history-size", self.history_size);
-        insert(
-            &mut res, "rtr-listen",
-            toml::Value::Array(
-                self.rtr_listen.iter().map(|a| {
+        if let Some(path) = self.rrdp_replay_responses.as_ref() {
+            insert(
+                &mut res, "rrdp-replay-responses",
+                format!("{}", path.display())
+            );
+        }
+        if let Some(relay) = self.erik_relay.as_ref() {
+            insert(&mut res, "erik-relay", relay.clone());
+        }
+        insert(&mut res, "erik-policy", self.erik_policy.to_string());
+        if !self.erik_hosts.is_empty() {
+            insert(
+                &mut res, "erik-hosts",
+                toml::Value::Array(
+                    self.erik_hosts.iter().map(|(left, right)| {
+                        toml::Value::Array([
+                            toml::Value::from(left.clone()),
+                            toml::Value::from(right.to_string()),
+                        ].into_iter().collect())
+                    }).collect()
+                )
+            );
+        }
+        if !self.uri_rewrites.is_empty() {
+            insert(
+                &mut res, "uri-rewrites",
+                toml::Value::Array(
+                    self.uri_rewrites.iter().map(|(left, right)| {
+                        toml::Value::Array([
+                            toml::Value::from(left.clone()),
+                            toml::Value::from(right.clone()),
+                        ].into_iter().collect())
+                    }).collect()
+                )
+            );
+        }
+        insert(
+            &mut res, "uri-rewrite-schemes",
+            toml::Value::Array(
+                self.uri_rewrite_schemes.iter().map(|s| {
+                    toml::Value::from(s.clone())
+                }).collect()
+            )
+        );
+        insert_int(
+            &mut res, "repository-backoff",
+            self.repository_backoff.map(|v| v.as_secs()).unwrap_or(0)
+        );
+        insert_int(
+            &mut res, "repository-backoff-max",
+            self.repository_backoff_max.as_secs()
+        );
+        insert_int(
+            &mut res, "bandwidth-limit", self.bandwidth_limit.unwrap_or(0)
+        );
+        insert_int(
+            &mut res, "host-bandwidth-limit",
+            self.host_bandwidth_limit.unwrap_or(0)
+        );
+        insert_int(
+            &mut res, "max-object-size",
+            self.max_object_size.unwrap_or(0),
+        );
+        insert_int(&mut res, "max-ca-depth", self.max_ca_depth);
+        insert_int(
+            &mut res, "max-repository-objects",
+            self.max_repository_objects.unwrap_or(0),
+        );
+        insert_int(
+            &mut res, "max-repository-size",
+            self.max_repository_size.unwrap_or(0),
+        );
+        insert_int(
+            &mut res, "max-repository-cas",
+            self.max_repository_cas.unwrap_or(0),
+        );
+        insert_int(
+            &mut res, "max-repository-points",
+            self.max_repository_points.unwrap_or(0),
+        );
+        insert_int(
+            &mut res, "max-repository-time",
+            self.max_repository_time.map(|time| time.as_secs()).unwrap_or(0),
+        );
+        insert(&mut res, "enable-bgpsec", self.enable_bgpsec);
+        #[cfg(feature = "aspa")]
+        insert(&mut res, "enable-aspa", self.enable_aspa);
+        insert(&mut res, "dirty", self.dirty_repository);
+        insert(
+            &mut res, "incremental-validation", self.incremental_validation
+        );
+        if let Some(value) = self.ta_pinning {
+            insert(&mut res, "ta-pinning", i64::from(value));
+        }
+        insert_int(&mut res, "validation-threads", self.validation_threads);
+        insert_int(&mut res, "refresh", self.refresh.as_secs());
+        insert_int(&mut res, "retry", self.retry.as_secs());
+        insert_int(&mut res, "expire", self.expire.as_secs());
+        insert_int(&mut res, "history-size", self.history_size);
+        insert(
+            &mut res, "rtr-listen",
+            toml::Value::Array(
+                self.rtr_listen.iter().map(|a| {
                     toml::Value::from(a.to_string())
                 }).collect()
             )
@@ -1462,6 +2206,34 @@ impl Config {
             }
         );
         insert( &mut res, "rtr-client-metrics", self.rtr_client_metrics);
+        if !self.rtr_client_labels.is_empty() {
+            insert(
+                &mut res, "rtr-client-labels",
+                toml::Value::Array(
+                    self.rtr_client_labels.iter().map(|(left, right)| {
+                        toml::Value::Array([
+                            toml::Value::from(left.to_string()),
+                            toml::Value::from(right.clone()),
+                        ].into_iter().collect())
+                    }).collect()
+                )
+            );
+        }
+        if let Some(ref path) = self.rtr_audit_log {
+            insert(&mut res, "rtr-audit-log", path.display().to_string());
+        }
+        insert_int(&mut res, "rtr-audit-log-size", self.rtr_audit_log_size);
+        insert_int(
+            &mut res, "rtr-audit-log-files", self.rtr_audit_log_files
+        );
+        insert_int(
+            &mut res, "rtr-shutdown-grace",
+            self.rtr_shutdown_grace.as_secs()
+        );
+        insert(&mut res, "object-report", self.object_report);
+        if let Some(ref uri) = self.rrdp_publish_uri {
+            insert(&mut res, "rrdp-publish-uri", uri.to_string());
+        }
         if let Some(ref path) = self.rtr_tls_key {
             insert(&mut res, "rtr-tls-key", path.display().to_string());
         }
@@ -1526,6 +2298,14 @@ impl Config {
                 )
             );
         }
+        if !self.tal_policies.is_empty() {
+            let mut policies = toml::Table::new();
+            policies.set_implicit(true);
+            for (name, policy) in &self.tal_policies {
+                policies.insert(name, toml::Item::Table(policy.to_toml()));
+            }
+            res.insert("tal-policy", toml::Item::Table(policies));
+        }
         res
     }
 }
@@ -1557,7 +2337,7 @@ impl Default for Config {
 
 impl fmt::Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_toml())
+        write!(f, "{}", toml::DocumentMut::from(self.to_toml()))
     }
 }
 
@@ -1729,6 +2509,182 @@ impl fmt::Display for FallbackPolicy {
 }
 
 
+//------------ ErikPolicy ----------------------------------------------------
+
+/// The policy for using Erik synchronisation for a repository host.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErikPolicy {
+    /// Try Erik first and only use RRDP or rsync if that fails.
+    Preferred,
+
+    /// Use Erik instead of rsync if RRDP is unavailable.
+    Fallback,
+
+    /// Never use Erik.
+    Disabled,
+}
+
+impl FromStr for ErikPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preferred" => Ok(ErikPolicy::Preferred),
+            "fallback" => Ok(ErikPolicy::Fallback),
+            "disabled" => Ok(ErikPolicy::Disabled),
+            _ => Err(format!("invalid policy '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for ErikPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ErikPolicy::Preferred => "preferred",
+            ErikPolicy::Fallback => "fallback",
+            ErikPolicy::Disabled => "disabled",
+        })
+    }
+}
+
+
+//------------ TalPolicy -----------------------------------------------------
+
+/// Overrides of the validation policy for a single TAL.
+///
+/// Each field that is not `None` replaces the corresponding global value
+/// for all objects published under the TAL.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TalPolicy {
+    /// Should we do strict validation?
+    pub strict: Option<bool>,
+
+    /// How should we deal with stale objects?
+    pub stale: Option<FilterPolicy>,
+
+    /// How should we deal with unsafe VRPs?
+    pub unsafe_vrps: Option<FilterPolicy>,
+
+    /// How should we deal with unknown RPKI object types?
+    pub unknown_objects: Option<FilterPolicy>,
+
+    /// How should we deal with replayed manifests?
+    pub manifest_replay: Option<FilterPolicy>,
+
+    /// The maximum length of the CA chain.
+    pub max_ca_depth: Option<usize>,
+
+    /// Should we not use rsync?
+    ///
+    /// Only `Some(true)` has an effect: a transport that is disabled
+    /// globally cannot be enabled for an individual TAL.
+    pub disable_rsync: Option<bool>,
+
+    /// Should we not use RRDP?
+    ///
+    /// Only `Some(true)` has an effect: a transport that is disabled
+    /// globally cannot be enabled for an individual TAL.
+    pub disable_rrdp: Option<bool>,
+}
+
+impl TalPolicy {
+    /// Returns a TOML representation of the overrides.
+    fn to_toml(&self) -> toml::Table {
+        let mut res = toml::Table::new();
+        if let Some(strict) = self.strict {
+            res.insert("strict", toml::value(strict));
+        }
+        if let Some(stale) = self.stale {
+            res.insert("stale", toml::value(stale.to_string()));
+        }
+        if let Some(unsafe_vrps) = self.unsafe_vrps {
+            res.insert("unsafe-vrps", toml::value(unsafe_vrps.to_string()));
+        }
+        if let Some(unknown) = self.unknown_objects {
+            res.insert("unknown-objects", toml::value(unknown.to_string()));
+        }
+        if let Some(replay) = self.manifest_replay {
+            res.insert("manifest-replay", toml::value(replay.to_string()));
+        }
+        if let Some(depth) = self.max_ca_depth {
+            res.insert(
+                "max-ca-depth",
+                toml::value(i64::try_from(depth).unwrap_or(i64::MAX))
+            );
+        }
+        if let Some(disable) = self.disable_rsync {
+            res.insert("disable-rsync", toml::value(disable));
+        }
+        if let Some(disable) = self.disable_rrdp {
+            res.insert("disable-rrdp", toml::value(disable));
+        }
+        res
+    }
+}
+
+
+//------------ ValidationPolicy ----------------------------------------------
+
+/// The effective validation policy for a TAL.
+///
+/// This is the global policy with the overrides of the TAL’s [`TalPolicy`]
+/// applied. It can be acquired via [`Config::tal_policy`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ValidationPolicy {
+    /// Should we do strict validation?
+    pub strict: bool,
+
+    /// How should we deal with stale objects?
+    pub stale: FilterPolicy,
+
+    /// How should we deal with unsafe VRPs?
+    pub unsafe_vrps: FilterPolicy,
+
+    /// How should we deal with unknown RPKI object types?
+    pub unknown_objects: FilterPolicy,
+
+    /// How should we deal with replayed manifests?
+    pub manifest_replay: FilterPolicy,
+
+    /// The maximum length of the CA chain.
+    pub max_ca_depth: usize,
+
+    /// Should we not use rsync?
+    pub disable_rsync: bool,
+
+    /// Should we not use RRDP?
+    pub disable_rrdp: bool,
+}
+
+impl ValidationPolicy {
+    /// Returns the policy with the given overrides applied.
+    pub fn with_overrides(self, policy: Option<&TalPolicy>) -> Self {
+        let policy = match policy {
+            Some(policy) => policy,
+            None => return self,
+        };
+        ValidationPolicy {
+            strict: policy.strict.unwrap_or(self.strict),
+            stale: policy.stale.unwrap_or(self.stale),
+            unsafe_vrps: policy.unsafe_vrps.unwrap_or(self.unsafe_vrps),
+            unknown_objects: {
+                policy.unknown_objects.unwrap_or(self.unknown_objects)
+            },
+            manifest_replay: {
+                policy.manifest_replay.unwrap_or(self.manifest_replay)
+            },
+            max_ca_depth: policy.max_ca_depth.unwrap_or(self.max_ca_depth),
+            disable_rsync: {
+                self.disable_rsync || policy.disable_rsync.unwrap_or(false)
+            },
+            disable_rrdp: {
+                self.disable_rrdp || policy.disable_rrdp.unwrap_or(false)
+            },
+        }
+    }
+}
+
+
 //------------ GlobalArgs ----------------------------------------------------
 
 /// The global command line arguments.
@@ -1774,6 +2730,10 @@ struct GlobalArgs {
     #[arg(long, value_name = "POLICY")]
     unknown_objects: Option<FilterPolicy>,
 
+    /// The policy for handling replayed manifests
+    #[arg(long, value_name = "POLICY")]
+    manifest_replay: Option<FilterPolicy>,
+
     /// Maximum length of IPv4 prefixes included in output
     #[arg(
         long,
@@ -1798,6 +2758,14 @@ struct GlobalArgs {
     #[arg(long)]
     fresh: bool,
 
+    /// Validate stored data as if at the given time (RFC 3339)
+    #[arg(long, value_name = "TIME")]
+    as_of: Option<Time>,
+
+    /// Read all data from a local repository tree or tarball
+    #[arg(long, value_name = "PATH")]
+    offline_source: Option<PathBuf>,
+
     /// Disable rsync and only use RRDP
     #[arg(long)]
     disable_rsync: bool,
@@ -1806,10 +2774,30 @@ struct GlobalArgs {
     #[arg(long, value_name="COMMAND")]
     rsync_command: Option<String>,
 
+    /// Use the built-in rsync client instead of the rsync command
+    #[arg(long)]
+    rsync_native: bool,
+
     /// Timeout for rsync commands (0 for none)
     #[arg(long, value_name = "SECONDS")]
     rsync_timeout: Option<u64>,
 
+    /// Maximum number of concurrent rsync updates per host (0 for no limit)
+    #[arg(long, value_name = "COUNT")]
+    rsync_max_host_processes: Option<usize>,
+
+    /// Number of updates per rsync module to keep in the history
+    #[arg(long, value_name = "COUNT")]
+    rsync_history_size: Option<usize>,
+
+    /// Keep updated rsync modules in the given directory
+    #[arg(long, value_name = "PATH")]
+    rsync_keep_responses: Option<PathBuf>,
+
+    /// Replay rsync modules kept in the given directory
+    #[arg(long, value_name = "PATH")]
+    rsync_replay_responses: Option<PathBuf>,
+
     /// Disable RRDP and only use rsync
     #[arg(long)]
     disable_rrdp: bool,
@@ -1822,6 +2810,14 @@ struct GlobalArgs {
     #[arg(long, value_name = "LEN")]
     rrdp_max_delta_list_len: Option<usize>,
 
+    /// Maximum number of concurrent RRDP updates per host (0 for no limit)
+    #[arg(long, value_name = "COUNT")]
+    rrdp_max_host_requests: Option<usize>,
+
+    /// Number of RRDP delta files fetched concurrently (0 for none)
+    #[arg(long, value_name = "COUNT")]
+    rrdp_delta_prefetch: Option<usize>,
+
     /// When to fall back to rsync if RRDP fails
     #[arg(long, value_name = "POLICY")]
     rrdp_fallback: Option<FallbackPolicy>,
@@ -1842,6 +2838,10 @@ struct GlobalArgs {
     #[arg(value_name = "SECONDS")]
     rrdp_tcp_keepalive: Option<u64>,
 
+    /// Time to keep idle RRDP connections for reuse (0 for no reuse)
+    #[arg(long, value_name = "SECONDS")]
+    rrdp_idle_timeout: Option<u64>,
+
     /// Local address for outgoing RRDP connections
     #[arg(long, value_name = "ADDR")]
     rrdp_local_addr: Option<IpAddr>,
@@ -1858,6 +2858,46 @@ struct GlobalArgs {
     #[arg(long, value_name = "PATH")]
     rrdp_keep_responses: Option<PathBuf>,
 
+    /// Replay RRDP responses kept in the given directory
+    #[arg(long, value_name = "PATH")]
+    rrdp_replay_responses: Option<PathBuf>,
+
+    /// Base URI of the Erik relay to synchronise from
+    #[arg(long, value_name = "URI")]
+    erik_relay: Option<String>,
+
+    /// When to use Erik for hosts not given via --erik-host
+    #[arg(long, value_name = "POLICY")]
+    erik_policy: Option<ErikPolicy>,
+
+    /// When to use Erik for a specific repository host
+    #[arg(long, value_name = "HOST=POLICY")]
+    erik_host: Option<Vec<String>>,
+
+    /// Fetch data for URIs starting with FROM from TO instead
+    #[arg(long, value_name = "FROM=TO")]
+    uri_rewrite: Option<Vec<String>>,
+
+    /// URI scheme allowed as the target of a rewrite
+    #[arg(long, value_name = "SCHEME")]
+    uri_rewrite_scheme: Option<Vec<String>>,
+
+    /// Initial delay before retrying a failed repository (0 for none)
+    #[arg(long, value_name = "SECONDS")]
+    repository_backoff: Option<u64>,
+
+    /// Maximum delay before retrying a failed repository
+    #[arg(long, value_name = "SECONDS")]
+    repository_backoff_max: Option<u64>,
+
+    /// Maximum rate for receiving data (0 for no limit)
+    #[arg(long, value_name = "BYTES_PER_SEC")]
+    bandwidth_limit: Option<u64>,
+
+    /// Maximum rate for receiving data from a host (0 for no limit)
+    #[arg(long, value_name = "BYTES_PER_SEC")]
+    host_bandwidth_limit: Option<u64>,
+
     /// Maximum size of downloaded objects (0 for no limit)
     #[arg(long, value_name = "BYTES")]
     max_object_size: Option<u64>,
@@ -1866,6 +2906,26 @@ struct GlobalArgs {
     #[arg(long, value_name = "COUNT")]
     max_ca_depth: Option<usize>,
 
+    /// Maximum number of objects per repository (0 for no limit)
+    #[arg(long, value_name = "COUNT")]
+    max_repository_objects: Option<u64>,
+
+    /// Maximum size of all objects per repository (0 for no limit)
+    #[arg(long, value_name = "BYTES")]
+    max_repository_size: Option<u64>,
+
+    /// Maximum number of CA certificates per repository (0 for no limit)
+    #[arg(long, value_name = "COUNT")]
+    max_repository_cas: Option<u64>,
+
+    /// Maximum number of publication points per repository (0 for no limit)
+    #[arg(long, value_name = "COUNT")]
+    max_repository_points: Option<u64>,
+
+    /// Maximum seconds spent on a repository per run (0 for no limit)
+    #[arg(long, value_name = "SECONDS")]
+    max_repository_time: Option<u64>,
+
     /// Include BGPsec router keys in the data set
     #[arg(long)]
     enable_bgpsec: bool,
@@ -1879,6 +2939,18 @@ struct GlobalArgs {
     #[arg(long)]
     dirty_repository: bool,
 
+    /// Reuse the results of unchanged publication points
+    #[arg(long)]
+    incremental_validation: bool,
+
+    /// Refuse TA certificates whose resources shrank by more than PERCENT
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        value_parser = clap::value_parser!(u8).range(..=100)
+    )]
+    ta_pinning: Option<u8>,
+
     /// Number of threads for validation
     #[arg(long, value_name = "COUNT")]
     validation_threads: Option<usize>,
@@ -1956,6 +3028,30 @@ struct ServerArgs {
     #[arg(long)]
     rtr_client_metrics: bool,
 
+    /// Write an audit log of data sent to RTR clients to this file
+    #[arg(long, value_name = "PATH")]
+    rtr_audit_log: Option<PathBuf>,
+
+    /// Size of the RTR audit log before rotating it [default 100000000]
+    #[arg(long, value_name = "BYTES")]
+    rtr_audit_log_size: Option<u64>,
+
+    /// Number of rotated RTR audit log files to keep [default 10]
+    #[arg(long, value_name = "COUNT")]
+    rtr_audit_log_files: Option<usize>,
+
+    /// Time to wait for RTR connections to close on shutdown [default 5]
+    #[arg(long, value_name = "SECONDS")]
+    rtr_shutdown_grace: Option<u64>,
+
+    /// Keep a report on all objects for the HTTP server
+    #[arg(long)]
+    object_report: bool,
+
+    /// Re-publish the store via RRDP under this base URI
+    #[arg(long, value_name = "URI")]
+    rrdp_publish_uri: Option<uri::Https>,
+
     /// The private key to use for RTR over TLS
     #[arg(long, value_name = "PATH")]
     rtr_tls_key: Option<PathBuf>,
@@ -2534,6 +3630,85 @@ impl ConfigFile {
         }
     }
 
+    /// Takes a hashmap with string encoded keys from the config file.
+    ///
+    /// The value is expected to be an array of string pairs like for
+    /// [`take_string_map`][Self::take_string_map]. The first element of
+    /// each pair is converted via `FromStr::from_str`.
+    fn take_from_str_map<K>(
+        &mut self,
+        key: &str
+    ) -> Result<Option<HashMap<K, String>>, Failed>
+    where K: FromStr + Eq + hash::Hash, K::Err: fmt::Display {
+        let map = match self.take_string_map(key)? {
+            Some(map) => map,
+            None => return Ok(None)
+        };
+        let mut res = HashMap::new();
+        for (left, right) in map {
+            match K::from_str(&left) {
+                Ok(left) => {
+                    res.insert(left, right);
+                }
+                Err(err) => {
+                    error!(
+                        "Failed in config file {}: \
+                         illegal value '{}' in '{}': {}.",
+                        self.path.display(), left, key, err
+                    );
+                    return Err(Failed)
+                }
+            }
+        }
+        Ok(Some(res))
+    }
+
+    /// Takes a table of tables from the config file.
+    ///
+    /// Returns each inner table under its key as a config file of its own
+    /// so that its values can be taken via the other methods. Returns an
+    /// empty vec if there is no such key.
+    fn take_tables(
+        &mut self, key: &str
+    ) -> Result<Vec<(String, ConfigFile)>, Failed> {
+        let table = match self.content.remove(key) {
+            Some(item) => item.into_table(),
+            None => return Ok(Vec::new())
+        };
+        let table = match table {
+            Ok(table) => table,
+            Err(_) => {
+                error!(
+                    "Failed in config file {}: \
+                     '{}' expected to be a table.",
+                    self.path.display(), key
+                );
+                return Err(Failed)
+            }
+        };
+        let mut res = Vec::new();
+        for (name, item) in table {
+            match item.into_table() {
+                Ok(inner) => {
+                    res.push((name.as_str().into(), ConfigFile {
+                        content: inner.into(),
+                        path: self.path.clone(),
+                        dir: self.dir.clone(),
+                    }))
+                }
+                Err(_) => {
+                    error!(
+                        "Failed in config file {}: \
+                         '{}.{}' expected to be a table.",
+                        self.path.display(), key, name.as_str()
+                    );
+                    return Err(Failed)
+                }
+            }
+        }
+        Ok(res)
+    }
+
     /// Checks whether the config file is now empty.
     ///
     /// If it isn’t, logs a complaint and returns an error.
@@ -2743,6 +3918,51 @@ mod test {
         );
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn tal_policy_config_file() {
+        let config = ConfigFile::parse(
+            "repository-dir = \"/repodir\"\n\
+             stale = \"warn\"\n\
+             [tal-policy.testbed]\n\
+             stale = \"reject\"\n\
+             strict = true\n\
+             max-ca-depth = 8\n\
+             disable-rrdp = true",
+            Path::new("/test/routinator.conf")
+        ).unwrap();
+        let config = Config::from_config_file(config).unwrap();
+        let policy = config.tal_policy("testbed");
+        assert_eq!(policy.stale, FilterPolicy::Reject);
+        assert!(policy.strict);
+        assert_eq!(policy.max_ca_depth, 8);
+        assert!(policy.disable_rrdp);
+        assert!(!policy.disable_rsync);
+        assert_eq!(policy.unsafe_vrps, DEFAULT_UNSAFE_VRPS_POLICY);
+        assert_eq!(policy.manifest_replay, DEFAULT_MANIFEST_REPLAY_POLICY);
+        let policy = config.tal_policy("ripe");
+        assert_eq!(policy.stale, FilterPolicy::Warn);
+        assert!(!policy.strict);
+        assert_eq!(policy.max_ca_depth, DEFAULT_MAX_CA_DEPTH);
+
+        let out_file = config.to_string();
+        let in_config = Config::from_config_file(
+            ConfigFile::parse(&out_file, &config.config_file).unwrap()
+        ).unwrap();
+        assert_eq!(config.tal_policies, in_config.tal_policies);
+
+        assert!(
+            Config::from_config_file(
+                ConfigFile::parse(
+                    "repository-dir = \"/repodir\"\n\
+                     [tal-policy.testbed]\n\
+                     foo = true",
+                    Path::new("/test/routinator.conf")
+                ).unwrap()
+            ).is_err()
+        );
+    }
+
     #[test]
     fn read_your_own_config() {
         let out_config = get_default_config();
@@ -2773,6 +3993,173 @@ mod test {
         assert_eq!(config.log_target, LogTarget::Syslog(Facility::LOG_AUTH));
     }
 
+    #[test]
+    fn as_of_arg() {
+        let config = process_basic_args(&["routinator"]);
+        assert_eq!(config.as_of, None);
+        let config = process_basic_args(&[
+            "routinator", "--as-of", "2024-06-01T12:00:00Z"
+        ]);
+        assert_eq!(
+            config.as_of, Some(Time::utc(2024, 6, 1, 12, 0, 0))
+        );
+    }
+
+    #[test]
+    fn offline_source_arg() {
+        let config = process_basic_args(&["routinator"]);
+        assert!(config.offline_source.is_none());
+        let config = process_basic_args(&[
+            "routinator", "--offline-source", "/test/snapshot.tar.gz"
+        ]);
+        assert_eq!(
+            config.offline_source.unwrap().to_str().unwrap(),
+            "/test/snapshot.tar.gz"
+        );
+    }
+
+    #[test]
+    fn ta_pinning_arg() {
+        let config = process_basic_args(&["routinator"]);
+        assert_eq!(config.ta_pinning, None);
+        let config = process_basic_args(&[
+            "routinator", "--ta-pinning", "10"
+        ]);
+        assert_eq!(config.ta_pinning, Some(10));
+    }
+
+    #[test]
+    fn repository_limit_args() {
+        let config = process_basic_args(&["routinator"]);
+        assert_eq!(config.max_repository_objects, None);
+        assert_eq!(config.max_repository_time, None);
+        let config = process_basic_args(&[
+            "routinator",
+            "--max-repository-objects", "1000",
+            "--max-repository-size", "0",
+            "--max-repository-time", "60",
+        ]);
+        assert_eq!(config.max_repository_objects, Some(1000));
+        assert_eq!(config.max_repository_size, None);
+        assert_eq!(config.max_repository_cas, None);
+        assert_eq!(
+            config.max_repository_time, Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn repository_backoff_args() {
+        let config = process_basic_args(&["routinator"]);
+        assert_eq!(config.repository_backoff, None);
+        assert_eq!(
+            config.repository_backoff_max, DEFAULT_REPOSITORY_BACKOFF_MAX
+        );
+        let config = process_basic_args(&[
+            "routinator",
+            "--repository-backoff", "300",
+            "--repository-backoff-max", "3600",
+        ]);
+        assert_eq!(
+            config.repository_backoff, Some(Duration::from_secs(300))
+        );
+        assert_eq!(config.repository_backoff_max, Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn throttle_args() {
+        let config = process_basic_args(&["routinator"]);
+        assert_eq!(config.rrdp_max_host_requests, 0);
+        assert_eq!(config.rsync_max_host_processes, 0);
+        assert_eq!(config.rsync_history_size, DEFAULT_RSYNC_HISTORY_SIZE);
+        assert_eq!(config.bandwidth_limit, None);
+        assert_eq!(config.host_bandwidth_limit, None);
+        let config = process_basic_args(&[
+            "routinator",
+            "--rrdp-max-host-requests", "4",
+            "--rsync-max-host-processes", "2",
+            "--rsync-history-size", "5",
+            "--bandwidth-limit", "1000000",
+            "--host-bandwidth-limit", "0",
+        ]);
+        assert_eq!(config.rrdp_max_host_requests, 4);
+        assert_eq!(config.rsync_max_host_processes, 2);
+        assert_eq!(config.rsync_history_size, 5);
+        assert_eq!(config.bandwidth_limit, Some(1000000));
+        assert_eq!(config.host_bandwidth_limit, None);
+    }
+
+    #[test]
+    fn rrdp_connection_args() {
+        let config = process_basic_args(&["routinator"]);
+        assert_eq!(config.rrdp_delta_prefetch, DEFAULT_RRDP_DELTA_PREFETCH);
+        assert_eq!(config.rrdp_idle_timeout, Some(DEFAULT_RRDP_IDLE_TIMEOUT));
+        let config = process_basic_args(&[
+            "routinator",
+            "--rrdp-delta-prefetch", "0",
+            "--rrdp-idle-timeout", "0",
+        ]);
+        assert_eq!(config.rrdp_delta_prefetch, 0);
+        assert_eq!(config.rrdp_idle_timeout, None);
+        let config = process_basic_args(&[
+            "routinator", "--rrdp-idle-timeout", "30",
+        ]);
+        assert_eq!(config.rrdp_idle_timeout, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn erik_args() {
+        let config = process_basic_args(&["routinator"]);
+        assert_eq!(config.erik_relay, None);
+        assert_eq!(config.erik_policy, DEFAULT_ERIK_POLICY);
+        assert!(config.erik_hosts.is_empty());
+        let config = process_basic_args(&[
+            "routinator",
+            "--erik-relay", "https://relay.example.net/",
+            "--erik-policy", "fallback",
+            "--erik-host", "RPKI.example.com=preferred",
+            "--erik-host", "rpki.example.org=disabled",
+        ]);
+        assert_eq!(
+            config.erik_relay.as_deref(), Some("https://relay.example.net/")
+        );
+        assert_eq!(config.erik_policy, ErikPolicy::Fallback);
+        assert_eq!(
+            config.erik_hosts.get("rpki.example.com"),
+            Some(&ErikPolicy::Preferred)
+        );
+        assert_eq!(
+            config.erik_hosts.get("rpki.example.org"),
+            Some(&ErikPolicy::Disabled)
+        );
+    }
+
+    #[test]
+    fn uri_rewrite_args() {
+        let config = process_basic_args(&["routinator"]);
+        assert!(config.uri_rewrites.is_empty());
+        assert!(config.uri_rewrite_schemes.is_empty());
+        let config = process_basic_args(&[
+            "routinator",
+            "--uri-rewrite",
+            "rsync://rpki.example.net/=file:///srv/fixtures/rsync/",
+            "--uri-rewrite",
+            "https://rrdp.example.net/=https://mirror.example.org/rrdp/",
+            "--uri-rewrite-scheme", "file",
+            "--uri-rewrite-scheme", "https",
+        ]);
+        assert_eq!(
+            config.uri_rewrites.get("rsync://rpki.example.net/")
+                .map(String::as_str),
+            Some("file:///srv/fixtures/rsync/")
+        );
+        assert_eq!(
+            config.uri_rewrites.get("https://rrdp.example.net/")
+                .map(String::as_str),
+            Some("https://mirror.example.org/rrdp/")
+        );
+        assert_eq!(config.uri_rewrite_schemes, ["file", "https"]);
+    }
+
     #[test]
     fn verbosity() {
         let config = process_basic_args(&["routinator"]);
@@ -2796,6 +4183,11 @@ mod test {
             "--rtr", "192.0.2.4:323",
             "--http", "192.0.2.4:8080",
             "--systemd-listen",
+            "--rtr-audit-log", "audit.log",
+            "--rtr-audit-log-size", "1000",
+            "--rtr-shutdown-grace", "12",
+            "--object-report",
+            "--rrdp-publish-uri", "https://rp.example.net:8323/",
         ]);
         assert_eq!(config.refresh, Duration::from_secs(7));
         assert_eq!(config.retry, Duration::from_secs(8));
@@ -2813,6 +4205,20 @@ mod test {
             vec![SocketAddr::from_str("192.0.2.4:8080").unwrap()]
         );
         assert!(config.systemd_listen);
+        assert_eq!(
+            config.rtr_audit_log,
+            Some(PathBuf::from("/test/audit.log"))
+        );
+        assert_eq!(config.rtr_audit_log_size, 1000);
+        assert_eq!(config.rtr_audit_log_files, DEFAULT_RTR_AUDIT_LOG_FILES);
+        assert_eq!(config.rtr_shutdown_grace, Duration::from_secs(12));
+        assert!(config.object_report);
+        assert_eq!(
+            config.rrdp_publish_uri,
+            Some(
+                uri::Https::from_str("https://rp.example.net:8323/").unwrap()
+            )
+        );
     }
     
     #[test]
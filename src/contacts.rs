@@ -0,0 +1,163 @@
+//! Looking up contact information for resources.
+//!
+//! Ghostbuster Records published by CAs are collected into the payload
+//! snapshot. This module allows selecting those of the CAs holding a given
+//! prefix or AS number and outputting them.
+
+use std::io;
+use chrono::{DateTime, Utc};
+use rpki::resources::{Asn, Prefix};
+use crate::payload::{Contact, PayloadSnapshot};
+use crate::utils::date::format_iso_date;
+use crate::utils::json::JsonBuilder;
+
+
+//------------ ContactQuery --------------------------------------------------
+
+/// Which contacts are we looking for?
+#[derive(Clone, Copy, Debug)]
+pub enum ContactQuery {
+    /// All contacts.
+    All,
+
+    /// The contacts of CAs holding resources overlapping a prefix.
+    Prefix(Prefix),
+
+    /// The contacts of CAs holding an AS number.
+    Asn(Asn),
+}
+
+
+//------------ ContactList ---------------------------------------------------
+
+/// A list of contacts selected from a snapshot.
+#[derive(Clone, Debug)]
+pub struct ContactList<'a> {
+    contacts: Vec<&'a Contact>,
+    created: DateTime<Utc>,
+}
+
+impl<'a> ContactList<'a> {
+    /// Selects the contacts for a query from a snapshot.
+    pub fn new(query: ContactQuery, snapshot: &'a PayloadSnapshot) -> Self {
+        ContactList {
+            contacts: match query {
+                ContactQuery::All => snapshot.contacts().iter().collect(),
+                ContactQuery::Prefix(prefix) => {
+                    snapshot.prefix_contacts(prefix).collect()
+                }
+                ContactQuery::Asn(asn) => {
+                    snapshot.asn_contacts(asn).collect()
+                }
+            },
+            created: snapshot.created(),
+        }
+    }
+
+    /// Returns whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    pub fn write_plain<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        for contact in &self.contacts {
+            let card = contact.card();
+            match (card.full_name.as_ref(), card.org.as_ref()) {
+                (Some(name), Some(org)) => {
+                    writeln!(target, "{} ({})", name, org)?
+                }
+                (Some(name), None) => writeln!(target, "{}", name)?,
+                (None, Some(org)) => writeln!(target, "{}", org)?,
+                (None, None) => writeln!(target, "(unnamed)")?,
+            }
+            for email in &card.emails {
+                writeln!(target, "  email:     {}", email)?;
+            }
+            for tel in &card.tels {
+                writeln!(target, "  tel:       {}", tel)?;
+            }
+            for addr in &card.addrs {
+                writeln!(target, "  address:   {}", addr)?;
+            }
+            if let Some(ca) = contact.ca() {
+                writeln!(target, "  CA:        {}", ca)?;
+            }
+            writeln!(target, "  GBR:       {}", contact.uri())?;
+            writeln!(target, "  TAL:       {}", contact.tal().name())?;
+            for block in contact.as_resources().iter() {
+                writeln!(target, "  resource:  {}", block)?;
+            }
+            for block in contact.v4_resources().iter() {
+                writeln!(target, "  resource:  {}", block.display_v4())?;
+            }
+            for block in contact.v6_resources().iter() {
+                writeln!(target, "  resource:  {}", block.display_v6())?;
+            }
+            writeln!(target)?;
+        }
+        Ok(())
+    }
+
+    pub fn into_json(self) -> Vec<u8> {
+        let mut res = Vec::new();
+        self.write_json(&mut res).unwrap();
+        res
+    }
+
+    pub fn write_json<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        target.write_all(JsonBuilder::build(|json| {
+            json.member_str("generatedTime", format_iso_date(self.created));
+            json.member_array("contacts", |json| {
+                for contact in &self.contacts {
+                    json.array_object(|json| {
+                        Self::contact_json(contact, json)
+                    })
+                }
+            })
+        }).as_bytes())?;
+        writeln!(target)
+    }
+
+    fn contact_json(contact: &Contact, json: &mut JsonBuilder) {
+        let card = contact.card();
+        if let Some(name) = card.full_name.as_ref() {
+            json.member_str("fullName", name);
+        }
+        if let Some(org) = card.org.as_ref() {
+            json.member_str("org", org);
+        }
+        json.member_array("email", |json| {
+            card.emails.iter().for_each(|item| json.array_str(item))
+        });
+        json.member_array("tel", |json| {
+            card.tels.iter().for_each(|item| json.array_str(item))
+        });
+        json.member_array("adr", |json| {
+            card.addrs.iter().for_each(|item| json.array_str(item))
+        });
+        if let Some(ca) = contact.ca() {
+            json.member_str("ca", ca);
+        }
+        json.member_str("uri", contact.uri());
+        json.member_str("tal", contact.tal().name());
+        json.member_array("asn", |json| {
+            for block in contact.as_resources().iter() {
+                json.array_str(block)
+            }
+        });
+        json.member_array("prefixes", |json| {
+            for block in contact.v4_resources().iter() {
+                json.array_str(block.display_v4())
+            }
+            for block in contact.v6_resources().iter() {
+                json.array_str(block.display_v6())
+            }
+        });
+    }
+}
@@ -18,37 +18,45 @@
 /// the accompanying trait [`ProcessPubPoint`] dealing with individual
 /// publication points.
 
-use std::{cmp, fmt, fs, thread};
+use std::{cmp, fmt, fs, iter, mem, thread};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use bytes::Bytes;
 use crossbeam_queue::{ArrayQueue, SegQueue};
 use log::{debug, error, info, warn};
+use rpki::crypto::digest::DigestAlgorithm;
 use rpki::crypto::keys::KeyIdentifier;
 #[allow(unused_imports)]
 use rpki::repository::aspa::{Aspa, AsProviderAttestation};
 use rpki::repository::cert::{Cert, KeyUsage, ResourceCert};
 use rpki::repository::crl::Crl;
-use rpki::repository::error::{InspectionError, ValidationError};
+use rpki::repository::error::{
+    InspectionError, ValidationError, VerificationError,
+};
 use rpki::repository::manifest::{Manifest, ManifestContent, ManifestHash};
-use rpki::repository::roa::{Roa, RouteOriginAttestation};
+use rpki::repository::resources::{AsBlocks, IpBlocks};
+use rpki::repository::roa::{Roa, RoaIpAddress, RouteOriginAttestation};
 use rpki::repository::sigobj::SignedObject;
 use rpki::repository::tal::{Tal, TalInfo, TalUri};
 use rpki::repository::x509::{Time, Validity};
 use rpki::uri;
 use crate::{collector, store, tals};
-use crate::config::{Config, FilterPolicy};
+use crate::config::{Config, FilterPolicy, TalPolicy, ValidationPolicy};
 use crate::collector::Collector;
 use crate::error::{Failed, Fatal, RunFailed};
 use crate::metrics::{
-    Metrics, PublicationMetrics, RepositoryMetrics, TalMetrics
+    Metrics, PublicationMetrics, RepositoryMetrics, TaCertMetrics, TalMetrics
+};
+use crate::store::{
+    Store, StoredManifest, StoredObject, StoredPoint, StoredTaCert
 };
-use crate::store::{Store, StoredManifest, StoredObject, StoredPoint};
-use crate::utils::str::str_from_ascii;
+use crate::utils::date::format_iso_date;
+use crate::utils::str::{append_hex, str_from_ascii};
 
 
 //------------ Configuration -------------------------------------------------
@@ -101,11 +109,14 @@ pub struct Engine {
     /// The store to load stored data from.
     store: Store,
 
-    /// Should we be strict when decoding data?
-    strict: bool,
+    /// The global validation policy.
+    policy: ValidationPolicy,
+
+    /// Overrides of the validation policy per TAL name.
+    tal_policies: HashMap<String, TalPolicy>,
 
-    /// How do we deal with stale objects?
-    stale: FilterPolicy,
+    /// The effective validation policy for each TAL in `tals`.
+    policies: Vec<ValidationPolicy>,
 
     /// Number of validation threads.
     validation_threads: usize,
@@ -113,8 +124,22 @@ pub struct Engine {
     /// Should we leave the repository dirty after a valiation run.
     dirty_repository: bool,
 
-    /// Maximum depth of the CA chain.
-    max_ca_depth: usize,
+    /// The resource limits for each repository.
+    limits: RepositoryLimits,
+
+    /// The time to validate at if not the current time.
+    as_of: Option<Time>,
+
+    /// The cached publication points of the last run.
+    ///
+    /// The key is the manifest URI of the point. If this is `None`,
+    /// incremental validation is disabled.
+    point_cache: Option<Mutex<HashMap<uri::Rsync, Arc<CachedPoint>>>>,
+
+    /// The resource shrinkage in percent allowed for new TA certificates.
+    ///
+    /// If this is `None`, TA certificates are not pinned.
+    ta_pinning: Option<u8>,
 }
 
 impl Engine {
@@ -141,13 +166,38 @@ impl Engine {
         config: &Config,
         update: bool,
     ) -> Result<Self, Failed> {
-        let collector = if update {
+        // An offline source replaces updating and can be validated at any
+        // time, so we need the collector for it even if we don’t update.
+        let collector = if
+            config.offline_source.is_some()
+            || (update && config.as_of.is_none())
+        {
             Some(Collector::new(config)?)
         }
         else {
             None
         };
-        let store = Store::new(config)?;
+        if let Some(as_of) = config.as_of {
+            if config.offline_source.is_some() {
+                info!(
+                    "Validating as of {}.", format_iso_date(as_of.into())
+                );
+            }
+            else {
+                info!(
+                    "Validating as of {} using only stored data.",
+                    format_iso_date(as_of.into())
+                );
+            }
+        }
+        // An offline source is validated on its own so that it neither
+        // mixes with nor replaces data stored earlier.
+        let store = if config.offline_source.is_some() {
+            Store::temporary(config)?
+        }
+        else {
+            Store::new(config)?
+        };
         let mut res = Engine {
             bundled_tals: tals::collect_tals(config)?,
             extra_tals_dir: config.extra_tals_dir.clone(),
@@ -155,11 +205,17 @@ impl Engine {
             tals: Vec::new(),
             collector,
             store,
-            strict: config.strict,
-            stale: config.stale,
+            policy: config.validation_policy(),
+            tal_policies: config.tal_policies.clone(),
+            policies: Vec::new(),
             validation_threads: config.validation_threads,
             dirty_repository: config.dirty_repository,
-            max_ca_depth: config.max_ca_depth,
+            limits: RepositoryLimits::from_config(config),
+            as_of: config.as_of,
+            point_cache: {
+                config.incremental_validation.then(Default::default)
+            },
+            ta_pinning: config.ta_pinning,
         };
         res.reload_tals()?;
         Ok(res)
@@ -246,6 +302,16 @@ impl Engine {
         res.sort_by(|left, right| {
             left.info().name().cmp(right.info().name())
         });
+        self.policies = res.iter().map(|tal| {
+            self.policy.with_overrides(
+                self.tal_policies.get(tal.info().name())
+            )
+        }).collect();
+        for name in self.tal_policies.keys() {
+            if !res.iter().any(|tal| tal.info().name() == name) {
+                warn!("Policy configured for unknown TAL {}.", name);
+            }
+        }
         self.tals = res;
 
         Ok(())
@@ -288,6 +354,21 @@ impl Engine {
         Ok(())
     }
 
+    /// Returns the effective validation policy for the TAL with the index.
+    ///
+    /// The index is the one used in [`CaCert::tal`].
+    pub fn tal_policy(&self, tal: usize) -> &ValidationPolicy {
+        &self.policies[tal]
+    }
+
+    /// Returns the time to validate at.
+    ///
+    /// This is the time given via the `as_of` configuration value or the
+    /// current time if that isn’t set.
+    pub fn now(&self) -> Time {
+        self.as_of.unwrap_or_else(Time::now)
+    }
+
     /// Starts a validation run.
     ///
     /// During the run, `processor` will be responsible for dealing with
@@ -317,6 +398,46 @@ impl Engine {
         }
         Ok(())
     }
+
+    /// Accepts the refused trust anchor certificate of the given TAL.
+    ///
+    /// Returns whether there was a refused certificate.
+    pub fn accept_ta_cert(&self, tal: &str) -> Result<bool, Failed> {
+        self.store.accept_ta_cert(tal)
+    }
+
+    /// Returns the state of the RRDP repositories known to the collector.
+    ///
+    /// If updates or RRDP are disabled, the list will be empty.
+    pub fn rrdp_states(
+        &self
+    ) -> Result<Vec<collector::RepositoryState>, Failed> {
+        match self.collector.as_ref() {
+            Some(collector) => collector.rrdp_states(),
+            None => Ok(Vec::new())
+        }
+    }
+
+    /// Returns the objects stored for the given RRDP repository.
+    pub fn rrdp_objects(
+        &self, rpki_notify: &uri::Https
+    ) -> Result<Vec<(uri::Rsync, Bytes)>, Failed> {
+        self.store.rrdp_objects(rpki_notify)
+    }
+
+    /// Returns the accepted trust anchor certificates of all TALs.
+    ///
+    /// TALs without an accepted certificate are skipped.
+    pub fn ta_certs(&self) -> Result<Vec<(&Tal, Bytes)>, Failed> {
+        let run = self.store.start();
+        let mut res = Vec::new();
+        for tal in &self.tals {
+            if let Some(stored) = run.load_ta_cert(tal.info().name())? {
+                res.push((tal, stored.accepted().clone()))
+            }
+        }
+        Ok(res)
+    }
 }
 
 
@@ -328,7 +449,7 @@ impl Engine {
 /// implement the [`ProcessRun`] trait. The actual run is triggered by the
 /// [`process`][Self::process] method. Upon completion, metrics of the run
 /// can be extracted through [`done`][Self::done].
-pub struct Run<'a, P> {
+pub struct Run<'a, P: ProcessRun> {
     /// A reference to the underlying validation.
     validation: &'a Engine,
 
@@ -347,11 +468,33 @@ pub struct Run<'a, P> {
     /// Was a fatal error encountered during the run?
     is_fatal: AtomicBool,
 
+    /// The resources used by each repository.
+    ///
+    /// The key is the string representation of the rpkiNotify or rsync
+    /// module URI. This is only used if any limits are configured.
+    usage: Mutex<HashMap<String, Arc<RepositoryUsage>>>,
+
+    /// The accepted publication points awaiting the end of the run.
+    ///
+    /// If any limits are configured, points are only committed once it is
+    /// known whether their repository or that of any of their ancestors
+    /// has exceeded a limit.
+    deferred: Mutex<Vec<DeferredPoint<P::PubPoint>>>,
+
+    /// The publication points cached during the run.
+    ///
+    /// This replaces the engine’s point cache if the run succeeds. It is
+    /// only used if incremental validation is enabled.
+    point_cache: Mutex<HashMap<uri::Rsync, Arc<CachedPoint>>>,
+
+    /// The trust anchor certificate metrics for each TAL index.
+    ta_certs: Mutex<HashMap<usize, TaCertMetrics>>,
+
     /// The metrics collected during the run.
     metrics: Metrics,
 }
 
-impl<'a, P> Run<'a, P> {
+impl<'a, P: ProcessRun> Run<'a, P> {
     /// Creates a new runner from all the parts.
     fn new(
         validation: &'a Engine,
@@ -363,6 +506,10 @@ impl<'a, P> Run<'a, P> {
             validation, collector, store, processor,
             had_err: AtomicBool::new(false),
             is_fatal: AtomicBool::new(false),
+            usage: Default::default(),
+            deferred: Default::default(),
+            point_cache: Default::default(),
+            ta_certs: Default::default(),
             metrics: Default::default()
         }
     }
@@ -373,6 +520,11 @@ impl<'a, P> Run<'a, P> {
             debug!("Skipping cleanup as configured.");
             return Ok(())
         }
+        if self.validation.as_of.is_some() {
+            // Data not used at some other time may still be needed now.
+            debug!("Skipping cleanup when validating at a given time.");
+            return Ok(())
+        }
 
         let mut retain = collector::Cleanup::new();
         self.store.cleanup(&mut retain)?;
@@ -405,11 +557,13 @@ impl<'a, P: ProcessRun> Run<'a, P> {
         }
 
         // Initialize our task queue with all the TALs.
-        let metrics = RunMetrics::default();
+        let mut metrics = RunMetrics::default();
         let tasks = SegQueue::new();
         for (index, tal) in self.validation.tals.iter().enumerate() {
             tasks.push(Task::Tal(TalTask { tal, index }));
-            self.metrics.tals.push(TalMetrics::new(tal.info().clone()));
+            self.metrics.tals.push(TalMetrics::new(
+                tal.info().clone(), *self.validation.tal_policy(index)
+            ));
         }
 
         // And off we trot.
@@ -443,14 +597,62 @@ impl<'a, P: ProcessRun> Run<'a, P> {
             }
         }
 
+        self.resolve_deferred(&mut metrics);
         metrics.prepare_final(&mut self.metrics);
         while let Some(metrics) = thread_metrics.pop() {
             metrics.collapse(&mut self.metrics);
         }
+        metrics.collapse(&mut self.metrics);
+        let usage = self.usage.get_mut().unwrap();
+        for repository in &mut self.metrics.repositories {
+            repository.limit_exceeded = usage.get(&repository.uri).and_then(
+                |usage| usage.exceeded()
+            );
+        }
+        for (index, ta_cert) in self.ta_certs.get_mut().unwrap().drain() {
+            self.metrics.tals[index].ta_cert = ta_cert;
+        }
+
+        // Only now can we replace the point cache. Points not seen in this
+        // run are dropped that way.
+        if let Some(cache) = self.validation.point_cache.as_ref() {
+            *cache.lock().unwrap() = mem::take(
+                self.point_cache.get_mut().unwrap()
+            );
+        }
 
         Ok(())
     }
 
+    /// Commits or cancels the points deferred because of resource limits.
+    ///
+    /// A point is cancelled if its own repository or the repository of any
+    /// of its ancestors has exceeded a limit at any time during the run.
+    fn resolve_deferred(&mut self, metrics: &mut RunMetrics) {
+        let usage = self.usage.get_mut().unwrap();
+        for point in self.deferred.get_mut().unwrap().drain(..) {
+            let exceeded = iter::successors(
+                Some(&point.cert), |cert| cert.parent()
+            ).any(|cert| {
+                usage.get(cert.repository_uri().as_ref()).is_some_and(
+                    |usage| usage.exceeded().is_some()
+                )
+            });
+            if exceeded {
+                metrics.apply(
+                    &point.rejected, point.repository_index, point.cert.tal
+                );
+                point.processor.cancel(&point.cert);
+            }
+            else {
+                metrics.apply(
+                    &point.accepted, point.repository_index, point.cert.tal
+                );
+                point.processor.commit();
+            }
+        }
+    }
+
     /// Process a task. Any task.
     fn process_task(
         &self,
@@ -486,16 +688,23 @@ impl<'a, P: ProcessRun> Run<'a, P> {
                 );
                 continue;
             }
-            let cert = match cert.validate_ta(
-                task.tal.info().clone(), self.validation.strict
+            let bytes = cert.to_captured().into_bytes();
+            let cert = match cert.validate_ta_at(
+                task.tal.info().clone(),
+                self.validation.tal_policy(task.index).strict,
+                self.validation.now(),
             ) {
-                Ok(cert) => CaCert::root(cert, uri.clone(), task.index),
+                Ok(cert) => cert,
                 Err(err) => {
                     warn!("Trust anchor {}: {}.", uri, err);
                     continue;
                 }
             };
-            let cert = match cert {
+            let cert = match self.check_ta_cert(&task, uri, bytes, cert)? {
+                Some(cert) => cert,
+                None => continue,
+            };
+            let cert = match CaCert::root(cert, uri.clone(), task.index) {
                 Ok(cert) => cert,
                 Err(_) => continue,
             };
@@ -549,6 +758,115 @@ impl<'a, P: ProcessRun> Run<'a, P> {
         })
     }
 
+    /// Checks a trust anchor certificate against the accepted certificate.
+    ///
+    /// A new certificate is recorded in the store and logged. If TA pinning
+    /// is enabled and the resources of the new certificate shrank by more
+    /// than the allowed percentage, the certificate is refused and kept
+    /// until an operator accepts it. In this case, the previously accepted
+    /// certificate is returned instead if it is still valid.
+    fn check_ta_cert(
+        &self,
+        task: &TalTask,
+        uri: &TalUri,
+        bytes: Bytes,
+        cert: ResourceCert,
+    ) -> Result<Option<ResourceCert>, Failed> {
+        let name = task.tal.info().name();
+        let mut stored = match self.store.load_ta_cert(name)? {
+            Some(stored) => stored,
+            None => {
+                let stored = StoredTaCert::new(bytes);
+                self.store.update_ta_cert(name, &stored)?;
+                self.ta_cert_metrics(task.index, &stored, false, false);
+                return Ok(Some(cert))
+            }
+        };
+        if *stored.accepted() == bytes {
+            if stored.pending().is_some() {
+                // The refused certificate has been withdrawn again.
+                stored.set_pending(None);
+                self.store.update_ta_cert(name, &stored)?;
+            }
+            self.ta_cert_metrics(task.index, &stored, false, false);
+            return Ok(Some(cert))
+        }
+
+        let old = Cert::decode(stored.accepted().clone()).ok();
+        let shrinkage = old.as_ref().map(|old| {
+            ta_resource_shrinkage(old, &cert)
+        }).unwrap_or(0.);
+        warn!(
+            "Trust anchor {}: certificate for TAL {} changed from {} to {}. \
+             Resources shrank by {:.1}%.",
+            uri, name, ta_fingerprint(stored.accepted()),
+            ta_fingerprint(&bytes), shrinkage,
+        );
+        if let (Some(limit), Some(old)) = (self.validation.ta_pinning, old) {
+            if shrinkage > f64::from(limit) {
+                error!(
+                    "Trust anchor {}: refusing new certificate as its \
+                     resources shrank by more than {}%. Run \
+                     'routinator accept-ta {}' to accept it.",
+                    uri, limit, name
+                );
+                if stored.pending() != Some(&bytes) {
+                    stored.set_pending(Some(bytes));
+                    self.store.update_ta_cert(name, &stored)?;
+                }
+                self.ta_cert_metrics(task.index, &stored, false, true);
+                return Ok(self.pinned_ta_cert(task, uri, old))
+            }
+        }
+        stored.accept(bytes);
+        self.store.update_ta_cert(name, &stored)?;
+        self.ta_cert_metrics(task.index, &stored, true, false);
+        Ok(Some(cert))
+    }
+
+    /// Validates the pinned trust anchor certificate.
+    fn pinned_ta_cert(
+        &self, task: &TalTask, uri: &TalUri, cert: Cert,
+    ) -> Option<ResourceCert> {
+        if cert.subject_public_key_info() != task.tal.key_info() {
+            warn!(
+                "Trust anchor {}: key of pinned certificate doesn’t match \
+                 TAL.",
+                uri
+            );
+            return None
+        }
+        match cert.validate_ta_at(
+            task.tal.info().clone(),
+            self.validation.tal_policy(task.index).strict,
+            self.validation.now(),
+        ) {
+            Ok(cert) => {
+                info!("Trust anchor {}: using pinned certificate.", uri);
+                Some(cert)
+            }
+            Err(err) => {
+                warn!("Trust anchor {}: pinned certificate: {}.", uri, err);
+                None
+            }
+        }
+    }
+
+    /// Records the trust anchor certificate metrics for a TAL.
+    fn ta_cert_metrics(
+        &self, index: usize, stored: &StoredTaCert,
+        changed: bool, refused: bool,
+    ) {
+        self.ta_certs.lock().unwrap().insert(
+            index,
+            TaCertMetrics {
+                fingerprint: Some(ta_fingerprint(stored.accepted())),
+                since: Some(stored.since().into()),
+                changed, refused,
+            }
+        );
+    }
+
     /// Processes a CA.
     fn process_ca_task(
         &self,
@@ -578,6 +896,47 @@ impl<'a, P: ProcessRun> Run<'a, P> {
         Ok(())
     }
 
+    /// Returns the resource usage of the repository of a CA.
+    ///
+    /// Returns `None` if no resource limits are configured.
+    fn repository_usage(
+        &self, cert: &CaCert
+    ) -> Option<Arc<RepositoryUsage>> {
+        if self.validation.limits.is_empty() {
+            return None
+        }
+        Some(
+            self.usage.lock().unwrap().entry(
+                cert.repository_uri().into_owned()
+            ).or_default().clone()
+        )
+    }
+
+    /// Returns the cached publication point for a CA if it can be reused.
+    ///
+    /// Returns `None` if incremental validation is disabled or if there is
+    /// no cached point for the CA that is still current.
+    fn cached_point(
+        &self, cert: &CaCert, manifest: &StoredManifest,
+    ) -> Option<Arc<CachedPoint>> {
+        let point = self.validation.point_cache.as_ref()?.lock().unwrap().get(
+            cert.rpki_manifest()
+        ).cloned()?;
+        if point.is_current(cert, manifest, self.validation.now()) {
+            Some(point)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Keeps a cached publication point for the next run.
+    fn cache_point(&self, cert: &CaCert, point: Arc<CachedPoint>) {
+        self.point_cache.lock().unwrap().insert(
+            cert.rpki_manifest().clone(), point
+        );
+    }
+
     /// Marks the run as failed.
     fn run_failed(&self, err: RunFailed) {
         self.had_err.store(true, Ordering::Relaxed);
@@ -611,6 +970,15 @@ struct PubPoint<'a, P: ProcessRun> {
     /// collected during object processing via `ValidPointManifest` so we can
     /// drop it if the point gets cancelled.
     metrics: PublicationMetrics,
+
+    /// The resources used by the point’s repository if limits are enabled.
+    usage: Option<Arc<RepositoryUsage>>,
+
+    /// The recording of the processing for the point cache.
+    ///
+    /// This is only `Some(_)` while processing stored data with incremental
+    /// validation enabled.
+    recording: Option<PointRecording>,
 }
 
 impl<'a, P: ProcessRun> PubPoint<'a, P> {
@@ -624,6 +992,8 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         Ok(PubPoint {
             run, cert, processor, repository_index,
             metrics: Default::default(),
+            usage: run.repository_usage(cert),
+            recording: None,
         })
     }
 
@@ -631,19 +1001,56 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
     ///
     /// Upon success, returns a list of all the child CAs of this publication
     /// point as CA processing tasks.
+    ///
+    /// If the point’s repository has exceeded one of its resource limits,
+    /// the point is rejected without processing it.
     pub fn process(
         self,
         metrics: &mut RunMetrics,
+    ) -> Result<Vec<CaTask<P::PubPoint>>, RunFailed> {
+        let usage = match self.usage.clone() {
+            Some(usage) => usage,
+            None => return self.process_limited(metrics),
+        };
+        if !self.use_resources(RepositoryLimit::Points, 1) {
+            self.reject_point(metrics);
+            return Ok(Vec::new())
+        }
+        let (run, cert) = (self.run, self.cert);
+        let start = Instant::now();
+        let res = self.process_limited(metrics);
+        usage.add(
+            RepositoryLimit::Time,
+            u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+            &run.validation.limits, cert,
+        );
+        res
+    }
+
+    /// Performs validation of the publication point within resource limits.
+    fn process_limited(
+        self,
+        metrics: &mut RunMetrics,
     ) -> Result<Vec<CaTask<P::PubPoint>>, RunFailed> {
         let mut store = self.run.store.pub_point(self.cert)?;
         if let Some(collector) = self.run.collector.as_ref() {
-            if let Some(collector) = collector.repository(self.cert)? {
+            let collector = collector.repository(
+                self.cert, self.policy()
+            )?;
+            if let Some(collector) = collector {
                 match self.process_collected(
                     collector, &mut store, metrics
                 )? {
                     Ok(res) => return Ok(res),
                     Err(mut this) => {
-                        this.metrics = Default::default();
+                        // Replayed manifests are only detected here, so we
+                        // need to keep them.
+                        this.metrics = PublicationMetrics {
+                            replayed_manifests: {
+                                this.metrics.replayed_manifests
+                            },
+                            .. Default::default()
+                        };
                         return Ok(this.process_stored(store, metrics)?)
                     }
                 }
@@ -706,31 +1113,49 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         };
 
         // Check that the collected manifest’s manifest number and thisUpdate
-        // fields are larger than the stored manifest’s. Otherwise return so
+        // fields are larger than the stored manifest’s. Otherwise, the
+        // manifest has been replayed and, depending on policy, we return so
         // we use the stored manifest.
         if let Some(mft) = store.manifest() {
-            if collected.content.manifest_number() <= mft.manifest_number() {
-                warn!(
-                    "{}: manifest number is not greater than in stored \
-                     version. Using stored publication point.",
-                     self.cert.rpki_manifest(),
-                );
-                return Ok(Err(self))
+            let number = collected.content.manifest_number();
+            let reason = if number <= mft.manifest_number() {
+                Some("manifest number is not greater than in stored version")
             }
-            if collected.content.this_update() <= mft.this_update() {
-                warn!(
-                    "{}: manifest thisUpdate is not later than in stored \
-                     version. Using stored publication point.",
-                     self.cert.rpki_manifest(),
-                );
-                return Ok(Err(self))
+            else if collected.content.this_update() <= mft.this_update() {
+                Some(
+                    "manifest thisUpdate is not later than in stored version"
+                )
+            }
+            else {
+                None
+            };
+            if let Some(reason) = reason {
+                self.metrics.replayed_manifests += 1;
+                match self.policy().manifest_replay {
+                    FilterPolicy::Reject => {
+                        warn!(
+                            "{}: {}. Using stored publication point.",
+                            self.cert.rpki_manifest(), reason
+                        );
+                        self.processor.restart()?;
+                        return Ok(Err(self))
+                    }
+                    FilterPolicy::Warn => {
+                        warn!(
+                            "{}: {}. Using it anyway.",
+                            self.cert.rpki_manifest(), reason
+                        );
+                    }
+                    FilterPolicy::Accept => { }
+                }
             }
         }
 
         // The manifest is fine, so we can continue.
         //
-        // First, report its validity to the processor.
-        collected.point_validity(&mut self.processor);
+        // First, report its validity and CRL to the processor.
+        self.point_validity(&collected);
+        self.process_crl(&collected);
 
         // We can look at the objects now. The objects are fine if they are
         // present and match the hash. If they don’t we have to cancel the
@@ -748,6 +1173,7 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         let mut ca_tasks = Vec::new();
         let mut items = collected.content.iter();
         let mut point_ok = true;
+        let mut aborted = None;
         let update_result = store.update(
             StoredManifest::new(
                 &collected.ee_cert,
@@ -766,10 +1192,15 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
                 let file = match str_from_ascii(item.file()) {
                     Ok(file) => file,
                     Err(_) => {
-                        warn!("{}: illegal file name '{}'.",
-                            self.cert.rpki_manifest(),
+                        let reason = format!(
+                            "illegal file name '{}'",
                             String::from_utf8_lossy(item.file())
                         );
+                        warn!("{}: {}.", self.cert.rpki_manifest(), reason);
+                        aborted = Some((
+                            self.cert.rpki_manifest().clone(),
+                            ObjectType::Manifest, reason
+                        ));
                         return Err(store::UpdateError::Abort)
                     }
                 };
@@ -785,12 +1216,21 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
                     Some(content) => content,
                     None => {
                         warn!("{}: failed to load.", uri);
+                        let object_type = ObjectType::from_uri(&uri);
+                        aborted = Some((
+                            uri, object_type, "failed to load".into()
+                        ));
                         return Err(store::UpdateError::Abort)
                     }
                 };
 
                 if hash.verify(&content).is_err() {
                     warn!("{}: file has wrong manifest hash.", uri);
+                    let object_type = ObjectType::from_uri(&uri);
+                    aborted = Some((
+                        uri, object_type,
+                        "file has wrong manifest hash".into()
+                    ));
                     return Err(store::UpdateError::Abort)
                 }
 
@@ -819,7 +1259,17 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
                 }
             }
             Err(store::UpdateError::Abort) => {
-                // Update was aborted. We need to use the store.
+                // Update was aborted. We need to use the store. The
+                // processor has to forget what it has seen so far, but
+                // should learn why we gave up.
+                self.processor.restart()?;
+                if let Some((uri, object_type, reason)) = aborted {
+                    self.processor.object_status(
+                        ObjectOutcome::new(
+                            &uri, object_type, ObjectStatus::Invalid
+                        ).with_reason(reason)
+                    );
+                }
                 Ok(Err(self))
             }
             Err(store::UpdateError::Failed(err)) => {
@@ -841,40 +1291,44 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         repository: &collector::Repository,
     ) -> Result<Option<ValidPointManifest>, RunFailed> {
         let manifest = match Manifest::decode(
-            manifest_bytes.clone(), self.run.validation.strict
+            manifest_bytes.clone(), self.policy().strict
         ) {
             Ok(manifest) => manifest,
             Err(_) => {
                 self.metrics.invalid_manifests += 1;
-                warn!(
-                    "{}: failed to decode manifest.",
-                    self.cert.rpki_manifest()
-                );
+                self.invalid_manifest("failed to decode manifest");
                 return Ok(None)
             }
         };
-        let (ee_cert, content) = match manifest.validate(
-            self.cert.cert(), self.run.validation.strict
+        let (ee_cert, content) = match manifest.validate_at(
+            self.cert.cert(), self.policy().strict,
+            self.run.validation.now(),
         ) {
             Ok(some) => some,
             Err(err) => {
                 self.metrics.invalid_manifests += 1;
-                warn!("{}: {}.", self.cert.rpki_manifest(), err);
+                self.invalid_manifest(err);
                 return Ok(None)
             }
         };
 
-        if content.this_update() > Time::now() {
+        if content.this_update() > self.run.validation.now() {
             self.metrics.premature_manifests += 1;
             warn!("{}: premature manifest", self.cert.rpki_manifest());
+            self.report_manifest(&content, ObjectStatus::Invalid, Some(
+                "premature manifest"
+            ));
             return Ok(None)
         }
 
-        if content.is_stale() {
+        if self.is_stale(content.next_update()) {
             self.metrics.stale_manifests += 1;
-            match self.run.validation.stale {
+            match self.policy().stale {
                 FilterPolicy::Reject => {
                     warn!("{}: stale manifest", self.cert.rpki_manifest());
+                    self.report_manifest(&content, ObjectStatus::Stale, Some(
+                        "stale manifest"
+                    ));
                     return Ok(None)
                 }
                 FilterPolicy::Warn => {
@@ -892,6 +1346,8 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         };
 
         self.metrics.valid_manifests += 1;
+        self.report_manifest(&content, ObjectStatus::Valid, None);
+        self.report_crl(&crl_uri, &crl, ObjectStatus::Valid, None);
 
         Ok(Some(ValidPointManifest {
             ee_cert, content, crl_uri, crl, manifest_bytes, crl_bytes,
@@ -920,7 +1376,7 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             Some(some) if some.ends_with(".crl") => some.clone(),
             _ => {
                 self.metrics.invalid_manifests += 1;
-                warn!("{}: invalid CRL URI.", self.cert.rpki_manifest());
+                self.invalid_manifest("invalid CRL URI");
                 return Ok(None)
             }
         };
@@ -928,10 +1384,7 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             Some(name) => name,
             None => {
                 self.metrics.invalid_manifests += 1;
-                warn!(
-                    "{}: CRL URI outside repository directory.",
-                    self.cert.rpki_manifest()
-                );
+                self.invalid_manifest("CRL URI outside repository directory");
                 return Ok(None)
             }
         };
@@ -946,14 +1399,18 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
                     Some(bytes) => bytes,
                     None => {
                         self.metrics.invalid_crls += 1;
-                        warn!("{}: failed to load.", crl_uri);
+                        self.invalid_object(
+                            &crl_uri, ObjectType::Crl, "failed to load"
+                        );
                         return Ok(None)
                     }
                 };
                 let hash = ManifestHash::new(hash, manifest.file_hash_alg());
                 if hash.verify(&bytes).is_err() {
                     self.metrics.invalid_crls += 1;
-                    warn!("{}: file has wrong hash.", crl_uri);
+                    self.invalid_object(
+                        &crl_uri, ObjectType::Crl, "file has wrong hash"
+                    );
                     return Ok(None)
                 }
                 crl_bytes = Some(bytes);
@@ -963,10 +1420,7 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             Some(some) => some,
             None => {
                 self.metrics.invalid_crls += 1;
-                warn!(
-                    "{}: CRL not listed on manifest.",
-                    self.cert.rpki_manifest()
-                );
+                self.invalid_manifest("CRL not listed on manifest");
                 return Ok(None)
             }
         };
@@ -976,7 +1430,9 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             Ok(crl) => crl,
             Err(_) => {
                 self.metrics.invalid_crls += 1;
-                warn!("{}: failed to decode CRL.", crl_uri);
+                self.invalid_object(
+                    &crl_uri, ObjectType::Crl, "failed to decode CRL"
+                );
                 return Ok(None)
             }
         };
@@ -984,14 +1440,17 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             self.cert.cert().subject_public_key_info()
         ) {
             self.metrics.invalid_crls += 1;
-            warn!("{}: {}.", crl_uri, err);
+            self.invalid_object(&crl_uri, ObjectType::Crl, err);
             return Ok(None)
         }
-        if crl.is_stale() {
+        if self.is_stale(crl.next_update()) {
             self.metrics.stale_crls += 1;
-            match self.run.validation.stale {
+            match self.policy().stale {
                 FilterPolicy::Reject => {
                     warn!("{}: stale CRL.", crl_uri);
+                    self.report_crl(
+                        &crl_uri, &crl, ObjectStatus::Stale, Some("stale CRL")
+                    );
                     return Ok(None)
                 }
                 FilterPolicy::Warn => {
@@ -1009,10 +1468,7 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         // Finally: has the manifest’s cert been revoked?
         if crl.contains(ee_cert.serial_number()) {
             self.metrics.invalid_manifests += 1;
-            warn!(
-                "{}: certificate has been revoked.",
-                self.cert.rpki_manifest()
-            );
+            self.invalid_manifest("certificate has been revoked");
             return Ok(None)
         }
 
@@ -1026,6 +1482,10 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
     /// Validates the objects of the currently stored version of the
     /// publication point if there is one.
     ///
+    /// If incremental validation is enabled and the stored point hasn’t
+    /// changed since it was last validated, the cached result is used
+    /// instead. Otherwise the outcome of a successful validation is cached.
+    ///
     /// Returns a list of all child CAs of this publication point.
     fn process_stored(
         mut self,
@@ -1041,12 +1501,28 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
                     "{}: no valid manifest {} found.",
                     self.cert.uri(), self.cert.rpki_manifest()
                 );
+                self.processor.object_status(
+                    ObjectOutcome::new(
+                        self.cert.rpki_manifest(), ObjectType::Manifest,
+                        ObjectStatus::Invalid,
+                    ).with_reason("no valid manifest found")
+                );
                 self.metrics.missing_manifests += 1;
                 self.reject_point(metrics);
                 return Ok(Vec::new())
             }
         };
 
+        if let Some(cached) = self.run.cached_point(self.cert, &manifest) {
+            if self.want_cached(&cached)? {
+                return self.process_cached(cached, metrics)
+            }
+        }
+        let start = Instant::now();
+        if self.run.validation.point_cache.is_some() {
+            self.recording = Some(PointRecording::new(&manifest));
+        }
+
         let mut manifest = match self.validate_stored_manifest(manifest) {
             Ok(manifest) => manifest,
             Err(_) => {
@@ -1055,7 +1531,8 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             }
         };
 
-        manifest.point_validity(&mut self.processor);
+        self.point_validity(&manifest);
+        self.process_crl(&manifest);
 
         let mut ca_tasks = Vec::new();
         for object in &mut store {
@@ -1089,10 +1566,113 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             }
         }
 
+        if let Some(recording) = self.recording.take() {
+            let point = recording.finalize(
+                self.cert, &manifest.metrics, start.elapsed(),
+                self.run.validation.now(),
+            );
+            if let Some(point) = point {
+                self.run.cache_point(self.cert, Arc::new(point));
+            }
+        }
         self.accept_point(manifest, metrics);
         Ok(ca_tasks)
     }
 
+    /// Checks whether the processor wants all objects of a cached point.
+    ///
+    /// If the processor doesn’t want some of the objects, we can’t use the
+    /// cached point since it was recorded with all objects processed.
+    fn want_cached(&self, cached: &CachedPoint) -> Result<bool, Failed> {
+        for uri in &cached.objects {
+            if !self.processor.want(uri)? {
+                return Ok(false)
+            }
+        }
+        Ok(true)
+    }
+
+    /// Processes a publication point from the point cache.
+    ///
+    /// Replays everything that was reported to the processor when the point
+    /// was validated. Child CAs are re-created from their cached validated
+    /// certificates.
+    fn process_cached(
+        mut self,
+        cached: Arc<CachedPoint>,
+        metrics: &mut RunMetrics,
+    ) -> Result<Vec<CaTask<P::PubPoint>>, Failed> {
+        // The point’s resources count towards the repository limits just
+        // as if it had been validated again.
+        let cas = cached.events.iter().filter(|event| {
+            matches!(event, CachedEvent::Ca(..))
+        }).count();
+        if !self.use_resources(
+                RepositoryLimit::Objects,
+                u64::try_from(cached.objects.len()).unwrap_or(u64::MAX)
+            )
+            || !self.use_resources(RepositoryLimit::Size, cached.size)
+            || !self.use_resources(
+                RepositoryLimit::Cas,
+                u64::try_from(cas).unwrap_or(u64::MAX)
+            )
+        {
+            self.reject_point(metrics);
+            return Ok(Vec::new())
+        }
+
+        let mut ca_tasks = Vec::new();
+        for event in &cached.events {
+            match event {
+                CachedEvent::PointValidity(manifest_ee, stale) => {
+                    self.processor.point_validity(*manifest_ee, *stale)
+                }
+                CachedEvent::Status(outcome) => {
+                    self.processor.object_status(outcome.outcome())
+                }
+                CachedEvent::Crl(uri, crl) => {
+                    self.processor.process_crl(uri, crl)
+                }
+                CachedEvent::Ca(uri, cert) => {
+                    self.process_valid_ca(uri, cert.clone(), &mut ca_tasks)?;
+                }
+                CachedEvent::RouterCert(uri, cert) => {
+                    self.processor.process_router_cert(
+                        uri, cert.clone(), self.cert
+                    )?
+                }
+                CachedEvent::Roa(uri, cert, route) => {
+                    self.processor.process_roa(
+                        uri, cert.clone(), route.clone()
+                    )?
+                }
+                #[cfg(feature = "aspa")]
+                CachedEvent::Aspa(uri, cert, aspa) => {
+                    self.processor.process_aspa(
+                        uri, cert.clone(), aspa.clone()
+                    )?
+                }
+                CachedEvent::Gbr(uri, cert, content) => {
+                    self.processor.process_gbr(
+                        uri, cert.clone(), content.clone()
+                    )?
+                }
+            }
+        }
+
+        // A cached point always had a valid manifest and CRL.
+        let rejected = self.metrics.clone();
+        self.metrics.valid_points += 1;
+        self.metrics.valid_manifests += 1;
+        self.metrics.valid_crls += 1;
+        self.metrics.cached_points += 1;
+        self.metrics.cache_time_saved += cached.duration;
+        self.metrics += &cached.metrics;
+        self.run.cache_point(self.cert, cached);
+        self.commit_point(rejected, metrics);
+        Ok(ca_tasks)
+    }
+
     /// Tries to validate a stored manifest.
     ///
     /// This is similar to
@@ -1105,33 +1685,34 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
     ) -> Result<ValidPointManifest, Failed> {
         // Decode and validate the manifest.
         let manifest = match Manifest::decode(
-            stored_manifest.manifest().clone(), self.run.validation.strict
+            stored_manifest.manifest().clone(), self.policy().strict
         ) {
             Ok(manifest) => manifest,
             Err(_) => {
                 self.metrics.invalid_manifests += 1;
-                warn!(
-                    "{}: failed to decode manifest.",
-                    self.cert.rpki_manifest(),
-                );
+                self.invalid_manifest("failed to decode manifest");
                 return Err(Failed);
             }
         };
-        let (ee_cert, content) = match manifest.validate(
-            self.cert.cert(), self.run.validation.strict
+        let (ee_cert, content) = match manifest.validate_at(
+            self.cert.cert(), self.policy().strict,
+            self.run.validation.now(),
         ) {
             Ok(some) => some,
             Err(err) => {
-                warn!("{}: {}.", self.cert.rpki_manifest(), err);
+                self.invalid_manifest(err);
                 self.metrics.invalid_manifests += 1;
                 return Err(Failed);
             }
         };
-        if content.is_stale() {
+        if self.is_stale(content.next_update()) {
             self.metrics.stale_manifests += 1;
-            match self.run.validation.stale {
+            match self.policy().stale {
                 FilterPolicy::Reject => {
                     warn!("{}: stale manifest", self.cert.rpki_manifest());
+                    self.report_manifest(&content, ObjectStatus::Stale, Some(
+                        "stale manifest"
+                    ));
                     self.metrics.invalid_manifests += 1;
                     return Err(Failed);
                 }
@@ -1147,10 +1728,7 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             Some(uri) => uri.clone(),
             None => {
                 // This should have been ruled out in manifest validation.
-                warn!(
-                    "{}: manifest without CRL URI.",
-                    self.cert.rpki_manifest()
-                );
+                self.invalid_manifest("manifest without CRL URI");
                 self.metrics.invalid_manifests += 1;
                 return Err(Failed)
             }
@@ -1162,23 +1740,28 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             Err(_) => {
                 self.metrics.invalid_manifests += 1;
                 self.metrics.invalid_crls += 1;
-                warn!("{}: failed to decode CRL.", crl_uri);
+                self.invalid_object(
+                    &crl_uri, ObjectType::Crl, "failed to decode CRL"
+                );
                 return Err(Failed)
             }
         };
         if let Err(err) = crl.verify_signature(
             self.cert.cert().subject_public_key_info()
         ) {
-            warn!("{}: {}.", crl_uri, err);
+            self.invalid_object(&crl_uri, ObjectType::Crl, err);
             self.metrics.invalid_manifests += 1;
             self.metrics.invalid_crls += 1;
             return Err(Failed)
         }
-        if crl.is_stale() {
+        if self.is_stale(crl.next_update()) {
             self.metrics.stale_crls += 1;
-            match self.run.validation.stale {
+            match self.policy().stale {
                 FilterPolicy::Reject => {
                     warn!("{}: stale CRL.", crl_uri);
+                    self.report_crl(
+                        &crl_uri, &crl, ObjectStatus::Stale, Some("stale CRL")
+                    );
                     self.metrics.invalid_manifests += 1;
                     self.metrics.invalid_crls += 1;
                     return Err(Failed)
@@ -1200,16 +1783,15 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         // XXX This shouldn’t really happen because if it were we would never
         //     have stored this manifest.
         if crl.contains(ee_cert.serial_number()) {
-            warn!(
-                "{}: certificate has been revoked.",
-                self.cert.rpki_manifest()
-            );
+            self.invalid_manifest("certificate has been revoked");
             self.metrics.invalid_manifests += 1;
             return Err(Failed)
         }
 
         self.metrics.valid_manifests += 1;
         self.metrics.valid_crls += 1;
+        self.report_manifest(&content, ObjectStatus::Valid, None);
+        self.report_crl(&crl_uri, &crl, ObjectStatus::Valid, None);
         Ok(ValidPointManifest {
             ee_cert, content, crl_uri, crl,
             manifest_bytes: stored_manifest.manifest().clone(),
@@ -1218,44 +1800,216 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         })
     }
 
-    // XXX Check metrics generation and concentrate at one central point.
+    /// Accounts for resources used by the publication point.
+    ///
+    /// Returns whether the point’s repository is still within its limits.
+    fn use_resources(&self, limit: RepositoryLimit, amount: u64) -> bool {
+        match self.usage.as_ref() {
+            Some(usage) => {
+                usage.add(
+                    limit, amount, &self.run.validation.limits, self.cert
+                )
+            }
+            None => true
+        }
+    }
 
-    fn accept_point(
-        mut self,
-        manifest: ValidPointManifest,
-        metrics: &mut RunMetrics,
-    ) {
-        self.metrics.valid_points += 1;
-        self.metrics += manifest.metrics;
-        self.apply_metrics(metrics);
-        self.processor.commit();
+    /// Returns the validation policy of the publication point’s TAL.
+    fn policy(&self) -> &'a ValidationPolicy {
+        self.run.validation.tal_policy(self.cert.tal)
     }
 
-    fn reject_point(
-        mut self,
-        metrics: &mut RunMetrics,
-    ) {
-        self.metrics.rejected_points += 1;
-        self.apply_metrics(metrics);
-        self.processor.cancel(self.cert);
+    /// Returns whether an object with the given next update time is stale.
+    fn is_stale(&self, next_update: Time) -> bool {
+        next_update < self.run.validation.now()
     }
 
-    fn apply_metrics(
+    /// Reports the validity of the publication point.
+    fn point_validity(&mut self, manifest: &ValidPointManifest) {
+        let (manifest_ee, stale) = manifest.point_validity();
+        self.record(|| CachedEvent::PointValidity(manifest_ee, stale));
+        self.processor.point_validity(manifest_ee, stale)
+    }
+
+    /// Hands the CRL of the publication point to the processor.
+    fn process_crl(&mut self, manifest: &ValidPointManifest) {
+        self.record(|| {
+            CachedEvent::Crl(manifest.crl_uri.clone(), manifest.crl.clone())
+        });
+        self.processor.process_crl(&manifest.crl_uri, &manifest.crl)
+    }
+
+    /// Reports the outcome of validating an object.
+    fn object_status(&mut self, outcome: ObjectOutcome) {
+        self.record(|| CachedEvent::Status(CachedOutcome::new(&outcome)));
+        self.processor.object_status(outcome)
+    }
+
+    /// Adds an event to the recording for the point cache if necessary.
+    fn record(&mut self, event: impl FnOnce() -> CachedEvent) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.events.push(event())
+        }
+    }
+
+    /// Logs and reports an invalid object.
+    fn invalid_object(
         &mut self,
-        metrics: &mut RunMetrics,
+        uri: &uri::Rsync,
+        object_type: ObjectType,
+        reason: impl fmt::Display,
     ) {
-        let repository_index = self.repository_index.unwrap_or_else(|| {
-            metrics.repository_index(self.cert)
-        });
-        self.processor.repository_index(repository_index);
-        metrics.apply(
-            &self.metrics,
-            repository_index,
-            self.cert.tal
+        warn!("{}: {}.", uri, reason);
+        self.object_status(
+            ObjectOutcome::new(
+                uri, object_type, ObjectStatus::Invalid
+            ).with_reason(reason)
         );
     }
 
-    /// Processes a single object.
+    /// Reports a valid signed object.
+    fn valid_object(
+        &mut self,
+        uri: &uri::Rsync,
+        object_type: ObjectType,
+        cert: &ResourceCert,
+    ) {
+        self.object_status(
+            ObjectOutcome::new(
+                uri, object_type, ObjectStatus::Valid
+            ).with_validity(cert.validity())
+        )
+    }
+
+    /// Logs and reports an invalid manifest.
+    fn invalid_manifest(&mut self, reason: impl fmt::Display) {
+        let cert = self.cert;
+        self.invalid_object(cert.rpki_manifest(), ObjectType::Manifest, reason)
+    }
+
+    /// Reports the outcome for a decoded manifest.
+    ///
+    /// A valid manifest is reported as stale if it is.
+    fn report_manifest(
+        &mut self,
+        content: &ManifestContent,
+        status: ObjectStatus,
+        reason: Option<&str>,
+    ) {
+        let status = match status {
+            ObjectStatus::Valid if self.is_stale(content.next_update()) => {
+                ObjectStatus::Stale
+            }
+            status => status
+        };
+        let mut outcome = ObjectOutcome::new(
+            self.cert.rpki_manifest(), ObjectType::Manifest, status
+        ).with_validity(
+            Validity::new(content.this_update(), content.next_update())
+        );
+        if let Some(reason) = reason {
+            outcome = outcome.with_reason(reason)
+        }
+        self.object_status(outcome)
+    }
+
+    /// Reports the outcome for a decoded CRL.
+    ///
+    /// A valid CRL is reported as stale if it is.
+    fn report_crl(
+        &mut self,
+        uri: &uri::Rsync,
+        crl: &Crl,
+        status: ObjectStatus,
+        reason: Option<&str>,
+    ) {
+        let status = match status {
+            ObjectStatus::Valid if self.is_stale(crl.next_update()) => {
+                ObjectStatus::Stale
+            }
+            status => status
+        };
+        let mut outcome = ObjectOutcome::new(
+            uri, ObjectType::Crl, status
+        ).with_validity(
+            Validity::new(crl.this_update(), crl.next_update())
+        );
+        if let Some(reason) = reason {
+            outcome = outcome.with_reason(reason)
+        }
+        self.object_status(outcome)
+    }
+
+    // XXX Check metrics generation and concentrate at one central point.
+
+    fn accept_point(
+        mut self,
+        manifest: ValidPointManifest,
+        metrics: &mut RunMetrics,
+    ) {
+        let rejected = self.metrics.clone();
+        self.metrics.valid_points += 1;
+        self.metrics += manifest.metrics;
+        self.commit_point(rejected, metrics);
+    }
+
+    /// Commits an accepted point.
+    ///
+    /// If limits are enabled, the point is deferred until the end of the
+    /// run. The `rejected` metrics are applied instead of the point’s
+    /// metrics if it is cancelled then.
+    fn commit_point(
+        mut self,
+        mut rejected: PublicationMetrics,
+        metrics: &mut RunMetrics,
+    ) {
+        if self.usage.is_none() {
+            self.apply_metrics(metrics);
+            self.processor.commit();
+            return
+        }
+        let repository_index = self.resolve_repository_index(metrics);
+        rejected.rejected_points += 1;
+        self.run.deferred.lock().unwrap().push(DeferredPoint {
+            cert: self.cert.clone(),
+            processor: self.processor,
+            repository_index,
+            accepted: self.metrics,
+            rejected,
+        });
+    }
+
+    fn reject_point(
+        mut self,
+        metrics: &mut RunMetrics,
+    ) {
+        self.metrics.rejected_points += 1;
+        self.apply_metrics(metrics);
+        self.processor.cancel(self.cert);
+    }
+
+    fn apply_metrics(
+        &mut self,
+        metrics: &mut RunMetrics,
+    ) {
+        let repository_index = self.resolve_repository_index(metrics);
+        metrics.apply(
+            &self.metrics,
+            repository_index,
+            self.cert.tal
+        );
+    }
+
+    /// Determines the point’s repository index and informs the processor.
+    fn resolve_repository_index(&mut self, metrics: &RunMetrics) -> usize {
+        let repository_index = self.repository_index.unwrap_or_else(|| {
+            metrics.repository_index(self.cert)
+        });
+        self.processor.repository_index(repository_index);
+        repository_index
+    }
+
+    /// Processes a single object.
     ///
     /// Returns whether processing should continue or whether the entire (!)
     /// publication point should be disregarded.
@@ -1266,31 +2020,78 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         manifest: &mut ValidPointManifest,
         ca_task: &mut Vec<CaTask<P::PubPoint>>,
     ) -> Result<bool, Failed> {
+        if !self.use_resources(RepositoryLimit::Objects, 1)
+            || !self.use_resources(
+                RepositoryLimit::Size,
+                u64::try_from(content.len()).unwrap_or(u64::MAX)
+            )
+        {
+            return Ok(false)
+        }
+
         if !self.processor.want(uri)? {
+            // A partially processed point can’t be cached.
+            self.recording = None;
+            self.processor.object_status(ObjectOutcome::new(
+                uri, ObjectType::from_uri(uri), ObjectStatus::Filtered
+            ));
             return Ok(true)
         }
+        if let Some(recording) = self.recording.as_mut() {
+            recording.objects.push(uri.clone());
+            recording.size = recording.size.saturating_add(
+                u64::try_from(content.len()).unwrap_or(u64::MAX)
+            );
+        }
 
         if uri.ends_with(".cer") {
+            let ca_count = ca_task.len();
             self.process_cer(uri, content, manifest, ca_task)?;
+            if ca_task.len() > ca_count
+                && !self.use_resources(RepositoryLimit::Cas, 1)
+            {
+                return Ok(false)
+            }
         }
         else if uri.ends_with(".roa") {
-            self.process_roa(uri, content, manifest)?;
+            let at = self.run.validation.as_of;
+            self.process_roa(uri, content, manifest, at)?;
         }
         else if uri.ends_with(".asa") {
-            self.process_aspa(uri, content, manifest)?;
+            let at = self.run.validation.as_of;
+            self.process_aspa(uri, content, manifest, at)?;
         }
         else if uri.ends_with(".gbr") {
             self.process_gbr(uri, content, manifest)?;
         }
         else if uri.ends_with(".crl") {
             if *uri != manifest.crl_uri {
-                warn!("{}: stray CRL.", uri);
+                self.invalid_object(uri, ObjectType::Crl, "stray CRL");
                 manifest.metrics.stray_crls += 1;
             }
         }
         else {
             manifest.metrics.others += 1;
-            warn!("{}: unknown object type.", uri);
+            match self.policy().unknown_objects {
+                FilterPolicy::Reject => {
+                    self.invalid_object(
+                        uri, ObjectType::Other, "unknown object type"
+                    );
+                    return Ok(false)
+                }
+                FilterPolicy::Warn => {
+                    self.invalid_object(
+                        uri, ObjectType::Other, "unknown object type"
+                    );
+                }
+                FilterPolicy::Accept => {
+                    self.object_status(
+                        ObjectOutcome::new(
+                            uri, ObjectType::Other, ObjectStatus::Unknown
+                        )
+                    );
+                }
+            }
         }
         Ok(true)
     }
@@ -1307,7 +2108,10 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
             Ok(cert) => cert,
             Err(_) => {
                 manifest.metrics.invalid_certs += 1;
-                warn!("{}: failed to decode certificate.", uri);
+                self.invalid_object(
+                    uri, ObjectType::Certificate,
+                    "failed to decode certificate"
+                );
                 return Ok(())
             }
         };
@@ -1328,49 +2132,91 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         ca_task: &mut Vec<CaTask<P::PubPoint>>,
     ) -> Result<(), Failed> {
         if self.cert.check_loop(&cert).is_err() {
-            warn!("{}: certificate loop detected.", uri);
+            self.invalid_object(
+                uri, ObjectType::CaCert, "certificate loop detected"
+            );
             manifest.metrics.invalid_certs += 1;
             return Ok(())
         }
-        let cert = match cert.validate_ca(
-            self.cert.cert(), self.run.validation.strict
+        let cert = match cert.validate_ca_at(
+            self.cert.cert(), self.policy().strict,
+            self.run.validation.now(),
         ) {
             Ok(cert) => cert,
             Err(err) => {
-                warn!("{}: {}.", uri, err);
+                self.invalid_object(uri, ObjectType::CaCert, err);
                 manifest.metrics.invalid_certs += 1;
                 return Ok(())
             }
         };
         if let Err(err) = manifest.check_crl(&cert) {
-            warn!("{}: {}.", uri, err);
+            self.invalid_object(uri, ObjectType::CaCert, err);
             manifest.metrics.invalid_certs += 1;
             return Ok(())
         }
 
+        // Everything reported from here on is reported again when
+        // replaying the CA from the point cache, so we only record the CA.
+        self.record(|| CachedEvent::Ca(uri.clone(), cert.clone()));
+        if self.process_valid_ca(uri, cert, ca_task)? {
+            manifest.metrics.valid_ca_certs += 1;
+        }
+        else {
+            manifest.metrics.invalid_certs += 1;
+        }
+        Ok(())
+    }
+
+    /// Processes a validated CA certificate.
+    ///
+    /// Adds a task for the CA to `ca_task` unless the processor doesn’t
+    /// want it. Returns whether the certificate is valid which it isn’t if
+    /// the maximum CA depth has been reached.
+    fn process_valid_ca(
+        &mut self, uri: &uri::Rsync, cert: ResourceCert,
+        ca_task: &mut Vec<CaTask<P::PubPoint>>,
+    ) -> Result<bool, Failed> {
         let cert = match CaCert::chain(
-            self.cert, uri.clone(), cert, self.run.validation.max_ca_depth,
+            self.cert, uri.clone(), cert, self.policy().max_ca_depth,
         ) {
             Ok(cert) => cert,
             Err(_) => {
-                manifest.metrics.invalid_certs += 1;
-                return Ok(())
+                self.processor.object_status(
+                    ObjectOutcome::new(
+                        uri, ObjectType::CaCert, ObjectStatus::Invalid
+                    ).with_reason("CA depth overrun")
+                );
+                return Ok(false)
             }
         };
 
-        manifest.metrics.valid_ca_certs += 1;
-
         let processor = match self.processor.process_ca(
             uri, &cert
         )? {
-            Some(processor) => processor,
-            None => return Ok(())
+            Some(processor) => {
+                self.processor.object_status(
+                    ObjectOutcome::new(
+                        uri, ObjectType::CaCert, ObjectStatus::Valid
+                    ).with_validity(cert.cert().validity())
+                );
+                processor
+            }
+            None => {
+                self.processor.object_status(
+                    ObjectOutcome::new(
+                        uri, ObjectType::CaCert, ObjectStatus::Filtered
+                    ).with_validity(cert.cert().validity())
+                );
+                return Ok(true)
+            }
         };
 
         // Defer operation if we need to update the repository part where
         // the CA lives.
         let defer = match self.run.collector.as_ref() {
-            Some(collector) => !collector.was_updated(&cert),
+            Some(collector) => {
+                !collector.was_updated(&cert, self.policy())
+            }
             None => false,
         };
 
@@ -1385,7 +2231,7 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         ca_task.push(CaTask {
             cert, processor, repository_index, defer
         });
-        Ok(())
+        Ok(true)
     }
 
     /// Processes a router certificate.
@@ -1393,19 +2239,26 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         &mut self, uri: &uri::Rsync, cert: Cert,
         manifest: &mut ValidPointManifest,
     ) -> Result<(), Failed> {
-        if let Err(err) = cert.validate_router(
-            self.cert.cert(), self.run.validation.strict
+        if let Err(err) = cert.validate_router_at(
+            self.cert.cert(), self.policy().strict,
+            self.run.validation.now(),
         ) {
-            warn!("{}: {}.", uri, err);
+            self.invalid_object(uri, ObjectType::RouterCert, err);
             manifest.metrics.invalid_certs += 1;
             return Ok(())
         };
         if let Err(err) = manifest.check_crl(&cert) {
-            warn!("{}: {}.", uri, err);
+            self.invalid_object(uri, ObjectType::RouterCert, err);
             manifest.metrics.invalid_certs += 1;
             return Ok(())
         }
         manifest.metrics.valid_router_certs += 1;
+        self.object_status(
+            ObjectOutcome::new(
+                uri, ObjectType::RouterCert, ObjectStatus::Valid
+            ).with_validity(cert.validity())
+        );
+        self.record(|| CachedEvent::RouterCert(uri.clone(), cert.clone()));
         self.processor.process_router_cert(uri, cert, self.cert)?;
         Ok(())
     }
@@ -1413,30 +2266,51 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
     /// Processes a ROA object.
     fn process_roa(
         &mut self, uri: &uri::Rsync, content: Bytes,
-        manifest: &mut ValidPointManifest,
+        manifest: &mut ValidPointManifest, at: Option<Time>,
     ) -> Result<(), Failed> {
         let roa = match Roa::decode(
-            content, self.run.validation.strict
+            content.clone(), self.policy().strict
         ) {
             Ok(roa) => roa,
             Err(_) => {
                 manifest.metrics.invalid_roas += 1;
-                warn!("{}: failed to decode ROA.", uri);
+                self.invalid_object(
+                    uri, ObjectType::Roa, "failed to decode ROA"
+                );
                 return Ok(())
             }
         };
-        match roa.process(
-            self.cert.cert(),
-            self.run.validation.strict,
-            |cert| manifest.check_crl(cert)
-        ) {
+        let res = match at {
+            Some(at) => {
+                let route = roa.content().clone();
+                validate_signed_at(
+                    content, self.cert.cert(), self.policy().strict, at,
+                    |cert| manifest.check_crl(cert)
+                ).and_then(|cert| {
+                    verify_roa(&route, &cert)?;
+                    Ok((cert, route))
+                })
+            }
+            None => {
+                roa.process(
+                    self.cert.cert(),
+                    self.policy().strict,
+                    |cert| manifest.check_crl(cert)
+                )
+            }
+        };
+        match res {
             Ok((cert, route)) => {
                 manifest.metrics.valid_roas += 1;
+                self.valid_object(uri, ObjectType::Roa, &cert);
+                self.record(|| CachedEvent::Roa(
+                    uri.clone(), cert.clone(), route.clone()
+                ));
                 self.processor.process_roa(uri, cert, route)?
             }
             Err(err) => {
                 manifest.metrics.invalid_roas += 1;
-                warn!("{}: {}.", uri, err)
+                self.invalid_object(uri, ObjectType::Roa, err)
             }
         }
         Ok(())
@@ -1446,31 +2320,52 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
     #[allow(unused_variables)]
     fn process_aspa(
         &mut self, uri: &uri::Rsync, content: Bytes,
-        manifest: &mut ValidPointManifest,
+        manifest: &mut ValidPointManifest, at: Option<Time>,
     ) -> Result<(), Failed> {
         #[cfg(feature = "aspa")] {
             let aspa = match Aspa::decode(
-                content, self.run.validation.strict
+                content.clone(), self.policy().strict
             ) {
                 Ok(aspa) => aspa,
                 Err(err) => {
                     manifest.metrics.invalid_aspas += 1;
-                    warn!("{}: failed to decode ASPA.", uri);
+                    self.invalid_object(
+                        uri, ObjectType::Aspa, "failed to decode ASPA"
+                    );
                     return Ok(())
                 }
             };
-            match aspa.process(
-                self.cert.cert(),
-                self.run.validation.strict,
-                |cert| manifest.check_crl(cert)
-            ) {
+            let res = match at {
+                Some(at) => {
+                    let attestation = aspa.content().clone();
+                    validate_signed_at(
+                        content, self.cert.cert(), self.policy().strict, at,
+                        |cert| manifest.check_crl(cert)
+                    ).and_then(|cert| {
+                        verify_aspa(&attestation, &cert)?;
+                        Ok((cert, attestation))
+                    })
+                }
+                None => {
+                    aspa.process(
+                        self.cert.cert(),
+                        self.policy().strict,
+                        |cert| manifest.check_crl(cert)
+                    )
+                }
+            };
+            match res {
                 Ok((cert, aspa)) => {
                     manifest.metrics.valid_aspas += 1;
+                    self.valid_object(uri, ObjectType::Aspa, &cert);
+                    self.record(|| CachedEvent::Aspa(
+                        uri.clone(), cert.clone(), aspa.clone()
+                    ));
                     self.processor.process_aspa(uri, cert, aspa)?
                 }
                 Err(err) => {
                     manifest.metrics.invalid_aspas += 1;
-                    warn!("{}: {}.", uri, err)
+                    self.invalid_object(uri, ObjectType::Aspa, err)
                 }
             }
         }
@@ -1483,27 +2378,35 @@ impl<'a, P: ProcessRun> PubPoint<'a, P> {
         manifest: &mut ValidPointManifest,
     ) -> Result<(), Failed> {
         let obj = match SignedObject::decode(
-            content, self.run.validation.strict
+            content, self.policy().strict
         ) {
             Ok(obj) => obj,
             Err(_) => {
                 manifest.metrics.invalid_gbrs += 1;
-                warn!("{}: failed to decode GBR.", uri);
+                self.invalid_object(
+                    uri, ObjectType::Gbr, "failed to decode GBR"
+                );
                 return Ok(())
             }
         };
-        match obj.process(
+        match process_signed_at(
+            obj,
             self.cert.cert(),
-            self.run.validation.strict,
+            self.policy().strict,
+            self.run.validation.now(),
             |cert| manifest.check_crl(cert)
         ) {
             Ok((cert, content)) => {
                 manifest.metrics.valid_gbrs += 1;
+                self.valid_object(uri, ObjectType::Gbr, &cert);
+                self.record(|| CachedEvent::Gbr(
+                    uri.clone(), cert.clone(), content.clone()
+                ));
                 self.processor.process_gbr(uri, cert, content)?
             }
             Err(err) => {
                 manifest.metrics.invalid_gbrs += 1;
-                warn!("{}: {}.", uri, err)
+                self.invalid_object(uri, ObjectType::Gbr, err)
             }
         }
         Ok(())
@@ -1571,9 +2474,12 @@ impl ValidPointManifest {
         Ok(())
     }
 
-    /// Reports the validity to the given processor.
-    fn point_validity(&self, processor: &mut impl ProcessPubPoint) {
-        processor.point_validity(
+    /// Returns the validity of the publication point.
+    ///
+    /// Returns the validity of the manifest’s EE certificate and the
+    /// smaller of the manifest’s and CRL’s next update time.
+    fn point_validity(&self) -> (Validity, Time) {
+        (
             self.ee_cert.validity(),
             cmp::min(
                 self.content.next_update(),
@@ -1584,6 +2490,383 @@ impl ValidPointManifest {
 }
 
 
+//------------ PointRecording ------------------------------------------------
+
+/// The recording of processing a publication point for the point cache.
+#[derive(Debug)]
+struct PointRecording {
+    /// The raw bytes of the stored manifest.
+    manifest: Bytes,
+
+    /// The raw bytes of the stored CRL.
+    crl: Bytes,
+
+    /// The URIs of all objects processed.
+    objects: Vec<uri::Rsync>,
+
+    /// The total size of all objects processed.
+    size: u64,
+
+    /// Everything reported to the processor.
+    events: Vec<CachedEvent>,
+}
+
+impl PointRecording {
+    /// Starts a new recording for the given stored manifest.
+    fn new(manifest: &StoredManifest) -> Self {
+        PointRecording {
+            manifest: manifest.manifest().clone(),
+            crl: manifest.crl().clone(),
+            objects: Vec::new(),
+            size: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Converts the recording into a cached point.
+    ///
+    /// Returns `None` if the point cannot be cached. This is the case if any
+    /// of its objects were invalid – they may become valid later – or if
+    /// the point isn’t valid at `now`, e.g., because it is stale.
+    fn finalize(
+        self,
+        cert: &CaCert,
+        metrics: &PublicationMetrics,
+        duration: Duration,
+        now: Time,
+    ) -> Option<CachedPoint> {
+        let validity = cert.cert().validity();
+        let mut not_before = validity.not_before();
+        let mut not_after = validity.not_after();
+        let mut limit = |validity: Validity| {
+            not_before = cmp::max(not_before, validity.not_before());
+            not_after = cmp::min(not_after, validity.not_after());
+        };
+        for event in &self.events {
+            match event {
+                CachedEvent::PointValidity(manifest_ee, stale) => {
+                    limit(*manifest_ee);
+                    limit(Validity::new(manifest_ee.not_before(), *stale));
+                }
+                CachedEvent::Status(outcome) => {
+                    if !matches!(
+                        outcome.status,
+                        ObjectStatus::Valid | ObjectStatus::Unknown
+                    ) {
+                        return None
+                    }
+                    if let Some(validity) = outcome.validity {
+                        limit(validity)
+                    }
+                }
+                CachedEvent::Crl(..) => { }
+                CachedEvent::Ca(_, cert)
+                | CachedEvent::Roa(_, cert, _)
+                | CachedEvent::Gbr(_, cert, _) => {
+                    limit(cert.validity())
+                }
+                #[cfg(feature = "aspa")]
+                CachedEvent::Aspa(_, cert, _) => {
+                    limit(cert.validity())
+                }
+                CachedEvent::RouterCert(_, cert) => {
+                    limit(cert.validity())
+                }
+            }
+        }
+        if now < not_before || now >= not_after {
+            return None
+        }
+        Some(CachedPoint {
+            manifest: self.manifest,
+            crl: self.crl,
+            ca_key: cert.cert().subject_key_identifier(),
+            v4_resources: cert.cert().v4_resources().clone(),
+            v6_resources: cert.cert().v6_resources().clone(),
+            as_resources: cert.cert().as_resources().clone(),
+            not_before, not_after,
+            objects: self.objects,
+            size: self.size,
+            events: self.events,
+            metrics: metrics.clone(),
+            duration,
+        })
+    }
+}
+
+
+//------------ CachedPoint ---------------------------------------------------
+
+/// The result of validating a publication point kept for later runs.
+///
+/// A cached point can be reused if the stored manifest and CRL as well as
+/// the key and resources of the CA certificate are unchanged and all
+/// objects of the point are still within their validity.
+#[derive(Debug)]
+struct CachedPoint {
+    /// The raw bytes of the manifest the point was validated with.
+    manifest: Bytes,
+
+    /// The raw bytes of the CRL the point was validated with.
+    crl: Bytes,
+
+    /// The key identifier of the CA certificate.
+    ca_key: KeyIdentifier,
+
+    /// The IPv4 resources of the CA certificate.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources of the CA certificate.
+    v6_resources: IpBlocks,
+
+    /// The AS resources of the CA certificate.
+    as_resources: AsBlocks,
+
+    /// The earliest time the point can be reused.
+    not_before: Time,
+
+    /// The time when the first object of the point expires.
+    not_after: Time,
+
+    /// The URIs of all objects processed.
+    objects: Vec<uri::Rsync>,
+
+    /// The total size of all objects processed.
+    size: u64,
+
+    /// Everything reported to the processor.
+    events: Vec<CachedEvent>,
+
+    /// The publication metrics collected while processing the objects.
+    metrics: PublicationMetrics,
+
+    /// The time it took to validate the point.
+    duration: Duration,
+}
+
+impl CachedPoint {
+    /// Returns whether the cached point can be used for the given CA.
+    fn is_current(
+        &self, cert: &CaCert, manifest: &StoredManifest, now: Time
+    ) -> bool {
+        self.not_before <= now && now < self.not_after
+            && self.manifest == *manifest.manifest()
+            && self.crl == *manifest.crl()
+            && self.ca_key == cert.cert().subject_key_identifier()
+            && self.v4_resources == *cert.cert().v4_resources()
+            && self.v6_resources == *cert.cert().v6_resources()
+            && self.as_resources == *cert.cert().as_resources()
+    }
+}
+
+
+//------------ CachedEvent ---------------------------------------------------
+
+/// Something reported to the processor while processing a point.
+#[derive(Clone, Debug)]
+enum CachedEvent {
+    /// The validity of the point.
+    PointValidity(Validity, Time),
+
+    /// The outcome of validating an object.
+    Status(CachedOutcome),
+
+    /// The manifest CRL of the point.
+    Crl(uri::Rsync, Crl),
+
+    /// A valid CA certificate.
+    Ca(uri::Rsync, ResourceCert),
+
+    /// A valid router certificate.
+    RouterCert(uri::Rsync, Cert),
+
+    /// A valid ROA.
+    Roa(uri::Rsync, ResourceCert, RouteOriginAttestation),
+
+    /// A valid ASPA object.
+    #[cfg(feature = "aspa")]
+    Aspa(uri::Rsync, ResourceCert, AsProviderAttestation),
+
+    /// A valid Ghostbuster Record.
+    Gbr(uri::Rsync, ResourceCert, Bytes),
+}
+
+
+//------------ CachedOutcome -------------------------------------------------
+
+/// An owned version of an object outcome.
+#[derive(Clone, Debug)]
+struct CachedOutcome {
+    uri: uri::Rsync,
+    object_type: ObjectType,
+    status: ObjectStatus,
+    validity: Option<Validity>,
+    reason: Option<String>,
+}
+
+impl CachedOutcome {
+    /// Creates a cached outcome from an object outcome.
+    fn new(outcome: &ObjectOutcome) -> Self {
+        CachedOutcome {
+            uri: outcome.uri.clone(),
+            object_type: outcome.object_type,
+            status: outcome.status,
+            validity: outcome.validity,
+            reason: outcome.reason.clone(),
+        }
+    }
+
+    /// Returns the object outcome for the cached outcome.
+    fn outcome(&self) -> ObjectOutcome {
+        ObjectOutcome {
+            uri: &self.uri,
+            object_type: self.object_type,
+            status: self.status,
+            validity: self.validity,
+            reason: self.reason.clone(),
+        }
+    }
+}
+
+
+//------------ Point-in-Time Processing --------------------------------------
+//
+// The rpki crate processes ROAs and ASPAs at the current time only. When
+// validating at a different time, we validate their signed objects
+// ourselves and repeat the checks of their content.
+
+/// Processes a signed object at the given time.
+///
+/// This is the same as [`SignedObject::process`] except that the EE
+/// certificate’s validity is checked against `now`.
+fn process_signed_at<F>(
+    obj: SignedObject,
+    issuer: &ResourceCert,
+    strict: bool,
+    now: Time,
+    check_crl: F,
+) -> Result<(ResourceCert, Bytes), ValidationError>
+where F: FnOnce(&Cert) -> Result<(), ValidationError> {
+    let content = obj.content().clone();
+    let cert = obj.validate_at(issuer, strict, now)?;
+    check_crl(cert.as_ref())?;
+    Ok((cert, content.into_bytes()))
+}
+
+/// Validates the signed object encoded in `data` at the given time.
+///
+/// The data must already have been decoded successfully as a ROA or
+/// ASPA. Returns the validated EE certificate.
+fn validate_signed_at<F>(
+    data: Bytes,
+    issuer: &ResourceCert,
+    strict: bool,
+    now: Time,
+    check_crl: F,
+) -> Result<ResourceCert, ValidationError>
+where F: FnOnce(&Cert) -> Result<(), ValidationError> {
+    let obj = SignedObject::decode(data, strict).map_err(|_| {
+        VerificationError::new("failed to decode signed object")
+    })?;
+    process_signed_at(obj, issuer, strict, now, check_crl).map(|res| res.0)
+}
+
+/// Checks that the prefixes of a ROA are covered by its EE certificate.
+fn verify_roa(
+    route: &RouteOriginAttestation, cert: &ResourceCert,
+) -> Result<(), ValidationError> {
+    for addr in route.iter() {
+        let blocks = if addr.is_v4() {
+            cert.v4_resources()
+        }
+        else {
+            cert.v6_resources()
+        };
+        if !blocks.contains_roa(&RoaIpAddress::new(addr.prefix(), None)) {
+            return Err(VerificationError::new(format!(
+                "ROA prefix {} not covered by certificate", addr
+            )).into())
+        }
+    }
+    Ok(())
+}
+
+/// Checks that an ASPA matches the resources of its EE certificate.
+#[cfg(feature = "aspa")]
+fn verify_aspa(
+    aspa: &AsProviderAttestation, cert: &ResourceCert,
+) -> Result<(), ValidationError> {
+    if !cert.as_resources().contains_asn(aspa.customer_as()) {
+        return Err(VerificationError::new(
+            "customer AS not covered by certificate"
+        ).into())
+    }
+    if cert.as_cert().as_resources().is_inherited() {
+        return Err(VerificationError::new(
+            "certificate contains inherited AS resources"
+        ).into())
+    }
+    if cert.as_cert().has_ip_resources() {
+        return Err(VerificationError::new(
+            "certificate contains IP resources"
+        ).into())
+    }
+    Ok(())
+}
+
+
+//------------ Trust Anchor Certificates -------------------------------------
+
+/// Returns the SHA-256 fingerprint of a TA certificate as a hex string.
+fn ta_fingerprint(cert: &[u8]) -> String {
+    let mut res = String::new();
+    append_hex(DigestAlgorithm::sha256().digest(cert).as_ref(), &mut res);
+    res
+}
+
+/// Returns how much the resources of a new TA certificate shrank.
+///
+/// The share of the old certificate’s resources missing from the new
+/// certificate is determined separately for IPv4, IPv6, and AS numbers.
+/// The largest of these shares is returned as a percentage.
+fn ta_resource_shrinkage(old: &Cert, new: &ResourceCert) -> f64 {
+    fn share(old: f64, lost: f64) -> f64 {
+        if old > 0. { lost / old * 100. } else { 0. }
+    }
+
+    let v4 = old.v4_resources().to_blocks().unwrap_or_else(|_| {
+        IpBlocks::empty()
+    });
+    let v6 = old.v6_resources().to_blocks().unwrap_or_else(|_| {
+        IpBlocks::empty()
+    });
+    let asns = old.as_resources().to_blocks().unwrap_or_else(|_| {
+        AsBlocks::empty()
+    });
+    [
+        share(ip_size(&v4), ip_size(&v4.difference(new.v4_resources()))),
+        share(ip_size(&v6), ip_size(&v6.difference(new.v6_resources()))),
+        share(
+            as_size(&asns), as_size(&asns.difference(new.as_resources()))
+        ),
+    ].into_iter().fold(0., f64::max)
+}
+
+/// Returns the number of addresses in a set of IP blocks.
+fn ip_size(blocks: &IpBlocks) -> f64 {
+    blocks.iter().map(|block| {
+        (block.max().to_bits() - block.min().to_bits()) as f64 + 1.
+    }).sum()
+}
+
+/// Returns the number of AS numbers in a set of AS blocks.
+fn as_size(blocks: &AsBlocks) -> f64 {
+    blocks.iter().map(|block| {
+        f64::from(block.max().into_u32() - block.min().into_u32()) + 1.
+    }).sum()
+}
+
+
 //------------ Task ----------------------------------------------------------
 
 /// Any task that can be queued for delayed processing.
@@ -1624,6 +2907,27 @@ struct TalTask<'a> {
 }
 
 
+//------------ DeferredPoint -------------------------------------------------
+
+/// An accepted publication point waiting for the end of the run.
+struct DeferredPoint<P> {
+    /// The CA certificate of the publication point.
+    cert: Arc<CaCert>,
+
+    /// The processor for valid data at this publication point.
+    processor: P,
+
+    /// The index of the point’s repository in the run’s metrics.
+    repository_index: usize,
+
+    /// The metrics to apply if the point is committed.
+    accepted: PublicationMetrics,
+
+    /// The metrics to apply if the point is cancelled.
+    rejected: PublicationMetrics,
+}
+
+
 //------------ CaTask --------------------------------------------------------
 
 /// A task for processing a single CA.
@@ -1788,7 +3092,14 @@ impl CaCert {
         &self.cert
     }
 
-    /// Returns a reference to the certificate’s URI.
+    /// Returns a reference to the parent CA.
+    ///
+    /// Returns `None` for a trust anchor certificate.
+    pub fn parent(&self) -> Option<&Arc<CaCert>> {
+        self.parent.as_ref()
+    }
+
+    /// Returns a reference to the certificate’s URI.
     ///
     /// Note that this really is the URI of the certificate itself despite
     /// the type of `TalUri`. This type is used because TAL certificates
@@ -1813,6 +3124,18 @@ impl CaCert {
         self.cert.rpki_notify()
     }
 
+    /// Returns the URI identifying the repository of the CA.
+    ///
+    /// This is the rpkiNotify URI if present or the rsync module URI of the
+    /// caRepository URI otherwise.
+    pub fn repository_uri(&self) -> Cow<str> {
+        self.rpki_notify().map(|uri| {
+            Cow::Borrowed(uri.as_str())
+        }).unwrap_or_else(|| {
+            self.ca_repository.canonical_module()
+        })
+    }
+
     /// Returns whether the CA is in a different repository from its parent.
     ///
     /// This is just a quick check and may report a switch when in fact there
@@ -1836,6 +3159,146 @@ impl CaCert {
 } 
 
 
+//------------ RepositoryLimit -----------------------------------------------
+
+/// A resource limit applied to each repository.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RepositoryLimit {
+    /// The number of objects listed on manifests.
+    Objects,
+
+    /// The total size of all objects listed on manifests.
+    Size,
+
+    /// The number of valid CA certificates.
+    Cas,
+
+    /// The number of publication points.
+    Points,
+
+    /// The time spent on processing publication points in milliseconds.
+    Time,
+}
+
+impl RepositoryLimit {
+    /// Returns the index of the limit in arrays of per-limit values.
+    fn index(self) -> usize {
+        match self {
+            RepositoryLimit::Objects => 0,
+            RepositoryLimit::Size => 1,
+            RepositoryLimit::Cas => 2,
+            RepositoryLimit::Points => 3,
+            RepositoryLimit::Time => 4,
+        }
+    }
+
+    /// Returns a string representation of the limit.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RepositoryLimit::Objects => "objects",
+            RepositoryLimit::Size => "size",
+            RepositoryLimit::Cas => "cas",
+            RepositoryLimit::Points => "points",
+            RepositoryLimit::Time => "time",
+        }
+    }
+}
+
+impl fmt::Display for RepositoryLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ RepositoryLimits ----------------------------------------------
+
+/// The configured maximum values for each [`RepositoryLimit`].
+#[derive(Clone, Copy, Debug, Default)]
+struct RepositoryLimits([Option<u64>; 5]);
+
+impl RepositoryLimits {
+    fn from_config(config: &Config) -> Self {
+        RepositoryLimits([
+            config.max_repository_objects,
+            config.max_repository_size,
+            config.max_repository_cas,
+            config.max_repository_points,
+            config.max_repository_time.map(|time| {
+                u64::try_from(time.as_millis()).unwrap_or(u64::MAX)
+            }),
+        ])
+    }
+
+    /// Returns the maximum value for a limit if there is one.
+    fn get(&self, limit: RepositoryLimit) -> Option<u64> {
+        self.0[limit.index()]
+    }
+
+    /// Returns whether any limit is configured.
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(Option::is_none)
+    }
+}
+
+
+//------------ RepositoryUsage -----------------------------------------------
+
+/// The resources used by a repository during a validation run.
+///
+/// The value is shared between all threads processing publication points
+/// of the repository.
+#[derive(Debug, Default)]
+struct RepositoryUsage {
+    /// The amount used of each limit.
+    used: [AtomicU64; 5],
+
+    /// The limit that was exceeded first, if any.
+    exceeded: OnceLock<RepositoryLimit>,
+}
+
+impl RepositoryUsage {
+    /// Returns the limit that has been exceeded if any.
+    fn exceeded(&self) -> Option<RepositoryLimit> {
+        self.exceeded.get().copied()
+    }
+
+    /// Adds `amount` to the usage of `limit`.
+    ///
+    /// Returns whether the repository is still within all its limits. Logs
+    /// a warning the first time a limit is exceeded.
+    fn add(
+        &self,
+        limit: RepositoryLimit,
+        amount: u64,
+        limits: &RepositoryLimits,
+        cert: &CaCert,
+    ) -> bool {
+        if self.exceeded.get().is_some() {
+            return false
+        }
+        let max = match limits.get(limit) {
+            Some(max) => max,
+            None => return true,
+        };
+        let used = self.used[limit.index()].fetch_add(
+            amount, Ordering::Relaxed
+        ).saturating_add(amount);
+        if used <= max {
+            return true
+        }
+        if self.exceeded.set(limit).is_ok() {
+            warn!(
+                "Repository {}: exceeded {} limit of {}. \
+                 Rejecting all its publication points.",
+                cert.repository_uri(), limit, max
+            );
+        }
+        false
+    }
+}
+
+
 //------------ RunMetrics ----------------------------------------------------
 
 /// The metrics collected during a engine run.
@@ -1872,11 +3335,7 @@ impl RunMetrics {
     ///
     /// Adds a new repository if necessary.
     pub fn repository_index(&self, cert: &CaCert) -> usize {
-        let uri = cert.rpki_notify().map(|uri| {
-            Cow::Borrowed(uri.as_str())
-        }).unwrap_or_else(|| {
-            cert.ca_repository.canonical_module()
-        });
+        let uri = cert.repository_uri();
 
         let mut repository_indexes = self.repository_indexes.lock().unwrap();
         if let Some(index) = repository_indexes.get(uri.as_ref()) {
@@ -1985,6 +3444,16 @@ pub trait ProcessPubPoint: Sized + Send + Sync {
         let _ = (manifest_ee, stale);
     }
 
+    /// Process the manifest CRL of the publication point.
+    ///
+    /// The method is called once the manifest and its CRL have been found
+    /// valid and before any of the objects are processed.
+    ///
+    /// The default implementation does nothing.
+    fn process_crl(&mut self, uri: &uri::Rsync, crl: &Crl) {
+        let _ = (uri, crl);
+    }
+
     /// Determines whether an object with the given URI should be processed.
     ///
     /// The object will only be processed if the method returns `Ok(true)`.
@@ -2062,6 +3531,18 @@ pub trait ProcessPubPoint: Sized + Send + Sync {
         Ok(())
     }
 
+    /// Reports the outcome of validating an object.
+    ///
+    /// The method is called for every object encountered at the
+    /// publication point including its manifest and CRL whether it turned
+    /// out to be valid or not. For valid objects, it is called before the
+    /// object is handed to one of the processing methods above.
+    ///
+    /// The default implementation does nothing.
+    fn object_status(&mut self, outcome: ObjectOutcome) {
+        let _ = outcome;
+    }
+
     /// Restart processing with an empty data set.
     ///
     /// The method is called if processing has to switch to a different
@@ -2089,12 +3570,321 @@ pub trait ProcessPubPoint: Sized + Send + Sync {
 }
 
 
+//------------ ObjectType ----------------------------------------------------
+
+/// The type of an object published at a publication point.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ObjectType {
+    /// A manifest.
+    Manifest,
+
+    /// A certificate revocation list.
+    Crl,
+
+    /// A CA certificate.
+    CaCert,
+
+    /// A BGPsec router certificate.
+    RouterCert,
+
+    /// A certificate that couldn’t be decoded.
+    Certificate,
+
+    /// A ROA.
+    Roa,
+
+    /// An ASPA object.
+    Aspa,
+
+    /// A Ghostbuster Record.
+    Gbr,
+
+    /// Anything else.
+    Other,
+}
+
+impl ObjectType {
+    /// Determines the object type from the file name extension.
+    ///
+    /// Since the extension doesn’t tell us what kind of certificate we
+    /// have, all certificates are reported as `Certificate`.
+    pub fn from_uri(uri: &uri::Rsync) -> Self {
+        if uri.ends_with(".mft") {
+            ObjectType::Manifest
+        }
+        else if uri.ends_with(".crl") {
+            ObjectType::Crl
+        }
+        else if uri.ends_with(".cer") {
+            ObjectType::Certificate
+        }
+        else if uri.ends_with(".roa") {
+            ObjectType::Roa
+        }
+        else if uri.ends_with(".asa") {
+            ObjectType::Aspa
+        }
+        else if uri.ends_with(".gbr") {
+            ObjectType::Gbr
+        }
+        else {
+            ObjectType::Other
+        }
+    }
+
+    /// Returns a string representation of the object type.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ObjectType::Manifest => "manifest",
+            ObjectType::Crl => "crl",
+            ObjectType::CaCert => "ca-cert",
+            ObjectType::RouterCert => "router-cert",
+            ObjectType::Certificate => "cert",
+            ObjectType::Roa => "roa",
+            ObjectType::Aspa => "aspa",
+            ObjectType::Gbr => "gbr",
+            ObjectType::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ ObjectStatus --------------------------------------------------
+
+/// The validation status of an object.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ObjectStatus {
+    /// The object is valid.
+    Valid,
+
+    /// The object is invalid.
+    Invalid,
+
+    /// The manifest or CRL is past its next update time.
+    ///
+    /// Whether the object was used depends on the stale policy. If it
+    /// wasn’t, the outcome has a reason.
+    Stale,
+
+    /// The object was valid but its publication point was rejected.
+    RejectedPoint,
+
+    /// The object was skipped by the processor.
+    Filtered,
+
+    /// The object is of an unknown type and was accepted.
+    Unknown,
+}
+
+impl ObjectStatus {
+    /// Returns a string representation of the status.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ObjectStatus::Valid => "valid",
+            ObjectStatus::Invalid => "invalid",
+            ObjectStatus::Stale => "stale",
+            ObjectStatus::RejectedPoint => "rejected-point",
+            ObjectStatus::Filtered => "filtered",
+            ObjectStatus::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for ObjectStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+
+//------------ ObjectOutcome -------------------------------------------------
+
+/// The outcome of validating a single object.
+#[derive(Clone, Debug)]
+pub struct ObjectOutcome<'a> {
+    /// The URI of the object.
+    pub uri: &'a uri::Rsync,
+
+    /// The type of the object.
+    pub object_type: ObjectType,
+
+    /// The validation status.
+    pub status: ObjectStatus,
+
+    /// The validity of the object if it is known.
+    pub validity: Option<Validity>,
+
+    /// The reason why the object was rejected.
+    pub reason: Option<String>,
+}
+
+impl<'a> ObjectOutcome<'a> {
+    /// Creates a new outcome without validity and reason.
+    pub fn new(
+        uri: &'a uri::Rsync, object_type: ObjectType, status: ObjectStatus,
+    ) -> Self {
+        ObjectOutcome {
+            uri, object_type, status,
+            validity: None,
+            reason: None,
+        }
+    }
+
+    /// Adds the validity of the object.
+    pub fn with_validity(mut self, validity: Validity) -> Self {
+        self.validity = Some(validity);
+        self
+    }
+
+    /// Adds the reason for rejecting the object.
+    pub fn with_reason(mut self, reason: impl fmt::Display) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+}
+
+
 //============ Tests =========================================================
 
 #[cfg(test)]
 mod test {
+    use crate::utils::testrepo::{TestCa, TestRepo, CA_KEY, EE_KEY};
     use super::*;
 
+    //--- Helpers
+
+    /// A processor recording what happened during a run.
+    #[derive(Default)]
+    struct Collect {
+        /// The URIs of the ROAs of committed publication points.
+        roas: Mutex<Vec<String>>,
+
+        /// The status of the objects of all publication points.
+        status: Mutex<HashMap<String, ObjectStatus>>,
+
+        /// The manifest URIs of cancelled publication points.
+        cancelled: Mutex<Vec<String>>,
+    }
+
+    impl Collect {
+        /// Returns the sorted URIs of all accepted ROAs.
+        fn roas(&self) -> Vec<String> {
+            let mut res = self.roas.lock().unwrap().clone();
+            res.sort();
+            res
+        }
+
+        /// Returns the status reported for an object.
+        fn status(&self, uri: &uri::Rsync) -> Option<ObjectStatus> {
+            self.status.lock().unwrap().get(uri.as_str()).copied()
+        }
+
+        /// Returns the manifest URIs of all cancelled points.
+        fn cancelled(&self) -> Vec<String> {
+            self.cancelled.lock().unwrap().clone()
+        }
+    }
+
+    impl<'a> ProcessRun for &'a Collect {
+        type PubPoint = CollectPoint<'a>;
+
+        fn process_ta(
+            &self, _tal: &Tal, _uri: &TalUri, _cert: &CaCert,
+            _tal_index: usize
+        ) -> Result<Option<Self::PubPoint>, Failed> {
+            Ok(Some(CollectPoint { run: self, roas: Vec::new() }))
+        }
+    }
+
+    /// The publication point processor for [`Collect`].
+    struct CollectPoint<'a> {
+        run: &'a Collect,
+        roas: Vec<String>,
+    }
+
+    impl ProcessPubPoint for CollectPoint<'_> {
+        fn want(&self, _uri: &uri::Rsync) -> Result<bool, Failed> {
+            Ok(true)
+        }
+
+        fn process_ca(
+            &mut self, _uri: &uri::Rsync, _cert: &CaCert,
+        ) -> Result<Option<Self>, Failed> {
+            Ok(Some(CollectPoint { run: self.run, roas: Vec::new() }))
+        }
+
+        fn process_roa(
+            &mut self, uri: &uri::Rsync, _cert: ResourceCert,
+            _route: RouteOriginAttestation
+        ) -> Result<(), Failed> {
+            self.roas.push(uri.to_string());
+            Ok(())
+        }
+
+        fn object_status(&mut self, outcome: ObjectOutcome) {
+            self.run.status.lock().unwrap().insert(
+                outcome.uri.to_string(), outcome.status
+            );
+        }
+
+        fn restart(&mut self) -> Result<(), Failed> {
+            self.roas.clear();
+            Ok(())
+        }
+
+        fn commit(self) {
+            self.run.roas.lock().unwrap().extend(self.roas)
+        }
+
+        fn cancel(self, cert: &CaCert) {
+            self.run.cancelled.lock().unwrap().push(
+                cert.rpki_manifest().to_string()
+            )
+        }
+    }
+
+    /// Creates a repository with a CA below the trust anchor.
+    ///
+    /// Only the certificates and the trust anchor’s publication point are
+    /// written.
+    fn make_repo() -> (TestRepo, TestCa) {
+        let _ = crate::process::Process::init(); // May be inited already.
+        let repo = TestRepo::new();
+        let ta = repo.ta();
+        let ca = repo.ca(&ta, "ca", CA_KEY);
+        repo.ta_cert("AS64496-AS64511", "192.0.2.0/24");
+        let ca_cert = repo.ca_cert(&ta, &ca, "AS64496", "192.0.2.0/24");
+        repo.publish(&ta, 1, &[("ca.cer", &ca_cert)], &[]);
+        (repo, ca)
+    }
+
+    /// Performs a validation run with the engine.
+    fn run(engine: &Engine) -> (Collect, Metrics) {
+        let collect = Collect::default();
+        let metrics = {
+            let mut run = engine.start(&collect).unwrap();
+            run.process().unwrap();
+            run.done()
+        };
+        (collect, metrics)
+    }
+
+    /// Creates and ignites an engine.
+    fn engine(config: &Config) -> Engine {
+        let mut engine = Engine::new(config, true).unwrap();
+        engine.ignite().unwrap();
+        engine
+    }
+
+
+    //--- Tests
+
     #[test]
     fn dump_empty_cache() {
         let _ = crate::process::Process::init(); // May be inited already.
@@ -2109,5 +3899,319 @@ mod test {
         let engine = Engine::new(&config, true).unwrap();
         engine.dump(&target).unwrap();
     }
-}
 
+    #[test]
+    fn unknown_objects() {
+        let (repo, ca) = make_repo();
+        let roa = repo.roa(&ca, "test.roa", 64496, "192.0.2.0/24");
+        let other = Bytes::from_static(b"something");
+        repo.write(&ca.uri("test.xyz"), &other);
+        repo.publish(
+            &ca, 1, &[("test.roa", &roa), ("test.xyz", &other)], &[]
+        );
+        let roa_uri = ca.uri("test.roa").to_string();
+
+        let mut config = repo.config();
+        config.unknown_objects = FilterPolicy::Accept;
+        let (collect, _) = run(&engine(&config));
+        assert_eq!(collect.roas(), [roa_uri.as_str()]);
+        assert_eq!(
+            collect.status(&ca.uri("test.xyz")), Some(ObjectStatus::Unknown)
+        );
+
+        config.unknown_objects = FilterPolicy::Warn;
+        let (collect, _) = run(&engine(&config));
+        assert_eq!(collect.roas(), [roa_uri.as_str()]);
+        assert_eq!(
+            collect.status(&ca.uri("test.xyz")), Some(ObjectStatus::Invalid)
+        );
+
+        config.unknown_objects = FilterPolicy::Reject;
+        let (collect, _) = run(&engine(&config));
+        assert!(collect.roas().is_empty());
+        assert_eq!(collect.cancelled(), [ca.manifest().to_string()]);
+    }
+
+    #[test]
+    fn repository_limit_rejects_all_points() {
+        let (repo, ca) = make_repo();
+        let ta = repo.ta();
+        let ca_cert = repo.ca_cert(&ta, &ca, "AS64496", "192.0.2.0/24");
+        let ta_roa = repo.roa(&ta, "ta.roa", 64497, "192.0.2.0/24");
+        repo.publish(
+            &ta, 2, &[("ca.cer", &ca_cert), ("ta.roa", &ta_roa)], &[]
+        );
+        let roas = ["a.roa", "b.roa", "c.roa"].map(|name| {
+            (name, repo.roa(&ca, name, 64496, "192.0.2.0/24"))
+        });
+        let roas: Vec<_> = roas.iter().map(|(name, roa)| {
+            (*name, roa)
+        }).collect();
+        repo.publish(&ca, 1, &roas, &[]);
+
+        // With a single thread, the trust anchor’s point is accepted
+        // before the limit is hit while processing the CA’s point. It
+        // still needs to be rejected.
+        let mut config = repo.config();
+        config.validation_threads = 1;
+        config.max_repository_objects = Some(4);
+        let (collect, metrics) = run(&engine(&config));
+        assert!(collect.roas().is_empty());
+        let mut cancelled = collect.cancelled();
+        cancelled.sort();
+        assert_eq!(
+            cancelled, [ca.manifest().to_string(), ta.manifest().to_string()]
+        );
+        assert_eq!(metrics.publication.valid_points, 0);
+        assert_eq!(metrics.publication.rejected_points, 2);
+        assert_eq!(metrics.repositories.len(), 1);
+        assert_eq!(
+            metrics.repositories[0].limit_exceeded,
+            Some(RepositoryLimit::Objects)
+        );
+
+        // Within the limit, everything is accepted.
+        config.max_repository_objects = Some(7);
+        let (collect, metrics) = run(&engine(&config));
+        assert_eq!(collect.roas().len(), 4);
+        assert_eq!(metrics.publication.valid_points, 2);
+        assert_eq!(metrics.repositories[0].limit_exceeded, None);
+    }
+
+    #[test]
+    fn manifest_replay() {
+        // Validates a point with manifest number 5 and then one with
+        // manifest number 3 using the given config modification.
+        fn replay(policy: impl Fn(&mut Config)) -> (Collect, Metrics) {
+            let (repo, ca) = make_repo();
+            let roa = repo.roa(&ca, "old.roa", 64496, "192.0.2.0/24");
+            repo.publish(&ca, 5, &[("old.roa", &roa)], &[]);
+            let mut config = repo.config();
+            policy(&mut config);
+            let (collect, metrics) = run(&engine(&config));
+            assert_eq!(collect.roas(), [ca.uri("old.roa").as_str()]);
+            assert_eq!(metrics.publication.replayed_manifests, 0);
+
+            let roa = repo.roa(&ca, "new.roa", 64496, "192.0.2.0/24");
+            repo.publish(&ca, 3, &[("new.roa", &roa)], &[]);
+            run(&engine(&config))
+        }
+
+        let old = ["rsync://example.test/repo/ca/old.roa"];
+        let new = ["rsync://example.test/repo/ca/new.roa"];
+
+        let (collect, metrics) = replay(|config| {
+            config.manifest_replay = FilterPolicy::Reject;
+        });
+        assert_eq!(collect.roas(), old);
+        assert_eq!(metrics.publication.replayed_manifests, 1);
+        assert_eq!(metrics.publication.valid_points, 2);
+
+        let (collect, metrics) = replay(|config| {
+            config.manifest_replay = FilterPolicy::Warn;
+        });
+        assert_eq!(collect.roas(), new);
+        assert_eq!(metrics.publication.replayed_manifests, 1);
+
+        let (collect, metrics) = replay(|config| {
+            config.manifest_replay = FilterPolicy::Accept;
+        });
+        assert_eq!(collect.roas(), new);
+        assert_eq!(metrics.publication.replayed_manifests, 1);
+
+        // The TAL’s policy overrides the global one.
+        let (collect, metrics) = replay(|config| {
+            config.manifest_replay = FilterPolicy::Reject;
+            config.tal_policies.insert(
+                "test".into(),
+                TalPolicy {
+                    manifest_replay: Some(FilterPolicy::Accept),
+                    .. Default::default()
+                }
+            );
+        });
+        assert_eq!(collect.roas(), new);
+        assert_eq!(metrics.publication.replayed_manifests, 1);
+        assert_eq!(metrics.tals[0].publication.replayed_manifests, 1);
+    }
+
+    /// Creates a repository with a CA point and an engine using the cache.
+    ///
+    /// Performs a first run to fill the store and a second one to fill
+    /// the cache since only stored points are cached.
+    fn cached_repo() -> (TestRepo, TestCa, Config, Engine) {
+        let (repo, ca) = make_repo();
+        let roa = repo.roa(&ca, "test.roa", 64496, "192.0.2.0/24");
+        repo.publish(&ca, 1, &[("test.roa", &roa)], &[]);
+        let mut config = repo.config();
+        config.incremental_validation = true;
+        let engine = engine(&config);
+        for _ in 0..2 {
+            let (collect, metrics) = run(&engine);
+            assert_eq!(collect.roas(), [ca.uri("test.roa").as_str()]);
+            assert_eq!(metrics.publication.cached_points, 0);
+        }
+        (repo, ca, config, engine)
+    }
+
+    #[test]
+    fn as_of_expired_ee() {
+        let _ = crate::process::Process::init(); // May be inited already.
+        let past = Time::now() - chrono::Duration::days(30);
+        let repo = TestRepo::new_at(past);
+        let ta = repo.ta();
+        let ca = repo.ca(&ta, "ca", CA_KEY);
+        repo.ta_cert("AS64496-AS64511", "192.0.2.0/24");
+        let ca_cert = repo.ca_cert(&ta, &ca, "AS64496", "192.0.2.0/24");
+        repo.publish(&ta, 1, &[("ca.cer", &ca_cert)], &[]);
+        let roa = repo.roa_with_validity(
+            &ca, "test.roa", 64496, "192.0.2.0/24",
+            Validity::new(
+                past - chrono::Duration::minutes(5),
+                past + chrono::Duration::days(1),
+            )
+        );
+        repo.publish(&ca, 1, &[("test.roa", &roa)], &[]);
+        let mut engine = engine(&repo.config());
+
+        // The EE certificate has expired since but was valid back then.
+        engine.as_of = Some(past);
+        let (collect, _) = run(&engine);
+        assert_eq!(collect.roas(), [ca.uri("test.roa").to_string()]);
+
+        // A day later, it had expired already.
+        engine.as_of = Some(past + chrono::Duration::days(2));
+        let (collect, _) = run(&engine);
+        assert!(collect.roas().is_empty());
+        assert_eq!(
+            collect.status(&ca.uri("test.roa")), Some(ObjectStatus::Invalid)
+        );
+    }
+
+    #[test]
+    fn point_cache_reused() {
+        let (_repo, ca, _, engine) = cached_repo();
+        let (collect, metrics) = run(&engine);
+        assert_eq!(collect.roas(), [ca.uri("test.roa").as_str()]);
+        assert_eq!(metrics.publication.cached_points, 2);
+        assert_eq!(metrics.publication.valid_points, 2);
+    }
+
+    #[test]
+    fn point_cache_manifest_changed() {
+        let (repo, ca, config, cached) = cached_repo();
+        let roa = repo.roa(&ca, "new.roa", 64496, "192.0.2.0/24");
+        repo.publish(&ca, 2, &[("new.roa", &roa)], &[]);
+
+        // Update the store through a second engine so the cached engine
+        // finds the new manifest and CRL in the store rather than
+        // collecting them.
+        let (collect, _) = run(&engine(&config));
+        assert_eq!(collect.roas(), [ca.uri("new.roa").as_str()]);
+
+        let (collect, metrics) = run(&cached);
+        assert_eq!(collect.roas(), [ca.uri("new.roa").as_str()]);
+        assert_eq!(metrics.publication.cached_points, 1);
+    }
+
+    #[test]
+    fn point_cache_ca_resources_changed() {
+        let (repo, ca, _, engine) = cached_repo();
+        let ta = repo.ta();
+        let ca_cert = repo.ca_cert(&ta, &ca, "AS64496", "192.0.2.0/25");
+        repo.publish(&ta, 2, &[("ca.cer", &ca_cert)], &[]);
+
+        // The CA’s point itself is unchanged but it now overclaims.
+        let (collect, metrics) = run(&engine);
+        assert!(collect.roas().is_empty());
+        assert_eq!(metrics.publication.cached_points, 0);
+    }
+
+    #[test]
+    fn point_cache_ca_key_changed() {
+        let (repo, ca, _, engine) = cached_repo();
+        let ta = repo.ta();
+        let new_ca = repo.ca(&ta, "ca", EE_KEY);
+        let ca_cert = repo.ca_cert(&ta, &new_ca, "AS64496", "192.0.2.0/24");
+        repo.publish(&ta, 2, &[("ca.cer", &ca_cert)], &[]);
+
+        // The CA’s point is still signed with the old key.
+        let (collect, metrics) = run(&engine);
+        assert!(collect.roas().is_empty());
+        assert_eq!(metrics.publication.cached_points, 0);
+        assert_eq!(collect.cancelled(), [ca.manifest().to_string()]);
+    }
+
+    #[test]
+    fn point_cache_expired() {
+        let (_repo, _, _, mut engine) = cached_repo();
+
+        // The manifests are stale after a week.
+        engine.as_of = Some(Time::now() + chrono::Duration::days(8));
+        let (collect, metrics) = run(&engine);
+        assert!(collect.roas().is_empty());
+        assert_eq!(metrics.publication.cached_points, 0);
+        assert_eq!(metrics.publication.valid_points, 0);
+    }
+
+    #[test]
+    fn point_cache_limits() {
+        let (_repo, _, mut config, mut engine) = cached_repo();
+
+        // Each point has two objects, its CRL and a certificate or ROA.
+        config.max_repository_objects = Some(3);
+        engine.limits = RepositoryLimits::from_config(&config);
+        let (collect, metrics) = run(&engine);
+        assert!(collect.roas().is_empty());
+        assert_eq!(metrics.publication.valid_points, 0);
+        assert_eq!(
+            metrics.repositories[0].limit_exceeded,
+            Some(RepositoryLimit::Objects)
+        );
+    }
+
+    #[test]
+    fn ta_resources_shrink() {
+        let repo = TestRepo::new();
+        let decode = |bytes: Bytes| Cert::decode(bytes).unwrap();
+        let old = decode(repo.ta_cert("AS64496-AS64511", "192.0.2.0/24"));
+        let new = |asns, v4| {
+            decode(repo.ta_cert(asns, v4)).validate_ta(
+                TalInfo::from_name("test".into()).into_arc(), true
+            ).unwrap()
+        };
+
+        assert_eq!(
+            ta_resource_shrinkage(
+                &old, &new("AS64496-AS64511", "192.0.2.0/24")
+            ),
+            0.
+        );
+        assert_eq!(
+            ta_resource_shrinkage(
+                &old, &new("AS64480-AS64511", "192.0.0.0/16")
+            ),
+            0.
+        );
+
+        // The largest share is used: 12 out of 16 ASNs vs. half the
+        // addresses.
+        assert_eq!(
+            ta_resource_shrinkage(
+                &old, &new("AS64496-AS64499", "192.0.2.0/25")
+            ),
+            75.
+        );
+        assert_eq!(
+            ta_resource_shrinkage(
+                &old, &new("AS64496-AS64511", "192.0.2.128/25")
+            ),
+            50.
+        );
+
+        assert_eq!(
+            ta_resource_shrinkage(&old, &new("AS65000", "198.51.100.0/24")),
+            100.
+        );
+    }
+}
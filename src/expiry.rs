@@ -0,0 +1,257 @@
+//! Summarizing what is about to expire.
+//!
+//! The payload snapshot keeps the times when the manifests, CRLs, and VRPs
+//! of all valid publication points expire or go stale. This module
+//! aggregates these times per TAL and repository, both for a given time
+//! window via [`ExpiryList`] and as histograms over a fixed set of windows
+//! via [`ExpiryHistograms`].
+
+use std::cmp;
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use rpki::repository::x509::Time;
+use crate::payload::{ExpiryEvent, ExpiryType, PayloadSnapshot};
+use crate::utils::date::format_iso_date;
+use crate::utils::json::JsonBuilder;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The upper bounds in seconds of the buckets of the histograms.
+pub const HISTOGRAM_BUCKETS: [i64; 6] = [
+    3_600, 4 * 3_600, 12 * 3_600, 24 * 3_600, 48 * 3_600, 7 * 24 * 3_600
+];
+
+
+//------------ ExpiryCounts --------------------------------------------------
+
+/// The number of things expiring per expiry type.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExpiryCounts([usize; 3]);
+
+impl ExpiryCounts {
+    /// Adds `count` items of the given type.
+    fn add(&mut self, expiry_type: ExpiryType, count: usize) {
+        self.0[expiry_type.index()] += count
+    }
+
+    /// Returns the count for the given type.
+    pub fn get(&self, expiry_type: ExpiryType) -> usize {
+        self.0[expiry_type.index()]
+    }
+
+    fn json(&self, json: &mut JsonBuilder) {
+        for expiry_type in ExpiryType::ALL {
+            json.member_raw(expiry_type, self.get(expiry_type));
+        }
+    }
+}
+
+
+//------------ ExpiryList ----------------------------------------------------
+
+/// Everything expiring before a given time.
+#[derive(Clone, Debug)]
+pub struct ExpiryList<'a> {
+    /// The time the list was created.
+    now: Time,
+
+    /// The end of the time window.
+    until: Time,
+
+    /// The time the snapshot was created.
+    created: DateTime<Utc>,
+
+    /// The number of expiring things per TAL.
+    tals: BTreeMap<&'a str, ExpiryCounts>,
+
+    /// The number of expiring things per repository.
+    repositories: BTreeMap<&'a str, ExpiryCounts>,
+
+    /// The soonest expiring objects ordered by time.
+    soonest: Vec<ExpiryEvent<'a>>,
+}
+
+impl<'a> ExpiryList<'a> {
+    /// Creates the list for all things expiring before `until`.
+    ///
+    /// At most `limit` objects will be listed individually.
+    pub fn new(
+        snapshot: &'a PayloadSnapshot, now: Time, until: Time, limit: usize,
+    ) -> Self {
+        let mut tals = BTreeMap::<_, ExpiryCounts>::new();
+        let mut repositories = BTreeMap::<_, ExpiryCounts>::new();
+        let mut soonest = Vec::new();
+        for point in snapshot.expiry() {
+            for event in point.events() {
+                if event.time > until {
+                    continue
+                }
+                tals.entry(point.tal().name()).or_default().add(
+                    event.expiry_type, event.count
+                );
+                repositories.entry(point.repository()).or_default().add(
+                    event.expiry_type, event.count
+                );
+                soonest.push(event);
+            }
+        }
+        soonest.sort_by_key(|event| event.time);
+        soonest.truncate(limit);
+        ExpiryList {
+            now, until, created: snapshot.created(),
+            tals, repositories, soonest,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        JsonBuilder::build(|json| {
+            json.member_str("generatedTime", format_iso_date(self.created));
+            json.member_str("now", format_iso_date(self.now.into()));
+            json.member_str("until", format_iso_date(self.until.into()));
+            json.member_array("tals", |json| {
+                for (name, counts) in &self.tals {
+                    json.array_object(|json| {
+                        json.member_str("name", name);
+                        counts.json(json);
+                    })
+                }
+            });
+            json.member_array("repositories", |json| {
+                for (uri, counts) in &self.repositories {
+                    json.array_object(|json| {
+                        json.member_str("uri", uri);
+                        counts.json(json);
+                    })
+                }
+            });
+            json.member_array("soonest", |json| {
+                for event in &self.soonest {
+                    json.array_object(|json| {
+                        json.member_str("type", event.expiry_type);
+                        if let Some(uri) = event.uri {
+                            json.member_str("uri", uri);
+                        }
+                        json.member_str(
+                            "expires", format_iso_date(event.time.into())
+                        );
+                        if event.expiry_type == ExpiryType::Vrps {
+                            json.member_raw("vrps", event.count);
+                        }
+                        json.member_str("tal", event.point.tal().name());
+                        json.member_str(
+                            "repository", event.point.repository()
+                        );
+                    })
+                }
+            })
+        })
+    }
+}
+
+
+//------------ Histogram -----------------------------------------------------
+
+/// A histogram of the time until things expire.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Histogram {
+    /// The cumulative counts for the buckets in [`HISTOGRAM_BUCKETS`].
+    pub buckets: [usize; HISTOGRAM_BUCKETS.len()],
+
+    /// The total number of observations.
+    pub count: usize,
+
+    /// The sum of all observations in seconds.
+    pub sum: i64,
+}
+
+impl Histogram {
+    /// Adds `count` observations of `seconds`.
+    fn observe(&mut self, seconds: i64, count: usize) {
+        let buckets = self.buckets.iter_mut().zip(HISTOGRAM_BUCKETS);
+        for (bucket, bound) in buckets {
+            if seconds <= bound {
+                *bucket += count
+            }
+        }
+        self.count += count;
+        self.sum += seconds * count as i64;
+    }
+}
+
+
+//------------ ExpiryHistograms ----------------------------------------------
+
+/// Histograms of the time until things expire per TAL and repository.
+#[derive(Clone, Debug, Default)]
+pub struct ExpiryHistograms<'a> {
+    /// The histograms per TAL and expiry type.
+    pub tals: BTreeMap<&'a str, [Histogram; 3]>,
+
+    /// The histograms per repository and expiry type.
+    pub repositories: BTreeMap<&'a str, [Histogram; 3]>,
+}
+
+impl<'a> ExpiryHistograms<'a> {
+    /// Creates the histograms for a snapshot.
+    ///
+    /// Things that already have expired are counted as expiring now.
+    pub fn new(snapshot: &'a PayloadSnapshot, now: Time) -> Self {
+        let mut res = Self::default();
+        for point in snapshot.expiry() {
+            let tal = res.tals.entry(point.tal().name()).or_default();
+            for event in point.events() {
+                tal[event.expiry_type.index()].observe(
+                    seconds_until(now, event.time), event.count
+                );
+            }
+            let repository = res.repositories.entry(
+                point.repository()
+            ).or_default();
+            for event in point.events() {
+                repository[event.expiry_type.index()].observe(
+                    seconds_until(now, event.time), event.count
+                );
+            }
+        }
+        res
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the number of seconds from `now` until `time`.
+///
+/// Returns zero if `time` is in the past.
+fn seconds_until(now: Time, time: Time) -> i64 {
+    cmp::max(time.timestamp() - now.timestamp(), 0)
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn observe() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0, 1);
+        histogram.observe(5 * 3_600, 2);
+        histogram.observe(30 * 24 * 3_600, 1);
+        assert_eq!(histogram.buckets, [1, 1, 3, 3, 3, 3]);
+        assert_eq!(histogram.count, 4);
+        assert_eq!(histogram.sum, 10 * 3_600 + 30 * 24 * 3_600);
+    }
+
+    #[test]
+    fn seconds() {
+        let now = Time::utc(2024, 6, 1, 12, 0, 0);
+        assert_eq!(
+            seconds_until(now, Time::utc(2024, 6, 1, 13, 0, 0)), 3_600
+        );
+        assert_eq!(seconds_until(now, Time::utc(2024, 6, 1, 11, 0, 0)), 0);
+    }
+}
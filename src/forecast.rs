@@ -0,0 +1,206 @@
+//! Forecasting which VRPs will disappear.
+//!
+//! A forecast is created by validating the data currently in the store as
+//! if at some point in the future and comparing the resulting VRPs with
+//! those of the current payload snapshot. Every VRP missing from the
+//! future data set would vanish if nobody re-signed the objects it is
+//! derived from in the meantime.
+
+use std::collections::{BTreeMap, HashSet};
+use chrono::{DateTime, Utc};
+use rpki::repository::x509::Time;
+use rpki::rtr::payload::RouteOrigin;
+use rpki::uri;
+use crate::config::Config;
+use crate::engine::Engine;
+use crate::error::Failed;
+use crate::payload::{PayloadSnapshot, ValidationReport};
+use crate::slurm::LocalExceptions;
+use crate::utils::date::format_iso_date;
+use crate::utils::json::JsonBuilder;
+
+
+//------------ Forecast ------------------------------------------------------
+
+/// The VRPs that will be lost at some future time.
+#[derive(Clone, Debug)]
+pub struct Forecast {
+    /// The time the current snapshot was created.
+    created: DateTime<Utc>,
+
+    /// The time the forecast is for.
+    as_of: Time,
+
+    /// The number of VRPs in the current snapshot.
+    current: usize,
+
+    /// The VRPs that will be lost.
+    lost: Vec<LostOrigin>,
+}
+
+impl Forecast {
+    /// Creates a forecast by validating the store at the given time.
+    ///
+    /// The repository is not updated. The forecast is made against the
+    /// given current snapshot.
+    pub fn process(
+        config: &Config, current: &PayloadSnapshot, as_of: Time,
+    ) -> Result<Self, Failed> {
+        let mut config = config.clone();
+        config.as_of = Some(as_of);
+        config.object_report = false;
+        let engine = Engine::new(&config, false)?;
+        let (report, mut metrics) = ValidationReport::process(
+            &engine, &config
+        ).map_err(|_| Failed)?;
+        let exceptions = LocalExceptions::load(&config, false)?;
+        let future = report.into_snapshot(&exceptions, &mut metrics);
+        Ok(Self::new(current, &future, as_of))
+    }
+
+    /// Creates a forecast by comparing two snapshots.
+    pub fn new(
+        current: &PayloadSnapshot, future: &PayloadSnapshot, as_of: Time,
+    ) -> Self {
+        let remaining: HashSet<_> = future.origins().map(|item| {
+            item.0
+        }).collect();
+        let mut lost = Vec::new();
+        let mut count = 0;
+        for (origin, info) in current.origins() {
+            count += 1;
+            if !remaining.contains(&origin) {
+                lost.push(LostOrigin {
+                    origin,
+                    tal: info.tal_name().map(Into::into),
+                    uri: info.uri().cloned(),
+                })
+            }
+        }
+        Forecast {
+            created: current.created(),
+            as_of,
+            current: count,
+            lost,
+        }
+    }
+
+    /// Returns the time the forecast is for.
+    pub fn as_of(&self) -> Time {
+        self.as_of
+    }
+
+    /// Returns the VRPs that will be lost.
+    pub fn lost(&self) -> &[LostOrigin] {
+        &self.lost
+    }
+
+    /// Returns the number of lost VRPs per TAL.
+    pub fn lost_per_tal(&self) -> BTreeMap<&str, usize> {
+        let mut res = BTreeMap::new();
+        for item in &self.lost {
+            *res.entry(item.tal.as_deref().unwrap_or("N/A")).or_default()
+                += 1;
+        }
+        res
+    }
+
+    pub fn to_json(&self) -> String {
+        JsonBuilder::build(|json| {
+            json.member_str("generatedTime", format_iso_date(self.created));
+            json.member_str("asOf", format_iso_date(self.as_of.into()));
+            json.member_raw("currentVrps", self.current);
+            json.member_raw("lostVrps", self.lost.len());
+            json.member_object("lostPerTal", |json| {
+                for (tal, count) in self.lost_per_tal() {
+                    json.member_raw(tal, count);
+                }
+            });
+            json.member_array("lost", |json| {
+                for item in &self.lost {
+                    json.array_object(|json| {
+                        json.member_str("asn", item.origin.asn);
+                        json.member_str(
+                            "prefix",
+                            format_args!(
+                                "{}/{}",
+                                item.origin.prefix.addr(),
+                                item.origin.prefix.prefix_len(),
+                            )
+                        );
+                        json.member_raw(
+                            "maxLength",
+                            item.origin.prefix.resolved_max_len()
+                        );
+                        json.member_str(
+                            "ta", item.tal.as_deref().unwrap_or("N/A")
+                        );
+                        if let Some(uri) = item.uri.as_ref() {
+                            json.member_str("uri", uri);
+                        }
+                    })
+                }
+            })
+        })
+    }
+}
+
+
+//------------ LostOrigin ----------------------------------------------------
+
+/// A VRP that will be lost.
+#[derive(Clone, Debug)]
+pub struct LostOrigin {
+    /// The route origin.
+    pub origin: RouteOrigin,
+
+    /// The name of the TAL the VRP was derived under, if any.
+    pub tal: Option<String>,
+
+    /// The URI of the ROA the VRP was derived from, if any.
+    pub uri: Option<uri::Rsync>,
+}
+
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use crate::utils::testrepo::{TestRepo, CA_KEY};
+    use super::*;
+
+    #[test]
+    fn lost_roa() {
+        let _ = crate::process::Process::init(); // May be inited already.
+        let repo = TestRepo::new();
+        let ta = repo.ta();
+        let ca = repo.ca(&ta, "ca", CA_KEY);
+        repo.ta_cert("AS64496-AS64511", "192.0.2.0/24");
+        let ca_cert = repo.ca_cert(&ta, &ca, "AS64496", "192.0.2.0/24");
+        repo.publish(&ta, 1, &[("ca.cer", &ca_cert)], &[]);
+        let roa = repo.roa(&ca, "test.roa", 64496, "192.0.2.0/24");
+        repo.publish(&ca, 1, &[("test.roa", &roa)], &[]);
+
+        let config = repo.config();
+        let mut engine = Engine::new(&config, true).unwrap();
+        engine.ignite().unwrap();
+        let (report, mut metrics) = ValidationReport::process(
+            &engine, &config
+        ).unwrap();
+        let current = report.into_snapshot(
+            &LocalExceptions::empty(), &mut metrics
+        );
+        assert_eq!(current.origins().count(), 1);
+
+        // Manifest and CRL are good for a week, all certificates for a
+        // year.
+        let soon = Time::now() + chrono::TimeDelta::hours(1);
+        let forecast = Forecast::process(&config, &current, soon).unwrap();
+        assert!(forecast.lost().is_empty());
+
+        let later = Time::now() + chrono::TimeDelta::days(400);
+        let forecast = Forecast::process(&config, &current, later).unwrap();
+        assert_eq!(forecast.lost().len(), 1);
+    }
+}
@@ -0,0 +1,61 @@
+//! Handling of endpoints related to contact information.
+
+use std::str::FromStr;
+use rpki::resources::{Asn, Prefix};
+use crate::contacts::{ContactList, ContactQuery};
+use crate::payload::SharedHistory;
+use super::request::Request;
+use super::response::{ContentType, Response, ResponseBuilder};
+
+
+//------------ handle_get_or_head --------------------------------------------
+
+pub fn handle_get_or_head(
+    req: &Request,
+    history: &SharedHistory,
+) -> Option<Response> {
+    if req.uri().path() != "/api/v1/contacts" {
+        return None
+    }
+    let query = match contact_query(req.uri().query()) {
+        Some(query) => query,
+        None => return Some(Response::bad_request())
+    };
+    let current = match history.read().current() {
+        Some(current) => current,
+        None => return Some(Response::initial_validation())
+    };
+    let res = ResponseBuilder::ok().content_type(ContentType::JSON);
+    if req.is_head() {
+        Some(res.empty())
+    }
+    else {
+        Some(res.body(ContactList::new(query, &current).into_json()))
+    }
+}
+
+/// Parses the query parameters.
+///
+/// Accepts either no parameters at all or exactly one of `prefix` or `asn`.
+fn contact_query(query: Option<&str>) -> Option<ContactQuery> {
+    let query = match query {
+        Some(query) => query.as_bytes(),
+        None => return Some(ContactQuery::All)
+    };
+    let mut res = ContactQuery::All;
+    for (key, value) in form_urlencoded::parse(query) {
+        if !matches!(res, ContactQuery::All) {
+            return None
+        }
+        if key == "prefix" {
+            res = ContactQuery::Prefix(Prefix::from_str(&value).ok()?)
+        }
+        else if key == "asn" {
+            res = ContactQuery::Asn(Asn::from_str(&value).ok()?)
+        }
+        else {
+            return None
+        }
+    }
+    Some(res)
+}
@@ -6,7 +6,10 @@ use crate::config::Config;
 use crate::metrics::{HttpServerMetrics, SharedRtrServerMetrics};
 use crate::payload::SharedHistory;
 use crate::process::LogOutput;
-use super::{delta, log, metrics, payload, status, validity};
+use super::{
+    contacts, delta, expiring, forecast, log, metrics, objects, payload,
+    publish, rsc, status, validity
+};
 use super::request::Request;
 use super::response::Response;
 
@@ -15,6 +18,8 @@ use super::response::Response;
 pub struct State {
     payload: payload::State,
     log: log::State,
+    rsc: rsc::State,
+    forecast: forecast::State,
     history: SharedHistory,
     metrics: Arc<HttpServerMetrics>,
     rtr_metrics: SharedRtrServerMetrics,
@@ -32,6 +37,8 @@ impl State {
         Self {
             payload: payload::State::new(config),
             log: log::State::new(log),
+            rsc: rsc::State::new(config),
+            forecast: forecast::State::new(config),
             history,
             metrics: Arc::new(HttpServerMetrics::default()),
             rtr_metrics,
@@ -45,6 +52,9 @@ impl State {
 
     pub async fn handle_request(&self, req: Request) -> Response {
         self.metrics.inc_requests();
+        if req.is_post() {
+            return self.handle_post(req).await
+        }
         if !req.is_get_or_head() {
             return Response::method_not_allowed()
         }
@@ -81,6 +91,31 @@ impl State {
             &req, &self.history) {
             return response
         }
+        if let Some(response) = contacts::handle_get_or_head(
+            &req, &self.history
+        ) {
+            return response
+        }
+        if let Some(response) = objects::handle_get_or_head(
+            &req, &self.history
+        ) {
+            return response
+        }
+        if let Some(response) = expiring::handle_get_or_head(
+            &req, &self.history
+        ) {
+            return response
+        }
+        if let Some(response) = publish::handle_get_or_head(
+            &req, &self.history
+        ) {
+            return response
+        }
+        if let Some(response) = self.forecast.handle_get_or_head(
+            &req, &self.history
+        ).await {
+            return response
+        }
 
         #[cfg(feature = "ui")]
         if let Some(response) = super::ui::handle_get_or_head(&req) {
@@ -89,5 +124,12 @@ impl State {
         
         Response::not_found()
     }
+
+    async fn handle_post(&self, req: Request) -> Response {
+        if req.uri().path() == "/api/v1/rsc" {
+            return self.rsc.handle_post(req, &self.history).await
+        }
+        Response::method_not_allowed()
+    }
 }
 
@@ -0,0 +1,111 @@
+//! Handling of the endpoint for expiring objects.
+//!
+//! The endpoint `/api/v1/expiring` lists how many VRPs, manifests, and CRLs
+//! expire or go stale within a time window per TAL and repository as well
+//! as the objects expiring soonest. The window is given via the `within`
+//! query parameter as a number followed by one of the units `s`, `m`, `h`,
+//! or `d`. It defaults to 24 hours. The number of objects listed can be
+//! given via the `limit` query parameter.
+
+use rpki::repository::x509::Time;
+use crate::expiry::ExpiryList;
+use crate::payload::SharedHistory;
+use super::request::Request;
+use super::response::{ContentType, Response, ResponseBuilder};
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The default time window in seconds.
+const DEFAULT_WITHIN: i64 = 24 * 3_600;
+
+/// The default number of objects to list.
+const DEFAULT_LIMIT: usize = 100;
+
+
+//------------ handle_get_or_head --------------------------------------------
+
+pub fn handle_get_or_head(
+    req: &Request,
+    history: &SharedHistory,
+) -> Option<Response> {
+    if req.uri().path() != "/api/v1/expiring" {
+        return None
+    }
+    let (within, limit) = match parse_query(req.uri().query()) {
+        Some(some) => some,
+        None => return Some(Response::bad_request())
+    };
+    let now = Time::now();
+    let until = match chrono::Duration::try_seconds(within) {
+        Some(delta) => now + delta,
+        None => return Some(Response::bad_request())
+    };
+    let snapshot = match history.read().current() {
+        Some(snapshot) => snapshot,
+        None => return Some(Response::initial_validation())
+    };
+    let res = ResponseBuilder::ok().content_type(ContentType::JSON);
+    if req.is_head() {
+        return Some(res.empty())
+    }
+    Some(res.body(ExpiryList::new(&snapshot, now, until, limit).to_json()))
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses the query into the time window in seconds and the limit.
+fn parse_query(query: Option<&str>) -> Option<(i64, usize)> {
+    let mut within = DEFAULT_WITHIN;
+    let mut limit = DEFAULT_LIMIT;
+    let query = match query {
+        Some(query) => query,
+        None => return Some((within, limit))
+    };
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match key.as_ref() {
+            "within" => within = parse_duration(&value)?,
+            "limit" => limit = value.parse().ok()?,
+            _ => return None
+        }
+    }
+    Some((within, limit))
+}
+
+/// Parses a duration with a unit into seconds.
+fn parse_duration(s: &str) -> Option<i64> {
+    let (value, unit) = match s.char_indices().last()? {
+        (idx, 's') => (&s[..idx], 1),
+        (idx, 'm') => (&s[..idx], 60),
+        (idx, 'h') => (&s[..idx], 3_600),
+        (idx, 'd') => (&s[..idx], 24 * 3_600),
+        _ => return None
+    };
+    value.parse::<u32>().ok().map(|value| i64::from(value) * unit)
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn query() {
+        assert_eq!(parse_query(None), Some((DEFAULT_WITHIN, DEFAULT_LIMIT)));
+        assert_eq!(
+            parse_query(Some("within=6h&limit=10")), Some((6 * 3_600, 10))
+        );
+        assert_eq!(
+            parse_query(Some("within=2d")), Some((2 * 86_400, DEFAULT_LIMIT))
+        );
+        assert_eq!(parse_query(Some("within=30m")).unwrap().0, 1_800);
+        assert_eq!(parse_query(Some("within=45s")).unwrap().0, 45);
+        assert_eq!(parse_query(Some("within=24")), None);
+        assert_eq!(parse_query(Some("within=h")), None);
+        assert_eq!(parse_query(Some("within=-1h")), None);
+        assert_eq!(parse_query(Some("foo=1")), None);
+    }
+}
@@ -0,0 +1,203 @@
+//! Handling of the VRP forecast endpoint.
+//!
+//! The endpoint `/api/v1/forecast?hours=N` validates the data in the local
+//! store as if N hours after the last validation run and returns the VRPs
+//! of the current data set that would be lost by then.
+//!
+//! Because this requires a full validation run over the store, only one
+//! request is processed at a time. Forecasts are kept until the payload
+//! snapshot changes, i.e., until the next validation run finishes.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use rpki::repository::x509::Time;
+use tokio::sync::Mutex;
+use crate::config::Config;
+use crate::forecast::Forecast;
+use crate::payload::{PayloadSnapshot, SharedHistory};
+use super::request::Request;
+use super::response::{ContentType, Response, ResponseBuilder};
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The maximum number of forecasts kept per validation run.
+const MAX_CACHED: usize = 32;
+
+
+//------------ State ---------------------------------------------------------
+
+pub struct State {
+    /// The configuration to create the validation engine from.
+    config: Arc<Config>,
+
+    /// The forecasts for the current snapshot.
+    ///
+    /// The lock also ensures only one validation happens at a time.
+    cache: Mutex<Cache>,
+}
+
+impl State {
+    pub fn new(config: &Config) -> Self {
+        State {
+            config: Arc::new(config.clone()),
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    pub async fn handle_get_or_head(
+        &self,
+        req: &Request,
+        history: &SharedHistory,
+    ) -> Option<Response> {
+        if req.uri().path() != "/api/v1/forecast" {
+            return None
+        }
+        let hours = match parse_hours(req.uri().query()) {
+            Some(hours) => hours,
+            None => return Some(Response::bad_request())
+        };
+        let current = match history.read().current() {
+            Some(current) => current,
+            None => return Some(Response::initial_validation())
+        };
+        let as_of = match chrono::Duration::try_hours(hours.into()) {
+            Some(delta) => Time::new(current.created()) + delta,
+            None => return Some(Response::bad_request())
+        };
+        if req.is_head() {
+            return Some(
+                ResponseBuilder::ok().content_type(ContentType::JSON).empty()
+            )
+        }
+        let mut cache = self.cache.lock().await;
+        let forecast = match cache.get(&current, hours) {
+            Some(forecast) => forecast,
+            None => {
+                let config = self.config.clone();
+                let snapshot = current.clone();
+                let forecast = tokio::task::spawn_blocking(move || {
+                    Forecast::process(&config, &snapshot, as_of)
+                }).await;
+                match forecast {
+                    Ok(Ok(forecast)) => {
+                        let forecast = Arc::new(forecast);
+                        cache.insert(current, hours, forecast.clone());
+                        forecast
+                    }
+                    _ => {
+                        return Some(
+                            ResponseBuilder::service_unavailable().empty()
+                        )
+                    }
+                }
+            }
+        };
+        Some(
+            ResponseBuilder::ok().content_type(
+                ContentType::JSON
+            ).body(forecast.to_json())
+        )
+    }
+}
+
+
+//------------ Cache ---------------------------------------------------------
+
+/// The forecasts made for a payload snapshot.
+#[derive(Default)]
+struct Cache {
+    /// The snapshot the forecasts were made for.
+    snapshot: Option<Arc<PayloadSnapshot>>,
+
+    /// The forecasts by number of hours.
+    forecasts: HashMap<u32, Arc<Forecast>>,
+}
+
+impl Cache {
+    /// Returns the forecast for the given snapshot and hours if available.
+    fn get(
+        &self, snapshot: &Arc<PayloadSnapshot>, hours: u32
+    ) -> Option<Arc<Forecast>> {
+        if !self.is_for(snapshot) {
+            return None
+        }
+        self.forecasts.get(&hours).cloned()
+    }
+
+    /// Adds a forecast.
+    ///
+    /// Drops all forecasts for a different snapshot first.
+    fn insert(
+        &mut self,
+        snapshot: Arc<PayloadSnapshot>, hours: u32, forecast: Arc<Forecast>,
+    ) {
+        if !self.is_for(&snapshot) {
+            self.forecasts.clear();
+            self.snapshot = Some(snapshot);
+        }
+        if self.forecasts.len() < MAX_CACHED {
+            self.forecasts.insert(hours, forecast);
+        }
+    }
+
+    /// Returns whether the cache is for the given snapshot.
+    fn is_for(&self, snapshot: &Arc<PayloadSnapshot>) -> bool {
+        self.snapshot.as_ref().map(|cached| {
+            Arc::ptr_eq(cached, snapshot)
+        }).unwrap_or(false)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Parses the number of hours from the query.
+///
+/// The query must consist of exactly one `hours` parameter.
+fn parse_hours(query: Option<&str>) -> Option<u32> {
+    let mut res = None;
+    for (key, value) in form_urlencoded::parse(query?.as_bytes()) {
+        if key != "hours" || res.is_some() {
+            return None
+        }
+        res = Some(value.parse().ok()?);
+    }
+    res
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hours() {
+        assert_eq!(parse_hours(Some("hours=6")), Some(6));
+        assert_eq!(parse_hours(None), None);
+        assert_eq!(parse_hours(Some("")), None);
+        assert_eq!(parse_hours(Some("hours=-1")), None);
+        assert_eq!(parse_hours(Some("hours=6&hours=7")), None);
+        assert_eq!(parse_hours(Some("days=1")), None);
+    }
+
+    #[test]
+    fn cache() {
+        let first = Arc::new(PayloadSnapshot::default());
+        let second = Arc::new(PayloadSnapshot::default());
+        let forecast = Arc::new(
+            Forecast::new(&first, &first, Time::now())
+        );
+        let mut cache = Cache::default();
+        assert!(cache.get(&first, 1).is_none());
+        cache.insert(first.clone(), 1, forecast.clone());
+        assert!(cache.get(&first, 1).is_some());
+        assert!(cache.get(&first, 2).is_none());
+        assert!(cache.get(&second, 1).is_none());
+        cache.insert(second.clone(), 2, forecast);
+        assert!(cache.get(&first, 1).is_none());
+        assert!(cache.get(&second, 2).is_some());
+    }
+}
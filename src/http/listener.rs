@@ -20,7 +20,8 @@ use crate::error::ExitError;
 use crate::metrics::{HttpServerMetrics, SharedRtrServerMetrics};
 use crate::payload::SharedHistory;
 use crate::process::LogOutput;
-use crate::utils::{net, tls};
+use crate::utils::net::ListenSockets;
+use crate::utils::tls;
 use crate::utils::tls::MaybeTlsTcpStream;
 use super::dispatch::State;
 
@@ -34,6 +35,7 @@ pub fn http_listener(
     log: Option<Arc<LogOutput>>,
     config: &Config,
     notify: NotifySender,
+    sockets: &mut ListenSockets,
 ) -> Result<impl Future<Output = ()>, ExitError> {
     let state = Arc::new(
         State::new(config, origins, rtr_metrics, log, notify)
@@ -43,13 +45,13 @@ pub fn http_listener(
     // during detach. So we do this here synchronously.
     let mut listeners = Vec::new();
     for addr in &config.http_listen {
-        listeners.push((*addr, None, net::bind(addr)?));
+        listeners.push((*addr, None, sockets.bind(addr)?));
     }
     if !config.http_tls_listen.is_empty() {
         let tls_config = create_tls_config(config)?;
         for addr in &config.http_tls_listen {
             listeners.push(
-                (*addr, Some(tls_config.clone()), net::bind(addr)?)
+                (*addr, Some(tls_config.clone()), sockets.bind(addr)?)
             );
         }
     }
@@ -2,14 +2,21 @@
 
 use std::{cmp, fmt};
 use std::fmt::Write;
+use std::net::IpAddr;
 use chrono::Utc;
+use rpki::repository::x509::Time;
+use rpki::rtr::state::Serial;
 use crate::config::FilterPolicy;
+use crate::expiry::{ExpiryHistograms, Histogram, HISTOGRAM_BUCKETS};
 use crate::metrics::{
-    HttpServerMetrics, Metrics, PayloadMetrics, PublicationMetrics,
-    RrdpRepositoryMetrics, RsyncModuleMetrics, SharedRtrServerMetrics,
-    VrpMetrics
+    ErikHostMetrics, HttpServerMetrics, Metrics, PayloadMetrics,
+    PublicationMetrics,
+    RepositoryMetrics, RrdpHostMetrics, RrdpRepositoryMetrics,
+    RsyncModuleMetrics,
+    SharedRtrServerMetrics, TalMetrics, UriRewriteMetrics, VrpMetrics,
+    later_serial, serial_lag,
 };
-use crate::payload::SharedHistory;
+use crate::payload::{ExpiryType, SharedHistory};
 use super::request::Request;
 use super::response::{ContentType, Response, ResponseBuilder};
 
@@ -39,13 +46,14 @@ async fn handle_metrics(
     http: &HttpServerMetrics,
     rtr: &SharedRtrServerMetrics,
 ) -> Response {
-    let (metrics, serial, start, done, duration, unsafe_vrps) = {
+    let (metrics, snapshot, serial, start, done, duration, unsafe_vrps) = {
         let history = history.read();
         (
             match history.metrics() {
                 Some(metrics) => metrics,
                 None => return Response::initial_validation(),
             },
+            history.current(),
             history.serial(),
             history.last_update_start(),
             history.last_update_done(),
@@ -111,6 +119,10 @@ async fn handle_metrics(
         &mut target, Group::Ta,
         metrics.tals.iter().map(|m| (m.tal.name(), &m.publication))
     );
+    point_cache_metrics(
+        &mut target, Group::Ta,
+        metrics.tals.iter().map(|m| (m.tal.name(), &m.publication))
+    );
     object_metrics(
         &mut target, Group::Ta,
         metrics.tals.iter().map(|m| (m.tal.name(), &m.publication))
@@ -123,12 +135,17 @@ async fn handle_metrics(
         &mut target, Group::Ta, unsafe_vrps,
         metrics.tals.iter().map(|m| (m.tal.name(), &m.payload))
     );
+    ta_cert_metrics(&mut target, &metrics.tals);
 
     // Per-repository metrics.
     pub_point_metrics(
         &mut target, Group::Repository,
         metrics.repositories.iter().map(|m| (m.uri.as_ref(), &m.publication))
     );
+    point_cache_metrics(
+        &mut target, Group::Repository,
+        metrics.repositories.iter().map(|m| (m.uri.as_ref(), &m.publication))
+    );
     object_metrics(
         &mut target, Group::Repository,
         metrics.repositories.iter().map(|m| (m.uri.as_ref(), &m.publication))
@@ -141,6 +158,20 @@ async fn handle_metrics(
         &mut target, Group::Repository, unsafe_vrps,
         metrics.repositories.iter().map(|m| (m.uri.as_ref(), &m.payload))
     );
+    repository_limit_metrics(&mut target, &metrics.repositories);
+
+    // Expiry histograms.
+    if let Some(snapshot) = snapshot.as_ref() {
+        let histograms = ExpiryHistograms::new(snapshot, Time::now());
+        expiry_metrics(
+            &mut target, Group::Ta,
+            histograms.tals.iter().map(|(name, h)| (*name, h))
+        );
+        expiry_metrics(
+            &mut target, Group::Repository,
+            histograms.repositories.iter().map(|(uri, h)| (*uri, h))
+        );
+    }
 
     // Locally added VRPs
     target.single(
@@ -154,10 +185,14 @@ async fn handle_metrics(
 
     // Collector metrics.
     rrdp_metrics(&mut target, &metrics.rrdp);
+    rrdp_http_metrics(&mut target, &metrics.rrdp);
+    rrdp_host_metrics(&mut target, &metrics.rrdp_hosts);
     rsync_metrics(&mut target, &metrics.rsync);
+    erik_metrics(&mut target, &metrics.erik);
+    uri_rewrite_metrics(&mut target, &metrics.uri_rewrites);
 
     // Server metrics.
-    rtr_metrics(&mut target, rtr).await;
+    rtr_metrics(&mut target, rtr, serial).await;
     http_metrics(&mut target, http);
 
     //  Deprecated metrics.
@@ -186,6 +221,103 @@ fn pub_point_metrics<'a>(
     }
 }
 
+fn point_cache_metrics<'a>(
+    target: &mut Target, group: Group,
+    metrics: impl Iterator<Item = (&'a str, &'a PublicationMetrics)>
+) {
+    let points = Metric::with_prefix(
+        group.prefix(), "cached_publication_points",
+        ("publication points reused from the point cache per ", group.help()),
+        MetricType::Gauge
+    );
+    let saved = Metric::with_prefix(
+        group.prefix(), "cache_time_saved_seconds",
+        ("validation time saved by the point cache per ", group.help()),
+        MetricType::Gauge
+    );
+    let metrics: Vec<_> = metrics.collect();
+    target.header(points);
+    for (name, metrics) in &metrics {
+        target.multi(points).label(group.label(), name)
+            .value(metrics.cached_points);
+    }
+    target.header(saved);
+    for (name, metrics) in &metrics {
+        target.multi(saved).label(group.label(), name)
+            .value(metrics.cache_time_saved.as_secs_f64());
+    }
+}
+
+fn ta_cert_metrics(target: &mut Target, tals: &[TalMetrics]) {
+    let metric = Metric::new(
+        "ta_certificate",
+        "fingerprint of the trust anchor certificate in use",
+        MetricType::Gauge
+    );
+    target.header(metric);
+    for tal in tals {
+        if let Some(fingerprint) = tal.ta_cert.fingerprint.as_ref() {
+            target.multi(metric).label("name", tal.name())
+                .label("fingerprint", fingerprint)
+                .value(1);
+        }
+    }
+
+    let metric = Metric::new(
+        "ta_certificate_age",
+        "seconds since the trust anchor certificate in use was accepted",
+        MetricType::Gauge
+    );
+    let now = Utc::now();
+    target.header(metric);
+    for tal in tals {
+        if let Some(since) = tal.ta_cert.since {
+            target.multi(metric).label("name", tal.name())
+                .value(now.signed_duration_since(since).num_seconds());
+        }
+    }
+
+    let metric = Metric::new(
+        "ta_certificate_changed",
+        "whether a new trust anchor certificate was accepted",
+        MetricType::Gauge
+    );
+    target.header(metric);
+    for tal in tals {
+        target.multi(metric).label("name", tal.name())
+            .value(u8::from(tal.ta_cert.changed));
+    }
+
+    let metric = Metric::new(
+        "ta_certificate_refused",
+        "whether a new trust anchor certificate was refused due to pinning",
+        MetricType::Gauge
+    );
+    target.header(metric);
+    for tal in tals {
+        target.multi(metric).label("name", tal.name())
+            .value(u8::from(tal.ta_cert.refused));
+    }
+}
+
+fn repository_limit_metrics(
+    target: &mut Target, repositories: &[RepositoryMetrics]
+) {
+    let metric = Metric::new(
+        "repository_limit_exceeded",
+        "repositories rejected for exceeding a resource limit",
+        MetricType::Gauge
+    );
+    target.header(metric);
+    for repository in repositories {
+        if let Some(limit) = repository.limit_exceeded {
+            target.multi(metric).label("uri", &repository.uri)
+                .label("limit", limit)
+                .value(1);
+        }
+    }
+}
+
 fn object_metrics<'a>(
     target: &mut Target, group: Group,
     metrics: impl Iterator<Item = (&'a str, &'a PublicationMetrics)>
@@ -217,6 +349,10 @@ fn object_metrics<'a>(
             .label("type", "manifest")
             .label("state", "missing")
             .value(metrics.missing_manifests);
+        target.multi(metric).label(group.label(), name)
+            .label("type", "manifest")
+            .label("state", "replayed")
+            .value(metrics.replayed_manifests);
 
         target.multi(metric).label(group.label(), name)
             .label("type", "crl")
@@ -340,6 +476,52 @@ fn vrp_metrics<'a>(
     }
 }
 
+fn expiry_metrics<'a>(
+    target: &mut Target, group: Group,
+    histograms: impl Iterator<Item = (&'a str, &'a [Histogram; 3])>
+) {
+    let help = (
+        "seconds until objects expire or go stale per ", group.help()
+    );
+    let metric = Metric::with_prefix(
+        group.prefix(), "expiry_seconds", help, MetricType::Histogram
+    );
+    let bucket_metric = Metric::with_prefix(
+        group.prefix(), "expiry_seconds_bucket", help, MetricType::Histogram
+    );
+    let sum_metric = Metric::with_prefix(
+        group.prefix(), "expiry_seconds_sum", help, MetricType::Histogram
+    );
+    let count_metric = Metric::with_prefix(
+        group.prefix(), "expiry_seconds_count", help, MetricType::Histogram
+    );
+
+    target.header(metric);
+    for (name, histograms) in histograms {
+        for expiry_type in ExpiryType::ALL {
+            let histogram = &histograms[expiry_type.index()];
+            for (bound, value) in HISTOGRAM_BUCKETS.iter().zip(
+                histogram.buckets
+            ) {
+                target.multi(bucket_metric).label(group.label(), name)
+                    .label("type", expiry_type)
+                    .label("le", bound)
+                    .value(value);
+            }
+            target.multi(bucket_metric).label(group.label(), name)
+                .label("type", expiry_type)
+                .label("le", "+Inf")
+                .value(histogram.count);
+            target.multi(sum_metric).label(group.label(), name)
+                .label("type", expiry_type)
+                .value(histogram.sum);
+            target.multi(count_metric).label(group.label(), name)
+                .label("type", expiry_type)
+                .value(histogram.count);
+        }
+    }
+}
+
 fn payload_metrics<'a>(
     target: &mut Target, group: Group, unsafe_vrps: FilterPolicy,
     metrics: impl Iterator<Item = (&'a str, &'a PayloadMetrics)>
@@ -455,6 +637,12 @@ fn rrdp_metrics(target: &mut Target, metrics: &[RrdpRepositoryMetrics]) {
         MetricType::Gauge
     );
     target.header(payload_status);
+    let payload_bytes = Metric::new(
+        "rrdp_payload_bytes",
+        "number of bytes received for RRDP snapshot and delta files",
+        MetricType::Gauge
+    );
+    target.header(payload_bytes);
     let duration = Metric::new(
         "rrdp_duration",
         "duration of RRDP update in seconds",
@@ -467,6 +655,27 @@ fn rrdp_metrics(target: &mut Target, metrics: &[RrdpRepositoryMetrics]) {
         MetricType::Gauge
     );
     target.header(serial);
+    let failures = Metric::new(
+        "rrdp_consecutive_failures",
+        "number of consecutive failed RRDP updates",
+        MetricType::Gauge
+    );
+    target.header(failures);
+    let skipped = Metric::new(
+        "rrdp_skipped",
+        "whether the RRDP update was skipped because of backoff",
+        MetricType::Gauge
+    );
+    target.header(skipped);
+    let slot_wait = Metric::new(
+        "rrdp_slot_wait_seconds",
+        "time spent waiting for an RRDP update slot for the host",
+        MetricType::Gauge
+    );
+    let has_slot_wait = metrics.iter().any(|rrdp| rrdp.slot_wait.is_some());
+    if has_slot_wait {
+        target.header(slot_wait);
+    }
 
     for rrdp in metrics {
         target.multi(status).label("uri", &rrdp.notify_uri).value(
@@ -478,6 +687,9 @@ fn rrdp_metrics(target: &mut Target, metrics: &[RrdpRepositoryMetrics]) {
         target.multi(payload_status).label("uri", &rrdp.notify_uri).value(
             rrdp.payload_status.map(|status| status.into_i16()).unwrap_or(0)
         );
+        target.multi(payload_bytes).label("uri", &rrdp.notify_uri).value(
+            rrdp.payload_bytes
+        );
         if let Ok(value) = rrdp.duration {
             target.multi(duration).label("uri", &rrdp.notify_uri).value(
                 format_args!(
@@ -490,6 +702,146 @@ fn rrdp_metrics(target: &mut Target, metrics: &[RrdpRepositoryMetrics]) {
         if let Some(value) = rrdp.serial {
             target.multi(serial).label("uri", &rrdp.notify_uri).value(value)
         }
+        if let Some(backoff) = rrdp.backoff {
+            target.multi(failures).label("uri", &rrdp.notify_uri).value(
+                backoff.failures
+            );
+            target.multi(skipped).label("uri", &rrdp.notify_uri).value(
+                u8::from(backoff.skipped)
+            );
+        }
+        if let Some(value) = rrdp.slot_wait {
+            target.multi(slot_wait).label("uri", &rrdp.notify_uri).value(
+                format_args!(
+                    "{}.{:03}",
+                    value.as_secs(),
+                    value.subsec_millis(),
+                )
+            )
+        }
+    }
+}
+
+fn rrdp_http_metrics(target: &mut Target, metrics: &[RrdpRepositoryMetrics]) {
+    let requests = Metric::new(
+        "rrdp_http_requests",
+        "number of HTTP requests sent for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(requests);
+    let connections = Metric::new(
+        "rrdp_http_connections",
+        "number of new HTTP connections opened for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(connections);
+    let dns = Metric::new(
+        "rrdp_http_dns_seconds",
+        "time spent resolving host names for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(dns);
+    let connect = Metric::new(
+        "rrdp_http_connect_seconds",
+        "time spent establishing HTTP connections for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(connect);
+    let ttfb = Metric::new(
+        "rrdp_http_ttfb_seconds",
+        "time spent waiting for responses for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(ttfb);
+    let transfer = Metric::new(
+        "rrdp_http_transfer_seconds",
+        "time spent receiving responses for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(transfer);
+    let received = Metric::new(
+        "rrdp_http_received_bytes",
+        "number of bytes received in HTTP responses for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(received);
+    let redirects = Metric::new(
+        "rrdp_http_redirects",
+        "number of HTTP redirects followed for the RRDP update",
+        MetricType::Gauge
+    );
+    target.header(redirects);
+    let not_modified = Metric::new(
+        "rrdp_http_not_modified",
+        "number of conditional HTTP requests answered with not modified",
+        MetricType::Gauge
+    );
+    target.header(not_modified);
+    let errors = Metric::new(
+        "rrdp_http_errors",
+        "number of failed HTTP requests for the RRDP update by cause",
+        MetricType::Gauge
+    );
+    target.header(errors);
+
+    for rrdp in metrics {
+        let uri = &rrdp.notify_uri;
+        target.multi(requests).label("uri", uri).value(rrdp.http.requests);
+        target.multi(connections).label("uri", uri).value(
+            rrdp.http.connections
+        );
+        for (metric, value) in [
+            (dns, rrdp.http.dns),
+            (connect, rrdp.http.connect),
+            (ttfb, rrdp.http.ttfb),
+            (transfer, rrdp.http.transfer),
+        ] {
+            target.multi(metric).label("uri", uri).value(
+                format_args!(
+                    "{}.{:03}",
+                    value.as_secs(),
+                    value.subsec_millis(),
+                )
+            );
+        }
+        target.multi(received).label("uri", uri).value(rrdp.http.bytes);
+        target.multi(redirects).label("uri", uri).value(
+            rrdp.http.redirects
+        );
+        target.multi(not_modified).label("uri", uri).value(
+            rrdp.http.not_modified
+        );
+        for (kind, value) in rrdp.http.errors.iter() {
+            target.multi(errors).label("uri", uri)
+                .label("kind", kind).value(value);
+        }
+    }
+}
+
+fn rrdp_host_metrics(target: &mut Target, metrics: &[RrdpHostMetrics]) {
+    if metrics.is_empty() {
+        return
+    }
+    let requests = Metric::new(
+        "rrdp_host_requests",
+        "number of HTTP requests sent to an RRDP host",
+        MetricType::Gauge
+    );
+    target.header(requests);
+    let connections = Metric::new(
+        "rrdp_host_connections",
+        "number of HTTP connections opened to an RRDP host",
+        MetricType::Gauge
+    );
+    target.header(connections);
+
+    for host in metrics {
+        target.multi(requests).label("host", &host.host).value(
+            host.requests
+        );
+        target.multi(connections).label("host", &host.host).value(
+            host.connections
+        );
     }
 }
 
@@ -498,12 +850,86 @@ fn rsync_metrics(target: &mut Target, metrics: &[RsyncModuleMetrics]) {
         "rsync_status", "exit status of the rsync command", MetricType::Gauge
     );
     target.header(status);
+    let cause = Metric::new(
+        "rsync_cause",
+        "named cause of the rsync update status",
+        MetricType::Gauge
+    );
+    target.header(cause);
     let duration = Metric::new(
         "rsync_duration",
         "duration of the rsync command in seconds",
         MetricType::Gauge
     );
     target.header(duration);
+    let failures = Metric::new(
+        "rsync_consecutive_failures",
+        "number of consecutive failed rsync updates",
+        MetricType::Gauge
+    );
+    target.header(failures);
+    let skipped = Metric::new(
+        "rsync_skipped",
+        "whether the rsync update was skipped because of backoff",
+        MetricType::Gauge
+    );
+    target.header(skipped);
+    let recent = Metric::new(
+        "rsync_recent_updates",
+        "number of recent rsync updates kept in the module history",
+        MetricType::Gauge
+    );
+    target.header(recent);
+    let recent_failures = Metric::new(
+        "rsync_recent_failures",
+        "number of failed rsync updates in the module history",
+        MetricType::Gauge
+    );
+    target.header(recent_failures);
+    let slot_wait = Metric::new(
+        "rsync_slot_wait_seconds",
+        "time spent waiting for an rsync update slot for the host",
+        MetricType::Gauge
+    );
+    let has_slot_wait = metrics.iter().any(|rsync| {
+        rsync.slot_wait.is_some()
+    });
+    if has_slot_wait {
+        target.header(slot_wait);
+    }
+    let files = Metric::new(
+        "rsync_files",
+        "number of files in the rsync module",
+        MetricType::Gauge
+    );
+    let transferred = Metric::new(
+        "rsync_transferred_files",
+        "number of files transferred during the rsync update",
+        MetricType::Gauge
+    );
+    let deleted = Metric::new(
+        "rsync_deleted_files",
+        "number of files deleted during the rsync update",
+        MetricType::Gauge
+    );
+    let received = Metric::new(
+        "rsync_received_bytes",
+        "number of bytes received during the rsync update",
+        MetricType::Gauge
+    );
+    let sent = Metric::new(
+        "rsync_sent_bytes",
+        "number of bytes sent during the rsync update",
+        MetricType::Gauge
+    );
+    let has_stats = metrics.iter().any(|rsync| rsync.stats.is_some());
+    if has_stats {
+        target.header(files);
+        target.header(transferred);
+        target.header(deleted);
+        target.header(received);
+        target.header(sent);
+    }
 
     for rsync in metrics {
         target.multi(status).label("uri", &rsync.module).value(
@@ -512,6 +938,10 @@ fn rsync_metrics(target: &mut Target, metrics: &[RsyncModuleMetrics]) {
                 Err(_) => -1
             }
         );
+        target.multi(cause)
+            .label("uri", &rsync.module)
+            .label("cause", rsync.cause)
+            .value(1);
         if let Ok(value) = rsync.duration {
             target.multi(duration).label("uri", &rsync.module).value(
                 format_args!(
@@ -521,12 +951,152 @@ fn rsync_metrics(target: &mut Target, metrics: &[RsyncModuleMetrics]) {
                 )
             );
         }
+        if let Some(backoff) = rsync.backoff {
+            target.multi(failures).label("uri", &rsync.module).value(
+                backoff.failures
+            );
+            target.multi(skipped).label("uri", &rsync.module).value(
+                u8::from(backoff.skipped)
+            );
+        }
+        target.multi(recent).label("uri", &rsync.module).value(
+            rsync.history.len()
+        );
+        target.multi(recent_failures).label("uri", &rsync.module).value(
+            rsync.history.iter().filter(|item| {
+                !item.cause.is_success()
+            }).count()
+        );
+        if let Some(value) = rsync.slot_wait {
+            target.multi(slot_wait).label("uri", &rsync.module).value(
+                format_args!(
+                    "{}.{:03}",
+                    value.as_secs(),
+                    value.subsec_millis(),
+                )
+            )
+        }
+        if let Some(stats) = rsync.stats {
+            target.multi(files).label("uri", &rsync.module).value(
+                stats.files
+            );
+            target.multi(transferred).label("uri", &rsync.module).value(
+                stats.transferred
+            );
+            target.multi(deleted).label("uri", &rsync.module).value(
+                stats.deleted
+            );
+            target.multi(received).label("uri", &rsync.module).value(
+                stats.bytes_received
+            );
+            target.multi(sent).label("uri", &rsync.module).value(
+                stats.bytes_sent
+            );
+        }
+    }
+}
+
+fn erik_metrics(target: &mut Target, metrics: &[ErikHostMetrics]) {
+    if metrics.is_empty() {
+        return
+    }
+    let status = Metric::new(
+        "erik_status",
+        "whether the Erik synchronisation of the host succeeded",
+        MetricType::Gauge
+    );
+    target.header(status);
+    let duration = Metric::new(
+        "erik_duration",
+        "duration of the Erik synchronisation in seconds",
+        MetricType::Gauge
+    );
+    target.header(duration);
+    let requests = Metric::new(
+        "erik_requests",
+        "number of requests sent to the Erik relay for the host",
+        MetricType::Gauge
+    );
+    target.header(requests);
+    let fetched = Metric::new(
+        "erik_fetched_objects",
+        "number of objects fetched from the Erik relay for the host",
+        MetricType::Gauge
+    );
+    target.header(fetched);
+    let received = Metric::new(
+        "erik_received_bytes",
+        "number of bytes received from the Erik relay for the host",
+        MetricType::Gauge
+    );
+    target.header(received);
+    let failures = Metric::new(
+        "erik_consecutive_failures",
+        "number of consecutive failed Erik synchronisations",
+        MetricType::Gauge
+    );
+    target.header(failures);
+    let skipped = Metric::new(
+        "erik_skipped",
+        "whether the Erik synchronisation was skipped because of backoff",
+        MetricType::Gauge
+    );
+    target.header(skipped);
+
+    for erik in metrics {
+        target.multi(status).label("host", &erik.host).value(
+            u8::from(erik.success)
+        );
+        target.multi(duration).label("host", &erik.host).value(
+            format_args!(
+                "{}.{:03}",
+                erik.duration.as_secs(),
+                erik.duration.subsec_millis(),
+            )
+        );
+        target.multi(requests).label("host", &erik.host).value(
+            erik.requests
+        );
+        target.multi(fetched).label("host", &erik.host).value(
+            erik.fetched
+        );
+        target.multi(received).label("host", &erik.host).value(
+            erik.bytes
+        );
+        if let Some(backoff) = erik.backoff {
+            target.multi(failures).label("host", &erik.host).value(
+                backoff.failures
+            );
+            target.multi(skipped).label("host", &erik.host).value(
+                u8::from(backoff.skipped)
+            );
+        }
     }
 }
 
-async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
-    let detailed = metrics.detailed();
-    let metrics = metrics.read().await;
+fn uri_rewrite_metrics(target: &mut Target, metrics: &[UriRewriteMetrics]) {
+    if metrics.is_empty() {
+        return
+    }
+    let rewrites = Metric::new(
+        "uri_rewrites",
+        "number of URIs rewritten by the rule during the last run",
+        MetricType::Gauge
+    );
+    target.header(rewrites);
+    for rule in metrics {
+        target.multi(rewrites)
+            .label("from", &rule.from)
+            .label("to", &rule.to)
+            .value(rule.count);
+    }
+}
+
+async fn rtr_metrics(
+    target: &mut Target, shared: &SharedRtrServerMetrics, serial: Serial,
+) {
+    let detailed = shared.detailed();
+    let metrics = shared.read().await;
 
     target.single(
         Metric::new(
@@ -565,7 +1135,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
                 *count += 1
             }
         }).for_each(|(addr, count)| {
-            target.multi(item).label("addr", addr).value(count)
+            rtr_client_value(target, item, addr, shared).value(count)
         });
 
         let item = Metric::new(
@@ -584,10 +1154,60 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
         }).for_each(|(addr, count)| {
             match count {
                 Some(count) => {
-                    target.multi(item).label("addr", addr).value(count);
+                    rtr_client_value(target, item, addr, shared).value(count);
+                }
+                None => {
+                    rtr_client_value(target, item, addr, shared).value(-1);
+                }
+            }
+        });
+
+        let item = Metric::new(
+            "rtr_client_serial_lag",
+            "number of serials a client address is behind",
+            MetricType::Gauge
+        );
+        target.header(item);
+        metrics.fold_clients(None, |max, client| {
+            *max = match (*max, client.serial().map(u32::from)) {
+                (Some(left), Some(right)) => Some(later_serial(left, right)),
+                (Some(left), None) => Some(left),
+                (None, Some(right)) => Some(right),
+                (None, None) => None
+            };
+        }).for_each(|(addr, client_serial)| {
+            let lag = client_serial.and_then(|client_serial| {
+                serial_lag(client_serial.into(), serial)
+            });
+            match lag {
+                Some(lag) => {
+                    rtr_client_value(target, item, addr, shared).value(lag);
+                }
+                None => {
+                    rtr_client_value(target, item, addr, shared).value(-1);
+                }
+            }
+        });
+
+        let item = Metric::new(
+            "rtr_client_version",
+            "RTR protocol version used by a client address",
+            MetricType::Gauge
+        );
+        target.header(item);
+        metrics.fold_clients(None, |version, client| {
+            if let Some(client) = client.version() {
+                *version = Some(client)
+            }
+        }).for_each(|(addr, version)| {
+            match version {
+                Some(version) => {
+                    rtr_client_value(target, item, addr, shared).value(
+                        version
+                    );
                 }
                 None => {
-                    target.multi(item).label("addr", addr).value(-1);
+                    rtr_client_value(target, item, addr, shared).value(-1);
                 }
             }
         });
@@ -609,7 +1229,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
             match update {
                 Some(update) => {
                     let duration = Utc::now() - update;
-                    target.multi(item).label("addr", addr).value(
+                    rtr_client_value(target, item, addr, shared).value(
                         format_args!(
                             "{}.{:03}",
                             duration.num_seconds(),
@@ -618,7 +1238,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
                     );
                 }
                 None => {
-                    target.multi(item).label("addr", addr).value(-1)
+                    rtr_client_value(target, item, addr, shared).value(-1)
                 }
             }
         });
@@ -640,7 +1260,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
             match update {
                 Some(update) => {
                     let duration = Utc::now() - update;
-                    target.multi(item).label("addr", addr).value(
+                    rtr_client_value(target, item, addr, shared).value(
                         format_args!(
                             "{}.{:03}",
                             duration.num_seconds(),
@@ -649,11 +1269,54 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
                     );
                 }
                 None => {
-                    target.multi(item).label("addr", addr).value(-1)
+                    rtr_client_value(target, item, addr, shared).value(-1)
                 }
             }
         });
 
+        let item = Metric::new(
+            "rtr_client_last_serial_query_seconds",
+            "seconds since last serial query by a client address",
+            MetricType::Gauge
+        );
+        target.header(item);
+        metrics.fold_clients(None, |query, client| {
+            *query = match (*query, client.last_serial_query()) {
+                (Some(left), Some(right)) => Some(cmp::max(left, right)),
+                (Some(left), None) => Some(left),
+                (None, Some(right)) => Some(right),
+                (None, None) => None
+            };
+        }).for_each(|(addr, query)| {
+            match query {
+                Some(query) => {
+                    let duration = Utc::now() - query;
+                    rtr_client_value(target, item, addr, shared).value(
+                        format_args!(
+                            "{}.{:03}",
+                            duration.num_seconds(),
+                            duration.num_milliseconds() % 1000,
+                        )
+                    );
+                }
+                None => {
+                    rtr_client_value(target, item, addr, shared).value(-1)
+                }
+            }
+        });
+
+        let item = Metric::new(
+            "rtr_client_cache_resets",
+            "number of cache resets sent to a client address",
+            MetricType::Counter,
+        );
+        target.header(item);
+        metrics.fold_clients(0, |count, client| {
+            *count += client.cache_resets();
+        }).for_each(|(addr, count)| {
+            rtr_client_value(target, item, addr, shared).value(count)
+        });
+
         let item = Metric::new(
             "rtr_client_reset_queries",
             "number of of reset queries by a client address",
@@ -663,7 +1326,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
         metrics.fold_clients(0, |count, client| {
             *count += client.reset_queries();
         }).for_each(|(addr, count)| {
-            target.multi(item).label("addr", addr).value(count)
+            rtr_client_value(target, item, addr, shared).value(count)
         });
 
         let item = Metric::new(
@@ -675,7 +1338,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
         metrics.fold_clients(0, |count, client| {
             *count += client.serial_queries();
         }).for_each(|(addr, count)| {
-            target.multi(item).label("addr", addr).value(count)
+            rtr_client_value(target, item, addr, shared).value(count)
         });
 
         let item = Metric::new(
@@ -687,7 +1350,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
         metrics.fold_clients(0, |count, client| {
             *count += client.reset_queries();
         }).for_each(|(addr, count)| {
-            target.multi(item).label("addr", addr).value(count)
+            rtr_client_value(target, item, addr, shared).value(count)
         });
 
         let item = Metric::new(
@@ -699,7 +1362,7 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
         metrics.fold_clients(0, |count, client| {
             *count += client.bytes_read();
         }).for_each(|(addr, count)| {
-            target.multi(item).label("addr", addr).value(count)
+            rtr_client_value(target, item, addr, shared).value(count)
         });
 
         let item = Metric::new(
@@ -711,11 +1374,28 @@ async fn rtr_metrics(target: &mut Target, metrics: &SharedRtrServerMetrics) {
         metrics.fold_clients(0, |count, client| {
             *count += client.bytes_written();
         }).for_each(|(addr, count)| {
-            target.multi(item).label("addr", addr).value(count)
+            rtr_client_value(target, item, addr, shared).value(count)
         });
     }
 }
 
+/// Starts the value of a per-client RTR metric.
+///
+/// Adds the address label and, if configured, the name label for the
+/// client.
+fn rtr_client_value<'a>(
+    target: &'a mut Target,
+    item: Metric,
+    addr: IpAddr,
+    shared: &SharedRtrServerMetrics,
+) -> LabelValue<'a> {
+    let res = target.multi(item).label("addr", addr);
+    match shared.client_label(addr) {
+        Some(name) => res.label("name", name),
+        None => res
+    }
+}
+
 fn http_metrics(target: &mut Target, metrics: &HttpServerMetrics) {
     target.single(
         Metric::new(
@@ -955,8 +1635,8 @@ impl<'a> LabelValue<'a> {
 enum MetricType {
     Counter,
     Gauge,
-    /* Not currently used:
     Histogram,
+    /* Not currently used:
     Summary,
     */
 }
@@ -967,8 +1647,8 @@ impl fmt::Display for MetricType {
             match *self {
                 MetricType::Counter => "counter",
                 MetricType::Gauge => "gauge",
-                /*
                 MetricType::Histogram => "histogram",
+                /*
                 MetricType::Summary => "summary",
                 */
             }
@@ -19,10 +19,16 @@ mod request;
 mod response;
 
 // Finally, these modules actually handle requests.
+mod contacts;
 mod delta;
+mod expiring;
+mod forecast;
 mod log;
 mod metrics;
+mod objects;
 mod payload;
+mod publish;
+mod rsc;
 mod status;
 mod ui;
 mod validity;
@@ -0,0 +1,52 @@
+//! Handling of the endpoint for the object report.
+
+use crate::payload::SharedHistory;
+use super::request::Request;
+use super::response::{ContentType, Response, ResponseBuilder};
+
+
+//------------ handle_get_or_head --------------------------------------------
+
+pub fn handle_get_or_head(
+    req: &Request,
+    history: &SharedHistory,
+) -> Option<Response> {
+    if req.uri().path() != "/api/v1/objects" {
+        return None
+    }
+    let csv = match req.uri().query() {
+        None | Some("format=json") => false,
+        Some("format=csv") => true,
+        _ => return Some(Response::bad_request())
+    };
+    let objects = {
+        let history = history.read();
+        match history.objects() {
+            Some(objects) => objects,
+            None => {
+                // Either we haven’t finished the first run yet or the
+                // report is disabled.
+                if history.current().is_none() {
+                    return Some(Response::initial_validation())
+                }
+                else {
+                    return Some(Response::not_found())
+                }
+            }
+        }
+    };
+    let res = ResponseBuilder::ok().content_type(
+        if csv { ContentType::CSV } else { ContentType::JSON }
+    );
+    if req.is_head() {
+        return Some(res.empty())
+    }
+    if csv {
+        let mut body = Vec::new();
+        objects.write_csv(&mut body).expect("writing to vec");
+        Some(res.body(body))
+    }
+    else {
+        Some(res.body(objects.to_json()))
+    }
+}
@@ -0,0 +1,44 @@
+//! Handles endpoints for the re-published RRDP data.
+
+use crate::payload::SharedHistory;
+use super::request::Request;
+use super::response::{ContentType, Response, ResponseBuilder};
+
+
+//------------ handle_get_or_head --------------------------------------------
+
+pub fn handle_get_or_head(
+    req: &Request,
+    history: &SharedHistory,
+) -> Option<Response> {
+    let path = req.uri().path().strip_prefix("/rrdp/")?;
+    let publication = {
+        let history = history.read();
+        match history.rrdp_publication() {
+            Some(publication) => publication,
+            None => {
+                // Either we haven’t finished the first run yet or
+                // re-publishing is disabled.
+                if history.current().is_none() {
+                    return Some(Response::initial_validation())
+                }
+                else {
+                    return Some(Response::not_found())
+                }
+            }
+        }
+    };
+    let file = match publication.get(path) {
+        Some(file) => file,
+        None => return Some(Response::not_found())
+    };
+    let res = ResponseBuilder::ok().content_type(
+        ContentType::external(file.content_type().as_bytes())
+    );
+    if req.is_head() {
+        Some(res.empty())
+    }
+    else {
+        Some(res.body(file.data().clone()))
+    }
+}
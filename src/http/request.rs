@@ -1,5 +1,7 @@
 //! Request handling.
 
+use bytes::Bytes;
+use http_body_util::{BodyExt, Limited};
 use hyper::{Method, Uri};
 use hyper::header::HeaderMap;
 
@@ -17,6 +19,11 @@ impl Request {
             || self.hyper.method() == Method::HEAD
     }
 
+    /// Returns whether the method is POST.
+    pub fn is_post(&self) -> bool {
+        self.hyper.method() == Method::POST
+    }
+
     /// Returns whether the method is HEAD.
     pub fn is_head(&self) -> bool {
         self.hyper.method() == Method::HEAD
@@ -31,6 +38,16 @@ impl Request {
     pub fn headers(&self) -> &HeaderMap {
         self.hyper.headers()
     }
+
+    /// Reads the body of the request.
+    ///
+    /// Returns `None` if reading fails or the body is longer than `limit`
+    /// octets.
+    pub async fn into_body(self, limit: usize) -> Option<Bytes> {
+        Limited::new(
+            self.hyper.into_body(), limit
+        ).collect().await.ok().map(|body| body.to_bytes())
+    }
 }
 
 
@@ -0,0 +1,183 @@
+//! Handling of endpoints related to RPKI Signed Checklists.
+//!
+//! The endpoint `/api/v1/rsc` accepts a signed checklist as the body of a
+//! POST request. It validates the checklist and returns the outcome as a
+//! JSON object. Digests of files can be given via repeated `digest` query
+//! parameters in hex encoding and will be checked against the checklist.
+//!
+//! The issuer of the checklist is looked up in the CAs accepted by the
+//! most recent validation run. If it isn’t among them, the checklist is
+//! reported as invalid right away. Otherwise, only the publication points
+//! on the path to the issuer are validated again from the stored data in
+//! order to check the checklist’s EE certificate against the issuer’s
+//! CRL. Only one such validation happens at a time.
+
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use crate::config::Config;
+use crate::engine::Engine;
+use crate::payload::{CaEntry, SharedHistory};
+use crate::rsc::{Checked, Rsc, ValidationReport};
+use super::request::Request;
+use super::response::{ContentType, Response, ResponseBuilder};
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The maximum size of a signed checklist we accept.
+const MAX_RSC_SIZE: usize = 1_000_000;
+
+
+//------------ State ---------------------------------------------------------
+
+pub struct State {
+    /// The configuration to create the validation engine from.
+    config: Arc<Config>,
+
+    /// The validation engine working on the stored data.
+    ///
+    /// The engine is created upon the first request that needs it. The
+    /// lock also ensures that only one validation happens at a time.
+    engine: Mutex<Option<Arc<Engine>>>,
+}
+
+impl State {
+    pub fn new(config: &Config) -> Self {
+        State {
+            config: Arc::new(config.clone()),
+            engine: Mutex::new(None),
+        }
+    }
+
+    pub async fn handle_post(
+        &self, req: Request, history: &SharedHistory,
+    ) -> Response {
+        let digests = match parse_digests(req.uri().query()) {
+            Some(digests) => digests,
+            None => return Response::bad_request()
+        };
+        let data = match req.into_body(MAX_RSC_SIZE).await {
+            Some(data) => data,
+            None => return Response::bad_request()
+        };
+        let rsc = match Rsc::decode(data, self.config.strict) {
+            Ok(rsc) => rsc,
+            Err(_) => return Response::bad_request()
+        };
+
+        // Find the path to the issuing CA in the last validation run.
+        let path = {
+            let current = match history.read().current() {
+                Some(current) => current,
+                None => return Response::initial_validation()
+            };
+            rsc.cert().authority_key_identifier().and_then(|key| {
+                current.cas().path(key)
+            }).map(|path| {
+                path.into_iter().cloned().collect::<Vec<_>>()
+            })
+        };
+        let path = match path {
+            Some(path) => path,
+            None => return checked(&rsc, None, digests),
+        };
+
+        let mut engine = self.engine.lock().await;
+        let engine = match engine.as_ref() {
+            Some(engine) => engine.clone(),
+            None => {
+                let new = match Engine::new(&self.config, false) {
+                    Ok(new) => Arc::new(new),
+                    Err(_) => {
+                        return ResponseBuilder::service_unavailable().empty()
+                    }
+                };
+                engine.insert(new).clone()
+            }
+        };
+        let strict = self.config.strict;
+        tokio::task::spawn_blocking(move || {
+            check(&engine, rsc, strict, path, digests)
+        }).await.unwrap_or_else(|_| {
+            ResponseBuilder::service_unavailable().empty()
+        })
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Validates the checklist along the given path and checks the digests.
+fn check(
+    engine: &Engine, rsc: Rsc, strict: bool, path: Vec<CaEntry>,
+    digests: Vec<(String, Vec<u8>)>
+) -> Response {
+    let report = ValidationReport::new(&rsc, strict).with_path(path);
+    if report.process(engine).is_err() {
+        return ResponseBuilder::service_unavailable().empty()
+    }
+    checked(&rsc, report.finalize().ok(), digests)
+}
+
+/// Checks the digests and produces the response.
+fn checked(
+    rsc: &Rsc, chain: Option<Vec<String>>,
+    digests: Vec<(String, Vec<u8>)>
+) -> Response {
+    let mut checked = Checked::new(rsc, chain);
+    for (name, digest) in digests {
+        checked.add_digest(name, &digest);
+    }
+    ResponseBuilder::ok().content_type(ContentType::JSON).body(
+        checked.to_json()
+    )
+}
+
+/// Parses the digests given in the query.
+///
+/// Returns `None` if the query contains anything else.
+fn parse_digests(query: Option<&str>) -> Option<Vec<(String, Vec<u8>)>> {
+    let query = match query {
+        Some(query) => query,
+        None => return Some(Vec::new())
+    };
+    form_urlencoded::parse(query.as_bytes()).map(|(key, value)| {
+        if key != "digest" {
+            return None
+        }
+        parse_hex(&value).map(|digest| (value.into_owned(), digest))
+    }).collect()
+}
+
+/// Parses a hex-encoded octet sequence.
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None
+    }
+    (0..s.len()).step_by(2).map(|idx| {
+        u8::from_str_radix(s.get(idx..idx + 2)?, 16).ok()
+    }).collect()
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn digests() {
+        assert_eq!(parse_digests(None), Some(Vec::new()));
+        assert_eq!(
+            parse_digests(Some("digest=00ff&digest=A0")),
+            Some(vec![
+                (String::from("00ff"), vec![0x00, 0xff]),
+                (String::from("A0"), vec![0xa0]),
+            ])
+        );
+        assert_eq!(parse_digests(Some("digest=0")), None);
+        assert_eq!(parse_digests(Some("digest=zz")), None);
+        assert_eq!(parse_digests(Some("foo=00")), None);
+    }
+}
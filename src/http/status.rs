@@ -3,9 +3,11 @@
 use std::cmp;
 use chrono::{DateTime, Duration, Utc};
 use clap::{crate_name, crate_version};
+use crate::config::ValidationPolicy;
 use crate::metrics::{
-    HttpServerMetrics, PayloadMetrics, PublicationMetrics,
-    RtrClientMetrics, SharedRtrServerMetrics, VrpMetrics,
+    BackoffMetrics, HttpServerMetrics, PayloadMetrics, PublicationMetrics,
+    RrdpHttpMetrics, RtrClientMetrics, SharedRtrServerMetrics, VrpMetrics,
+    later_serial, serial_lag,
 };
 use crate::payload::SharedHistory;
 use crate::utils::fmt::WriteOrPanic;
@@ -192,6 +194,14 @@ async fn handle_status(
         res, "stale-count: {}", metrics.publication.stale_objects()
     );
 
+    // repository-limits-exceeded
+    writeln!(res, "repository-limits-exceeded:");
+    for repo in &metrics.repositories {
+        if let Some(limit) = repo.limit_exceeded {
+            writeln!(res, "   {}: {}", repo.uri, limit);
+        }
+    }
+
     // rsync_status
     writeln!(res, "rsync-durations:");
     for metrics in &metrics.rsync {
@@ -204,6 +214,7 @@ async fn handle_status(
                 Err(_) => -1
             }
         );
+        write!(res, ", cause={}", metrics.cause);
         if let Ok(duration) = metrics.duration {
             writeln!(
                 res,
@@ -245,6 +256,7 @@ async fn handle_status(
     }
 
     let detailed_rtr = rtr_metrics.detailed();
+    let rtr_labels = rtr_metrics;
     let rtr_metrics = rtr_metrics.read().await;
 
     // rtr
@@ -264,12 +276,26 @@ async fn handle_status(
         rtr_metrics.fold_clients(
             RtrClientStatus::default(), RtrClientStatus::fold
         ).for_each(|(addr, data)| {
-            write!(res, "    {}: connections={}, ", addr, data.connections);
-            if let Some(serial) = data.serial {
-                write!(res, "serial={}, ", serial);
+            write!(res, "    {}: ", addr);
+            if let Some(name) = rtr_labels.client_label(addr) {
+                write!(res, "name={}, ", name);
+            }
+            write!(res, "connections={}, ", data.connections);
+            if let Some(client_serial) = data.serial {
+                write!(res, "serial={}, ", client_serial);
+                match serial_lag(client_serial.into(), serial) {
+                    Some(lag) => write!(res, "serial-lag={}, ", lag),
+                    None => write!(res, "serial-lag=N/A, "),
+                }
+            }
+            else {
+                write!(res, "serial=N/A, serial-lag=N/A, ");
+            }
+            if let Some(version) = data.version {
+                write!(res, "version={}, ", version);
             }
             else {
-                write!(res, "serial=N/A, ");
+                write!(res, "version=N/A, ");
             }
             if let Some(update) = data.updated {
                 let update = Utc::now() - update;
@@ -293,9 +319,21 @@ async fn handle_status(
             else {
                 write!(res, "last-reset=N/A, ");
             }
+            if let Some(query) = data.last_serial_query {
+                let query = Utc::now() - query;
+                write!(
+                    res,
+                    "last-serial-query-ago={}.{:03}s, ",
+                    query.num_seconds(), query.num_milliseconds() % 1000
+                );
+            }
+            else {
+                write!(res, "last-serial-query=N/A, ");
+            }
             writeln!(res,
-                "reset-queries={}, serial-queries={}, read={}, written={}",
-                data.reset_queries, data.serial_queries,
+                "reset-queries={}, serial-queries={}, cache-resets={}, \
+                 read={}, written={}",
+                data.reset_queries, data.serial_queries, data.cache_resets,
                 data.bytes_read, data.bytes_written,
             );
         });
@@ -349,6 +387,7 @@ async fn handle_api_status(
 
     let now = Utc::now();
     let detailed_rtr = rtr_metrics.detailed();
+    let rtr_labels = rtr_metrics;
     let rtr_metrics = rtr_metrics.read().await;
 
     let res = JsonBuilder::build(|target| {
@@ -382,6 +421,7 @@ async fn handle_api_status(
                     json_publication_metrics(
                         target, &tal.publication
                     );
+                    json_validation_policy(target, &tal.policy);
                 });
             }
         });
@@ -402,6 +442,12 @@ async fn handle_api_status(
                     json_publication_metrics(
                         target, &repo.publication
                     );
+                    match repo.limit_exceeded {
+                        Some(limit) => {
+                            target.member_str("limitExceeded", limit)
+                        }
+                        None => target.member_raw("limitExceeded", "null")
+                    }
                 })
             }
         });
@@ -420,6 +466,7 @@ async fn handle_api_status(
                             Err(_) => -1
                         }
                     );
+                    target.member_str("cause", metrics.cause);
                     match metrics.duration {
                         Ok(duration) => {
                             target.member_raw("duration",
@@ -428,6 +475,41 @@ async fn handle_api_status(
                         }
                         Err(_) => target.member_raw("duration", "null")
                     }
+                    match metrics.stats {
+                        Some(stats) => {
+                            target.member_object("stats", |target| {
+                                target.member_raw("files", stats.files);
+                                target.member_raw(
+                                    "transferred", stats.transferred
+                                );
+                                target.member_raw("deleted", stats.deleted);
+                                target.member_raw(
+                                    "bytesReceived", stats.bytes_received
+                                );
+                                target.member_raw(
+                                    "bytesSent", stats.bytes_sent
+                                );
+                            })
+                        }
+                        None => target.member_raw("stats", "null")
+                    }
+                    json_backoff_metrics(target, metrics.backoff.as_ref());
+                    json_slot_wait(target, metrics.slot_wait);
+                    target.member_array("history", |target| {
+                        for item in &metrics.history {
+                            target.array_object(|target| {
+                                target.member_str(
+                                    "time", item.time.format("%+")
+                                );
+                                target.member_str("cause", item.cause);
+                                target.member_raw("duration",
+                                    format_args!(
+                                        "{:.3}", item.duration.as_secs_f32()
+                                    )
+                                );
+                            })
+                        }
+                    });
                 })
             }
         });
@@ -449,6 +531,14 @@ async fn handle_api_status(
                             status.into_i16()
                         }).unwrap_or(0)
                     );
+                    target.member_raw("payloadBytes", metrics.payload_bytes);
+                    json_size_estimate(
+                        target, "deltaEstimate", metrics.delta_estimate
+                    );
+                    json_size_estimate(
+                        target, "snapshotEstimate", metrics.snapshot_estimate
+                    );
+                    json_rrdp_http(target, &metrics.http);
                     match metrics.duration {
                         Ok(duration) => {
                             target.member_raw("duration",
@@ -481,6 +571,44 @@ async fn handle_api_status(
                             target.member_raw("snapshot_reason", "null");
                         }
                     }
+                    json_backoff_metrics(target, metrics.backoff.as_ref());
+                    json_slot_wait(target, metrics.slot_wait);
+                })
+            }
+        });
+
+        target.member_object("rrdpHosts", |target| {
+            for metrics in &metrics.rrdp_hosts {
+                target.member_object(&metrics.host, |target| {
+                    target.member_raw("requests", metrics.requests);
+                    target.member_raw("connections", metrics.connections);
+                })
+            }
+        });
+
+        target.member_object("erik", |target| {
+            for metrics in &metrics.erik {
+                target.member_object(&metrics.host, |target| {
+                    target.member_raw("success", metrics.success);
+                    target.member_raw("duration",
+                        format_args!(
+                            "{:.3}", metrics.duration.as_secs_f32()
+                        )
+                    );
+                    target.member_raw("requests", metrics.requests);
+                    target.member_raw("fetchedObjects", metrics.fetched);
+                    target.member_raw("receivedBytes", metrics.bytes);
+                    json_backoff_metrics(target, metrics.backoff.as_ref());
+                })
+            }
+        });
+
+        target.member_array("uriRewrites", |target| {
+            for metrics in &metrics.uri_rewrites {
+                target.array_object(|target| {
+                    target.member_str("from", &metrics.from);
+                    target.member_str("to", &metrics.to);
+                    target.member_raw("count", metrics.count);
                 })
             }
         });
@@ -504,14 +632,42 @@ async fn handle_api_status(
                    ).for_each(
                         |(addr, data)| {
                             target.member_object(addr, |target| {
+                                if let Some(name) = rtr_labels.client_label(
+                                    addr
+                                ) {
+                                    target.member_str("name", name);
+                                }
                                 target.member_raw(
                                     "connections", data.connections
                                 );
-                                if let Some(serial) = data.serial {
-                                    target.member_raw("serial", serial);
+                                if let Some(client_serial) = data.serial {
+                                    target.member_raw(
+                                        "serial", client_serial
+                                    );
+                                    match serial_lag(
+                                        client_serial.into(), serial
+                                    ) {
+                                        Some(lag) => {
+                                            target.member_raw(
+                                                "serialLag", lag
+                                            );
+                                        }
+                                        None => {
+                                            target.member_raw(
+                                                "serialLag", "null"
+                                            );
+                                        }
+                                    }
                                 }
                                 else {
                                     target.member_raw("serial", "null");
+                                    target.member_raw("serialLag", "null");
+                                }
+                                if let Some(version) = data.version {
+                                    target.member_raw("version", version);
+                                }
+                                else {
+                                    target.member_raw("version", "null");
                                 }
                                 if let Some(update) = data.updated {
                                     target.member_str(
@@ -531,12 +687,26 @@ async fn handle_api_status(
                                 else {
                                     target.member_raw("lastReset", "null");
                                 }
+                                if let Some(query) = data.last_serial_query {
+                                    target.member_str(
+                                        "lastSerialQuery",
+                                        query.format("%+")
+                                    );
+                                }
+                                else {
+                                    target.member_raw(
+                                        "lastSerialQuery", "null"
+                                    );
+                                }
                                 target.member_raw(
                                     "resetQueries", data.reset_queries
                                 );
                                 target.member_raw(
                                     "serialQueries", data.serial_queries
                                 );
+                                target.member_raw(
+                                    "cacheResets", data.cache_resets
+                                );
                                 target.member_raw(
                                     "read", data.bytes_read
                                 );
@@ -574,16 +744,36 @@ async fn handle_api_status(
     ResponseBuilder::ok().content_type(ContentType::JSON).body(res)
 }
 
+fn json_validation_policy(
+    target: &mut JsonBuilder, policy: &ValidationPolicy
+) {
+    target.member_object("policy", |target| {
+        target.member_raw("strict", policy.strict);
+        target.member_str("stale", policy.stale);
+        target.member_str("unsafeVrps", policy.unsafe_vrps);
+        target.member_str("unknownObjects", policy.unknown_objects);
+        target.member_str("manifestReplay", policy.manifest_replay);
+        target.member_raw("maxCaDepth", policy.max_ca_depth);
+        target.member_raw("disableRsync", policy.disable_rsync);
+        target.member_raw("disableRrdp", policy.disable_rrdp);
+    })
+}
+
 fn json_publication_metrics(
     target: &mut JsonBuilder, metrics: &PublicationMetrics
 ) {
     target.member_raw("validPublicationPoints", metrics.valid_points);
     target.member_raw("rejectedPublicationPoints", metrics.rejected_points);
+    target.member_raw("cachedPublicationPoints", metrics.cached_points);
+    target.member_raw(
+        "cacheTimeSaved", metrics.cache_time_saved.as_secs_f64()
+    );
     target.member_raw("validManifests", metrics.valid_manifests);
     target.member_raw("invalidManifests", metrics.invalid_manifests);
     target.member_raw("prematureManifests", metrics.premature_manifests);
     target.member_raw("staleManifests", metrics.stale_manifests);
     target.member_raw("missingManifests", metrics.missing_manifests);
+    target.member_raw("replayedManifests", metrics.replayed_manifests);
     target.member_raw("validCRLs", metrics.valid_crls);
     target.member_raw("invalidCRLs", metrics.invalid_crls);
     target.member_raw("staleCRLs", metrics.stale_crls);
@@ -606,6 +796,79 @@ fn json_publication_metrics(
     target.member_raw("otherObjects", metrics.others);
 }
 
+fn json_backoff_metrics(
+    target: &mut JsonBuilder, metrics: Option<&BackoffMetrics>
+) {
+    let metrics = match metrics {
+        Some(metrics) => metrics,
+        None => {
+            target.member_raw("backoff", "null");
+            return
+        }
+    };
+    target.member_object("backoff", |target| {
+        target.member_raw("skipped", metrics.skipped);
+        target.member_raw("failures", metrics.failures);
+        for (key, value) in [
+            ("lastSuccess", metrics.last_success),
+            ("lastFailure", metrics.last_failure),
+            ("nextAttempt", metrics.next_attempt),
+        ] {
+            match value {
+                Some(value) => target.member_str(key, value.format("%+")),
+                None => target.member_raw(key, "null"),
+            }
+        }
+    })
+}
+
+fn json_size_estimate(
+    target: &mut JsonBuilder, key: &str, estimate: Option<u64>
+) {
+    match estimate {
+        Some(estimate) => target.member_raw(key, estimate),
+        None => target.member_raw(key, "null")
+    }
+}
+
+fn json_rrdp_http(target: &mut JsonBuilder, http: &RrdpHttpMetrics) {
+    target.member_object("http", |target| {
+        target.member_raw("requests", http.requests);
+        target.member_raw("connections", http.connections);
+        for (key, value) in [
+            ("dns", http.dns),
+            ("connect", http.connect),
+            ("ttfb", http.ttfb),
+            ("transfer", http.transfer),
+        ] {
+            target.member_raw(
+                key, format_args!("{:.3}", value.as_secs_f32())
+            );
+        }
+        target.member_raw("receivedBytes", http.bytes);
+        target.member_raw("redirects", http.redirects);
+        target.member_raw("notModified", http.not_modified);
+        target.member_object("errors", |target| {
+            for (kind, value) in http.errors.iter() {
+                target.member_raw(kind, value);
+            }
+        });
+    })
+}
+
+fn json_slot_wait(
+    target: &mut JsonBuilder, wait: Option<std::time::Duration>
+) {
+    match wait {
+        Some(wait) => {
+            target.member_raw(
+                "slotWait", format_args!("{:.3}", wait.as_secs_f32())
+            );
+        }
+        None => target.member_raw("slotWait", "null")
+    }
+}
+
 fn json_compat_payload_metrics(
     target: &mut JsonBuilder, payload: &PayloadMetrics
 ) {
@@ -676,8 +939,11 @@ struct RtrClientStatus {
     serial: Option<u32>,
     updated: Option<DateTime<Utc>>,
     last_reset: Option<DateTime<Utc>>,
+    last_serial_query: Option<DateTime<Utc>>,
+    version: Option<u8>,
     reset_queries: u32,
     serial_queries: u32,
+    cache_resets: u32,
     bytes_read: u64,
     bytes_written: u64,
 }
@@ -690,7 +956,7 @@ impl RtrClientStatus {
         self.serial = match (
             self.serial, client.serial().map(u32::from)
         ) {
-            (Some(left), Some(right)) => Some(cmp::max(left, right)),
+            (Some(left), Some(right)) => Some(later_serial(left, right)),
             (Some(left), None) => Some(left),
             (None, Some(right)) => Some(right),
             (None, None) => None
@@ -709,8 +975,20 @@ impl RtrClientStatus {
             (None, Some(right)) => Some(right),
             (None, None) => None
         };
+        self.last_serial_query = match
+            (self.last_serial_query, client.last_serial_query())
+        {
+            (Some(left), Some(right)) => Some(cmp::max(left, right)),
+            (Some(left), None) => Some(left),
+            (None, Some(right)) => Some(right),
+            (None, None) => None
+        };
+        if let Some(version) = client.version() {
+            self.version = Some(version)
+        }
         self.reset_queries += client.reset_queries();
         self.serial_queries += client.serial_queries();
+        self.cache_resets += client.cache_resets();
         self.bytes_read += client.bytes_read();
         self.bytes_written += client.bytes_written();
     }
@@ -22,12 +22,13 @@
 //! * [engine], which performs a validation run using both collector and
 //!   store.
 //!
-//! The second part currently comes in two flavours:
+//! The second part currently comes in three flavours:
 //!
 //! * [payload], which collects and processes data for distribution to
-//!   routers or local use, and
+//!   routers or local use,
 //! * [rta], which processes Resource Tagged Authorizations (i.e., objects
-//!   signed by resource holders).
+//!   signed by resource holders), and
+//! * [rsc], which processes RPKI Signed Checklists.
 //!
 //! Additional modules can be added in the future.
 //!
@@ -35,6 +36,10 @@
 //! purposes:
 //!
 //! * [output] allows formatting data  in different formats,
+//! * [report] lists the validation outcome of every object,
+//! * [contacts] looks up the contact information published by CAs,
+//! * [expiry] summarizes which objects are about to expire,
+//! * [forecast] predicts which VRPs will disappear if nothing is re-signed,
 //! * [http] provides an HTTP server with multiple endpoints for all sorts
 //!   of purposes,
 //! * [rtr] provides an RTR server which allows routers to synchronize their
@@ -55,14 +60,20 @@ pub use reqwest;
 
 pub mod collector;
 pub mod config;
+pub mod contacts;
 pub mod engine;
 pub mod error;
+pub mod expiry;
+pub mod forecast;
 pub mod http;
 pub mod metrics;
 pub mod operation;
 pub mod output;
 pub mod payload;
 pub mod process;
+pub mod publish;
+pub mod report;
+pub mod rsc;
 pub mod rtr;
 pub mod rta;
 pub mod slurm;
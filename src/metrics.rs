@@ -6,10 +6,13 @@
 //! types contain the metrics related to specific processed entities.
 
 use std::{cmp, io, ops, process, slice};
+use std::collections::HashMap;
 use std::iter::Peekable;
 use std::net::IpAddr;
 use std::sync::{Arc};
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicI64, AtomicU64, Ordering};
+use std::sync::atomic::{
+    AtomicBool, AtomicU8, AtomicU32, AtomicI64, AtomicU64, Ordering
+};
 use std::time::{Duration, SystemTimeError};
 use chrono::{DateTime, TimeZone, Utc};
 use rpki::uri;
@@ -17,7 +20,9 @@ use rpki::repository::tal::TalInfo;
 use rpki::rtr::state::Serial;
 use tokio::sync::Mutex;
 use uuid::Uuid;
-use crate::collector::{HttpStatus, SnapshotReason};
+use crate::collector::{HttpStatus, RsyncCause, SnapshotReason};
+use crate::config::ValidationPolicy;
+use crate::engine::RepositoryLimit;
 
 
 //------------ Metrics -------------------------------------------------------
@@ -34,6 +39,15 @@ pub struct Metrics {
     /// RRDP metrics.
     pub rrdp: Vec<RrdpRepositoryMetrics>,
 
+    /// RRDP connection metrics per host.
+    pub rrdp_hosts: Vec<RrdpHostMetrics>,
+
+    /// Erik metrics per repository host.
+    pub erik: Vec<ErikHostMetrics>,
+
+    /// Metrics for the rules of the URI rewrite table.
+    pub uri_rewrites: Vec<UriRewriteMetrics>,
+
     /// Per-TAL metrics.
     pub tals: Vec<TalMetrics>,
 
@@ -57,6 +71,9 @@ impl Metrics {
             time: Utc::now(),
             rsync: Vec::new(),
             rrdp: Vec::new(),
+            rrdp_hosts: Vec::new(),
+            erik: Vec::new(),
+            uri_rewrites: Vec::new(),
             tals: Vec::new(),
             repositories: Vec::new(),
             publication: Default::default(),
@@ -138,8 +155,36 @@ pub struct RrdpRepositoryMetrics {
     /// repository was up-to-date.
     pub payload_status: Option<HttpStatus>,
 
+    /// The number of bytes received for snapshot and delta files.
+    pub payload_bytes: u64,
+
+    /// The metrics of the HTTP requests made for the update.
+    pub http: RrdpHttpMetrics,
+
+    /// The estimated number of bytes for fetching the necessary deltas.
+    ///
+    /// This is `None` if no estimate was made because the repository
+    /// was up-to-date or the sizes of earlier deltas are not known.
+    pub delta_estimate: Option<u64>,
+
+    /// The estimated number of bytes for fetching the snapshot.
+    ///
+    /// This is `None` if no estimate was made.
+    pub snapshot_estimate: Option<u64>,
+
     /// The duration of the last update.
     pub duration: Result<Duration, SystemTimeError>,
+
+    /// The backoff state of the repository.
+    ///
+    /// This is `None` if the update wasn’t attempted at all.
+    pub backoff: Option<BackoffMetrics>,
+
+    /// The time spent waiting for an update slot for the host.
+    ///
+    /// This is `None` if the number of slots isn’t limited or no update
+    /// was attempted.
+    pub slot_wait: Option<Duration>,
 }
 
 impl RrdpRepositoryMetrics {
@@ -151,7 +196,13 @@ impl RrdpRepositoryMetrics {
             serial: None,
             snapshot_reason: None,
             payload_status: None,
-            duration: Ok(Duration::from_secs(0))
+            payload_bytes: 0,
+            http: Default::default(),
+            delta_estimate: None,
+            snapshot_estimate: None,
+            duration: Ok(Duration::from_secs(0)),
+            backoff: None,
+            slot_wait: None,
         }
     }
 
@@ -171,6 +222,141 @@ impl RrdpRepositoryMetrics {
 }
 
 
+//------------ RrdpHttpMetrics -----------------------------------------------
+
+/// Metrics about the HTTP requests made for an RRDP repository.
+///
+/// The durations are the sums over all requests, including those that
+/// failed. The phases of establishing a connection are only included for
+/// requests that needed a new connection. Redirects are followed as part
+/// of a request.
+#[derive(Clone, Debug, Default)]
+pub struct RrdpHttpMetrics {
+    /// The number of requests sent, not counting redirects.
+    pub requests: u64,
+
+    /// The number of new connections opened.
+    pub connections: u64,
+
+    /// The time spent resolving host names.
+    pub dns: Duration,
+
+    /// The time spent establishing connections.
+    ///
+    /// This includes both the TCP connect and the TLS handshake.
+    pub connect: Duration,
+
+    /// The time spent waiting for the response headers.
+    ///
+    /// For requests that needed a new connection, this is the time after
+    /// the connection was established.
+    pub ttfb: Duration,
+
+    /// The time spent receiving response bodies.
+    pub transfer: Duration,
+
+    /// The number of bytes received in response bodies.
+    pub bytes: u64,
+
+    /// The number of redirects followed.
+    pub redirects: u64,
+
+    /// The number of conditional requests answered with “not modified.”
+    ///
+    /// Each of these saved downloading a file again.
+    pub not_modified: u64,
+
+    /// The number of failed requests by the cause of failure.
+    pub errors: RrdpHttpErrors,
+}
+
+impl RrdpHttpMetrics {
+    /// Adds the values of `other` to `self`.
+    pub fn add(&mut self, other: &Self) {
+        self.requests += other.requests;
+        self.connections += other.connections;
+        self.dns += other.dns;
+        self.connect += other.connect;
+        self.ttfb += other.ttfb;
+        self.transfer += other.transfer;
+        self.bytes += other.bytes;
+        self.redirects += other.redirects;
+        self.not_modified += other.not_modified;
+        self.errors.add(&other.errors);
+    }
+}
+
+
+//------------ RrdpHttpErrors ------------------------------------------------
+
+/// The number of failed HTTP requests by the cause of failure.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RrdpHttpErrors {
+    /// The request or reading the response timed out.
+    pub timeout: u64,
+
+    /// Resolving the host name or establishing a connection failed.
+    pub connect: u64,
+
+    /// The server responded with a client or server error status.
+    pub status: u64,
+
+    /// The server redirected to a different origin or too many times.
+    pub redirect: u64,
+
+    /// Receiving the response body failed.
+    pub transfer: u64,
+
+    /// The request failed for some other reason.
+    pub other: u64,
+}
+
+impl RrdpHttpErrors {
+    /// Adds the values of `other` to `self`.
+    pub fn add(&mut self, other: &Self) {
+        self.timeout += other.timeout;
+        self.connect += other.connect;
+        self.status += other.status;
+        self.redirect += other.redirect;
+        self.transfer += other.transfer;
+        self.other += other.other;
+    }
+
+    /// Returns the number of failed requests for each cause.
+    ///
+    /// The cause is given by the name used in metrics output.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> {
+        [
+            ("timeout", self.timeout),
+            ("connect", self.connect),
+            ("status", self.status),
+            ("redirect", self.redirect),
+            ("transfer", self.transfer),
+            ("other", self.other),
+        ].into_iter()
+    }
+}
+
+
+//------------ RrdpHostMetrics -----------------------------------------------
+
+/// Metrics about the HTTP connections to a single RRDP host.
+#[derive(Clone, Debug)]
+pub struct RrdpHostMetrics {
+    /// The host name and port if not the default.
+    pub host: String,
+
+    /// The number of requests sent to the host.
+    pub requests: u64,
+
+    /// The number of connections opened to the host.
+    ///
+    /// The difference to `requests` is the number of requests that reused
+    /// an already open connection.
+    pub connections: u64,
+}
+
+
 //------------ RsyncModuleMetrics --------------------------------------------
 
 /// Metrics collected while updating an rsync module.
@@ -178,7 +364,126 @@ impl RrdpRepositoryMetrics {
 pub struct RsyncModuleMetrics {
     pub module: uri::Rsync,
     pub status: Result<process::ExitStatus, io::Error>,
+
+    /// The named cause of the status.
+    pub cause: RsyncCause,
+
     pub duration: Result<Duration, SystemTimeError>,
+    pub stats: Option<RsyncTransferStats>,
+    pub backoff: Option<BackoffMetrics>,
+    pub slot_wait: Option<Duration>,
+
+    /// The outcomes of the most recent updates of the module.
+    ///
+    /// The list is ordered oldest first and includes this update unless
+    /// it was skipped.
+    pub history: Vec<RsyncRunSummary>,
+}
+
+
+//------------ RsyncTransferStats --------------------------------------------
+
+/// Statistics about the transfer of an rsync module.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RsyncTransferStats {
+    /// The number of regular files in the module.
+    pub files: u64,
+
+    /// The number of files that were transferred.
+    pub transferred: u64,
+
+    /// The number of local files and directories that were deleted.
+    pub deleted: u64,
+
+    /// The number of bytes received from the server.
+    pub bytes_received: u64,
+
+    /// The number of bytes sent to the server.
+    pub bytes_sent: u64,
+}
+
+
+//------------ RsyncRunSummary -----------------------------------------------
+
+/// The outcome of an update of an rsync module kept in its history.
+#[derive(Clone, Copy, Debug)]
+pub struct RsyncRunSummary {
+    /// When the update finished.
+    pub time: DateTime<Utc>,
+
+    /// The named cause of the update’s status.
+    pub cause: RsyncCause,
+
+    /// The duration of the update.
+    pub duration: Duration,
+}
+
+
+//------------ ErikHostMetrics -----------------------------------------------
+
+/// Metrics collected while synchronising a repository host via Erik.
+#[derive(Clone, Debug)]
+pub struct ErikHostMetrics {
+    /// The repository host.
+    pub host: String,
+
+    /// Whether the synchronisation succeeded.
+    pub success: bool,
+
+    /// The duration of the synchronisation.
+    pub duration: Duration,
+
+    /// The number of requests sent to the relay.
+    pub requests: u64,
+
+    /// The number of objects that were fetched.
+    ///
+    /// This includes partitions and manifests.
+    pub fetched: u64,
+
+    /// The number of bytes received from the relay.
+    pub bytes: u64,
+
+    /// The backoff state of the host.
+    pub backoff: Option<BackoffMetrics>,
+}
+
+
+//------------ UriRewriteMetrics ---------------------------------------------
+
+/// Metrics for a single rule of the URI rewrite table.
+#[derive(Clone, Debug)]
+pub struct UriRewriteMetrics {
+    /// The URI prefix that is replaced.
+    pub from: String,
+
+    /// The replacement prefix.
+    pub to: String,
+
+    /// The number of URIs rewritten by the rule during the run.
+    pub count: u64,
+}
+
+
+//------------ BackoffMetrics ------------------------------------------------
+
+/// The backoff state of a repository after a validation run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BackoffMetrics {
+    /// Whether the update was skipped because of earlier failures.
+    pub skipped: bool,
+
+    /// The number of consecutive failed updates.
+    pub failures: u32,
+
+    /// The time of the last successful update if there was one.
+    pub last_success: Option<DateTime<Utc>>,
+
+    /// The time of the last failed update if there was one.
+    pub last_failure: Option<DateTime<Utc>>,
+
+    /// The earliest time of the next update attempt if backing off.
+    pub next_attempt: Option<DateTime<Utc>>,
 }
 
 
@@ -190,19 +495,27 @@ pub struct TalMetrics {
     /// The TAL.
     pub tal: Arc<TalInfo>,
 
+    /// The effective validation policy for the TAL.
+    pub policy: ValidationPolicy,
+
     /// Publication metrics.
     pub publication: PublicationMetrics,
 
     /// The VRP metrics.
     pub payload: PayloadMetrics,
+
+    /// The trust anchor certificate metrics.
+    pub ta_cert: TaCertMetrics,
 }
 
 impl TalMetrics {
-    pub fn new(tal: Arc<TalInfo>) -> Self {
+    pub fn new(tal: Arc<TalInfo>, policy: ValidationPolicy) -> Self {
         TalMetrics {
             tal,
+            policy,
             publication: Default::default(),
             payload: Default::default(),
+            ta_cert: Default::default(),
         }
     }
 
@@ -216,6 +529,25 @@ impl TalMetrics {
 }
 
 
+//------------ TaCertMetrics -------------------------------------------------
+
+/// Metrics about the trust anchor certificate of a TAL.
+#[derive(Clone, Debug, Default)]
+pub struct TaCertMetrics {
+    /// The SHA-256 fingerprint of the certificate in use as hex string.
+    pub fingerprint: Option<String>,
+
+    /// The time the certificate in use was accepted.
+    pub since: Option<DateTime<Utc>>,
+
+    /// Whether a new certificate was accepted during the run.
+    pub changed: bool,
+
+    /// Whether a new certificate was refused because of pinning.
+    pub refused: bool,
+}
+
+
 //------------ RepositoryMetrics ---------------------------------------------
 
 /// Metrics for all publication points in a repository.
@@ -229,6 +561,9 @@ pub struct RepositoryMetrics {
 
     /// The VRP metrics.
     pub payload: PayloadMetrics,
+
+    /// The resource limit the repository exceeded, if any.
+    pub limit_exceeded: Option<RepositoryLimit>,
 }
 
 impl RepositoryMetrics {
@@ -237,6 +572,7 @@ impl RepositoryMetrics {
             uri,
             publication: Default::default(),
             payload: Default::default(),
+            limit_exceeded: None,
         }
     }
 
@@ -257,6 +593,17 @@ pub struct PublicationMetrics {
     /// The number of rejected publication points.
     pub rejected_points: u32,
 
+    /// The number of valid publication points reused from the last run.
+    ///
+    /// These points are included in `valid_points`.
+    pub cached_points: u32,
+
+    /// The time spent validating the cached points when they were cached.
+    ///
+    /// This is the time not spent on validation in this run thanks to
+    /// reusing cached publication points.
+    pub cache_time_saved: Duration,
+
     /// The number of valid manifests.
     pub valid_manifests: u32,
 
@@ -272,6 +619,13 @@ pub struct PublicationMetrics {
     /// The number of missing manifests.
     pub missing_manifests: u32,
 
+    /// The number of replayed manifests.
+    ///
+    /// A collected manifest is replayed if its manifest number isn’t
+    /// greater or its thisUpdate isn’t later than that of the stored
+    /// manifest.
+    pub replayed_manifests: u32,
+
     /// The number of valid CRLs.
     pub valid_crls: u32,
 
@@ -339,12 +693,15 @@ impl<'a> ops::AddAssign<&'a Self> for PublicationMetrics {
     fn add_assign(&mut self, other: &'a Self) {
         self.valid_points += other.valid_points;
         self.rejected_points += other.rejected_points;
+        self.cached_points += other.cached_points;
+        self.cache_time_saved += other.cache_time_saved;
 
         self.valid_manifests += other.valid_manifests;
         self.invalid_manifests += other.invalid_manifests;
         self.premature_manifests += other.premature_manifests;
         self.stale_manifests += other.stale_manifests;
         self.missing_manifests += other.missing_manifests;
+        self.replayed_manifests += other.replayed_manifests;
         self.valid_crls += other.valid_crls;
         self.invalid_crls += other.invalid_crls;
         self.stale_crls += other.stale_crls;
@@ -567,17 +924,22 @@ pub struct SharedRtrServerMetrics {
 
     /// Do we want to publish detailed metrics?
     detailed: bool,
+
+    /// Names to use for clients with certain addresses.
+    labels: Arc<HashMap<IpAddr, String>>,
 }
 
 impl SharedRtrServerMetrics {
     /// Creates a new shareable value.
     ///
     /// If `detailed` is `true` per-client statistics should be produced when
-    /// presenting the metrics.
-    pub fn new(detailed: bool) -> Self {
+    /// presenting the metrics. The `labels` provide names for clients that
+    /// will be added to these per-client statistics.
+    pub fn new(detailed: bool, labels: HashMap<IpAddr, String>) -> Self {
         SharedRtrServerMetrics {
             metrics: Default::default(),
-            detailed
+            detailed,
+            labels: labels.into(),
         }
     }
 
@@ -595,6 +957,11 @@ impl SharedRtrServerMetrics {
         self.detailed
     }
 
+    /// Returns the configured name for a client address if there is one.
+    pub fn client_label(&self, addr: IpAddr) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
     /// Provides read access to the underlying server metrics.
     ///
     /// This method acquires the lock asynchronously.
@@ -840,6 +1207,20 @@ pub struct RtrClientMetrics {
     /// The number of successful serial queries.
     serial_queries: AtomicU32,
 
+    /// The number of Cache Reset PDUs sent to the client.
+    cache_resets: AtomicU32,
+
+    /// The time the client last sent a Serial Query PDU.
+    ///
+    /// This is an option of the unix timestamp. The value of `i64::MIN`
+    /// serves as a `None`.
+    last_serial_query: AtomicI64,
+
+    /// The RTR protocol version used with the client.
+    ///
+    /// This is an option with the value of `u8::MAX` serving as `None`.
+    version: AtomicU8,
+
     /// The number of bytes read.
     bytes_read: AtomicU64,
 
@@ -858,6 +1239,9 @@ impl RtrClientMetrics {
             last_reset: AtomicI64::new(i64::MIN),
             reset_queries: AtomicU32::new(0),
             serial_queries: AtomicU32::new(0),
+            cache_resets: AtomicU32::new(0),
+            last_serial_query: AtomicI64::new(i64::MIN),
+            version: AtomicU8::new(u8::MAX),
             bytes_read: AtomicU64::new(0),
             bytes_written: AtomicU64::new(0),
         }
@@ -942,6 +1326,54 @@ impl RtrClientMetrics {
         self.serial_queries.load(Ordering::Relaxed)
     }
 
+    /// Returns the number of Cache Reset PDUs sent to the client.
+    pub fn cache_resets(&self) -> u32 {
+        self.cache_resets.load(Ordering::Relaxed)
+    }
+
+    /// A Cache Reset PDU has been sent to the client.
+    pub fn inc_cache_resets(&self) {
+        self.cache_resets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the time the client last sent a Serial Query.
+    ///
+    /// Returns `None` if the client never sent a Serial Query.
+    pub fn last_serial_query(&self) -> Option<DateTime<Utc>> {
+        let updated = self.last_serial_query.load(Ordering::Relaxed);
+        if updated == i64::MIN {
+            None
+        }
+        else {
+            Utc.timestamp_opt(updated, 0).single()
+        }
+    }
+
+    /// The client has sent a Serial Query just now.
+    pub fn serial_query_now(&self) {
+        self.last_serial_query.store(
+            Utc::now().timestamp(), Ordering::Relaxed
+        );
+    }
+
+    /// Returns the RTR protocol version used with the client.
+    ///
+    /// Returns `None` if the version hasn’t been determined yet.
+    pub fn version(&self) -> Option<u8> {
+        let version = self.version.load(Ordering::Relaxed);
+        if version == u8::MAX {
+            None
+        }
+        else {
+            Some(version)
+        }
+    }
+
+    /// Sets the RTR protocol version used with the client.
+    pub fn set_version(&self, version: u8) {
+        self.version.store(version, Ordering::Relaxed)
+    }
+
     /// A successful update with the given serial number has finished now.
     ///
     /// Updates the serial number and update time accordingly.
@@ -998,6 +1430,25 @@ impl RtrClientMetrics {
                 self.serial_queries.load(Ordering::Relaxed)
                 + other.serial_queries.load(Ordering::Relaxed)
             ),
+            cache_resets: AtomicU32::new(
+                self.cache_resets.load(Ordering::Relaxed)
+                + other.cache_resets.load(Ordering::Relaxed)
+            ),
+            last_serial_query: AtomicI64::new(
+                cmp::max(
+                    self.last_serial_query.load(Ordering::Relaxed),
+                    other.last_serial_query.load(Ordering::Relaxed)
+                )
+            ),
+            version: AtomicU8::new({
+                let left = self.version.load(Ordering::Relaxed);
+                if left == u8::MAX {
+                    other.version.load(Ordering::Relaxed)
+                }
+                else {
+                    left
+                }
+            }),
             bytes_read: AtomicU64::new(
                 self.bytes_read.load(Ordering::Relaxed)
                 + other.bytes_read.load(Ordering::Relaxed)
@@ -1011,6 +1462,37 @@ impl RtrClientMetrics {
 }
 
 
+//------------ serial_lag ----------------------------------------------------
+
+/// Returns how many serials `serial` is behind `current`.
+///
+/// Serial numbers wrap around, so this is the distance from `serial` to
+/// `current` in serial number arithmetic as defined in RFC 1982. If
+/// `serial` is ahead of `current` or the two can’t be compared, returns
+/// `None`.
+pub fn serial_lag(serial: Serial, current: Serial) -> Option<u32> {
+    match serial.partial_cmp(&current) {
+        Some(cmp::Ordering::Less) | Some(cmp::Ordering::Equal) => {
+            Some(u32::from(current).wrapping_sub(u32::from(serial)))
+        }
+        _ => None
+    }
+}
+
+/// Returns the later of two serials.
+///
+/// Serials are compared using serial number arithmetic as defined in
+/// RFC 1982. If they can’t be compared, returns `left`.
+pub fn later_serial(left: u32, right: u32) -> u32 {
+    if Serial::from(right) > Serial::from(left) {
+        right
+    }
+    else {
+        left
+    }
+}
+
+
 //------------ FoldedRtrClientsIter ------------------------------------------
 
 /// An iterator over groups of RTR clients in RTR server metrics.
@@ -1075,6 +1557,25 @@ mod test {
     use super::*;
     use std::str::FromStr;
 
+    #[test]
+    fn serial_arithmetic() {
+        let lag = |serial: u32, current: u32| {
+            serial_lag(serial.into(), current.into())
+        };
+        assert_eq!(lag(10, 10), Some(0));
+        assert_eq!(lag(8, 10), Some(2));
+        assert_eq!(lag(u32::MAX, 1), Some(2));
+        assert_eq!(lag(11, 10), None);
+        assert_eq!(lag(1, u32::MAX), None);
+        assert_eq!(lag(0, 0x8000_0000), None);
+
+        assert_eq!(later_serial(8, 10), 10);
+        assert_eq!(later_serial(10, 8), 10);
+        assert_eq!(later_serial(u32::MAX, 1), 1);
+        assert_eq!(later_serial(1, u32::MAX), 1);
+        assert_eq!(later_serial(0, 0x8000_0000), 0);
+    }
+
     #[test]
     fn insert_rtr_metrics() {
         let addr1 = IpAddr::from_str("10.0.0.1").unwrap();
@@ -14,14 +14,15 @@
 use std::{fs, io, thread};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Child, Command};
 use std::str::FromStr;
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::mpsc::RecvTimeoutError;
 use std::time::{Duration, Instant};
-#[cfg(feature = "rta")] use bytes::Bytes;
+use bytes::Bytes;
 use clap::{Arg, Args, ArgAction, ArgMatches, FromArgMatches, Parser};
+use futures::FutureExt;
 use log::{error, info, warn};
 use rpki::resources::{Asn, Prefix};
 #[cfg(feature = "rta")] use rpki::repository::rta::Rta;
@@ -29,7 +30,7 @@ use rpki::rtr::server::NotifySender;
 use tempfile::NamedTempFile;
 use tokio::sync::oneshot;
 #[cfg(feature = "rta")] use crate::rta;
-use crate::{output, validity};
+use crate::{contacts, output, report, rsc, validity};
 use crate::config::Config;
 use crate::error::{ExitError, Failed, RunFailed};
 use crate::http::http_listener;
@@ -37,12 +38,14 @@ use crate::metrics::{SharedRtrServerMetrics};
 use crate::output::{Output, OutputFormat};
 use crate::payload::{PayloadSnapshot, SharedHistory, ValidationReport};
 use crate::process::Process;
+use crate::publish::RrdpPublication;
 use crate::engine::Engine;
-use crate::rtr::{rtr_listener};
+use crate::rtr::{RtrShutdown, rtr_listener};
 use crate::slurm::LocalExceptions;
 
 #[cfg(unix)] use tokio::signal::unix::{Signal, SignalKind, signal};
 #[cfg(not(unix))] use futures::future::pending;
+#[cfg(not(unix))] use tokio::signal::ctrl_c;
 
 
 //------------ Operation -----------------------------------------------------
@@ -65,11 +68,15 @@ pub enum Operation {
     Server(Server),
     Vrps(Vrps),
     Validate(Validate),
+    Contacts(Contacts),
+    Report(Report),
     #[cfg(feature = "rta")]
     ValidateDocument(ValidateDocument),
+    ValidateChecklist(ValidateChecklist),
     Update(Update),
     PrintConfig(PrintConfig),
     Dump(Dump),
+    AcceptTa(AcceptTa),
     Man(Man),
 }
 
@@ -86,13 +93,17 @@ impl Operation {
         let app = Server::config_args(app);
         let app = Vrps::config_args(app);
         let app = Validate::config_args(app);
+        let app = Contacts::config_args(app);
+        let app = Report::config_args(app);
 
         #[cfg(feature = "rta")]
         let app = ValidateDocument::config_args(app);
 
+        let app = ValidateChecklist::config_args(app);
         let app = Update::config_args(app);
         let app = PrintConfig::config_args(app);
         let app = Dump::config_args(app);
+        let app = AcceptTa::config_args(app);
         Man::config_args(app)
     }
 
@@ -114,12 +125,23 @@ impl Operation {
             Some(("validate", matches)) => {
                 Operation::Validate(Validate::from_arg_matches(matches)?)
             },
+            Some(("contacts", matches)) => {
+                Operation::Contacts(Contacts::from_arg_matches(matches)?)
+            }
+            Some(("report", matches)) => {
+                Operation::Report(Report::from_arg_matches(matches)?)
+            }
             #[cfg(feature = "rta")]
             Some(("rta", matches)) => {
                 Operation::ValidateDocument(
                     ValidateDocument::from_arg_matches(matches)?
                 )
             }
+            Some(("rsc", matches)) => {
+                Operation::ValidateChecklist(
+                    ValidateChecklist::from_arg_matches(matches)?
+                )
+            }
             Some(("update", matches)) => {
                 Operation::Update(Update::from_arg_matches(matches)?)
             }
@@ -131,6 +153,9 @@ impl Operation {
             Some(("dump", matches)) => {
                 Operation::Dump( Dump::from_arg_matches(matches, cur_dir)?)
             }
+            Some(("accept-ta", matches)) => {
+                Operation::AcceptTa(AcceptTa::from_arg_matches(matches)?)
+            }
             Some(("man", matches)) => {
                 Operation::Man(Man::from_arg_matches(matches)?)
             }
@@ -162,11 +187,15 @@ impl Operation {
             Operation::Server(cmd) => cmd.run(process),
             Operation::Vrps(cmd) => cmd.run(process),
             Operation::Validate(cmd) => cmd.run(process),
+            Operation::Contacts(cmd) => cmd.run(process),
+            Operation::Report(cmd) => cmd.run(process),
             #[cfg(feature = "rta")]
             Operation::ValidateDocument(cmd) => cmd.run(process),
+            Operation::ValidateChecklist(cmd) => cmd.run(process),
             Operation::Update(cmd) => cmd.run(process),
             Operation::PrintConfig(cmd) => cmd.run(process),
             Operation::Dump(cmd) => cmd.run(process),
+            Operation::AcceptTa(cmd) => cmd.run(process),
             Operation::Man(cmd) => cmd.run(process),
         }
     }
@@ -224,26 +253,54 @@ impl Server {
         process.setup_service(self.detach)?;
         let log = log.map(Arc::new);
         let rtr_metrics = SharedRtrServerMetrics::new(
-            process.config().rtr_client_metrics
+            process.config().rtr_client_metrics,
+            process.config().rtr_client_labels.clone(),
         );
 
         let history = SharedHistory::from_config(process.config());
         let mut notify = NotifySender::new();
+        let rtr_shutdown = RtrShutdown::new();
+        let rtr_shutdown_grace = process.config().rtr_shutdown_grace;
+        let mut sockets = process.listen_sockets();
         let rtr = rtr_listener(
             history.clone(), rtr_metrics.clone(), process.config(),
-            notify.clone(), process.get_listen_fd()?
+            notify.clone(), process.get_listen_fd()?, &mut sockets,
+            rtr_shutdown.clone(),
         )?;
         let http = http_listener(
             history.clone(), rtr_metrics, log.clone(), process.config(),
-            notify.clone(),
+            notify.clone(), &mut sockets,
         )?;
+        sockets.close_unclaimed();
 
         process.drop_privileges()?;
 
         let mut validation = Engine::new(process.config(), true)?;
         let runtime = process.runtime()?;
-        let mut rtr = runtime.spawn(rtr);
-        let mut http = runtime.spawn(http);
+
+        // When taking over from another process, that process keeps
+        // serving until our first validation run has finished. Only then
+        // we start accepting connections.
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let mut ready_tx = if process.is_upgrade() {
+            Some(ready_tx)
+        }
+        else {
+            let _ = ready_tx.send(());
+            None
+        };
+        let ready = ready_rx.shared();
+        let mut rtr = runtime.spawn({
+            let ready = ready.clone();
+            async move {
+                let _ = ready.await;
+                rtr.await
+            }
+        });
+        let mut http = runtime.spawn(async move {
+            let _ = ready.await;
+            http.await
+        });
         let (sig_tx, sig_rx) = mpsc::channel();
         let (err_tx, mut err_rx) = oneshot::channel();
 
@@ -251,6 +308,10 @@ impl Server {
 
         let join = thread::spawn(move || {
             let mut can_retry = true;
+
+            // The new process while we are upgrading.
+            let mut upgrade: Option<Child> = None;
+
             let err = loop {
                 if let Some(log) = log.as_ref() {
                     log.start();
@@ -264,6 +325,10 @@ impl Server {
                             &mut notify, exceptions,
                         ) {
                             Ok(()) => {
+                                if let Some(ready) = ready_tx.take() {
+                                    process.finish_upgrade();
+                                    let _ = ready.send(());
+                                }
                                 history.read().refresh_wait()
                             }
                             Err(err) => {
@@ -311,10 +376,25 @@ impl Server {
                 let deadline = Instant::now() + timeout;
 
                 let end = loop {
-                    let timeout = deadline.saturating_duration_since(
-                        Instant::now()
-                    );
+                    // While upgrading, we don’t validate anymore so we
+                    // don’t get in the way of the new process. But if it
+                    // fails, we carry on.
+                    if let Some(child) = upgrade.as_mut() {
+                        if Self::upgrade_failed(child) {
+                            upgrade = None;
+                            break None;
+                        }
+                    }
+                    let timeout = if upgrade.is_some() {
+                        UPGRADE_POLL_INTERVAL
+                    }
+                    else {
+                        deadline.saturating_duration_since(Instant::now())
+                    };
                     match sig_rx.recv_timeout(timeout) {
+                        Ok(UserSignal::ReloadTals) if upgrade.is_some() => {
+                            warn!("Ignoring TAL reload during upgrade.");
+                        }
                         Ok(UserSignal::ReloadTals) => {
                             match validation.reload_tals() {
                                 Ok(_) => {
@@ -335,8 +415,25 @@ impl Server {
                                 break Some(Err(Failed));
                             }
                         }
+                        Ok(UserSignal::Upgrade) if upgrade.is_some() => {
+                            warn!("Upgrade already in progress.");
+                        }
+                        Ok(UserSignal::Upgrade) => {
+                            if let Ok(child) = process.upgrade(&sockets) {
+                                info!(
+                                    "Started process {} for upgrade.",
+                                    child.id()
+                                );
+                                upgrade = Some(child);
+                            }
+                        }
+                        Ok(UserSignal::Shutdown) => {
+                            break Some(Ok(()));
+                        }
                         Err(RecvTimeoutError::Timeout) => {
-                            break None;
+                            if upgrade.is_none() {
+                                break None;
+                            }
                         }
                         Err(RecvTimeoutError::Disconnected) => {
                             break Some(Ok(()));
@@ -351,18 +448,23 @@ impl Server {
             let _ = err_tx.send(err);
         });
 
-        let res: Result<(), Failed> = runtime.block_on(async move {
+        let res: Result<bool, Failed> = runtime.block_on(async move {
             let mut signal = SignalListener::new()?;
             let res = loop {
                 tokio::select! {
                     sig = signal.next() => {
+                        if let UserSignal::Shutdown = sig {
+                            info!("Shutting down.");
+                            rtr_shutdown.shutdown(rtr_shutdown_grace).await;
+                            break Ok(true);
+                        }
                         if sig_tx.send(sig).is_err() {
                             break Err(Failed);
                         }
                     }
                     res = &mut err_rx => {
                         match res {
-                            Ok(res) => break res,
+                            Ok(res) => break res.map(|_| false),
                             Err(_) => break Err(Failed)
                         }
                     }
@@ -378,8 +480,33 @@ impl Server {
             res
         });
 
-        let _ = join.join();
-        res.map_err(Into::into)
+        // When shutting down, we don’t wait for a validation run that may
+        // currently be in progress.
+        match res {
+            Ok(true) => Ok(()),
+            res => {
+                let _ = join.join();
+                res.map(|_| ()).map_err(Into::into)
+            }
+        }
+    }
+
+    /// Returns whether the new process of an upgrade has failed.
+    ///
+    /// When detaching, the new process forks and exits successfully right
+    /// away. We can only detect failure before that.
+    fn upgrade_failed(child: &mut Child) -> bool {
+        match child.try_wait() {
+            Ok(Some(status)) if !status.success() => {
+                error!(
+                    "Upgrade failed: new process exited with {}. \
+                     Resuming validation.",
+                    status
+                );
+                true
+            }
+            _ => false
+        }
     }
 
     fn process_once(
@@ -395,6 +522,12 @@ impl Server {
         let must_notify = history.update(
             report, &exceptions, metrics,
         );
+        if let Some(base_uri) = config.rrdp_publish_uri.as_ref() {
+            let current = history.read().rrdp_publication();
+            history.update_rrdp_publication(RrdpPublication::update(
+                current.as_deref(), base_uri, config.history_size, engine,
+            )?);
+        }
         if log::max_level() >= log::Level::Info {
             info!("Validation completed.");
             let (metrics, serial) = {
@@ -921,6 +1054,187 @@ impl Validate {
 }
 
 
+//------------ Contacts ------------------------------------------------------
+
+/// Shows the contact information published for resources.
+#[derive(Clone, Debug, Parser)]
+pub struct Contacts {
+    /// Show the contacts for CAs holding this prefix
+    #[arg(short, long, conflicts_with = "asn")]
+    prefix: Option<Prefix>,
+
+    /// Show the contacts for CAs holding this AS number
+    #[arg(short, long, conflicts_with = "prefix")]
+    asn: Option<Asn>,
+
+    /// Produce output in JSON
+    #[arg(short, long)]
+    json: bool,
+
+    /// Don't update the local cache
+    #[arg(short, long)]
+    noupdate: bool,
+
+    /// Return an error status on incomplete update
+    #[arg(long)]
+    complete: bool,
+}
+
+impl Contacts {
+    /// Adds the command configuration to a clap app.
+    pub fn config_args<'a: 'b, 'b>(app: clap::Command) -> clap::Command {
+        app.subcommand(
+            Contacts::augment_args(
+                clap::Command::new("contacts")
+                    .about("Shows contacts from Ghostbuster Records")
+                    .after_help(AFTER_HELP)
+            )
+        )
+    }
+
+    /// Creates a command from clap matches.
+    pub fn from_arg_matches(matches: &ArgMatches) -> Result<Self, Failed> {
+        Ok(
+            <Contacts as FromArgMatches>::from_arg_matches(matches).unwrap()
+        )
+    }
+
+    /// Outputs the contacts for the requested resources.
+    fn run(self, process: Process) -> Result<(), ExitError> {
+        let mut engine = Engine::new(process.config(), !self.noupdate)?;
+        engine.ignite()?;
+        process.switch_logging(false, false)?;
+        let (report, mut metrics) = ValidationReport::process(
+            &engine, process.config(),
+        )?;
+        let snapshot = report.into_snapshot(
+            &LocalExceptions::load(process.config(), false)?,
+            &mut metrics,
+        );
+        if self.complete && !metrics.rsync_complete() {
+            error!("Failed: Incomplete update.");
+            return Err(ExitError::IncompleteUpdate)
+        }
+
+        let query = if let Some(prefix) = self.prefix {
+            contacts::ContactQuery::Prefix(prefix)
+        }
+        else if let Some(asn) = self.asn {
+            contacts::ContactQuery::Asn(asn)
+        }
+        else {
+            contacts::ContactQuery::All
+        };
+        let list = contacts::ContactList::new(query, &snapshot);
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let res = if self.json {
+            list.write_json(&mut stdout)
+        }
+        else {
+            list.write_plain(&mut stdout)
+        };
+        res.map_err(|err| {
+            error!("Failed to write output: {}", err);
+            ExitError::Generic
+        })
+    }
+}
+
+
+//------------ Report --------------------------------------------------------
+
+/// Produces a report on the validation outcome of every object.
+#[derive(Clone, Debug, Parser)]
+pub struct Report {
+    /// Output format: json or csv
+    #[arg(
+        short, long, value_name = "FORMAT", default_value = "json",
+        value_parser = ["json", "csv"]
+    )]
+    format: String,
+
+    /// Write output to a file
+    #[arg(short, long, value_name = "PATH", default_value = "-")]
+    output: PathBuf,
+
+    /// Don't update the local cache
+    #[arg(short, long)]
+    noupdate: bool,
+
+    /// Return an error status on incomplete update
+    #[arg(long)]
+    complete: bool,
+}
+
+impl Report {
+    /// Adds the command configuration to a clap app.
+    pub fn config_args<'a: 'b, 'b>(app: clap::Command) -> clap::Command {
+        app.subcommand(
+            Report::augment_args(
+                clap::Command::new("report")
+                    .about("Reports the validation outcome of all objects")
+                    .after_help(AFTER_HELP)
+            )
+        )
+    }
+
+    /// Creates a command from clap matches.
+    pub fn from_arg_matches(matches: &ArgMatches) -> Result<Self, Failed> {
+        Ok(<Report as FromArgMatches>::from_arg_matches(matches).unwrap())
+    }
+
+    /// Produces the report.
+    fn run(self, process: Process) -> Result<(), ExitError> {
+        let mut engine = Engine::new(process.config(), !self.noupdate)?;
+        engine.ignite()?;
+        process.switch_logging(false, false)?;
+        let (report, metrics) = report::ObjectReport::process(&engine)?;
+        let list = report.into_list();
+
+        let res = if self.output == Path::new("-") {
+            let stdout = io::stdout();
+            self.write_list(&list, &mut stdout.lock())
+        }
+        else {
+            let mut file = match fs::File::create(&self.output) {
+                Ok(file) => file,
+                Err(err) => {
+                    error!(
+                        "Failed to open output file '{}': {}",
+                        self.output.display(), err
+                    );
+                    return Err(ExitError::Generic)
+                }
+            };
+            self.write_list(&list, &mut file)
+        };
+        if let Err(err) = res {
+            error!("Failed to write output: {}", err);
+            return Err(ExitError::Generic)
+        }
+
+        if self.complete && !metrics.rsync_complete() {
+            Err(ExitError::IncompleteUpdate)
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    fn write_list(
+        &self, list: &report::ObjectList, target: &mut impl io::Write
+    ) -> Result<(), io::Error> {
+        if self.format == "csv" {
+            list.write_csv(target)
+        }
+        else {
+            list.write_json(target)
+        }
+    }
+}
+
+
 //------------ ValidateDocument ----------------------------------------------
 
 /// Validates an RTA-signed document.
@@ -1052,6 +1366,113 @@ impl ValidateDocument {
 }
 
 
+//------------ ValidateChecklist ---------------------------------------------
+
+/// Validates files against an RPKI Signed Checklist.
+///
+/// Performs a validation run in order to find the necessary certificates.
+#[derive(Clone, Debug, Parser)]
+pub struct ValidateChecklist {
+    /// Path to the signed checklist.
+    #[arg(long, value_name = "PATH")]
+    signature: PathBuf,
+
+    /// Don’t update the repository.
+    #[arg(short, long)]
+    noupdate: bool,
+
+    /// The files to check against the checklist.
+    #[arg(value_name = "FILE")]
+    files: Vec<PathBuf>,
+}
+
+impl ValidateChecklist {
+    /// Adds the command configuration to a clap app.
+    pub fn config_args<'a: 'b, 'b>(app: clap::Command) -> clap::Command {
+        app.subcommand(
+            ValidateChecklist::augment_args(
+                clap::Command::new("rsc")
+                .about("Validates files against an RPKI Signed Checklist")
+                .after_help(AFTER_HELP)
+            )
+        )
+    }
+
+    /// Creates a command from clap matches.
+    pub fn from_arg_matches(
+        matches: &ArgMatches,
+    ) -> Result<Self, Failed> {
+        Ok(
+            <ValidateChecklist as FromArgMatches>::from_arg_matches(
+                matches
+            ).unwrap()
+        )
+    }
+
+    /// Validates the checklist and checks the files.
+    ///
+    /// Prints the outcome. Returns successfully if the checklist is valid
+    /// and all files are listed in it or with an appropriate error
+    /// otherwise.
+    fn run(self, process: Process) -> Result<(), ExitError> {
+        let mut validation = Engine::new(process.config(), !self.noupdate)?;
+        validation.ignite()?;
+        process.switch_logging(false, false)?;
+
+        // Load and decode the checklist.
+        let data = match fs::read(&self.signature) {
+            Ok(data) => Bytes::from(data),
+            Err(err) => {
+                error!(
+                    "Failed to read signed checklist '{}': {}",
+                    self.signature.display(), err
+                );
+                return Err(ExitError::Generic)
+            }
+        };
+        let rsc = match rsc::Rsc::decode(data, process.config().strict) {
+            Ok(rsc) => rsc,
+            Err(err) => {
+                error!(
+                    "Failed to decode signed checklist '{}': {}",
+                    self.signature.display(), err
+                );
+                return Err(ExitError::Invalid)
+            }
+        };
+
+        // Find the issuing CA and validate.
+        let report = rsc::ValidationReport::new(
+            &rsc, process.config().strict
+        );
+        if report.process(&validation).is_err() {
+            error!("Validation run failed.");
+            return Err(ExitError::Generic)
+        }
+        let mut checked = rsc::Checked::new(&rsc, report.finalize().ok());
+
+        // Digest and check the files.
+        for path in &self.files {
+            if let Err(err) = checked.add_file(path) {
+                error!("Failed to read file '{}': {}", path.display(), err);
+                return Err(ExitError::Generic)
+            }
+        }
+
+        if let Err(err) = checked.write_text(&mut io::stdout().lock()) {
+            error!("Failed to write output: {}", err);
+            return Err(ExitError::Generic)
+        }
+        if checked.is_valid() {
+            Ok(())
+        }
+        else {
+            Err(ExitError::Invalid)
+        }
+    }
+}
+
+
 //------------ Update --------------------------------------------------------
 
 
@@ -1184,6 +1605,50 @@ impl Dump {
 }
 
 
+//------------ AcceptTa ------------------------------------------------------
+
+/// Accepts refused trust anchor certificates.
+#[derive(Clone, Debug, Parser)]
+pub struct AcceptTa {
+    /// Name of the TAL whose refused certificate to accept
+    #[arg(value_name = "NAME", required = true)]
+    tals: Vec<String>,
+}
+
+impl AcceptTa {
+    /// Adds the command configuration to a clap app.
+    pub fn config_args<'a: 'b, 'b>(app: clap::Command) -> clap::Command {
+        app.subcommand(
+            AcceptTa::augment_args(
+                clap::Command::new("accept-ta")
+                    .about("Accepts refused trust anchor certificates")
+                    .after_help(AFTER_HELP)
+            )
+        )
+    }
+
+    /// Creates a command from clap matches.
+    pub fn from_arg_matches(matches: &ArgMatches) -> Result<Self, Failed> {
+        Ok(<AcceptTa as FromArgMatches>::from_arg_matches(matches).unwrap())
+    }
+
+    /// Accepts the refused certificates of the given TALs.
+    fn run(self, process: Process) -> Result<(), ExitError> {
+        let engine = Engine::new(process.config(), false)?;
+        process.switch_logging(false, false)?;
+        for tal in &self.tals {
+            if engine.accept_ta_cert(tal)? {
+                info!("Accepted new trust anchor certificate for {}.", tal);
+            }
+            else {
+                warn!("No refused trust anchor certificate for {}.", tal);
+            }
+        }
+        Ok(())
+    }
+}
+
+
 //------------ Man -----------------------------------------------------------
 
 /// Show the manual page.
@@ -1312,6 +1777,8 @@ impl Man {
 enum UserSignal {
     ReloadTals,
     RotateLog,
+    Upgrade,
+    Shutdown,
 }
 
 /// Wait for the next validation run or a user telling us to quit or reload.
@@ -1321,6 +1788,9 @@ enum UserSignal {
 struct SignalListener {
     usr1: Signal,
     usr2: Signal,
+    hup: Signal,
+    term: Signal,
+    int: Signal,
 }
 
 #[cfg(unix)]
@@ -1341,6 +1811,27 @@ impl SignalListener {
                     return Err(Failed)
                 }
             },
+            hup: match signal(SignalKind::hangup()) {
+                Ok(hup) => hup,
+                Err(err) => {
+                    error!("Attaching to signal HUP failed: {}", err);
+                    return Err(Failed)
+                }
+            },
+            term: match signal(SignalKind::terminate()) {
+                Ok(term) => term,
+                Err(err) => {
+                    error!("Attaching to signal TERM failed: {}", err);
+                    return Err(Failed)
+                }
+            },
+            int: match signal(SignalKind::interrupt()) {
+                Ok(int) => int,
+                Err(err) => {
+                    error!("Attaching to signal INT failed: {}", err);
+                    return Err(Failed)
+                }
+            },
         })
     }
 
@@ -1351,6 +1842,9 @@ impl SignalListener {
         tokio::select! {
             _ = self.usr1.recv() => UserSignal::ReloadTals,
             _ = self.usr2.recv() => UserSignal::RotateLog,
+            _ = self.hup.recv() => UserSignal::Upgrade,
+            _ = self.term.recv() => UserSignal::Shutdown,
+            _ = self.int.recv() => UserSignal::Shutdown,
         }
     }
 }
@@ -1368,7 +1862,10 @@ impl SignalListener {
     ///
     /// Returns whether to continue working.
     pub async fn next(&mut self) -> UserSignal {
-        pending().await
+        if ctrl_c().await.is_err() {
+            pending::<()>().await
+        }
+        UserSignal::Shutdown
     }
 }
 
@@ -1378,6 +1875,9 @@ impl SignalListener {
 /// The raw bytes of the manual page.
 const MAN_PAGE: &[u8] = include_bytes!("../doc/routinator.1");
 
+/// How often to check on the new process during an upgrade.
+const UPGRADE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 /// The after help message pointing to the main help.
 const AFTER_HELP: &str = 
     "Additional global options are available. \
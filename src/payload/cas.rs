@@ -0,0 +1,103 @@
+//! The CAs of the publication points accepted during a validation run.
+//!
+//! For every publication point that was accepted, [`CaIndex`] keeps the
+//! URI of the CA’s certificate and the key identifier of its issuer. This
+//! allows finding the chain of certificates leading to a CA without going
+//! over the whole repository again.
+//!
+//! This is a private module. Its public types are re-exported by the parent.
+
+use std::collections::HashMap;
+use rpki::crypto::keys::KeyIdentifier;
+use rpki::repository::tal::TalUri;
+use crate::engine::CaCert;
+
+
+//------------ CaIndex -------------------------------------------------------
+
+/// The CAs of all accepted publication points.
+#[derive(Clone, Debug, Default)]
+pub struct CaIndex {
+    /// The CAs keyed by the subject key identifier of their certificate.
+    cas: HashMap<KeyIdentifier, CaEntry>,
+}
+
+impl CaIndex {
+    /// Adds a CA to the index.
+    pub(super) fn insert(&mut self, ca: CaEntry) {
+        self.cas.insert(ca.key, ca);
+    }
+
+    /// Returns the number of CAs in the index.
+    pub fn len(&self) -> usize {
+        self.cas.len()
+    }
+
+    /// Returns whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.cas.is_empty()
+    }
+
+    /// Returns the chain of CAs leading to the CA with the given key.
+    ///
+    /// The chain starts with the trust anchor CA and ends with the CA
+    /// itself. Returns `None` if the CA or one of its ancestors is not
+    /// part of the index.
+    pub fn path(&self, key: KeyIdentifier) -> Option<Vec<&CaEntry>> {
+        let mut res = Vec::new();
+        let mut key = Some(key);
+        while let Some(current) = key {
+            // More entries than CAs means we are running in circles.
+            if res.len() >= self.cas.len() {
+                return None
+            }
+            let entry = self.cas.get(&current)?;
+            res.push(entry);
+            key = entry.parent;
+        }
+        res.reverse();
+        Some(res)
+    }
+}
+
+
+//------------ CaEntry -------------------------------------------------------
+
+/// A CA in the index.
+#[derive(Clone, Debug)]
+pub struct CaEntry {
+    /// The subject key identifier of the CA certificate.
+    key: KeyIdentifier,
+
+    /// The URI of the CA certificate.
+    uri: TalUri,
+
+    /// The subject key identifier of the issuing CA’s certificate.
+    ///
+    /// This is `None` for a trust anchor CA.
+    parent: Option<KeyIdentifier>,
+}
+
+impl CaEntry {
+    /// Creates a new entry for a CA certificate.
+    pub(super) fn new(cert: &CaCert) -> Self {
+        CaEntry {
+            key: cert.cert().subject_key_identifier(),
+            uri: cert.uri().clone(),
+            parent: cert.parent().map(|parent| {
+                parent.cert().subject_key_identifier()
+            }),
+        }
+    }
+
+    /// Returns the subject key identifier of the CA certificate.
+    pub fn key(&self) -> KeyIdentifier {
+        self.key
+    }
+
+    /// Returns the URI of the CA certificate.
+    pub fn uri(&self) -> &TalUri {
+        &self.uri
+    }
+}
+
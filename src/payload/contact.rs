@@ -0,0 +1,286 @@
+//! Contact information from Ghostbuster Records.
+//!
+//! Ghostbuster Records as defined in RFC 6493 allow a CA to publish contact
+//! information in the form of a vCard. This module contains the type
+//! [`Contact`] that keeps the relevant parts of such a record together with
+//! the resources of the CA that published it, allowing to find out who is
+//! responsible for a certain prefix or AS number.
+//!
+//! This is a private module. Its public types are re-exported by the parent.
+
+use std::sync::Arc;
+use rpki::uri;
+use rpki::repository::cert::ResourceCert;
+use rpki::repository::resources::{AsBlocks, IpBlocks};
+use rpki::repository::tal::TalInfo;
+use rpki::resources::{Asn, Prefix};
+
+
+//------------ Contact -------------------------------------------------------
+
+/// The contact information published by a CA.
+#[derive(Clone, Debug)]
+pub struct Contact {
+    /// The rsync URI of the Ghostbuster Record.
+    uri: uri::Rsync,
+
+    /// The rsync URI of the certificate of the CA if available.
+    ca: Option<uri::Rsync>,
+
+    /// The TAL the CA is published under.
+    tal: Arc<TalInfo>,
+
+    /// The AS resources of the CA.
+    as_resources: AsBlocks,
+
+    /// The IPv4 resources of the CA.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources of the CA.
+    v6_resources: IpBlocks,
+
+    /// The content of the vCard.
+    card: VCard,
+}
+
+impl Contact {
+    /// Creates a new contact from a validated Ghostbuster Record.
+    ///
+    /// The EE certificate of a Ghostbuster Record has to inherit all its
+    /// resources, so the resources of the certificate are those of the
+    /// CA.
+    pub fn new(uri: uri::Rsync, cert: &ResourceCert, card: VCard) -> Self {
+        Contact {
+            uri,
+            ca: cert.ca_issuer().cloned(),
+            tal: cert.tal().clone(),
+            as_resources: cert.as_resources().clone(),
+            v4_resources: cert.v4_resources().clone(),
+            v6_resources: cert.v6_resources().clone(),
+            card,
+        }
+    }
+
+    /// Returns the rsync URI of the Ghostbuster Record.
+    pub fn uri(&self) -> &uri::Rsync {
+        &self.uri
+    }
+
+    /// Returns the rsync URI of the CA certificate if available.
+    pub fn ca(&self) -> Option<&uri::Rsync> {
+        self.ca.as_ref()
+    }
+
+    /// Returns the TAL the CA is published under.
+    pub fn tal(&self) -> &TalInfo {
+        &self.tal
+    }
+
+    /// Returns the AS resources of the CA.
+    pub fn as_resources(&self) -> &AsBlocks {
+        &self.as_resources
+    }
+
+    /// Returns the IPv4 resources of the CA.
+    pub fn v4_resources(&self) -> &IpBlocks {
+        &self.v4_resources
+    }
+
+    /// Returns the IPv6 resources of the CA.
+    pub fn v6_resources(&self) -> &IpBlocks {
+        &self.v6_resources
+    }
+
+    /// Returns the content of the vCard.
+    pub fn card(&self) -> &VCard {
+        &self.card
+    }
+
+    /// Returns whether the CA holds resources overlapping with a prefix.
+    pub fn covers_prefix(&self, prefix: Prefix) -> bool {
+        let raw = rpki::repository::resources::Prefix::new(
+            prefix.addr(), prefix.len()
+        );
+        if prefix.is_v4() {
+            self.v4_resources.intersects_block(raw)
+        }
+        else {
+            self.v6_resources.intersects_block(raw)
+        }
+    }
+
+    /// Returns whether the CA holds the given AS number.
+    pub fn covers_asn(&self, asn: Asn) -> bool {
+        self.as_resources.contains_asn(asn)
+    }
+}
+
+
+//------------ VCard ---------------------------------------------------------
+
+/// The contact data of a vCard.
+///
+/// Only the properties allowed in Ghostbuster Records and relevant for
+/// contacting somebody are kept.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VCard {
+    /// The formatted name of the contact.
+    pub full_name: Option<String>,
+
+    /// The organization of the contact.
+    pub org: Option<String>,
+
+    /// The email addresses of the contact.
+    pub emails: Vec<String>,
+
+    /// The telephone numbers of the contact.
+    pub tels: Vec<String>,
+
+    /// The postal addresses of the contact.
+    pub addrs: Vec<String>,
+}
+
+impl VCard {
+    /// Parses a vCard from its encoded form.
+    ///
+    /// Returns `None` if the data isn’t a vCard. Properties we don’t know
+    /// are skipped.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let data = String::from_utf8_lossy(data);
+        let mut lines = unfold(&data).into_iter();
+        let first = lines.next()?;
+        let (name, _) = split_line(&first)?;
+        if !name.eq_ignore_ascii_case("BEGIN") {
+            return None
+        }
+        let mut res = VCard::default();
+        for line in lines {
+            let (name, value) = match split_line(&line) {
+                Some(some) => some,
+                None => continue,
+            };
+            if name.eq_ignore_ascii_case("END") {
+                return Some(res)
+            }
+            else if name.eq_ignore_ascii_case("FN") {
+                res.full_name = Some(unescape(value, ", "))
+            }
+            else if name.eq_ignore_ascii_case("ORG") {
+                res.org = Some(unescape(value, ", "))
+            }
+            else if name.eq_ignore_ascii_case("EMAIL") {
+                res.emails.push(unescape(value, ", "))
+            }
+            else if name.eq_ignore_ascii_case("TEL") {
+                res.tels.push(unescape(value, ", "))
+            }
+            else if name.eq_ignore_ascii_case("ADR") {
+                res.addrs.push(unescape(value, ", "))
+            }
+        }
+        // Missing END. Be lenient.
+        Some(res)
+    }
+}
+
+/// Splits the data into unfolded content lines.
+fn unfold(data: &str) -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+    for line in data.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(cont) = line.strip_prefix([' ', '\t']) {
+            if let Some(last) = res.last_mut() {
+                last.push_str(cont);
+                continue
+            }
+        }
+        if !line.is_empty() {
+            res.push(line.into())
+        }
+    }
+    res
+}
+
+/// Splits a content line into property name and value.
+///
+/// Drops the group and any parameters.
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    // The value starts after the first colon not inside a quoted
+    // parameter value.
+    let mut quoted = false;
+    let colon = line.char_indices().find(|&(_, ch)| {
+        if ch == '"' {
+            quoted = !quoted;
+        }
+        ch == ':' && !quoted
+    })?.0;
+    let name = line[..colon].split(';').next()?;
+    let name = match name.rfind('.') {
+        Some(idx) => &name[idx + 1..],
+        None => name,
+    };
+    Some((name, &line[colon + 1..]))
+}
+
+/// Unescapes a property value.
+///
+/// Structured values have their non-empty components joined by `sep`.
+/// Control characters are replaced by spaces.
+fn unescape(value: &str, sep: &str) -> String {
+    let mut components = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => {
+                match chars.next() {
+                    Some('n') | Some('N') => current.push(' '),
+                    Some(ch) => current.push(ch),
+                    None => { }
+                }
+            }
+            ';' => {
+                components.push(current);
+                current = String::new();
+            }
+            ch if ch.is_control() => current.push(' '),
+            ch => current.push(ch),
+        }
+    }
+    components.push(current);
+    let components: Vec<_> = components.iter().map(|item| {
+        item.trim()
+    }).filter(|item| !item.is_empty()).collect();
+    components.join(sep)
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_vcard() {
+        let card = VCard::parse(
+            b"BEGIN:VCARD\r\n\
+              VERSION:4.0\r\n\
+              FN:Human Resources\r\n\
+              ORG:Example Inc.;RPKI Operations\r\n\
+              ADR;TYPE=WORK:;;42 Twelfth Street;Nowhere;;\r\n \
+              12345;Utopia\r\n\
+              TEL;TYPE=VOICE,TEXT,WORK;VALUE=uri:tel:+1-666-555-1212\r\n\
+              item1.EMAIL;TYPE=\"WORK:x\":human@example.com\r\n\
+              END:VCARD\r\n"
+        ).unwrap();
+        assert_eq!(card.full_name.as_deref(), Some("Human Resources"));
+        assert_eq!(card.org.as_deref(), Some("Example Inc., RPKI Operations"));
+        assert_eq!(
+            card.addrs, ["42 Twelfth Street, Nowhere, 12345, Utopia"]
+        );
+        assert_eq!(card.tels, ["tel:+1-666-555-1212"]);
+        assert_eq!(card.emails, ["human@example.com"]);
+        assert!(VCard::parse(b"FN:Nobody\r\n").is_none());
+    }
+}
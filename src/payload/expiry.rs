@@ -0,0 +1,220 @@
+//! When the objects of publication points expire.
+//!
+//! For every publication point that was accepted during a validation run,
+//! the type [`PointExpiry`] keeps the times when its manifest and CRL become
+//! stale or expire and when the VRPs derived from each of its ROAs will
+//! disappear. This allows spotting CAs that are about to let their
+//! publication point lapse before it actually happens.
+//!
+//! This is a private module. Its public types are re-exported by the parent.
+
+use std::{cmp, fmt};
+use std::sync::Arc;
+use rpki::uri;
+use rpki::repository::tal::TalInfo;
+use rpki::repository::x509::Time;
+use crate::engine::CaCert;
+
+
+//------------ PointExpiry ---------------------------------------------------
+
+/// The expiry times of the objects of a publication point.
+#[derive(Clone, Debug)]
+pub struct PointExpiry {
+    /// The TAL the publication point is published under.
+    tal: Arc<TalInfo>,
+
+    /// The URI identifying the repository of the publication point.
+    repository: Arc<str>,
+
+    /// The URI of the manifest.
+    manifest: uri::Rsync,
+
+    /// When the manifest becomes stale or its EE certificate expires.
+    manifest_expires: Option<Time>,
+
+    /// The URI of the CRL and when it becomes stale.
+    crl: Option<(uri::Rsync, Time)>,
+
+    /// The expiry of the VRPs derived from each ROA.
+    roas: Vec<RoaExpiry>,
+}
+
+impl PointExpiry {
+    /// Creates a new, empty value for the publication point of a CA.
+    pub(super) fn new(cert: &CaCert) -> Self {
+        PointExpiry {
+            tal: cert.cert().tal().clone(),
+            repository: cert.repository_uri().into(),
+            manifest: cert.rpki_manifest().clone(),
+            manifest_expires: None,
+            crl: None,
+            roas: Vec::new(),
+        }
+    }
+
+    /// Updates the time the manifest expires to be no later than `time`.
+    pub(super) fn update_manifest(&mut self, time: Time) {
+        self.manifest_expires = Some(match self.manifest_expires {
+            Some(current) => cmp::min(current, time),
+            None => time,
+        })
+    }
+
+    /// Sets the CRL and the time it becomes stale.
+    pub(super) fn set_crl(&mut self, uri: uri::Rsync, stale: Time) {
+        self.crl = Some((uri, stale))
+    }
+
+    /// Adds the expiry of the VRPs from a ROA.
+    pub(super) fn add_roa(
+        &mut self, uri: Option<uri::Rsync>, expires: Time, vrps: usize
+    ) {
+        self.roas.push(RoaExpiry { uri, expires, vrps })
+    }
+
+    /// Forgets everything learned so far.
+    pub(super) fn restart(&mut self) {
+        self.manifest_expires = None;
+        self.crl = None;
+        self.roas.clear();
+    }
+
+    /// Returns whether anything is known about the publication point.
+    pub(super) fn is_known(&self) -> bool {
+        self.manifest_expires.is_some()
+    }
+
+    /// Returns the TAL the publication point is published under.
+    pub fn tal(&self) -> &TalInfo {
+        &self.tal
+    }
+
+    /// Returns the URI identifying the repository.
+    pub fn repository(&self) -> &str {
+        &self.repository
+    }
+
+    /// Returns an iterator over all expiry events of the publication point.
+    pub fn events(&self) -> impl Iterator<Item = ExpiryEvent<'_>> + '_ {
+        let manifest = self.manifest_expires.map(|time| {
+            ExpiryEvent {
+                point: self,
+                expiry_type: ExpiryType::Manifest,
+                uri: Some(&self.manifest),
+                time,
+                count: 1,
+            }
+        });
+        let crl = self.crl.as_ref().map(|(uri, time)| {
+            ExpiryEvent {
+                point: self,
+                expiry_type: ExpiryType::Crl,
+                uri: Some(uri),
+                time: *time,
+                count: 1,
+            }
+        });
+        manifest.into_iter().chain(crl).chain(
+            self.roas.iter().map(move |roa| {
+                ExpiryEvent {
+                    point: self,
+                    expiry_type: ExpiryType::Vrps,
+                    uri: roa.uri.as_ref(),
+                    time: roa.expires,
+                    count: roa.vrps,
+                }
+            })
+        )
+    }
+}
+
+
+//------------ RoaExpiry -----------------------------------------------------
+
+/// The expiry of the VRPs derived from a ROA.
+#[derive(Clone, Debug)]
+struct RoaExpiry {
+    /// The URI of the ROA.
+    uri: Option<uri::Rsync>,
+
+    /// When the VRPs will disappear.
+    ///
+    /// This considers the entire validation chain and the publication
+    /// point becoming stale.
+    expires: Time,
+
+    /// The number of VRPs derived from the ROA.
+    vrps: usize,
+}
+
+
+//------------ ExpiryEvent ---------------------------------------------------
+
+/// Something expiring at some point in time.
+#[derive(Clone, Copy, Debug)]
+pub struct ExpiryEvent<'a> {
+    /// The publication point the event belongs to.
+    pub point: &'a PointExpiry,
+
+    /// What is expiring.
+    pub expiry_type: ExpiryType,
+
+    /// The URI of the object that is expiring if known.
+    pub uri: Option<&'a uri::Rsync>,
+
+    /// The time of expiry.
+    pub time: Time,
+
+    /// The number of items expiring.
+    ///
+    /// This is the number of VRPs for [`ExpiryType::Vrps`] and always one
+    /// for the other types.
+    pub count: usize,
+}
+
+
+//------------ ExpiryType ----------------------------------------------------
+
+/// The kind of thing that is expiring.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ExpiryType {
+    /// The VRPs from a ROA.
+    Vrps,
+
+    /// A manifest becomes stale or its EE certificate expires.
+    Manifest,
+
+    /// A CRL becomes stale.
+    Crl,
+}
+
+impl ExpiryType {
+    /// All expiry types.
+    pub const ALL: [Self; 3] = [Self::Vrps, Self::Manifest, Self::Crl];
+
+    /// Returns the index of the type in [`Self::ALL`].
+    pub fn index(self) -> usize {
+        match self {
+            ExpiryType::Vrps => 0,
+            ExpiryType::Manifest => 1,
+            ExpiryType::Crl => 2,
+        }
+    }
+
+    /// Returns the name of the type.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExpiryType::Vrps => "vrps",
+            ExpiryType::Manifest => "manifest",
+            ExpiryType::Crl => "crl",
+        }
+    }
+}
+
+impl fmt::Display for ExpiryType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
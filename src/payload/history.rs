@@ -13,6 +13,8 @@ use rpki::rtr::{Serial, State, Timing};
 use rpki::rtr::server::PayloadSource;
 use crate::config::{Config, FilterPolicy};
 use crate::metrics::Metrics;
+use crate::publish::RrdpPublication;
+use crate::report::ObjectList;
 use crate::slurm::LocalExceptions;
 use super::delta::{DeltaArcIter, PayloadDelta};
 use super::snapshot::{PayloadSnapshot, SnapshotArcIter};
@@ -55,10 +57,13 @@ impl SharedHistory {
     /// The method returns whether it has indeed added a new version.
     pub fn update(
         &self,
-        report: ValidationReport,
+        mut report: ValidationReport,
         exceptions: &LocalExceptions,
         mut metrics: Metrics
     ) -> bool {
+        let objects = report.take_objects().map(|objects| {
+            Arc::new(objects.into_list())
+        });
         let snapshot = report.into_snapshot(
             exceptions, &mut metrics,
         );
@@ -74,6 +79,7 @@ impl SharedHistory {
 
         let mut history = self.write();
         history.metrics = Some(metrics.into());
+        history.objects = objects;
         let res = if let Some(delta) = delta {
             // Data has changed.
             info!(
@@ -98,6 +104,11 @@ impl SharedHistory {
         res
     }
 
+    /// Updates the re-published RRDP data.
+    pub fn update_rrdp_publication(&self, publication: RrdpPublication) {
+        self.write().rrdp_publication = Some(publication.into());
+    }
+
     /// Marks the beginning of an update cycle.
     pub fn mark_update_start(&self) {
         self.write().last_update_start = Utc::now();
@@ -205,6 +216,12 @@ pub struct PayloadHistory {
     /// The current metrics.
     metrics: Option<Arc<Metrics>>,
 
+    /// The report on all objects of the last validation run if kept.
+    objects: Option<Arc<ObjectList>>,
+
+    /// The re-published RRDP data if enabled.
+    rrdp_publication: Option<Arc<RrdpPublication>>,
+
     /// The session ID.
     session: u64,
 
@@ -248,6 +265,8 @@ impl PayloadHistory {
             current: None,
             deltas: VecDeque::with_capacity(config.history_size),
             metrics: None,
+            objects: None,
+            rrdp_publication: None,
             session: {
                 SystemTime::now()
                     .duration_since(SystemTime::UNIX_EPOCH).unwrap()
@@ -402,6 +421,16 @@ impl PayloadHistory {
         self.metrics.clone()
     }
 
+    /// Returns the report on all objects of the last run if available.
+    pub fn objects(&self) -> Option<Arc<ObjectList>> {
+        self.objects.clone()
+    }
+
+    /// Returns the re-published RRDP data if available.
+    pub fn rrdp_publication(&self) -> Option<Arc<RrdpPublication>> {
+        self.rrdp_publication.clone()
+    }
+
     /// Returns the time the last update was started.
     pub fn last_update_start(&self) -> DateTime<Utc> {
         self.last_update_start
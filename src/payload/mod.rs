@@ -7,7 +7,10 @@
 //! collection of the two plus additional information is the
 //! [`PayloadHistory`] or, wrapped in an arc, [`SharedHistory`].
 
+pub use self::cas::{CaEntry, CaIndex};
+pub use self::contact::{Contact, VCard};
 pub use self::delta::{DeltaArcIter, PayloadDelta};
+pub use self::expiry::{ExpiryEvent, ExpiryType, PointExpiry};
 pub use self::history::{PayloadHistory, SharedHistory};
 pub use self::info::PayloadInfo;
 pub use self::snapshot::{
@@ -16,7 +19,10 @@ pub use self::snapshot::{
 };
 pub use self::validation::ValidationReport;
 
+mod cas;
+mod contact;
 mod delta;
+mod expiry;
 mod history;
 mod info;
 mod validation;
@@ -7,10 +7,14 @@
 use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use rpki::repository::x509::Time;
+use rpki::resources::{Asn, Prefix};
 use rpki::rtr::payload::{
     Aspa, PayloadRef, PayloadType, RouteOrigin, RouterKey
 };
 use rpki::rtr::server::PayloadSet;
+use super::cas::CaIndex;
+use super::contact::Contact;
+use super::expiry::PointExpiry;
 use super::info::PayloadInfo;
 
 
@@ -29,6 +33,18 @@ pub struct PayloadSnapshot {
     /// The AS providers,
     aspas: PayloadCollection<Aspa>,
 
+    /// The contacts from Ghostbuster Records.
+    #[cfg_attr(feature = "arbitrary", arbitrary(default))]
+    contacts: Vec<Contact>,
+
+    /// The expiry times of the valid publication points.
+    #[cfg_attr(feature = "arbitrary", arbitrary(default))]
+    expiry: Vec<PointExpiry>,
+
+    /// The CAs of the valid publication points.
+    #[cfg_attr(feature = "arbitrary", arbitrary(default))]
+    cas: CaIndex,
+
     /// The time when this snapshot was created.
     created: DateTime<Utc>,
 
@@ -45,6 +61,9 @@ impl Default for PayloadSnapshot {
             origins: Default::default(),
             router_keys: Default::default(),
             aspas: Default::default(),
+            contacts: Default::default(),
+            expiry: Default::default(),
+            cas: Default::default(),
             created: Utc::now(),
             refresh: None
         }
@@ -57,12 +76,18 @@ impl PayloadSnapshot {
         origins: impl Iterator<Item = (RouteOrigin, PayloadInfo)>,
         router_keys: impl Iterator<Item = (RouterKey, PayloadInfo)>,
         aspas: impl Iterator<Item = (Aspa, PayloadInfo)>,
+        contacts: Vec<Contact>,
+        expiry: Vec<PointExpiry>,
+        cas: CaIndex,
         refresh: Option<Time>
     ) -> Self {
         Self {
             origins: PayloadCollection::from_iter(origins),
             router_keys: PayloadCollection::from_iter(router_keys),
             aspas: PayloadCollection::from_iter(aspas),
+            contacts,
+            expiry,
+            cas,
             created: Utc::now(),
             refresh,
         }
@@ -140,6 +165,35 @@ impl PayloadSnapshot {
         self.aspas.iter_payload()
     }
 
+    /// Returns the contacts from all Ghostbuster Records.
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// Returns the expiry times of all valid publication points.
+    pub fn expiry(&self) -> &[PointExpiry] {
+        &self.expiry
+    }
+
+    /// Returns the CAs of all valid publication points.
+    pub fn cas(&self) -> &CaIndex {
+        &self.cas
+    }
+
+    /// Returns an iterator over the contacts of CAs overlapping a prefix.
+    pub fn prefix_contacts(
+        &self, prefix: Prefix
+    ) -> impl Iterator<Item = &Contact> + '_ {
+        self.contacts.iter().filter(move |item| item.covers_prefix(prefix))
+    }
+
+    /// Returns an iterator over the contacts of CAs holding an AS number.
+    pub fn asn_contacts(
+        &self, asn: Asn
+    ) -> impl Iterator<Item = &Contact> + '_ {
+        self.contacts.iter().filter(move |item| item.covers_asn(asn))
+    }
+
     /// Returns an iterator over the payload of a shared snapshot.
     pub fn arc_iter(self: Arc<Self>) -> SnapshotArcIter {
         SnapshotArcIter::new(self)
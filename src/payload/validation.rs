@@ -17,6 +17,7 @@ use std::cmp;
 use std::collections::hash_map;
 use std::collections::HashMap;
 use std::sync::Arc;
+use bytes::Bytes;
 use crossbeam_queue::SegQueue;
 use log::{info, warn};
 use rpki::uri;
@@ -33,10 +34,17 @@ use rpki::resources::{Asn, Prefix, SmallAsnSet};
 use rpki::rtr::payload::{Aspa, RouteOrigin, RouterKey};
 use rpki::rtr::pdu::{ProviderAsns, RouterKeyInfo};
 use crate::config::{Config, FilterPolicy};
-use crate::engine::{CaCert, Engine, ProcessPubPoint, ProcessRun};
+use crate::engine::{
+    CaCert, Engine, ObjectOutcome, ObjectStatus, ObjectType,
+    ProcessPubPoint, ProcessRun
+};
 use crate::error::{Failed, RunFailed};
 use crate::metrics::{Metrics, PayloadMetrics, VrpMetrics};
+use crate::report::{ObjectReport, PointReport};
 use crate::slurm::LocalExceptions;
+use super::cas::{CaEntry, CaIndex};
+use super::contact::{Contact, VCard};
+use super::expiry::PointExpiry;
 use super::info::{PayloadInfo, PublishInfo};
 use super::snapshot::PayloadSnapshot;
 
@@ -52,6 +60,12 @@ pub struct ValidationReport {
     /// its data to this queue.
     pub_points: SegQueue<PubPoint>,
 
+    /// The expiry times of all valid publication points.
+    expiry: SegQueue<PointExpiry>,
+
+    /// The CAs of all valid publication points.
+    cas: SegQueue<CaEntry>,
+
     /// Filter for invalid resources.
     ///
     /// If a publication point is rejected, the resources from its CA
@@ -75,6 +89,14 @@ pub struct ValidationReport {
 
     /// How are we dealing with unsafe VRPs?
     unsafe_vrps: FilterPolicy,
+
+    /// How are we dealing with unsafe VRPs for TALs overriding the policy?
+    ///
+    /// The keys are TAL names.
+    tal_unsafe_vrps: HashMap<String, FilterPolicy>,
+
+    /// The report on all objects if we are to keep one.
+    objects: Option<ObjectReport>,
 }
 
 impl ValidationReport {
@@ -82,13 +104,27 @@ impl ValidationReport {
     pub fn new(config: &Config) -> Self {
         ValidationReport {
             pub_points: Default::default(),
+            expiry: Default::default(),
+            cas: Default::default(),
             rejected: Default::default(),
-            log_rejected: config.unsafe_vrps.log(),
+            log_rejected: {
+                config.unsafe_vrps.log()
+                || config.tal_policies.values().any(|policy| {
+                    policy.unsafe_vrps.map(FilterPolicy::log)
+                        .unwrap_or(false)
+                })
+            },
             enable_bgpsec: config.enable_bgpsec,
             enable_aspa: config.enable_aspa,
             limit_v4_len: config.limit_v4_len,
             limit_v6_len: config.limit_v6_len,
             unsafe_vrps: config.unsafe_vrps,
+            tal_unsafe_vrps: {
+                config.tal_policies.iter().filter_map(|(name, policy)| {
+                    policy.unsafe_vrps.map(|value| (name.clone(), value))
+                }).collect()
+            },
+            objects: config.object_report.then(ObjectReport::new),
         }
     }
 
@@ -104,6 +140,11 @@ impl ValidationReport {
         Ok((report, metrics))
     }
 
+    /// Takes the report on all objects out of the report if there is one.
+    pub fn take_objects(&mut self) -> Option<ObjectReport> {
+        self.objects.take()
+    }
+
     /// Converts the report into a payload snapshot.
     pub fn into_snapshot(
         self,
@@ -111,12 +152,17 @@ impl ValidationReport {
         metrics: &mut Metrics,
     ) -> PayloadSnapshot {
         let mut builder = SnapshotBuilder::new(
-            self.rejected.finalize(), self.unsafe_vrps,
-            exceptions,
+            self.rejected.finalize(), exceptions,
         );
         while let Some(point) = self.pub_points.pop() {
             builder.process_pub_point(point, metrics)
         }
+        while let Some(expiry) = self.expiry.pop() {
+            builder.expiry.push(expiry)
+        }
+        while let Some(ca) = self.cas.pop() {
+            builder.cas.insert(ca)
+        }
         builder.finalize(metrics)
     }
 }
@@ -126,15 +172,23 @@ impl<'a> ProcessRun for &'a ValidationReport {
 
     fn process_ta(
         &self,
-        _tal: &Tal, _uri: &TalUri, cert: &CaCert,
+        tal: &Tal, _uri: &TalUri, cert: &CaCert,
         tal_index: usize,
     ) -> Result<Option<Self::PubPoint>, Failed> {
+        let unsafe_vrps = self.tal_unsafe_vrps.get(
+            tal.info().name()
+        ).copied().unwrap_or(self.unsafe_vrps);
         Ok(Some(
             PubPointProcessor {
                 report: self,
-                pub_point: PubPoint::new_ta(cert, tal_index),
+                pub_point: PubPoint::new_ta(cert, tal_index, unsafe_vrps),
                 validity: cert.cert().validity(),
                 point_stale: cert.cert().validity().not_after(),
+                expiry: PointExpiry::new(cert),
+                ca: CaEntry::new(cert),
+                objects: self.objects.as_ref().map(|objects| {
+                    objects.pub_point(cert)
+                }),
             }
         ))
     }
@@ -161,6 +215,15 @@ pub struct PubPointProcessor<'a> {
 
     /// When will this publication point become stale.
     point_stale: Time,
+
+    /// The expiry times of the objects of the publication point.
+    expiry: PointExpiry,
+
+    /// The CA of the publication point.
+    ca: CaEntry,
+
+    /// The outcomes of all objects if we keep them.
+    objects: Option<PointReport<'a>>,
 }
 
 impl<'a> ProcessPubPoint for PubPointProcessor<'a> {
@@ -175,6 +238,7 @@ impl<'a> ProcessPubPoint for PubPointProcessor<'a> {
         );
         self.validity = self.validity.trim(manifest);
         self.point_stale = cmp::min(self.point_stale, stale);
+        self.expiry.update_manifest(manifest.not_after());
     }
 
     fn want(&self, _uri: &uri::Rsync) -> Result<bool, Failed> {
@@ -194,6 +258,11 @@ impl<'a> ProcessPubPoint for PubPointProcessor<'a> {
                 point_stale: cmp::min(
                     self.point_stale, cert.cert().validity().not_after()
                 ),
+                expiry: PointExpiry::new(cert),
+                ca: CaEntry::new(cert),
+                objects: self.report.objects.as_ref().map(|objects| {
+                    objects.pub_point(cert)
+                }),
             }
         ))
     }
@@ -257,6 +326,16 @@ impl<'a> ProcessPubPoint for PubPointProcessor<'a> {
         cert: ResourceCert,
         route: RouteOriginAttestation
     ) -> Result<(), Failed> {
+        self.expiry.add_roa(
+            cert.signed_object().cloned(),
+            cmp::min(
+                cmp::min(
+                    cert.validity().not_after(), self.validity.not_after()
+                ),
+                self.point_stale
+            ),
+            route.v4_addrs().iter().count() + route.v6_addrs().iter().count()
+        );
         if self.pub_point.add_roa(
             route,
             Arc::new(PublishInfo::signed_object(
@@ -288,18 +367,74 @@ impl<'a> ProcessPubPoint for PubPointProcessor<'a> {
         Ok(())
     }
 
+    fn process_gbr(
+        &mut self,
+        uri: &uri::Rsync,
+        cert: ResourceCert,
+        content: Bytes
+    ) -> Result<(), Failed> {
+        match VCard::parse(&content) {
+            Some(card) => {
+                self.pub_point.contacts.push(
+                    Contact::new(uri.clone(), &cert, card)
+                )
+            }
+            None => {
+                warn!("{}: GBR does not contain a vCard.", uri);
+            }
+        }
+        Ok(())
+    }
+
+    fn object_status(&mut self, outcome: ObjectOutcome) {
+        if matches!(
+            outcome.status, ObjectStatus::Valid | ObjectStatus::Stale
+        ) {
+            if let Some(validity) = outcome.validity {
+                match outcome.object_type {
+                    ObjectType::Manifest => {
+                        self.expiry.update_manifest(validity.not_after())
+                    }
+                    ObjectType::Crl => {
+                        self.expiry.set_crl(
+                            outcome.uri.clone(), validity.not_after()
+                        )
+                    }
+                    _ => { }
+                }
+            }
+        }
+        if let Some(objects) = self.objects.as_mut() {
+            objects.object_status(outcome)
+        }
+    }
+
     fn restart(&mut self) -> Result<(), Failed> {
         self.pub_point.restart();
+        self.expiry.restart();
+        if let Some(objects) = self.objects.as_mut() {
+            objects.restart()?
+        }
         Ok(())
     }
 
     fn commit(self) {
+        if let Some(objects) = self.objects {
+            objects.commit()
+        }
+        if self.expiry.is_known() {
+            self.report.expiry.push(self.expiry);
+        }
+        self.report.cas.push(self.ca);
         if !self.pub_point.is_empty() {
             self.report.pub_points.push(self.pub_point);
         }
     }
 
     fn cancel(self, cert: &CaCert) {
+        if let Some(objects) = self.objects {
+            objects.cancel(cert)
+        }
         if self.report.log_rejected {
             warn!(
                 "CA for {} rejected, resources marked as unsafe:",
@@ -337,6 +472,9 @@ pub struct PubPoint {
     /// The list of valid ASPA payload.
     aspas: Vec<PubAspa>,
 
+    /// The contacts from valid Ghostbuster Records.
+    contacts: Vec<Contact>,
+
     /// The time when the publication point needs to be refreshed.
     refresh: Time,
 
@@ -350,25 +488,34 @@ pub struct PubPoint {
 
     /// The index of the repository containing the payload in the metrics.
     repository_index: Option<usize>,
+
+    /// How to deal with unsafe VRPs under the TAL.
+    unsafe_vrps: FilterPolicy,
 }
 
 impl PubPoint {
     /// Creates a new publication point.
-    fn new(refresh: Time, tal_index: usize) -> Self {
+    fn new(
+        refresh: Time, tal_index: usize, unsafe_vrps: FilterPolicy
+    ) -> Self {
         PubPoint {
             origins: Vec::new(),
             router_keys: Vec::new(),
             aspas: Vec::new(),
+            contacts: Vec::new(),
             refresh,
             orig_refresh: refresh,
             tal_index,
             repository_index: None,
+            unsafe_vrps,
         }
     }
 
     /// Creates a new publication point for a trust anchor CA.
-    fn new_ta(cert: &CaCert, tal_index: usize) -> Self {
-        Self::new(cert.cert().validity().not_after(), tal_index)
+    fn new_ta(
+        cert: &CaCert, tal_index: usize, unsafe_vrps: FilterPolicy
+    ) -> Self {
+        Self::new(cert.cert().validity().not_after(), tal_index, unsafe_vrps)
     }
 
     /// Creates a new publication for a regular CA.
@@ -378,6 +525,7 @@ impl PubPoint {
                 parent.refresh, cert.cert().validity().not_after()
             ),
             parent.tal_index,
+            parent.unsafe_vrps,
         )
     }
 
@@ -386,6 +534,7 @@ impl PubPoint {
         self.origins.is_empty()
         && self.router_keys.is_empty()
         && self.aspas.is_empty()
+        && self.contacts.is_empty()
     }
 
     /// Updates the refresh time to be no later than the given time.
@@ -398,6 +547,7 @@ impl PubPoint {
         self.origins.clear();
         self.router_keys.clear();
         self.aspas.clear();
+        self.contacts.clear();
         self.refresh = self.orig_refresh;
     }
 
@@ -598,13 +748,19 @@ struct SnapshotBuilder<'a> {
     /// The key is the customer ASN.
     aspas: HashMap<Asn, (SmallAsnSet, PayloadInfo)>,
 
+    /// The collected contacts.
+    contacts: Vec<Contact>,
+
+    /// The expiry times of the publication points.
+    expiry: Vec<PointExpiry>,
+
+    /// The CAs of the publication points.
+    cas: CaIndex,
+
     /// The list of rejected resources.
     rejected: RejectedResources,
 
-    /// How are we dealing with unsafe VRPs?
-    unsafe_vrps: FilterPolicy,
-
-    /// Are unsafe VRPs present?
+    /// Are unsafe VRPs present under a policy that wants them logged?
     unsafe_vrps_present: bool,
 
     /// The time when this snapshot needs to be refreshed at the latest.
@@ -616,16 +772,16 @@ struct SnapshotBuilder<'a> {
 impl<'a> SnapshotBuilder<'a> {
     fn new(
         rejected: RejectedResources,
-        unsafe_vrps: FilterPolicy,
         exceptions: &'a LocalExceptions,
-        
     ) -> Self {
         Self {
             origins: Default::default(),
             router_keys: Default::default(),
             aspas: Default::default(),
+            contacts: Default::default(),
+            expiry: Default::default(),
+            cas: Default::default(),
             rejected,
-            unsafe_vrps,
             unsafe_vrps_present: false,
             refresh: None,
             exceptions,
@@ -641,7 +797,7 @@ impl<'a> SnapshotBuilder<'a> {
         );
         self.update_refresh(point.refresh);
         point.origins.into_iter().for_each(|item| {
-            self.process_origin(item, &mut metrics)
+            self.process_origin(item, point.unsafe_vrps, &mut metrics)
         });
         point.router_keys.into_iter().for_each(|item| {
             self.process_key(item, &mut metrics)
@@ -649,6 +805,7 @@ impl<'a> SnapshotBuilder<'a> {
         point.aspas.into_iter().for_each(|item| {
             self.process_aspa(item, &mut metrics)
         });
+        self.contacts.extend(point.contacts);
     }
 
     /// Updates the refresh time.
@@ -660,15 +817,18 @@ impl<'a> SnapshotBuilder<'a> {
     }
 
     fn process_origin(
-        &mut self, origin: PubRouteOrigin, metrics: &mut AllVrpMetrics,
+        &mut self, origin: PubRouteOrigin, unsafe_vrps: FilterPolicy,
+        metrics: &mut AllVrpMetrics,
     ) {
         let v4 = origin.origin.is_v4();
         metrics.update_origin(v4, |m| m.valid += 1);
 
         // Is the prefix in the rejected resources?
         if !self.rejected.keep_prefix(origin.origin.prefix.prefix()) {
-            self.unsafe_vrps_present = true;
-            match self.unsafe_vrps {
+            if unsafe_vrps.log() {
+                self.unsafe_vrps_present = true;
+            }
+            match unsafe_vrps {
                 FilterPolicy::Accept => {
                     // Don’t count, don’t warn ...
                 }
@@ -770,7 +930,7 @@ impl<'a> SnapshotBuilder<'a> {
     }
 
     fn finalize(mut self, metrics: &mut Metrics) -> PayloadSnapshot {
-        if self.unsafe_vrps_present && self.unsafe_vrps.log()  {
+        if self.unsafe_vrps_present {
             warn!(
                 "For more information on unsafe VRPs, see \
                  https://routinator.docs.nlnetlabs.nl\
@@ -850,6 +1010,9 @@ impl<'a> SnapshotBuilder<'a> {
                     }
                 }
             ),
+            self.contacts,
+            self.expiry,
+            self.cas,
             self.refresh,
         )
     }
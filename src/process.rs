@@ -4,6 +4,7 @@ use std::{fs, io, mem, process};
 use std::future::Future;
 use std::io::Write;
 use std::net::TcpListener;
+use std::process::Child;
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
 use std::sync::{Arc, OnceLock};
@@ -15,6 +16,7 @@ use crate::config::{Config, LogTarget};
 use crate::error::Failed;
 use crate::utils::date::{format_iso_date, format_local_iso_date};
 use crate::utils::fmt::WriteOrPanic;
+use crate::utils::net::ListenSockets;
 use crate::utils::sync::{Mutex, RwLock};
 
 
@@ -26,7 +28,7 @@ use crate::utils::sync::{Mutex, RwLock};
 /// platform independent way.
 pub struct Process {
     config: Config,
-    service: Option<ServiceImpl>,
+    service: ServiceImpl,
 }
 
 impl Process {
@@ -40,7 +42,7 @@ impl Process {
     ///
     pub fn new(config: Config) -> Self {
         Process { 
-            service: Some(ServiceImpl::new(&config)),
+            service: ServiceImpl::new(&config),
             config
         }
     }
@@ -122,7 +124,7 @@ impl Process {
     /// This method may encounter and log errors after detaching. You should
     /// therefore call `switch_logging` before this method.
     pub fn setup_service(&mut self, detach: bool) -> Result<(), Failed> {
-        self.service.as_mut().unwrap().setup_service(&self.config, detach)
+        self.service.setup_service(&self.config, detach)
     }
 
     /// Drops privileges.
@@ -131,7 +133,7 @@ impl Process {
     /// elevated privileges. This may include loosing root or system
     /// administrator permissions and change the file system root.
     pub fn drop_privileges(&mut self) -> Result<(), Failed> {
-        self.service.take().unwrap().drop_privileges(&mut self.config)
+        self.service.drop_privileges(&mut self.config)
     }
 
     /// Returns the first listen socket passed into the process if available.
@@ -162,6 +164,45 @@ impl Process {
 }
 
 
+/// # Upgrade
+///
+/// When upgrading, the running process starts a new process with the same
+/// arguments and hands its listening sockets and the PID file over to it.
+/// It keeps serving until the new process has finished its first
+/// validation run and tells it to shut down via SIGTERM.
+///
+impl Process {
+    /// Returns whether we have been started to take over from a process.
+    pub fn is_upgrade(&self) -> bool {
+        self.service.is_upgrade()
+    }
+
+    /// Returns the listening sockets handed over to us.
+    ///
+    /// Listeners should be bound through the returned value. If we
+    /// haven’t been started for an upgrade, it starts out empty.
+    pub fn listen_sockets(&mut self) -> ListenSockets {
+        ListenSockets::new(self.service.take_listen_sockets())
+    }
+
+    /// Starts a new process to take over from us.
+    ///
+    /// The sockets listened on in `sockets` are handed over to the new
+    /// process.
+    pub fn upgrade(&self, sockets: &ListenSockets) -> Result<Child, Failed> {
+        self.service.upgrade(&self.config, sockets)
+    }
+
+    /// Tells the process we are taking over from to shut down.
+    ///
+    /// Does nothing if we haven’t been started for an upgrade or have
+    /// already done so.
+    pub fn finish_upgrade(&mut self) {
+        self.service.finish_upgrade()
+    }
+}
+
+
 /// # Directory Management
 ///
 impl Process {
@@ -629,30 +670,217 @@ use self::noop::ServiceImpl;
 ///
 #[cfg(unix)]
 mod unix {
+    use std::env;
     use std::env::set_current_dir;
-    use std::ffi::CString;
-    use std::os::unix::io::RawFd;
-    use std::path::Path;
-    use log::error;
+    use std::ffi::{CString, OsString};
+    use std::net::TcpListener;
+    use std::os::unix::io::{AsRawFd, BorrowedFd, FromRawFd, RawFd};
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Command};
+    use log::{error, info, warn};
     use nix::libc;
-    use nix::fcntl::{flock, open, FlockArg, OFlag};
+    use nix::fcntl::{
+        fcntl, flock, open, FcntlArg, FdFlag, FlockArg, OFlag
+    };
+    use nix::sys::signal::{kill, Signal};
+    use nix::sys::socket::{getsockopt, sockopt};
     use nix::unistd::{
-        chown, chroot, fork, getpid, setgid, setuid, write, Gid, Uid
+        chown, chroot, fork, ftruncate, getpid, lseek, setgid, setuid,
+        write, Gid, Pid, Uid, Whence,
     };
     use nix::sys::stat::Mode;
     use crate::config::Config;
     use crate::error::Failed;
+    use crate::utils::net::ListenSockets;
+
+    /// The environment variable with the PID of the process to take over.
+    const UPGRADE_PID_VAR: &str = "ROUTINATOR_UPGRADE_PID";
+
+    /// The environment variable with the handed over listening sockets.
+    const UPGRADE_LISTEN_VAR: &str = "ROUTINATOR_UPGRADE_LISTEN_FDS";
+
+    /// The environment variable with the handed over PID file.
+    const UPGRADE_PID_FILE_VAR: &str = "ROUTINATOR_UPGRADE_PID_FILE";
 
     #[derive(Debug, Default)]
     pub struct ServiceImpl {
         pid_file: Option<RawFd>,
         uid: Option<Uid>,
         gid: Option<Gid>,
+
+        /// The working directory we were started in.
+        start_dir: Option<PathBuf>,
+
+        /// The process to tell to shut down once we have taken over.
+        upgrade_from: Option<Pid>,
+
+        /// The listening sockets handed over to us.
+        listen_fds: Vec<RawFd>,
+
+        /// The PID file handed over to us.
+        upgrade_pid_file: Option<RawFd>,
     }
 
     impl ServiceImpl {
         pub fn new(_config: &Config) -> Self {
-            ServiceImpl::default()
+            let mut res = ServiceImpl {
+                start_dir: env::current_dir().ok(),
+                .. Default::default()
+            };
+            res.take_upgrade_env();
+            res
+        }
+
+        /// Takes over what the process we are upgrading from handed to us.
+        ///
+        /// This also removes the environment variables used for that so
+        /// they don’t leak into child processes.
+        fn take_upgrade_env(&mut self) {
+            let pid = env::var(UPGRADE_PID_VAR).ok();
+            let listen = env::var(UPGRADE_LISTEN_VAR).unwrap_or_default();
+            let pid_file = env::var(UPGRADE_PID_FILE_VAR).ok();
+            env::remove_var(UPGRADE_PID_VAR);
+            env::remove_var(UPGRADE_LISTEN_VAR);
+            env::remove_var(UPGRADE_PID_FILE_VAR);
+
+            match pid.map(|pid| pid.parse()) {
+                Some(Ok(pid)) => self.upgrade_from = Some(Pid::from_raw(pid)),
+                Some(Err(_)) => {
+                    warn!("Ignoring invalid {} environment variable.",
+                        UPGRADE_PID_VAR
+                    );
+                    return
+                }
+                None => return,
+            }
+            for fd in listen.split(',').filter(|fd| !fd.is_empty()) {
+                match fd.parse() {
+                    Ok(fd) if Self::is_listener(fd) => {
+                        self.listen_fds.push(fd)
+                    }
+                    _ => {
+                        warn!(
+                            "Ignoring invalid listening socket '{}' \
+                             handed over for upgrade.",
+                            fd
+                        );
+                    }
+                }
+            }
+            self.upgrade_pid_file = pid_file.and_then(|fd| fd.parse().ok());
+        }
+
+        /// Returns whether `fd` is a listening socket.
+        fn is_listener(fd: RawFd) -> bool {
+            // Safety: The file descriptor is only used for the duration of
+            // the call. If it isn’t open, the call fails.
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            getsockopt(&fd, sockopt::AcceptConn).unwrap_or(false)
+        }
+
+        pub fn is_upgrade(&self) -> bool {
+            self.upgrade_from.is_some()
+        }
+
+        pub fn take_listen_sockets(&mut self) -> Vec<TcpListener> {
+            let mut res = Vec::new();
+            for fd in self.listen_fds.drain(..) {
+                // Safety: We checked that the file descriptor is a
+                // listening socket and we only take it once.
+                let sock = unsafe { TcpListener::from_raw_fd(fd) };
+                let prepared = fcntl(
+                    fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)
+                ).map_err(Into::into).and_then(|_| {
+                    sock.set_nonblocking(true)
+                });
+                match prepared {
+                    Ok(()) => res.push(sock),
+                    Err(err) => {
+                        warn!(
+                            "Ignoring listening socket handed over for \
+                             upgrade: {}",
+                            err
+                        );
+                    }
+                }
+            }
+            res
+        }
+
+        pub fn upgrade(
+            &self, config: &Config, sockets: &ListenSockets
+        ) -> Result<Child, Failed> {
+            if config.chroot.is_some() {
+                error!("Cannot upgrade: not supported with chroot.");
+                return Err(Failed)
+            }
+            if config.systemd_listen {
+                error!(
+                    "Cannot upgrade: not supported with systemd socket \
+                     activation. Restart the service instead."
+                );
+                return Err(Failed)
+            }
+            let mut args = env::args_os();
+            let program = args.next().unwrap_or_else(|| {
+                OsString::from("routinator")
+            });
+            let mut fds: Vec<_> = sockets.bound().iter().map(|sock| {
+                sock.as_raw_fd()
+            }).collect();
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd.env(UPGRADE_PID_VAR, getpid().to_string());
+            cmd.env(UPGRADE_LISTEN_VAR,
+                fds.iter().map(ToString::to_string).collect::<Vec<_>>()
+                    .join(",")
+            );
+            if let Some(fd) = self.pid_file {
+                cmd.env(UPGRADE_PID_FILE_VAR, fd.to_string());
+                fds.push(fd);
+            }
+            if let Some(dir) = self.start_dir.as_ref() {
+                cmd.current_dir(dir);
+            }
+
+            // Safety: fcntl is async-signal-safe and we don’t allocate.
+            unsafe {
+                cmd.pre_exec(move || {
+                    for &fd in &fds {
+                        fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))?;
+                    }
+                    Ok(())
+                });
+            }
+            cmd.spawn().map_err(|err| {
+                error!(
+                    "Cannot upgrade: failed to start new process: {}", err
+                );
+                Failed
+            })
+        }
+
+        pub fn finish_upgrade(&mut self) {
+            let pid = match self.upgrade_from.take() {
+                Some(pid) => pid,
+                None => return
+            };
+            match kill(pid, Signal::SIGTERM) {
+                Ok(()) => {
+                    info!(
+                        "Upgrade complete, told process {} to shut down.",
+                        pid
+                    );
+                }
+                Err(err) => {
+                    error!(
+                        "Failed to tell process {} to shut down after \
+                         upgrade: {}",
+                        pid, err
+                    );
+                }
+            }
         }
 
         pub fn setup_service(
@@ -690,7 +918,7 @@ mod unix {
         }
 
         pub fn drop_privileges(
-            self, config: &mut Config
+            &mut self, config: &mut Config
         ) -> Result<(), Failed> {
             config.adjust_chroot_paths()?;
             if let Some(path) = config.chroot.as_ref() {
@@ -719,11 +947,17 @@ mod unix {
         }
 
         fn create_pid_file(&mut self, path: &Path) -> Result<(), Failed> {
-            let fd = match open(
-                path,
-                OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC,
-                Mode::from_bits_truncate(0o666)
-            ) {
+            // The lock of the PID file handed over to us belongs to both
+            // processes, so taking it again succeeds.
+            let fd = match self.upgrade_pid_file.take() {
+                Some(fd) => Ok(fd),
+                None => open(
+                    path,
+                    OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC,
+                    Mode::from_bits_truncate(0o666)
+                )
+            };
+            let fd = match fd {
                 Ok(fd) => fd,
                 Err(err) => {
                     error!("Fatal: failed to create PID file {}: {}",
@@ -745,6 +979,14 @@ mod unix {
         fn write_pid_file(&self) -> Result<(), Failed> {
             if let Some(pid_file) = self.pid_file {
                 let pid = format!("{}", getpid());
+                // The file may contain the PID of the process we upgrade
+                // from, so we need to start afresh.
+                if let Err(err) = Self::truncate(pid_file) {
+                    error!(
+                        "Fatal: failed to truncate PID file: {}", err
+                    );
+                    return Err(Failed)
+                }
                 match write(pid_file, pid.as_bytes()) {
                     Ok(len) if len == pid.len() => {}
                     Ok(_) => {
@@ -765,6 +1007,13 @@ mod unix {
             Ok(())
         }
 
+        /// Truncates the file `fd` and moves to its start.
+        fn truncate(fd: RawFd) -> Result<(), nix::Error> {
+            // Safety: The file descriptor stays open during the call.
+            ftruncate(unsafe { BorrowedFd::borrow_raw(fd) }, 0)?;
+            lseek(fd, 0, Whence::SeekSet).map(|_| ())
+        }
+
         fn perform_fork(&self) -> Result<(), Failed> {
             match unsafe { fork() } {
                 Ok(res) => {
@@ -865,8 +1114,12 @@ mod unix {
 
 #[cfg(not(unix))]
 mod noop {
+    use std::net::TcpListener;
+    use std::process::Child;
+    use log::error;
     use crate::error::Failed;
     use crate::config::Config;
+    use crate::utils::net::ListenSockets;
 
     pub struct ServiceImpl;
 
@@ -882,10 +1135,27 @@ mod noop {
         }
 
         pub fn drop_privileges(
-            self, _config: &mut Config
+            &mut self, _config: &mut Config
         ) -> Result<(), Failed> {
             Ok(())
         }
+
+        pub fn is_upgrade(&self) -> bool {
+            false
+        }
+
+        pub fn take_listen_sockets(&mut self) -> Vec<TcpListener> {
+            Vec::new()
+        }
+
+        pub fn upgrade(
+            &self, _config: &Config, _sockets: &ListenSockets
+        ) -> Result<Child, Failed> {
+            error!("Cannot upgrade: not supported on this platform.");
+            Err(Failed)
+        }
+
+        pub fn finish_upgrade(&mut self) { }
  
         pub fn prepare_cache_dir(_config: &Config) -> Result<(), Failed> {
             Ok(())
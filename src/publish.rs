@@ -0,0 +1,585 @@
+//! Re-publishing the store via RRDP.
+//!
+//! If configured, the content of the store, i.e., all objects covered by
+//! valid manifests, is made available via RRDP on the HTTP server. This
+//! allows other relying party software to use a local Routinator instance
+//! as its source of RPKI data instead of accessing the global RPKI.
+//!
+//! Each upstream RRDP repository is re-published as a repository of its
+//! own. Its notification file is available under `/rrdp/notify/` followed
+//! by authority and path of the upstream rpkiNotify URI. This makes it
+//! possible for downstream relying parties to simply map the upstream URIs
+//! to the HTTP server. Snapshot and delta files live under `/rrdp/data/`.
+//! The accepted trust anchor certificate of each TAL is available as
+//! `/rrdp/ta/<name>.cer` and a TAL pointing to it as `/rrdp/tal/<name>.tal`.
+//!
+//! Only repositories retrieved via RRDP are re-published. Their session and
+//! serial are based on the state of the upstream repository kept by the
+//! RRDP collector: The session ID is derived from the upstream session ID
+//! and the serial number starts at the upstream serial number. It is then
+//! increased by one whenever the upstream serial number and the stored
+//! content have changed. As a consequence, the re-published serial never
+//! overtakes the upstream serial and a restarted server will pick up
+//! where it left off.
+
+use std::io;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use bytes::Bytes;
+use log::{debug, info};
+use rpki::crypto::DigestAlgorithm;
+use rpki::repository::tal::Tal;
+use rpki::rrdp::{
+    Delta, DeltaElement, DeltaInfo, Hash, NotificationFile, PublishElement,
+    Snapshot, UpdateElement, UriAndHash, WithdrawElement,
+};
+use rpki::uri;
+use rpki::util::base64;
+use uuid::Uuid;
+use crate::engine::Engine;
+use crate::error::Failed;
+use crate::utils::str::append_hex;
+
+
+//------------ RrdpPublication -----------------------------------------------
+
+/// The content of the store re-published via RRDP.
+///
+/// A new value is created after each validation run from the previous one
+/// via [`update`][Self::update]. The files to serve can be looked up via
+/// [`get`][Self::get].
+#[derive(Clone, Debug, Default)]
+pub struct RrdpPublication {
+    /// The published repositories by their upstream rpkiNotify URI.
+    repositories: HashMap<uri::Https, Arc<Repository>>,
+
+    /// The files to serve by their path relative to `/rrdp/`.
+    files: HashMap<String, PublishedFile>,
+}
+
+impl RrdpPublication {
+    /// Creates the next version of the publication.
+    ///
+    /// The `base_uri` is the URI of the HTTP server as seen by clients. For
+    /// each repository, at most `keep` deltas are retained.
+    #[allow(clippy::mutable_key_type)]
+    pub fn update(
+        current: Option<&Self>,
+        base_uri: &uri::Https,
+        keep: usize,
+        engine: &Engine,
+    ) -> Result<Self, Failed> {
+        let base = Base::new(base_uri);
+        let mut res = Self::default();
+        for state in engine.rrdp_states()? {
+            let current = current.and_then(|current| {
+                current.repositories.get(&state.rpki_notify)
+            });
+            let repository = match current {
+                Some(current)
+                    if current.is_current(state.session, state.serial)
+                => {
+                    current.clone()
+                }
+                Some(current) => {
+                    debug!(
+                        "Updating RRDP publication of {}.",
+                        state.rpki_notify
+                    );
+                    Arc::new(current.next(
+                        state.session, state.serial,
+                        engine.rrdp_objects(&state.rpki_notify)?,
+                        keep,
+                    ))
+                }
+                None => {
+                    debug!(
+                        "Creating RRDP publication of {}.",
+                        state.rpki_notify
+                    );
+                    Arc::new(Repository::new(
+                        state.session, state.serial,
+                        engine.rrdp_objects(&state.rpki_notify)?,
+                    ))
+                }
+            };
+            res.add_repository(&base, state.rpki_notify, repository);
+        }
+        for (tal, cert) in engine.ta_certs()? {
+            res.add_tal(&base, tal, cert);
+        }
+        info!(
+            "Re-publishing {} RRDP repositories.", res.repositories.len()
+        );
+        Ok(res)
+    }
+
+    /// Returns the file for the given path relative to `/rrdp/`.
+    pub fn get(&self, path: &str) -> Option<&PublishedFile> {
+        self.files.get(path)
+    }
+
+    /// Adds the files for a repository.
+    fn add_repository(
+        &mut self,
+        base: &Base,
+        rpki_notify: uri::Https,
+        repository: Arc<Repository>,
+    ) {
+        let mut dir = String::from("data/");
+        append_hex(
+            DigestAlgorithm::sha256().digest(
+                rpki_notify.as_slice()
+            ).as_ref(),
+            &mut dir
+        );
+        let dir = format!("{}/{}", dir, repository.session);
+
+        let deltas = repository.deltas.iter().map(|delta| {
+            let path = format!("{}/{}/delta.xml", dir, delta.serial);
+            let info = DeltaInfo::new(
+                delta.serial, base.join(&path), delta.hash
+            );
+            self.files.insert(path, PublishedFile::xml(delta.data.clone()));
+            info
+        }).collect();
+
+        let path = format!("{}/{}/snapshot.xml", dir, repository.serial);
+        let notification = NotificationFile::new(
+            repository.session, repository.serial,
+            UriAndHash::new(base.join(&path), repository.snapshot_hash),
+            deltas,
+        );
+        self.files.insert(
+            path, PublishedFile::xml(repository.snapshot.clone())
+        );
+        self.files.insert(
+            format!(
+                "notify/{}{}",
+                rpki_notify.canonical_authority(), rpki_notify.path()
+            ),
+            PublishedFile::xml(
+                render(|target| notification.write_xml(target)).0
+            )
+        );
+        self.repositories.insert(rpki_notify, repository);
+    }
+
+    /// Adds the trust anchor certificate and TAL for a TAL.
+    fn add_tal(&mut self, base: &Base, tal: &Tal, cert: Bytes) {
+        let name = tal.info().name();
+        let path = format!("ta/{}.cer", name);
+        self.files.insert(
+            format!("tal/{}.tal", name),
+            PublishedFile::new(
+                "text/plain;charset=utf-8",
+                format!(
+                    "{}{}\n\n{}\n",
+                    base.0, path,
+                    base64::Xml.encode(&tal.key_info().to_info_bytes())
+                ).into()
+            )
+        );
+        self.files.insert(
+            path, PublishedFile::new("application/pkix-cert", cert)
+        );
+    }
+}
+
+
+//------------ PublishedFile -------------------------------------------------
+
+/// A file served as part of the RRDP publication.
+#[derive(Clone, Debug)]
+pub struct PublishedFile {
+    /// The content type of the file.
+    content_type: &'static str,
+
+    /// The content of the file.
+    data: Bytes,
+}
+
+impl PublishedFile {
+    /// Creates a new file from content type and content.
+    fn new(content_type: &'static str, data: Bytes) -> Self {
+        PublishedFile { content_type, data }
+    }
+
+    /// Creates a new RRDP XML file.
+    fn xml(data: Bytes) -> Self {
+        Self::new("application/xml", data)
+    }
+
+    /// Returns the content type of the file.
+    pub fn content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    /// Returns the content of the file.
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+
+//------------ Repository ----------------------------------------------------
+
+/// A single re-published RRDP repository.
+#[derive(Clone, Debug)]
+struct Repository {
+    /// The session ID of the upstream repository this is based on.
+    upstream_session: Uuid,
+
+    /// The serial number of the upstream repository this is based on.
+    upstream_serial: u64,
+
+    /// The session ID of the repository.
+    session: Uuid,
+
+    /// The serial number of the repository.
+    serial: u64,
+
+    /// The published objects.
+    objects: HashMap<uri::Rsync, Bytes>,
+
+    /// The snapshot file.
+    snapshot: Bytes,
+
+    /// The hash of the snapshot file.
+    snapshot_hash: Hash,
+
+    /// The delta files with the newest delta first.
+    deltas: VecDeque<PublishedDelta>,
+}
+
+impl Repository {
+    /// Creates a new repository based on an upstream session and serial.
+    #[allow(clippy::mutable_key_type)]
+    fn new(
+        upstream_session: Uuid,
+        upstream_serial: u64,
+        objects: Vec<(uri::Rsync, Bytes)>,
+    ) -> Self {
+        let session = derive_session(upstream_session);
+        let objects: HashMap<_, _> = objects.into_iter().collect();
+        let (snapshot, snapshot_hash) = render_snapshot(
+            session, upstream_serial, &objects
+        );
+        Repository {
+            upstream_session, upstream_serial,
+            session,
+            serial: upstream_serial,
+            objects, snapshot, snapshot_hash,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Returns whether the repository is based on the given upstream state.
+    fn is_current(
+        &self, upstream_session: Uuid, upstream_serial: u64
+    ) -> bool {
+        self.upstream_session == upstream_session
+            && self.upstream_serial == upstream_serial
+    }
+
+    /// Creates the next version of the repository.
+    ///
+    /// If the upstream session has changed, starts a new session. Otherwise
+    /// adds a delta if the objects have changed and retains at most `keep`
+    /// deltas that together are not larger than the snapshot.
+    #[allow(clippy::mutable_key_type)]
+    fn next(
+        &self,
+        upstream_session: Uuid,
+        upstream_serial: u64,
+        objects: Vec<(uri::Rsync, Bytes)>,
+        keep: usize,
+    ) -> Self {
+        if upstream_session != self.upstream_session
+            || upstream_serial < self.upstream_serial
+        {
+            return Self::new(upstream_session, upstream_serial, objects)
+        }
+        let objects: HashMap<_, _> = objects.into_iter().collect();
+        let elements = delta_elements(&self.objects, &objects);
+        if elements.is_empty() {
+            return Repository { upstream_serial, ..self.clone() }
+        }
+
+        let serial = self.serial + 1;
+        let (snapshot, snapshot_hash) = render_snapshot(
+            self.session, serial, &objects
+        );
+        let delta = Delta::new(self.session, serial, elements);
+        let (data, hash) = render(|target| delta.write_xml(target));
+        let mut deltas = self.deltas.clone();
+        deltas.push_front(PublishedDelta { serial, data, hash });
+        let mut size = 0;
+        let len = deltas.iter().take(keep).take_while(|delta| {
+            size += delta.data.len();
+            size <= snapshot.len()
+        }).count();
+        deltas.truncate(len);
+
+        Repository {
+            upstream_session, upstream_serial,
+            session: self.session,
+            serial,
+            objects, snapshot, snapshot_hash, deltas,
+        }
+    }
+}
+
+
+//------------ PublishedDelta ------------------------------------------------
+
+/// A delta file of a re-published repository.
+#[derive(Clone, Debug)]
+struct PublishedDelta {
+    /// The serial number of the delta.
+    serial: u64,
+
+    /// The delta file.
+    data: Bytes,
+
+    /// The hash of the delta file.
+    hash: Hash,
+}
+
+
+//------------ Base ----------------------------------------------------------
+
+/// The base URI of all published files.
+///
+/// This is the configured URI with a trailing slash and `rrdp/` added.
+struct Base(String);
+
+impl Base {
+    fn new(uri: &uri::Https) -> Self {
+        let mut res = uri.to_string();
+        if !res.ends_with('/') {
+            res.push('/');
+        }
+        res.push_str("rrdp/");
+        Base(res)
+    }
+
+    /// Returns the URI for a path relative to the base.
+    fn join(&self, path: &str) -> uri::Https {
+        // The base is a valid HTTPS URI and we only append paths made of
+        // hex digits, UUIDs, and numbers, so this can’t fail.
+        uri::Https::from_string(
+            format!("{}{}", self.0, path)
+        ).expect("invalid RRDP publication URI")
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Derives our session ID from the upstream session ID.
+///
+/// Using a separate session avoids confusing clients that switch between
+/// the upstream repository and us.
+fn derive_session(upstream: Uuid) -> Uuid {
+    let digest = DigestAlgorithm::sha256().digest(upstream.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest.as_ref()[..16]);
+    uuid::Builder::from_random_bytes(bytes).into_uuid()
+}
+
+/// Renders a file into bytes and returns them with their hash.
+fn render(
+    op: impl FnOnce(&mut Vec<u8>) -> Result<(), io::Error>
+) -> (Bytes, Hash) {
+    let mut data = Vec::new();
+    op(&mut data).expect("writing to vec");
+    let hash = Hash::from_data(&data);
+    (data.into(), hash)
+}
+
+/// Renders the snapshot file for a set of objects.
+#[allow(clippy::mutable_key_type)]
+fn render_snapshot(
+    session: Uuid, serial: u64, objects: &HashMap<uri::Rsync, Bytes>
+) -> (Bytes, Hash) {
+    let snapshot = Snapshot::new(
+        session, serial,
+        objects.iter().map(|(uri, data)| {
+            PublishElement::new(uri.clone(), data.clone())
+        }).collect()
+    );
+    render(|target| snapshot.write_xml(target))
+}
+
+/// Returns the delta elements necessary to get from `old` to `new`.
+#[allow(clippy::mutable_key_type)]
+fn delta_elements(
+    old: &HashMap<uri::Rsync, Bytes>,
+    new: &HashMap<uri::Rsync, Bytes>,
+) -> Vec<DeltaElement> {
+    let mut res = Vec::new();
+    for (uri, data) in new {
+        match old.get(uri) {
+            Some(old_data) if old_data == data => { }
+            Some(old_data) => {
+                res.push(UpdateElement::new(
+                    uri.clone(), Hash::from_data(old_data), data.clone()
+                ).into())
+            }
+            None => {
+                res.push(
+                    PublishElement::new(uri.clone(), data.clone()).into()
+                )
+            }
+        }
+    }
+    for (uri, data) in old {
+        if !new.contains_key(uri) {
+            res.push(
+                WithdrawElement::new(uri.clone(), Hash::from_data(data))
+                    .into()
+            )
+        }
+    }
+    res
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Some unchanging content to make snapshots larger than deltas.
+    static FILLER: [u8; 4096] = [0; 4096];
+
+    fn object(path: &str, data: &'static [u8]) -> (uri::Rsync, Bytes) {
+        (
+            uri::Rsync::from_string(
+                format!("rsync://rpki.example.net/repo/{}", path)
+            ).unwrap(),
+            Bytes::from_static(data)
+        )
+    }
+
+    fn objects(
+        items: &[(&str, &'static [u8])]
+    ) -> Vec<(uri::Rsync, Bytes)> {
+        let mut res = vec![object("filler.cer", &FILLER)];
+        res.extend(items.iter().map(|(path, data)| object(path, data)));
+        res
+    }
+
+    fn delta(data: &Bytes) -> Delta {
+        Delta::parse(data.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn repository_next() {
+        let upstream = Uuid::from_u128(0x1234);
+        let repo = Repository::new(
+            upstream, 5, objects(&[("a.roa", b"a"), ("b.roa", b"b")])
+        );
+        assert_ne!(repo.session, upstream);
+        assert_eq!(repo.session, derive_session(upstream));
+        assert_eq!(repo.serial, 5);
+        assert!(repo.deltas.is_empty());
+        assert!(repo.is_current(upstream, 5));
+        assert!(!repo.is_current(upstream, 6));
+
+        // Unchanged content keeps the serial.
+        let repo = repo.next(
+            upstream, 6,
+            objects(&[("a.roa", b"a"), ("b.roa", b"b")]), 10
+        );
+        assert_eq!(repo.serial, 5);
+        assert!(repo.is_current(upstream, 6));
+        assert!(repo.deltas.is_empty());
+
+        // Changed content adds a delta.
+        let repo = repo.next(
+            upstream, 8,
+            objects(&[("a.roa", b"aa"), ("c.roa", b"c")]), 10
+        );
+        assert_eq!(repo.serial, 6);
+        assert_eq!(repo.deltas.len(), 1);
+        let delta = delta(&repo.deltas[0].data);
+        assert_eq!(delta.serial(), 6);
+        assert_eq!(delta.session_id(), repo.session);
+        let mut kinds = delta.elements().iter().map(|item| {
+            match item {
+                DeltaElement::Publish(_) => "publish",
+                DeltaElement::Update(item) => {
+                    assert_eq!(item.hash(), &Hash::from_data(b"a"));
+                    "update"
+                }
+                DeltaElement::Withdraw(_) => "withdraw",
+            }
+        }).collect::<Vec<_>>();
+        kinds.sort_unstable();
+        assert_eq!(kinds, ["publish", "update", "withdraw"]);
+
+        // Only `keep` deltas are retained.
+        let repo = repo.next(
+            upstream, 9, objects(&[("a.roa", b"aaa")]), 1
+        );
+        assert_eq!(repo.serial, 7);
+        assert_eq!(repo.deltas.len(), 1);
+        assert_eq!(repo.deltas[0].serial, 7);
+
+        // A new upstream session starts a new session.
+        let other = Uuid::from_u128(0x5678);
+        let repo = repo.next(other, 2, objects(&[("a.roa", b"aaa")]), 10);
+        assert_eq!(repo.session, derive_session(other));
+        assert_eq!(repo.serial, 2);
+        assert!(repo.deltas.is_empty());
+    }
+
+    #[test]
+    fn publication_files() {
+        let base = Base::new(
+            &uri::Https::from_str("https://rp.example.net:8323").unwrap()
+        );
+        let notify = uri::Https::from_str(
+            "https://rrdp.example.net/rrdp/notification.xml"
+        ).unwrap();
+        let upstream = Uuid::from_u128(0x1234);
+        let repo = Repository::new(upstream, 5, objects(&[("a.roa", b"a")]));
+        let repo = repo.next(upstream, 6, objects(&[("a.roa", b"b")]), 10);
+
+        let mut publication = RrdpPublication::default();
+        publication.add_repository(&base, notify, Arc::new(repo));
+
+        let file = publication.get(
+            "notify/rrdp.example.net/rrdp/notification.xml"
+        ).unwrap();
+        assert_eq!(file.content_type(), "application/xml");
+        let notification = NotificationFile::parse(
+            file.data().as_ref()
+        ).unwrap();
+        assert_eq!(notification.session_id(), derive_session(upstream));
+        assert_eq!(notification.serial(), 6);
+
+        let path = notification.snapshot().uri().as_str().strip_prefix(
+            "https://rp.example.net:8323/rrdp/"
+        ).unwrap();
+        let snapshot = Snapshot::parse(
+            publication.get(path).unwrap().data().as_ref()
+        ).unwrap();
+        assert_eq!(snapshot.serial(), 6);
+        assert_eq!(snapshot.elements().len(), 2);
+        assert_eq!(
+            notification.snapshot().hash(),
+            Hash::from_data(publication.get(path).unwrap().data())
+        );
+
+        assert_eq!(notification.deltas().len(), 1);
+        let path = notification.deltas()[0].uri().as_str().strip_prefix(
+            "https://rp.example.net:8323/rrdp/"
+        ).unwrap();
+        assert_eq!(delta(publication.get(path).unwrap().data()).serial(), 6);
+    }
+}
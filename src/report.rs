@@ -0,0 +1,361 @@
+//! A report on every object seen during a validation run.
+//!
+//! Normally, the reasons why objects were rejected only end up in the log.
+//! The types in this module collect the outcome of validating each object
+//! – its type, status, the reason for rejecting it, and its validity – as
+//! well as the CA and manifest it belongs to.
+//!
+//! [`ObjectReport`] can be used as the processor of a validation run on its
+//! own. It can also be attached to a
+//! [`ValidationReport`][crate::payload::ValidationReport] so the report is
+//! collected during normal operation. Either way, it eventually is turned
+//! into an [`ObjectList`] which can be written as JSON or CSV.
+
+use std::{fmt, io};
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use crossbeam_queue::SegQueue;
+use rpki::uri;
+use rpki::repository::tal::{Tal, TalUri};
+use rpki::repository::x509::Validity;
+use crate::engine::{
+    CaCert, Engine, ObjectOutcome, ObjectStatus, ObjectType,
+    ProcessPubPoint, ProcessRun,
+};
+use crate::error::{Failed, RunFailed};
+use crate::metrics::Metrics;
+use crate::utils::date::format_iso_date;
+use crate::utils::json::JsonBuilder;
+
+
+//------------ ObjectReport --------------------------------------------------
+
+/// The collected outcomes of all objects of a validation run.
+#[derive(Debug, Default)]
+pub struct ObjectReport {
+    /// The records of all processed publication points.
+    records: SegQueue<ObjectRecord>,
+}
+
+impl ObjectReport {
+    /// Creates a new, empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new report by running the engine.
+    pub fn process(engine: &Engine) -> Result<(Self, Metrics), RunFailed> {
+        let report = Self::new();
+        let mut run = engine.start(&report)?;
+        run.process()?;
+        run.cleanup()?;
+        let metrics = run.done();
+        Ok((report, metrics))
+    }
+
+    /// Creates the processor for a CA’s publication point.
+    pub fn pub_point(&self, cert: &CaCert) -> PointReport<'_> {
+        PointReport {
+            report: self,
+            point: Arc::new(PointInfo {
+                ca: cert.uri().clone(),
+                manifest: cert.rpki_manifest().clone(),
+            }),
+            records: Vec::new(),
+        }
+    }
+
+    /// Converts the report into a list ordered by URI.
+    pub fn into_list(self) -> ObjectList {
+        let mut records = Vec::with_capacity(self.records.len());
+        while let Some(record) = self.records.pop() {
+            records.push(record)
+        }
+        records.sort_unstable_by(|left, right| {
+            left.uri.as_str().cmp(right.uri.as_str())
+        });
+        ObjectList { records, created: Utc::now() }
+    }
+}
+
+impl<'a> ProcessRun for &'a ObjectReport {
+    type PubPoint = PointReport<'a>;
+
+    fn process_ta(
+        &self,
+        _tal: &Tal, _uri: &TalUri, cert: &CaCert,
+        _tal_index: usize,
+    ) -> Result<Option<Self::PubPoint>, Failed> {
+        Ok(Some(self.pub_point(cert)))
+    }
+}
+
+
+//------------ PointReport ---------------------------------------------------
+
+/// Collects the outcomes of the objects of a publication point.
+///
+/// The outcomes are only added to the report once the publication point
+/// has been completed. If it was rejected, all valid objects are marked as
+/// such.
+#[derive(Clone, Debug)]
+pub struct PointReport<'a> {
+    /// The report to add the records to eventually.
+    report: &'a ObjectReport,
+
+    /// Information about the publication point.
+    point: Arc<PointInfo>,
+
+    /// The records collected so far.
+    records: Vec<ObjectRecord>,
+}
+
+impl<'a> ProcessPubPoint for PointReport<'a> {
+    fn want(&self, _uri: &uri::Rsync) -> Result<bool, Failed> {
+        Ok(true)
+    }
+
+    fn process_ca(
+        &mut self, _uri: &uri::Rsync, cert: &CaCert,
+    ) -> Result<Option<Self>, Failed> {
+        Ok(Some(self.report.pub_point(cert)))
+    }
+
+    fn object_status(&mut self, outcome: ObjectOutcome) {
+        self.records.push(ObjectRecord {
+            uri: outcome.uri.clone(),
+            object_type: outcome.object_type,
+            status: outcome.status,
+            reason: outcome.reason,
+            validity: outcome.validity,
+            point: self.point.clone(),
+        })
+    }
+
+    fn restart(&mut self) -> Result<(), Failed> {
+        self.records.clear();
+        Ok(())
+    }
+
+    fn commit(self) {
+        self.records.into_iter().for_each(|record| {
+            self.report.records.push(record)
+        })
+    }
+
+    fn cancel(self, _cert: &CaCert) {
+        self.records.into_iter().for_each(|mut record| {
+            if matches!(
+                record.status,
+                ObjectStatus::Valid | ObjectStatus::Stale
+                    | ObjectStatus::Unknown
+            ) {
+                record.status = ObjectStatus::RejectedPoint
+            }
+            self.report.records.push(record)
+        })
+    }
+}
+
+
+//------------ PointInfo -----------------------------------------------------
+
+/// Information about the publication point an object belongs to.
+#[derive(Clone, Debug)]
+struct PointInfo {
+    /// The URI of the CA certificate.
+    ca: TalUri,
+
+    /// The URI of the manifest.
+    manifest: uri::Rsync,
+}
+
+
+//------------ ObjectRecord --------------------------------------------------
+
+/// The outcome of validating a single object.
+#[derive(Clone, Debug)]
+pub struct ObjectRecord {
+    /// The URI of the object.
+    uri: uri::Rsync,
+
+    /// The type of the object.
+    object_type: ObjectType,
+
+    /// The validation status.
+    status: ObjectStatus,
+
+    /// The reason for rejecting the object.
+    reason: Option<String>,
+
+    /// The validity of the object if known.
+    validity: Option<Validity>,
+
+    /// The publication point of the object.
+    point: Arc<PointInfo>,
+}
+
+impl ObjectRecord {
+    /// Returns the URI of the object.
+    pub fn uri(&self) -> &uri::Rsync {
+        &self.uri
+    }
+
+    /// Returns the type of the object.
+    pub fn object_type(&self) -> ObjectType {
+        self.object_type
+    }
+
+    /// Returns the validation status of the object.
+    pub fn status(&self) -> ObjectStatus {
+        self.status
+    }
+
+    /// Returns the reason for rejecting the object if there is one.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Returns the validity of the object if it is known.
+    pub fn validity(&self) -> Option<Validity> {
+        self.validity
+    }
+
+    /// Returns the URI of the CA certificate of the object.
+    pub fn ca(&self) -> &TalUri {
+        &self.point.ca
+    }
+
+    /// Returns the URI of the manifest the object is listed on.
+    pub fn manifest(&self) -> &uri::Rsync {
+        &self.point.manifest
+    }
+}
+
+
+//------------ ObjectList ----------------------------------------------------
+
+/// The final list of object records.
+#[derive(Clone, Debug)]
+pub struct ObjectList {
+    /// The records ordered by URI.
+    records: Vec<ObjectRecord>,
+
+    /// The time the list was created.
+    created: DateTime<Utc>,
+}
+
+impl ObjectList {
+    /// Returns an iterator over the records.
+    pub fn iter(&self) -> impl Iterator<Item = &ObjectRecord> + '_ {
+        self.records.iter()
+    }
+
+    /// Returns the time the list was created.
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn write_json<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        target.write_all(self.to_json().as_bytes())?;
+        writeln!(target)
+    }
+
+    pub fn to_json(&self) -> String {
+        JsonBuilder::build(|json| {
+            json.member_str("generatedTime", format_iso_date(self.created));
+            json.member_array("objects", |json| {
+                for record in &self.records {
+                    json.array_object(|json| {
+                        json.member_str("uri", &record.uri);
+                        json.member_str("type", record.object_type);
+                        json.member_str("status", record.status);
+                        if let Some(reason) = record.reason.as_ref() {
+                            json.member_str("reason", reason);
+                        }
+                        json.member_str("ca", record.ca());
+                        json.member_str("manifest", record.manifest());
+                        if let Some(validity) = record.validity {
+                            json.member_str(
+                                "notBefore",
+                                format_iso_date(validity.not_before().into())
+                            );
+                            json.member_str(
+                                "notAfter",
+                                format_iso_date(validity.not_after().into())
+                            );
+                        }
+                    })
+                }
+            })
+        })
+    }
+
+    pub fn write_csv<W: io::Write>(
+        &self,
+        target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(
+            target,
+            "URI,Type,Status,Reason,CA,Manifest,Not Before,Not After"
+        )?;
+        for record in &self.records {
+            write!(
+                target, "{},{},{},{},{},{},",
+                CsvStr(record.uri.as_str()),
+                record.object_type, record.status,
+                CsvStr(record.reason().unwrap_or("")),
+                CsvStr(&record.ca().to_string()),
+                CsvStr(record.manifest().as_str()),
+            )?;
+            match record.validity {
+                Some(validity) => {
+                    writeln!(
+                        target, "{},{}",
+                        format_iso_date(validity.not_before().into()),
+                        format_iso_date(validity.not_after().into()),
+                    )?
+                }
+                None => writeln!(target, ",")?
+            }
+        }
+        Ok(())
+    }
+}
+
+
+//------------ CsvStr --------------------------------------------------------
+
+/// A string that is quoted if it needs to be in a CSV field.
+struct CsvStr<'a>(&'a str);
+
+impl fmt::Display for CsvStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.contains([',', '"', '\n', '\r']) {
+            write!(f, "\"{}\"", self.0.replace('"', "\"\""))
+        }
+        else {
+            f.write_str(self.0)
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_str() {
+        assert_eq!(CsvStr("stale CRL").to_string(), "stale CRL");
+        assert_eq!(
+            CsvStr("file name \"a,b\"").to_string(),
+            "\"file name \"\"a,b\"\"\""
+        );
+    }
+}
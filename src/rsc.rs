@@ -0,0 +1,996 @@
+//! RPKI Signed Checklists.
+//!
+//! An RPKI Signed Checklist (RSC) as defined in [RFC 9323] is a signed
+//! object that carries a list of digests of files together with a set of
+//! Internet number resources. By signing it, the holder of the resources
+//! attests to these files. Unlike other signed objects, RSCs are not
+//! published in RPKI repositories but are passed around together with the
+//! files they cover.
+//!
+//! In order to validate an RSC, we need to find the CA certificate that
+//! issued its EE certificate. This happens through a validation run that
+//! looks for the CA with a subject key identifier matching the authority
+//! key identifier of the EE certificate. The run continues into the
+//! publication point of that CA to check the EE certificate against the
+//! CA’s manifest CRL.
+//!
+//! If the path to the issuing CA is known from an earlier validation run,
+//! the run can be restricted to the publication points along that path.
+//!
+//! [RFC 9323]: https://tools.ietf.org/html/rfc9323
+
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use bcder::{decode, ConstOid, Ia5String, Mode, Oid, OctetString, Tag};
+use bcder::decode::{DecodeError, IntoSource, Source};
+use bytes::Bytes;
+use rpki::oid;
+use rpki::uri;
+use rpki::crypto::{
+    DigestAlgorithm, KeyIdentifier, RpkiSignature,
+    RpkiSignatureAlgorithm,
+};
+use rpki::repository::cert::{Cert, ResourceCert};
+use rpki::repository::crl::Crl;
+use rpki::repository::error::{
+    InspectionError, ValidationError, VerificationError,
+};
+use rpki::repository::resources::{AddressFamily, AsBlocks, IpBlocks};
+use rpki::repository::sigobj::{MessageDigest, SignedAttrs};
+use rpki::repository::tal::{Tal, TalUri};
+use crate::engine::{CaCert, Engine, ProcessPubPoint, ProcessRun};
+use crate::error::{Failed, RunFailed};
+use crate::payload::CaEntry;
+use crate::utils::json::JsonBuilder;
+use crate::utils::str::append_hex;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The content type of an RPKI Signed Checklist.
+///
+/// This is id-ct-signedChecklist, 1.2.840.113549.1.9.16.1.48.
+const CT_SIGNED_CHECKLIST: ConstOid
+    = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 1, 48]);
+
+
+//------------ Rsc -----------------------------------------------------------
+
+/// An RPKI Signed Checklist.
+#[derive(Clone, Debug)]
+pub struct Rsc {
+    /// The digest algorithm of the CMS signed data.
+    digest_algorithm: DigestAlgorithm,
+
+    /// The raw encapsulated content.
+    content: OctetString,
+
+    /// The EE certificate the object is signed with.
+    cert: Cert,
+
+    /// The key identifier of the signer.
+    sid: KeyIdentifier,
+
+    /// The signed attributes.
+    signed_attrs: SignedAttrs,
+
+    /// The signature.
+    signature: RpkiSignature,
+
+    /// The message digest from the signed attributes.
+    message_digest: MessageDigest,
+
+    /// The decoded checklist.
+    checklist: Checklist,
+}
+
+impl Rsc {
+    /// Decodes an RSC from a source.
+    pub fn decode<S: IntoSource>(
+        source: S,
+        strict: bool
+    ) -> Result<Self, DecodeError<<S::Source as Source>::Error>> {
+        if strict {
+            Mode::Der
+        }
+        else {
+            Mode::Ber
+        }.decode(source.into_source(), Self::take_from)
+    }
+
+    /// Takes an RSC from the beginning of a constructed value.
+    ///
+    /// This follows the CMS profile of RFC 6488 but keeps all the parts
+    /// necessary to verify the signature ourselves since the EE certificate
+    /// of an RSC is a detached EE certificate.
+    fn take_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| { // ContentInfo
+            oid::SIGNED_DATA.skip_if(cons)?; // contentType
+            cons.take_constructed_if(Tag::CTX_0, |cons| { // content
+                cons.take_sequence(|cons| { // SignedData
+                    cons.skip_u8_if(3)?; // version -- must be 3
+                    let digest_algorithm =
+                        DigestAlgorithm::take_set_from(cons)?;
+                    let content = cons.take_sequence(|cons| {
+                        // encapContentInfo
+                        CT_SIGNED_CHECKLIST.skip_if(cons)?;
+                        cons.take_constructed_if(
+                            Tag::CTX_0, OctetString::take_from
+                        )
+                    })?;
+                    let cert = cons.take_constructed_if( // certificates
+                        Tag::CTX_0, Cert::take_from
+                    )?;
+                    // no crls
+                    let (sid, attrs, signature) = cons.take_set(|cons| {
+                        cons.take_sequence(|cons| { // SignerInfo
+                            cons.skip_u8_if(3)?;
+                            let sid = cons.take_value_if(
+                                Tag::CTX_0, KeyIdentifier::from_content
+                            )?;
+                            let alg = DigestAlgorithm::take_from(cons)?;
+                            if alg != digest_algorithm {
+                                return Err(cons.content_err(
+                                    "digest algorithm mismatch"
+                                ))
+                            }
+                            let attrs = SignedAttrs::take_from(cons)?;
+                            if attrs.2 != CT_SIGNED_CHECKLIST {
+                                return Err(cons.content_err(
+                                    "content type in signed attributes \
+                                     differs"
+                                ))
+                            }
+                            let signature = RpkiSignature::new(
+                                RpkiSignatureAlgorithm::cms_take_from(cons)?,
+                                OctetString::take_from(cons)?.into_bytes()
+                            );
+                            // no unsignedAttributes
+                            Ok((sid, attrs, signature))
+                        })
+                    })?;
+                    let checklist = Mode::Der.decode(
+                        content.clone(), Checklist::take_from
+                    ).map_err(|err| {
+                        cons.content_err(format!(
+                            "invalid checklist: {}", err
+                        ))
+                    })?;
+                    Ok(Rsc {
+                        digest_algorithm,
+                        content,
+                        cert,
+                        sid,
+                        signed_attrs: attrs.0,
+                        signature,
+                        message_digest: attrs.1,
+                        checklist,
+                    })
+                })
+            })
+        })
+    }
+
+    /// Returns the checklist.
+    pub fn checklist(&self) -> &Checklist {
+        &self.checklist
+    }
+
+    /// Returns the EE certificate.
+    pub fn cert(&self) -> &Cert {
+        &self.cert
+    }
+
+    /// Validates the RSC against the certificate of the issuing CA.
+    ///
+    /// Upon success, returns the validated EE certificate.
+    pub fn validate(
+        &self, issuer: &ResourceCert, strict: bool,
+    ) -> Result<ResourceCert, ValidationError> {
+        self.inspect()?;
+        self.verify()?;
+        let cert = self.cert.clone().validate_detached_ee(issuer, strict)?;
+        self.checklist.verify_resources(&cert)?;
+        Ok(cert)
+    }
+
+    /// Checks that the object follows the profile.
+    ///
+    /// Most of this has happened during decoding already. This leaves
+    /// checking that the signer is the EE certificate.
+    fn inspect(&self) -> Result<(), InspectionError> {
+        if self.sid != self.cert.subject_key_identifier() {
+            return Err(InspectionError::new(
+                "Subject Key Identifier mismatch in signed checklist"
+            ))
+        }
+        Ok(())
+    }
+
+    /// Verifies the signature of the object.
+    fn verify(&self) -> Result<(), VerificationError> {
+        let digest = {
+            let mut context = self.digest_algorithm.start();
+            self.content.iter().for_each(|x| context.update(x));
+            context.finish()
+        };
+        if digest.as_ref() != self.message_digest.as_ref() {
+            return Err(VerificationError::new(
+                "message digest mismatch in signed checklist"
+            ))
+        }
+        let msg = self.signed_attrs.encode_verify();
+        self.cert.subject_public_key_info().verify(
+            &msg, &self.signature
+        ).map_err(Into::into)
+    }
+}
+
+
+//------------ Checklist -----------------------------------------------------
+
+/// The content of an RSC.
+#[derive(Clone, Debug)]
+pub struct Checklist {
+    /// The AS resources covered by the checklist.
+    as_resources: AsBlocks,
+
+    /// The IPv4 resources covered by the checklist.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources covered by the checklist.
+    v6_resources: IpBlocks,
+
+    /// The digest algorithm used for the file digests.
+    digest_algorithm: DigestAlgorithm,
+
+    /// The entries of the checklist.
+    entries: Vec<ChecklistEntry>,
+}
+
+impl Checklist {
+    /// Returns the AS resources covered by the checklist.
+    pub fn as_resources(&self) -> &AsBlocks {
+        &self.as_resources
+    }
+
+    /// Returns the IPv4 resources covered by the checklist.
+    pub fn v4_resources(&self) -> &IpBlocks {
+        &self.v4_resources
+    }
+
+    /// Returns the IPv6 resources covered by the checklist.
+    pub fn v6_resources(&self) -> &IpBlocks {
+        &self.v6_resources
+    }
+
+    /// Returns the digest algorithm used for the file digests.
+    pub fn digest_algorithm(&self) -> DigestAlgorithm {
+        self.digest_algorithm
+    }
+
+    /// Returns the entries of the checklist.
+    pub fn entries(&self) -> &[ChecklistEntry] {
+        &self.entries
+    }
+
+    /// Returns the index of the entry with the given digest.
+    pub fn find(&self, digest: &[u8]) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.digest() == digest)
+    }
+
+    /// Checks that the resources are covered by the EE certificate.
+    fn verify_resources(
+        &self, cert: &ResourceCert
+    ) -> Result<(), VerificationError> {
+        if !cert.as_resources().contains(&self.as_resources) {
+            return Err(VerificationError::new(
+                "overclaimed AS resources in signed checklist"
+            ))
+        }
+        if !cert.v4_resources().contains(&self.v4_resources) {
+            return Err(VerificationError::new(
+                "overclaimed IPv4 resources in signed checklist"
+            ))
+        }
+        if !cert.v6_resources().contains(&self.v6_resources) {
+            return Err(VerificationError::new(
+                "overclaimed IPv6 resources in signed checklist"
+            ))
+        }
+        Ok(())
+    }
+
+    /// Takes the checklist from the beginning of a constructed value.
+    ///
+    /// ```text
+    /// RpkiSignedChecklist ::= SEQUENCE {
+    ///     version [0] INTEGER DEFAULT 0,
+    ///     resources ResourceBlock,
+    ///     digestAlgorithm DigestAlgorithmIdentifier,
+    ///     checkList SEQUENCE (SIZE(1..MAX)) OF FileNameAndHash }
+    /// ```
+    fn take_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            cons.take_opt_constructed_if(Tag::CTX_0, |c| c.skip_u8_if(0))?;
+            let (as_resources, v4_resources, v6_resources) =
+                Self::take_resources_from(cons)?;
+            let digest_algorithm = DigestAlgorithm::take_from(cons)?;
+            let entries = cons.take_sequence(|cons| {
+                let mut res = Vec::new();
+                while let Some(entry) = ChecklistEntry::take_opt_from(cons)? {
+                    res.push(entry)
+                }
+                Ok(res)
+            })?;
+            if entries.is_empty() {
+                return Err(cons.content_err("empty checklist"))
+            }
+            Ok(Checklist {
+                as_resources, v4_resources, v6_resources,
+                digest_algorithm, entries,
+            })
+        })
+    }
+
+    /// Takes the resource block.
+    ///
+    /// ```text
+    /// ResourceBlock ::= SEQUENCE {
+    ///     asID         [0] ConstrainedASIdentifiers OPTIONAL,
+    ///     ipAddrBlocks [1] ConstrainedIPAddrBlocks OPTIONAL }
+    ///
+    /// ConstrainedASIdentifiers ::= SEQUENCE {
+    ///     asnum [0] SEQUENCE (SIZE(1..MAX)) OF ASIdOrRange }
+    /// ```
+    fn take_resources_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<(AsBlocks, IpBlocks, IpBlocks), DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let asres = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                cons.take_sequence(|cons| {
+                    cons.take_constructed_if(Tag::CTX_0, AsBlocks::take_from)
+                })
+            })?;
+
+            let mut v4 = None;
+            let mut v6 = None;
+            cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+                cons.take_sequence(|cons| {
+                    while let Some(()) = cons.take_opt_sequence(|cons| {
+                        match AddressFamily::take_from(cons)? {
+                            AddressFamily::Ipv4 => {
+                                if v4.is_some() {
+                                    return Err(cons.content_err(
+                                        "multiple IPv4 blocks in checklist"
+                                    ));
+                                }
+                                v4 = Some(IpBlocks::take_from_with_family(
+                                    cons, AddressFamily::Ipv4
+                                )?);
+                            }
+                            AddressFamily::Ipv6 => {
+                                if v6.is_some() {
+                                    return Err(cons.content_err(
+                                        "multiple IPv6 blocks in checklist"
+                                    ));
+                                }
+                                v6 = Some(IpBlocks::take_from_with_family(
+                                    cons, AddressFamily::Ipv6
+                                )?);
+                            }
+                        }
+                        Ok(())
+                    })? { }
+                    Ok(())
+                })
+            })?;
+
+            if asres.is_none() && v4.is_none() && v6.is_none() {
+                return Err(cons.content_err("no resources in checklist"));
+            }
+            Ok((
+                asres.unwrap_or_default(),
+                v4.unwrap_or_default(),
+                v6.unwrap_or_default(),
+            ))
+        })
+    }
+}
+
+
+//------------ ChecklistEntry ------------------------------------------------
+
+/// A single entry of a checklist.
+#[derive(Clone, Debug)]
+pub struct ChecklistEntry {
+    /// The optional file name.
+    file_name: Option<String>,
+
+    /// The digest of the file.
+    digest: Bytes,
+}
+
+impl ChecklistEntry {
+    /// Returns the file name if present.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Returns the digest of the file.
+    pub fn digest(&self) -> &[u8] {
+        self.digest.as_ref()
+    }
+
+    /// Takes an optional entry from the beginning of a constructed value.
+    ///
+    /// ```text
+    /// FileNameAndHash ::= SEQUENCE {
+    ///     fileName PortableFilename OPTIONAL,
+    ///     hash     Digest }
+    /// ```
+    fn take_opt_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Option<Self>, DecodeError<S::Error>> {
+        cons.take_opt_sequence(|cons| {
+            let file_name = cons.take_opt_value_if(
+                Tag::IA5_STRING, Ia5String::from_content
+            )?;
+            let digest = OctetString::take_from(cons)?;
+            Ok(ChecklistEntry {
+                file_name: file_name.map(|name| name.to_string()),
+                digest: digest.into_bytes(),
+            })
+        })
+    }
+}
+
+
+//------------ ValidationReport ----------------------------------------------
+
+/// The result of an RSC validation run.
+#[derive(Debug)]
+pub struct ValidationReport<'a> {
+    /// The RSC to validate.
+    rsc: &'a Rsc,
+
+    /// Whether to validate strictly.
+    strict: bool,
+
+    /// The CAs leading to the issuer of the RSC if known.
+    ///
+    /// If present, only the publication points of these CAs are visited.
+    path: Option<Vec<CaEntry>>,
+
+    /// The URIs of the certificate chain if validation succeeded.
+    ///
+    /// The chain starts with the trust anchor certificate and ends with the
+    /// issuer of the RSC’s EE certificate.
+    chain: Mutex<Option<Vec<String>>>,
+
+    /// Have we found a valid chain yet?
+    complete: AtomicBool,
+}
+
+impl<'a> ValidationReport<'a> {
+    /// Creates a new report for the given RSC.
+    pub fn new(rsc: &'a Rsc, strict: bool) -> Self {
+        ValidationReport {
+            rsc, strict,
+            path: None,
+            chain: Mutex::new(None),
+            complete: AtomicBool::new(false),
+        }
+    }
+
+    /// Restricts validation to the given path of CAs.
+    ///
+    /// The path starts with a trust anchor CA and ends with the issuer of
+    /// the RSC as returned by [`CaIndex::path`].
+    ///
+    /// [`CaIndex::path`]: crate::payload::CaIndex::path
+    pub fn with_path(mut self, path: Vec<CaEntry>) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Performs a validation run to find the issuing CA.
+    pub fn process(
+        &self,
+        engine: &Engine,
+    ) -> Result<(), RunFailed> {
+        let mut run = engine.start(self)?;
+        run.process()?;
+        Ok(())
+    }
+
+    /// Finalizes validation.
+    ///
+    /// Returns the certificate chain if the RSC was found to be valid.
+    pub fn finalize(self) -> Result<Vec<String>, Failed> {
+        self.chain.into_inner().unwrap().ok_or(Failed)
+    }
+
+    /// Returns a processor for the publication point of a CA.
+    ///
+    /// Returns `None` if the point doesn’t need to be visited.
+    fn processor<'s>(&'s self, ca: &CaCert) -> Option<ValidateCa<'a, 's>> {
+        if self.complete.load(Ordering::Relaxed) {
+            return None
+        }
+        if let Some(path) = self.path.as_ref() {
+            let key = ca.cert().subject_key_identifier();
+            if !path.iter().any(|item| item.key() == key) {
+                return None
+            }
+        }
+        Some(ValidateCa {
+            report: self,
+            issued: self.check_ca(ca),
+            crl_ok: false,
+        })
+    }
+
+    /// Returns whether the certificate with the given URI is of interest.
+    fn want_cert(&self, uri: &uri::Rsync) -> bool {
+        match self.path.as_ref() {
+            Some(path) => {
+                path.iter().any(|item| {
+                    matches!(item.uri(), TalUri::Rsync(item) if item == uri)
+                })
+            }
+            None => uri.ends_with(".cer")
+        }
+    }
+
+    /// Checks whether the given CA is the issuer of the RSC.
+    ///
+    /// If so, validates the RSC against it and returns the certificate
+    /// chain upon success.
+    fn check_ca(&self, ca: &CaCert) -> Option<Vec<String>> {
+        if self.rsc.cert().authority_key_identifier()
+            != Some(ca.cert().subject_key_identifier())
+        {
+            return None
+        }
+        if self.rsc.validate(ca.cert(), self.strict).is_err() {
+            return None
+        }
+        let mut chain = vec![ca.uri().to_string()];
+        let mut parent = ca.parent();
+        while let Some(ca) = parent {
+            chain.push(ca.uri().to_string());
+            parent = ca.parent();
+        }
+        chain.reverse();
+        Some(chain)
+    }
+
+    /// Checks whether the RSC’s EE certificate has been revoked.
+    ///
+    /// The certificate must refer to the CRL of the issuing CA’s manifest
+    /// and must not be listed on it.
+    fn check_crl(&self, uri: &uri::Rsync, crl: &Crl) -> bool {
+        self.rsc.cert().crl_uri() == Some(uri)
+            && !crl.contains(self.rsc.cert().serial_number())
+    }
+
+    /// Stores the chain of a valid RSC.
+    fn complete(&self, chain: Vec<String>) {
+        let mut res = self.chain.lock().unwrap();
+        if res.is_none() {
+            *res = Some(chain);
+            self.complete.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<'a, 's> ProcessRun for &'s ValidationReport<'a> {
+    type PubPoint = ValidateCa<'a, 's>;
+
+    fn process_ta(
+        &self, _tal: &Tal, _uri: &TalUri, cert: &CaCert,
+        _tal_index: usize
+    ) -> Result<Option<Self::PubPoint>, Failed> {
+        Ok(self.processor(cert))
+    }
+}
+
+
+//------------ ValidateCa ----------------------------------------------------
+
+/// The processor for a publication point during RSC validation.
+pub struct ValidateCa<'a, 's> {
+    /// The report we are working for.
+    report: &'s ValidationReport<'a>,
+
+    /// The certificate chain if the point’s CA issued the RSC.
+    issued: Option<Vec<String>>,
+
+    /// Has the manifest CRL been checked and found not to revoke the RSC?
+    crl_ok: bool,
+}
+
+impl<'a, 's> ProcessPubPoint for ValidateCa<'a, 's> {
+    fn process_crl(&mut self, uri: &uri::Rsync, crl: &Crl) {
+        if self.issued.is_some() {
+            self.crl_ok = self.report.check_crl(uri, crl);
+        }
+    }
+
+    fn want(&self, uri: &uri::Rsync) -> Result<bool, Failed> {
+        // If this is the issuer, we only need the CRL.
+        Ok(self.issued.is_none() && self.report.want_cert(uri))
+    }
+
+    fn process_ca(
+        &mut self, _uri: &uri::Rsync, cert: &CaCert,
+    ) -> Result<Option<Self>, Failed> {
+        Ok(self.report.processor(cert))
+    }
+
+    fn restart(&mut self) -> Result<(), Failed> {
+        self.crl_ok = false;
+        Ok(())
+    }
+
+    fn commit(self) {
+        if let (Some(chain), true) = (self.issued, self.crl_ok) {
+            self.report.complete(chain)
+        }
+    }
+}
+
+
+//------------ Checked -------------------------------------------------------
+
+/// The outcome of checking an RSC and a set of files.
+#[derive(Debug)]
+pub struct Checked<'a> {
+    /// The RSC.
+    rsc: &'a Rsc,
+
+    /// The certificate chain if the RSC is valid.
+    chain: Option<Vec<String>>,
+
+    /// The files checked against the checklist.
+    ///
+    /// Each item contains the file’s name, its digest, and the index of the
+    /// matching checklist entry.
+    files: Vec<(String, Vec<u8>, Option<usize>)>,
+}
+
+impl<'a> Checked<'a> {
+    /// Creates a new value for the outcome of validating an RSC.
+    pub fn new(rsc: &'a Rsc, chain: Option<Vec<String>>) -> Self {
+        Checked { rsc, chain, files: Vec::new() }
+    }
+
+    /// Adds a file to check against the checklist.
+    pub fn add_file(&mut self, path: &Path) -> Result<(), io::Error> {
+        let digest = self.rsc.checklist().digest_algorithm().digest_file(
+            path
+        )?;
+        self.add_digest(path.display().to_string(), digest.as_ref());
+        Ok(())
+    }
+
+    /// Adds a digest to check against the checklist.
+    pub fn add_digest(&mut self, name: String, digest: &[u8]) {
+        let idx = self.rsc.checklist().find(digest);
+        self.files.push((name, digest.into(), idx));
+    }
+
+    /// Returns whether the RSC is valid and all files matched.
+    pub fn is_valid(&self) -> bool {
+        self.chain.is_some()
+            && self.files.iter().all(|(_, _, idx)| idx.is_some())
+    }
+
+    /// Writes a text representation of the outcome.
+    pub fn write_text(&self, target: &mut impl io::Write) -> io::Result<()> {
+        match self.chain.as_ref() {
+            Some(chain) => {
+                writeln!(target, "Signed checklist is valid.")?;
+                writeln!(target, "Certificate chain:")?;
+                for uri in chain {
+                    writeln!(target, "  {}", uri)?;
+                }
+            }
+            None => {
+                writeln!(target, "Signed checklist is NOT valid.")?;
+            }
+        }
+        let checklist = self.rsc.checklist();
+        writeln!(target, "Resources:")?;
+        for block in checklist.as_resources().iter() {
+            writeln!(target, "  {}", block)?;
+        }
+        for block in checklist.v4_resources().iter() {
+            writeln!(target, "  {}", block.display_v4())?;
+        }
+        for block in checklist.v6_resources().iter() {
+            writeln!(target, "  {}", block.display_v6())?;
+        }
+        writeln!(target, "Checklist:")?;
+        for entry in checklist.entries() {
+            writeln!(
+                target, "  {} {}",
+                hex(entry.digest()), entry.file_name().unwrap_or("-")
+            )?;
+        }
+        if !self.files.is_empty() {
+            writeln!(target, "Files:")?;
+            for (name, digest, idx) in &self.files {
+                writeln!(
+                    target, "  {} {} {}",
+                    if idx.is_some() { "OK     " } else { "MISSING" },
+                    hex(digest), name
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a JSON representation of the outcome.
+    pub fn to_json(&self) -> String {
+        let checklist = self.rsc.checklist();
+        JsonBuilder::build(|json| {
+            json.member_raw("valid", self.is_valid());
+            json.member_raw("signatureValid", self.chain.is_some());
+            json.member_array("chain", |json| {
+                for uri in self.chain.iter().flatten() {
+                    json.array_str(uri);
+                }
+            });
+            json.member_object("resources", |json| {
+                json.member_array("asn", |json| {
+                    for block in checklist.as_resources().iter() {
+                        json.array_str(block);
+                    }
+                });
+                json.member_array("ipv4", |json| {
+                    for block in checklist.v4_resources().iter() {
+                        json.array_str(block.display_v4());
+                    }
+                });
+                json.member_array("ipv6", |json| {
+                    for block in checklist.v6_resources().iter() {
+                        json.array_str(block.display_v6());
+                    }
+                });
+            });
+            json.member_array("checklist", |json| {
+                for entry in checklist.entries() {
+                    json.array_object(|json| {
+                        if let Some(name) = entry.file_name() {
+                            json.member_str("fileName", name);
+                        }
+                        json.member_str("digest", hex(entry.digest()));
+                    });
+                }
+            });
+            json.member_array("files", |json| {
+                for (name, digest, idx) in &self.files {
+                    json.array_object(|json| {
+                        json.member_str("name", name);
+                        json.member_str("digest", hex(digest));
+                        json.member_raw("matched", idx.is_some());
+                    });
+                }
+            });
+        })
+    }
+}
+
+
+//------------ Helpers -------------------------------------------------------
+
+/// Returns the hex representation of a slice.
+fn hex(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len() * 2);
+    append_hex(data, &mut res);
+    res
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use bcder::encode;
+    use bcder::encode::Values;
+    use rpki::repository::resources::AsResources;
+    use rpki::repository::x509::Serial;
+    use crate::config::Config;
+    use crate::utils::testrepo::{TestCa, TestRepo, CA_KEY};
+    use super::*;
+
+    /// Creates an RSC issued by `ca`.
+    ///
+    /// The EE certificate has the resources in `cert_asns`, the checklist
+    /// claims `asns` and lists a single file.
+    fn make_rsc(
+        repo: &TestRepo, ca: &TestCa, cert_asns: &str, asns: &str,
+    ) -> Bytes {
+        let asns = AsBlocks::from_str(asns).unwrap();
+        let content = encode::sequence((
+            encode::sequence(
+                encode::sequence_as(Tag::CTX_0, encode::sequence(
+                    encode::sequence_as(
+                        Tag::CTX_0, encode::sequence(asns.encode_ref())
+                    )
+                ))
+            ),
+            DigestAlgorithm::sha256().encode(),
+            encode::sequence(
+                encode::sequence((
+                    OctetString::encode_slice_as(
+                        b"file.txt", Tag::IA5_STRING
+                    ),
+                    OctetString::encode_slice(
+                        DigestAlgorithm::sha256().digest(b"content")
+                    ),
+                ))
+            ),
+        )).to_captured(Mode::Der).into_bytes();
+        let mut sigobj = repo.signed_object(ca, &ca.uri("test.sig"));
+        sigobj.set_as_resources(AsResources::blocks(
+            AsBlocks::from_str(cert_asns).unwrap()
+        ));
+        sigobj.finalize(
+            Oid(Bytes::from_static(CT_SIGNED_CHECKLIST.0)), content,
+            repo.signer(), &ca.key,
+        ).unwrap().encode_ref().to_captured(Mode::Der).into_bytes()
+    }
+
+    /// Creates a repository with a CA below the trust anchor.
+    ///
+    /// The CA’s CRL lists the given serial numbers.
+    fn make_repo(revoked: &[Serial]) -> (TestRepo, TestCa) {
+        let repo = TestRepo::new();
+        let ta = repo.ta();
+        let ca = repo.ca(&ta, "ca", CA_KEY);
+        repo.ta_cert("AS64496-AS64511", "192.0.2.0/24");
+        let ca_cert = repo.ca_cert(&ta, &ca, "AS64496-AS64500", "");
+        repo.publish(&ta, 1, &[("ca.cer", &ca_cert)], &[]);
+        repo.publish(&ca, 1, &[], revoked);
+        (repo, ca)
+    }
+
+    /// Validates the RSC and returns the chain if it is valid.
+    fn validate(config: &Config, rsc: &Rsc) -> Option<Vec<String>> {
+        let _ = crate::process::Process::init(); // May be inited already.
+        let mut engine = Engine::new(config, true).unwrap();
+        engine.ignite().unwrap();
+        let report = ValidationReport::new(rsc, config.strict);
+        report.process(&engine).unwrap();
+        report.finalize().ok()
+    }
+
+    #[test]
+    fn decode() {
+        let (repo, ca) = make_repo(&[]);
+        let rsc = Rsc::decode(
+            make_rsc(&repo, &ca, "AS64496", "AS64496"), true
+        ).unwrap();
+        assert_eq!(
+            rsc.checklist().as_resources(),
+            &AsBlocks::from_str("AS64496").unwrap()
+        );
+        assert!(rsc.checklist().v4_resources().is_empty());
+        assert_eq!(
+            rsc.checklist().digest_algorithm(), DigestAlgorithm::sha256()
+        );
+        let entries = rsc.checklist().entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].file_name(), Some("file.txt"));
+        assert_eq!(
+            entries[0].digest(),
+            DigestAlgorithm::sha256().digest(b"content").as_ref()
+        );
+        assert_eq!(
+            rsc.cert().authority_key_identifier(),
+            Some(repo.signer().public_key(CA_KEY).key_identifier())
+        );
+
+        assert!(Rsc::decode(Bytes::from_static(b"foo"), true).is_err());
+    }
+
+    #[test]
+    fn validate_chain() {
+        let (repo, ca) = make_repo(&[]);
+        let rsc = Rsc::decode(
+            make_rsc(&repo, &ca, "AS64496", "AS64496"), true
+        ).unwrap();
+        assert_eq!(
+            validate(&repo.config(), &rsc),
+            Some(vec![
+                repo.ta().cert_uri.to_string(),
+                ca.cert_uri.to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_revoked() {
+        // The TestRepo hands out serial numbers in order, so the RSC’s EE
+        // certificate will get the one after those used by make_repo.
+        let repo = TestRepo::new();
+        let ta = repo.ta();
+        let ca = repo.ca(&ta, "ca", CA_KEY);
+        repo.ta_cert("AS64496-AS64511", "192.0.2.0/24");
+        let ca_cert = repo.ca_cert(&ta, &ca, "AS64496-AS64500", "");
+        repo.publish(&ta, 1, &[("ca.cer", &ca_cert)], &[]);
+        let rsc = Rsc::decode(
+            make_rsc(&repo, &ca, "AS64496", "AS64496"), true
+        ).unwrap();
+        repo.publish(&ca, 1, &[], &[rsc.cert().serial_number()]);
+        assert_eq!(validate(&repo.config(), &rsc), None);
+    }
+
+    #[test]
+    fn validate_overclaim() {
+        let (repo, ca) = make_repo(&[]);
+
+        // The checklist claims more than the EE certificate.
+        let rsc = Rsc::decode(
+            make_rsc(&repo, &ca, "AS64496", "AS64496-AS64497"), true
+        ).unwrap();
+        assert_eq!(validate(&repo.config(), &rsc), None);
+
+        // The EE certificate claims more than the CA.
+        let rsc = Rsc::decode(
+            make_rsc(&repo, &ca, "AS64496-AS64510", "AS64496"), true
+        ).unwrap();
+        assert_eq!(validate(&repo.config(), &rsc), None);
+    }
+
+    #[test]
+    fn validate_path() {
+        let (repo, ca) = make_repo(&[]);
+        let config = repo.config();
+        let rsc = Rsc::decode(
+            make_rsc(&repo, &ca, "AS64496", "AS64496"), true
+        ).unwrap();
+
+        // Do a regular validation run to learn the CAs.
+        let _ = crate::process::Process::init(); // May be inited already.
+        let mut engine = Engine::new(&config, true).unwrap();
+        engine.ignite().unwrap();
+        let (report, mut metrics) = crate::payload::ValidationReport::process(
+            &engine, &config
+        ).unwrap();
+        let snapshot = report.into_snapshot(
+            &crate::slurm::LocalExceptions::empty(), &mut metrics
+        );
+        let path = snapshot.cas().path(
+            rsc.cert().authority_key_identifier().unwrap()
+        ).unwrap();
+        assert_eq!(path.len(), 2);
+        assert!(snapshot.cas().path(rsc.cert().subject_key_identifier())
+            .is_none()
+        );
+
+        // Now validate along the path using the stored data only.
+        let engine = Engine::new(&config, false).unwrap();
+        let report = ValidationReport::new(&rsc, config.strict).with_path(
+            path.into_iter().cloned().collect()
+        );
+        report.process(&engine).unwrap();
+        assert_eq!(
+            report.finalize().ok(),
+            Some(vec![
+                repo.ta().cert_uri.to_string(),
+                ca.cert_uri.to_string(),
+            ])
+        );
+    }
+}
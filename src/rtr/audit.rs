@@ -0,0 +1,471 @@
+//! An audit log of the data served to RTR clients.
+//!
+//! If configured, the RTR server writes a record for every query it answers
+//! into a log file. Each record is a JSON object on a line of its own. It
+//! contains the client’s address, the protocol version, the query and the
+//! serial number sent in the response together with the number of
+//! announcements and withdrawals. Since the serial numbers are those of the
+//! deltas in the payload history, this allows reconstructing the data set a
+//! router has received at any point in time.
+//!
+//! The log file is rotated once it grows beyond a configured size.
+
+use std::{fs, io};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use chrono::Utc;
+use log::{error, warn};
+use rpki::rtr::pdu::{
+    Aspa, CacheReset, CacheResponse, EndOfData, Error as ErrorReport,
+    Ipv4Prefix, Ipv6Prefix, ResetQuery, RouterKey, SerialNotify, SerialQuery,
+};
+use tokio::sync::mpsc;
+use crate::config::Config;
+use crate::error::Failed;
+use super::pdu::PduHead;
+
+
+//------------ AuditLog ------------------------------------------------------
+
+/// The audit log.
+///
+/// Records are handed over to a dedicated thread that writes them to the
+/// log file and rotates it, so that slow file operations don’t stall the
+/// RTR connections. Dropping the log waits until all records handed over
+/// have been written.
+#[derive(Debug)]
+pub struct AuditLog {
+    /// The sending end of the channel to the writer thread.
+    ///
+    /// This is only `None` while the log is being dropped.
+    tx: Option<mpsc::UnboundedSender<String>>,
+
+    /// The writer thread.
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+impl AuditLog {
+    /// Creates the audit log from the config if it is enabled.
+    pub fn from_config(config: &Config) -> Result<Option<Arc<Self>>, Failed> {
+        match config.rtr_audit_log.as_ref() {
+            Some(path) => {
+                Self::open(
+                    path.clone(),
+                    config.rtr_audit_log_size,
+                    config.rtr_audit_log_files,
+                ).map(|log| Some(Arc::new(log)))
+            }
+            None => Ok(None)
+        }
+    }
+
+    /// Opens the audit log at the given path.
+    fn open(
+        path: PathBuf, max_size: u64, max_files: usize,
+    ) -> Result<Self, Failed> {
+        let file = match AuditFile::open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                error!(
+                    "Failed to open RTR audit log {}: {}",
+                    path.display(), err
+                );
+                return Err(Failed)
+            }
+        };
+        let mut writer = AuditWriter { path, max_size, max_files, file };
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let writer = thread::Builder::new()
+            .name("rtr-audit".into())
+            .spawn(move || {
+                while let Some(record) = rx.blocking_recv() {
+                    writer.write_record(&record)
+                }
+            });
+        match writer {
+            Ok(writer) => {
+                Ok(AuditLog { tx: Some(tx), writer: Some(writer) })
+            }
+            Err(err) => {
+                error!("Failed to start RTR audit log writer: {}", err);
+                Err(Failed)
+            }
+        }
+    }
+
+    /// Hands a record over to the writer thread.
+    fn write_record(&self, record: String) {
+        if let Some(tx) = self.tx.as_ref() {
+            // This only fails if the writer thread has died in which case
+            // there is nothing we can do anymore.
+            let _ = tx.send(record);
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        // Closing the channel makes the writer thread finish once it has
+        // written all outstanding records.
+        self.tx = None;
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+
+//------------ AuditWriter ---------------------------------------------------
+
+/// The part of the audit log owned by the writer thread.
+#[derive(Debug)]
+struct AuditWriter {
+    /// The path of the log file.
+    path: PathBuf,
+
+    /// The size in bytes beyond which the file will be rotated.
+    max_size: u64,
+
+    /// The number of rotated files to keep.
+    max_files: usize,
+
+    /// The currently open file.
+    file: AuditFile,
+}
+
+/// The currently open audit log file.
+#[derive(Debug)]
+struct AuditFile {
+    /// The file itself.
+    file: fs::File,
+
+    /// The current size of the file.
+    size: u64,
+}
+
+impl AuditWriter {
+    /// Writes a record to the log.
+    ///
+    /// Errors are logged but otherwise ignored so that a broken audit log
+    /// doesn’t interrupt serving clients.
+    fn write_record(&mut self, record: &str) {
+        if self.file.size > 0
+            && self.file.size + record.len() as u64 + 1 > self.max_size
+        {
+            if let Err(err) = self.rotate() {
+                warn!(
+                    "Failed to rotate RTR audit log {}: {}",
+                    self.path.display(), err
+                );
+            }
+        }
+        match writeln!(self.file.file, "{}", record) {
+            Ok(()) => self.file.size += record.len() as u64 + 1,
+            Err(err) => {
+                warn!(
+                    "Failed to write to RTR audit log {}: {}",
+                    self.path.display(), err
+                );
+            }
+        }
+    }
+
+    /// Rotates the log file.
+    ///
+    /// Moves all existing rotated files one up, dropping the oldest one,
+    /// moves the current file to the first rotated file, and opens a new
+    /// file.
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        }
+        else {
+            for idx in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, idx);
+                if from.exists() {
+                    fs::rename(from, rotated_path(&self.path, idx + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = AuditFile::open(&self.path)?;
+        Ok(())
+    }
+}
+
+impl AuditFile {
+    /// Opens the file at the given path for appending.
+    fn open(path: &Path) -> Result<Self, io::Error> {
+        let file = fs::OpenOptions::new().create(true).append(true).open(
+            path
+        )?;
+        let size = file.metadata()?.len();
+        Ok(AuditFile { file, size })
+    }
+}
+
+/// Returns the path of the rotated file with the given index.
+fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+    let mut res = path.to_path_buf().into_os_string();
+    res.push(format!(".{}", idx));
+    res.into()
+}
+
+
+//------------ AuditSession --------------------------------------------------
+
+/// The audit state of a single RTR connection.
+///
+/// The session is fed the heads of all PDUs received from and sent to the
+/// client and writes a record to the log whenever a response has been
+/// completed.
+#[derive(Debug)]
+pub struct AuditSession {
+    /// The audit log to write to.
+    log: Arc<AuditLog>,
+
+    /// The address of the client.
+    client: SocketAddr,
+
+    /// The last query received from the client.
+    query: Option<AuditQuery>,
+
+    /// The number of announcements sent in the current response.
+    announced: u64,
+
+    /// The number of withdrawals sent in the current response.
+    withdrawn: u64,
+}
+
+/// A query received from a client.
+#[derive(Clone, Copy, Debug)]
+enum AuditQuery {
+    /// A Serial Query with the client’s session ID and serial number.
+    Serial { session: u16, serial: u32 },
+
+    /// A Reset Query.
+    Reset,
+}
+
+impl AuditSession {
+    /// Creates a new session for a client that has just connected.
+    pub fn new(log: Arc<AuditLog>, client: SocketAddr) -> Self {
+        let res = AuditSession {
+            log, client,
+            query: None,
+            announced: 0,
+            withdrawn: 0,
+        };
+        res.write_event("connect", None, |_| { });
+        res
+    }
+
+    /// Processes a PDU received from the client.
+    pub fn read_pdu(&mut self, head: PduHead) {
+        if head.pdu() == SerialQuery::PDU {
+            self.query = Some(AuditQuery::Serial {
+                session: head.session(),
+                serial: head.body_u32().unwrap_or(0),
+            });
+        }
+        else if head.pdu() == ResetQuery::PDU {
+            self.query = Some(AuditQuery::Reset);
+        }
+    }
+
+    /// Processes a PDU sent to the client.
+    pub fn write_pdu(&mut self, head: PduHead) {
+        match head.pdu() {
+            SerialNotify::PDU => {
+                self.write_event("notify", Some(head.version()), |res| {
+                    write!(
+                        res, ", \"session\": {}, \"serial\": {}",
+                        head.session(), head.body_u32().unwrap_or(0)
+                    ).expect("writing to string");
+                });
+            }
+            CacheResponse::PDU => {
+                self.announced = 0;
+                self.withdrawn = 0;
+            }
+            Ipv4Prefix::PDU | Ipv6Prefix::PDU => {
+                self.count_payload(head.body_u8().unwrap_or(0))
+            }
+            RouterKey::PDU | Aspa::PDU => {
+                self.count_payload(head.header_u8())
+            }
+            EndOfData::PDU => {
+                let query = self.query.take();
+                self.write_query(head, query, "data", |res| {
+                    write!(
+                        res,
+                        ", \"session\": {}, \"serial\": {}, \
+                         \"announced\": {}, \"withdrawn\": {}",
+                        head.session(), head.body_u32().unwrap_or(0),
+                        self.announced, self.withdrawn,
+                    ).expect("writing to string");
+                });
+            }
+            CacheReset::PDU => {
+                let query = self.query.take();
+                self.write_query(head, query, "cache-reset", |_| { });
+            }
+            ErrorReport::PDU => {
+                let query = self.query.take();
+                self.write_query(head, query, "error", |res| {
+                    write!(
+                        res, ", \"errorCode\": {}", head.session()
+                    ).expect("writing to string");
+                });
+            }
+            _ => { }
+        }
+    }
+
+    /// Counts a payload PDU with the given flags.
+    fn count_payload(&mut self, flags: u8) {
+        if flags & 1 == 1 {
+            self.announced += 1
+        }
+        else {
+            self.withdrawn += 1
+        }
+    }
+
+    /// Writes the record for a response to a query.
+    fn write_query(
+        &self,
+        head: PduHead,
+        query: Option<AuditQuery>,
+        response: &str,
+        op: impl FnOnce(&mut String),
+    ) {
+        let event = match query {
+            Some(AuditQuery::Serial { .. }) => "serial-query",
+            Some(AuditQuery::Reset) => "reset-query",
+            None => "unsolicited",
+        };
+        self.write_event(event, Some(head.version()), |res| {
+            if let Some(AuditQuery::Serial { session, serial }) = query {
+                write!(
+                    res,
+                    ", \"querySession\": {}, \"querySerial\": {}",
+                    session, serial
+                ).expect("writing to string");
+            }
+            write!(
+                res, ", \"response\": \"{}\"", response
+            ).expect("writing to string");
+            op(res)
+        })
+    }
+
+    /// Writes a record for an event.
+    ///
+    /// The closure can add more members to the record. Each of them needs
+    /// to start with a comma.
+    fn write_event(
+        &self,
+        event: &str,
+        version: Option<u8>,
+        op: impl FnOnce(&mut String),
+    ) {
+        let mut res = String::new();
+        write!(
+            res,
+            "{{\"time\": \"{}\", \"client\": \"{}\", \"event\": \"{}\"",
+            Utc::now().format("%+"), self.client, event,
+        ).expect("writing to string");
+        if let Some(version) = version {
+            write!(
+                res, ", \"version\": {}", version
+            ).expect("writing to string");
+        }
+        op(&mut res);
+        res.push('}');
+        self.log.write_record(res)
+    }
+}
+
+impl Drop for AuditSession {
+    fn drop(&mut self) {
+        self.write_event("disconnect", None, |_| { });
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use super::super::pdu::PduTracker;
+
+    #[test]
+    fn audit_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let log = Arc::new(AuditLog::open(path.clone(), 1_000_000, 2).unwrap());
+        {
+            let mut session = AuditSession::new(
+                log, SocketAddr::from_str("192.0.2.1:4711").unwrap()
+            );
+            let mut read = PduTracker::default();
+            let mut write = PduTracker::default();
+
+            // Serial Query for session 7, serial 41.
+            read.process(
+                &[1, 1, 0, 7, 0, 0, 0, 12, 0, 0, 0, 41],
+                |head| session.read_pdu(head)
+            );
+            write.process(
+                &[
+                    // Cache Response
+                    1, 3, 0, 7, 0, 0, 0, 8,
+                    // IPv4 Prefix announce
+                    1, 4, 0, 0, 0, 0, 0, 20,
+                    1, 24, 24, 0, 192, 0, 2, 0, 0, 0, 0, 1,
+                    // IPv4 Prefix withdraw
+                    1, 4, 0, 0, 0, 0, 0, 20,
+                    0, 24, 24, 0, 198, 51, 100, 0, 0, 0, 0, 1,
+                    // End of Data
+                    1, 7, 0, 7, 0, 0, 0, 24, 0, 0, 0, 42,
+                    0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3,
+                ],
+                |head| session.write_pdu(head)
+            );
+        }
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("\"event\": \"connect\""));
+        assert!(lines[1].contains("\"event\": \"serial-query\""));
+        assert!(lines[1].contains("\"version\": 1"));
+        assert!(lines[1].contains("\"querySerial\": 41"));
+        assert!(lines[1].contains("\"serial\": 42"));
+        assert!(lines[1].contains("\"announced\": 1"));
+        assert!(lines[1].contains("\"withdrawn\": 1"));
+        assert!(lines[2].contains("\"event\": \"disconnect\""));
+    }
+
+    #[test]
+    fn audit_log_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let log = AuditLog::open(path.clone(), 10, 2).unwrap();
+        for _ in 0..4 {
+            log.write_record("12345678".into());
+        }
+        drop(log);
+        assert!(path.exists());
+        assert!(rotated_path(&path, 1).exists());
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "12345678\n");
+    }
+}
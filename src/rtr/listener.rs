@@ -1,4 +1,4 @@
-/// Support for the RPKI-to-Router Protocol.
+//! The RTR listeners.
 
 use std::io;
 use std::future::Future;
@@ -7,9 +7,10 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
-use futures::{pin_mut, Stream};
+use futures::{pin_mut, ready, Stream};
 use futures::future::{pending, select_all};
 use log::error;
+use rpki::rtr::pdu::{CacheReset, Error as ErrorReport, SerialQuery};
 use rpki::rtr::server::{NotifySender, Server, Socket};
 use rpki::rtr::state::State;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
@@ -19,8 +20,24 @@ use crate::config::Config;
 use crate::error::ExitError;
 use crate::metrics::{SharedRtrServerMetrics, RtrClientMetrics};
 use crate::payload::SharedHistory;
-use crate::utils::{net, tls};
+use crate::utils::net::ListenSockets;
+use crate::utils::tls;
 use crate::utils::tls::MaybeTlsTcpStream;
+use super::audit::{AuditLog, AuditSession};
+use super::pdu::PduTracker;
+use super::shutdown::{RtrShutdown, ShutdownToken};
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The error code of the Error Report PDU sent when shutting down.
+///
+/// This is “No Data Available.” It is not fatal, so routers are free to
+/// keep using the data they have until they can connect again.
+const SHUTDOWN_ERROR_CODE: u16 = 2;
+
+/// The error text of the Error Report PDU sent when shutting down.
+const SHUTDOWN_ERROR_TEXT: &[u8] = b"Server shutting down";
 
 
 //------------ rtr_listener --------------------------------------------------
@@ -32,6 +49,8 @@ pub fn rtr_listener(
     config: &Config,
     sender: NotifySender,
     extra_listener: Option<StdListener>,
+    sockets: &mut ListenSockets,
+    shutdown: RtrShutdown,
 ) -> Result<impl Future<Output = ()>, ExitError> {
     // Binding needs to have happened before dropping privileges
     // during detach. So we do this here synchronously.
@@ -40,7 +59,7 @@ pub fn rtr_listener(
         listeners.push((String::from("systemd socket"), None, extra));
     }
     for addr in &config.rtr_listen {
-        listeners.push((format!("{}", addr), None, net::bind(addr)?));
+        listeners.push((format!("{}", addr), None, sockets.bind(addr)?));
     }
     if !config.rtr_tls_listen.is_empty() {
         let tls_config = create_tls_config(config)?;
@@ -48,13 +67,16 @@ pub fn rtr_listener(
             listeners.push((
                 format!("{}", addr),
                 Some(tls_config.clone()),
-                net::bind(addr)?
+                sockets.bind(addr)?
             ));
         }
     }
-    Ok(_rtr_listener(
-        history, metrics, sender, listeners, config.rtr_tcp_keepalive,
-    ))
+    let support = ConnectionSupport {
+        keepalive: config.rtr_tcp_keepalive,
+        audit: AuditLog::from_config(config)?,
+        shutdown,
+    };
+    Ok(_rtr_listener(history, metrics, sender, listeners, support))
 }
 
 fn create_tls_config(
@@ -82,7 +104,7 @@ async fn _rtr_listener(
     metrics: SharedRtrServerMetrics,
     sender: NotifySender,
     listeners: Vec<(String, Option<Arc<tls::ServerConfig>>, StdListener)>,
-    keepalive: Option<Duration>,
+    support: ConnectionSupport,
 ) {
     // If there are no listeners, just never return.
     if listeners.is_empty() {
@@ -94,7 +116,7 @@ async fn _rtr_listener(
         listeners.into_iter().map(|(addr, tls, listener)| {
             tokio::spawn(single_rtr_listener(
                 addr, tls, listener, origins.clone(), metrics.clone(),
-                sender.clone(), keepalive,
+                sender.clone(), support.clone(),
             ))
         })
     ).await;
@@ -107,7 +129,7 @@ async fn single_rtr_listener(
     origins: SharedHistory,
     server_metrics: SharedRtrServerMetrics,
     sender: NotifySender,
-    keepalive: Option<Duration>,
+    support: ConnectionSupport,
 ) {
     let listener = match TcpListener::from_std(listener) {
         Ok(listener) => listener,
@@ -118,7 +140,11 @@ async fn single_rtr_listener(
     };
     let tls = tls.map(TlsAcceptor::from);
     let listener = RtrListener {
-        tcp: listener, tls, keepalive, server_metrics
+        tcp: listener, tls, server_metrics,
+        keepalive: support.keepalive,
+        audit: support.audit,
+        token: support.shutdown.register(),
+        shutdown: support.shutdown,
     };
     if let Err(err) = Server::new(
         listener, sender, origins.clone()
@@ -128,6 +154,22 @@ async fn single_rtr_listener(
 }
 
 
+//------------ ConnectionSupport --------------------------------------------
+
+/// The settings and services shared by the connections of all listeners.
+#[derive(Clone)]
+struct ConnectionSupport {
+    /// The TCP keepalive duration if keepalive is enabled.
+    keepalive: Option<Duration>,
+
+    /// The audit log if audit logging is enabled.
+    audit: Option<Arc<AuditLog>>,
+
+    /// The shutdown handle to register listeners and connections with.
+    shutdown: RtrShutdown,
+}
+
+
 //------------ RtrListener --------------------------------------------------
 
 /// A wrapper around an TCP listener that produces RTR streams.
@@ -136,6 +178,13 @@ struct RtrListener {
     tls: Option<TlsAcceptor>,
     keepalive: Option<Duration>,
     server_metrics: SharedRtrServerMetrics,
+    audit: Option<Arc<AuditLog>>,
+
+    /// The shutdown handle to register new connections with.
+    shutdown: RtrShutdown,
+
+    /// Our own registration with the shutdown handle.
+    token: ShutdownToken,
 }
 
 impl Stream for RtrListener {
@@ -145,12 +194,17 @@ impl Stream for RtrListener {
         self: Pin<&mut Self>,
         ctx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
+        // Ending the stream stops the server from accepting connections.
+        if self.token.poll_active(ctx) {
+            return Poll::Ready(None)
+        }
         match self.tcp.poll_accept(ctx) {
             Poll::Ready(Ok((sock, addr))) => {
                 match RtrStream::new(
                     sock, addr,
                     self.tls.as_ref(), self.keepalive,
-                    self.server_metrics.clone()
+                    self.server_metrics.clone(), self.audit.clone(),
+                    self.shutdown.register(),
                 ) {
                     Ok(stream) => Poll::Ready(Some(Ok(stream))),
                     Err(_) => Poll::Pending,
@@ -168,6 +222,23 @@ impl Stream for RtrListener {
 struct RtrStream {
     sock: MaybeTlsTcpStream,
     metrics: Arc<RtrClientMetrics>,
+
+    /// Tracks the PDUs received from the client.
+    read_pdus: PduTracker,
+
+    /// Tracks the PDUs sent to the client.
+    write_pdus: PduTracker,
+
+    /// The audit session if audit logging is enabled.
+    audit: Option<AuditSession>,
+
+    /// Our registration with the shutdown handle.
+    shutdown: ShutdownToken,
+
+    /// The data still to be sent when closing during shutdown.
+    ///
+    /// This is `None` until shutdown has started.
+    closing: Option<Vec<u8>>,
 }
 
 impl RtrStream {
@@ -178,6 +249,8 @@ impl RtrStream {
         tls: Option<&TlsAcceptor>,
         keepalive: Option<Duration>,
         server_metrics: SharedRtrServerMetrics,
+        audit: Option<Arc<AuditLog>>,
+        shutdown: ShutdownToken,
     ) -> Result<Self, io::Error> {
         if let Some(duration) = keepalive {
             Self::set_keepalive(&sock, duration)?
@@ -189,10 +262,77 @@ impl RtrStream {
         });
         Ok(RtrStream {
             sock: MaybeTlsTcpStream::new(sock, tls),
-            metrics
+            metrics,
+            read_pdus: Default::default(),
+            write_pdus: Default::default(),
+            audit: audit.map(|audit| AuditSession::new(audit, addr)),
+            shutdown,
+            closing: None,
         })
     }
 
+    /// Processes data that has been written to the socket.
+    fn process_written(&mut self, data: &[u8]) {
+        self.metrics.inc_bytes_written(data.len() as u64);
+        let metrics = &self.metrics;
+        let audit = &mut self.audit;
+        self.write_pdus.process(data, |head| {
+            // The version of the PDUs we send is the version
+            // negotiated with the client.
+            metrics.set_version(head.version());
+            if head.pdu() == CacheReset::PDU {
+                metrics.inc_cache_resets()
+            }
+            if let Some(audit) = audit.as_mut() {
+                audit.write_pdu(head)
+            }
+        });
+    }
+
+    /// Closes the connection during shutdown.
+    ///
+    /// If a version has already been negotiated with the client, sends an
+    /// Error Report first. Then closes the socket. Returns ready once the
+    /// socket has been closed.
+    fn poll_close(
+        &mut self, cx: &mut Context
+    ) -> Poll<Result<(), io::Error>> {
+        if self.closing.is_none() {
+            self.closing = Some(match self.metrics.version() {
+                Some(version) => {
+                    ErrorReport::new(
+                        version, SHUTDOWN_ERROR_CODE, b"", SHUTDOWN_ERROR_TEXT
+                    ).as_ref().to_vec()
+                }
+                None => Vec::new()
+            });
+        }
+        let mut data = self.closing.take().unwrap_or_default();
+        while !data.is_empty() {
+            match Pin::new(&mut self.sock).poll_write(cx, &data) {
+                Poll::Ready(Ok(0)) => {
+                    self.closing = Some(Vec::new());
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()))
+                }
+                Poll::Ready(Ok(n)) => {
+                    self.process_written(&data[..n]);
+                    data.drain(..n);
+                }
+                Poll::Ready(Err(err)) => {
+                    self.closing = Some(Vec::new());
+                    return Poll::Ready(Err(err))
+                }
+                Poll::Pending => {
+                    self.closing = Some(data);
+                    return Poll::Pending
+                }
+            }
+        }
+        self.closing = Some(data);
+        ready!(Pin::new(&mut self.sock).poll_flush(cx))?;
+        Pin::new(&mut self.sock).poll_shutdown(cx)
+    }
+
     #[cfg(unix)]
     fn set_keepalive(
         sock: &TcpStream, duration: Duration
@@ -253,14 +393,31 @@ impl AsyncRead for RtrStream {
     fn poll_read(
         mut self: Pin<&mut Self>, cx: &mut Context, buf: &mut ReadBuf
     ) -> Poll<Result<(), io::Error>> {
+        // The server only reads when it has finished sending its last
+        // response, so if we are between two queries during shutdown, we
+        // can say goodbye and pretend the client has closed the
+        // connection.
+        if self.closing.is_some()
+            || (self.read_pdus.is_idle() && self.shutdown.poll_active(cx))
+        {
+            return self.poll_close(cx)
+        }
         let len = buf.filled().len();
         let sock = &mut self.sock;
         pin_mut!(sock);
         let res = sock.poll_read(cx, buf);
         if let Poll::Ready(Ok(())) = res {
-            self.metrics.inc_bytes_read(
-                (buf.filled().len().saturating_sub(len)) as u64
-            )    
+            let data = &buf.filled()[len..];
+            self.metrics.inc_bytes_read(data.len() as u64);
+            let this = &mut *self;
+            this.read_pdus.process(data, |head| {
+                if head.pdu() == SerialQuery::PDU {
+                    this.metrics.serial_query_now()
+                }
+                if let Some(audit) = this.audit.as_mut() {
+                    audit.read_pdu(head)
+                }
+            });
         }
         res
     }
@@ -274,7 +431,7 @@ impl AsyncWrite for RtrStream {
         pin_mut!(sock);
         let res = sock.poll_write(cx, buf);
         if let Poll::Ready(Ok(n)) = res {
-            self.metrics.inc_bytes_written(n as u64)
+            self.process_written(&buf[..n]);
         }
         res
     }
@@ -0,0 +1,20 @@
+//! Support for the RPKI-to-Router Protocol.
+//!
+//! The RTR server itself is provided by the _rpki_ crate. This module
+//! provides the listeners that accept connections and hand them over to
+//! the server. The only public item, [`rtr_listener`], creates all these
+//! listeners based on the current configuration and returns a future that
+//! drives them.
+//!
+//! In addition, the module follows the PDUs exchanged with clients in order
+//! to produce client metrics and an optional audit log. Finally, the
+//! [`RtrShutdown`] handle allows shutting down all listeners and
+//! connections gracefully.
+
+pub use self::listener::rtr_listener;
+pub use self::shutdown::RtrShutdown;
+
+mod audit;
+mod listener;
+mod pdu;
+mod shutdown;
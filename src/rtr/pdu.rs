@@ -0,0 +1,179 @@
+//! Following the PDUs in an RTR byte stream.
+//!
+//! The RTR server doesn’t tell us about the individual PDUs it receives
+//! and sends. Since we want to know about some of them for metrics and
+//! logging, we keep track of the PDU boundaries in the byte stream
+//! ourselves and pick out the beginning of each PDU.
+
+use std::cmp;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The length of the header of an RTR PDU.
+const HEADER_LEN: usize = 8;
+
+/// The number of octets at the beginning of a PDU we look at.
+///
+/// This is the header plus the first four octets of the body which is
+/// enough to get to the serial number of queries and End of Data PDUs and
+/// the flags of prefix PDUs.
+const HEAD_LEN: usize = HEADER_LEN + 4;
+
+
+//------------ PduTracker ----------------------------------------------------
+
+/// Follows the PDUs in one direction of an RTR byte stream.
+#[derive(Clone, Debug, Default)]
+pub struct PduTracker {
+    /// The head octets of the current PDU collected so far.
+    head: [u8; HEAD_LEN],
+
+    /// The number of head octets collected so far.
+    head_len: usize,
+
+    /// The number of head octets needed for the current PDU.
+    ///
+    /// This is zero until the header has been completely read.
+    head_target: usize,
+
+    /// The number of octets of the current PDU still to skip.
+    skip: usize,
+}
+
+impl PduTracker {
+    /// Returns whether the tracker is currently between two PDUs.
+    pub fn is_idle(&self) -> bool {
+        self.head_len == 0 && self.skip == 0
+    }
+
+    /// Processes the next chunk of data from the stream.
+    ///
+    /// Calls `op` with the head of every PDU that is completed by the data.
+    pub fn process(&mut self, mut data: &[u8], mut op: impl FnMut(PduHead)) {
+        while !data.is_empty() {
+            if self.skip > 0 {
+                let len = cmp::min(self.skip, data.len());
+                self.skip -= len;
+                data = &data[len..];
+                continue
+            }
+            let target = if self.head_target == 0 {
+                HEADER_LEN
+            }
+            else {
+                self.head_target
+            };
+            let len = cmp::min(target - self.head_len, data.len());
+            self.head[self.head_len..self.head_len + len]
+                .copy_from_slice(&data[..len]);
+            self.head_len += len;
+            data = &data[len..];
+            if self.head_len < target {
+                continue
+            }
+            let pdu_len = usize::try_from(u32::from_be_bytes([
+                self.head[4], self.head[5], self.head[6], self.head[7],
+            ])).unwrap_or(usize::MAX);
+            if self.head_target == 0 {
+                // We just completed the header. Figure out how much of the
+                // body we need and go round again if that is anything.
+                self.head_target = pdu_len.clamp(HEADER_LEN, HEAD_LEN);
+                if self.head_target > self.head_len {
+                    continue
+                }
+            }
+            op(PduHead(&self.head[..self.head_len]));
+            self.skip = pdu_len.saturating_sub(self.head_len);
+            self.head_len = 0;
+            self.head_target = 0;
+        }
+    }
+}
+
+
+//------------ PduHead -------------------------------------------------------
+
+/// The beginning of an RTR PDU.
+///
+/// This contains the complete header and, if the PDU is long enough, the
+/// first four octets of its body.
+#[derive(Clone, Copy, Debug)]
+pub struct PduHead<'a>(&'a [u8]);
+
+impl PduHead<'_> {
+    /// Returns the protocol version of the PDU.
+    pub fn version(self) -> u8 {
+        self.0[0]
+    }
+
+    /// Returns the PDU type.
+    pub fn pdu(self) -> u8 {
+        self.0[1]
+    }
+
+    /// Returns the session ID field of the header.
+    ///
+    /// Some PDUs re-use this field for other purposes.
+    pub fn session(self) -> u16 {
+        u16::from_be_bytes([self.0[2], self.0[3]])
+    }
+
+    /// Returns the first octet of the session ID field.
+    ///
+    /// Some PDUs use this octet for their flags.
+    pub fn header_u8(self) -> u8 {
+        self.0[2]
+    }
+
+    /// Returns the first octet of the body if present.
+    pub fn body_u8(self) -> Option<u8> {
+        self.0.get(HEADER_LEN).copied()
+    }
+
+    /// Returns the first four octets of the body as an integer if present.
+    pub fn body_u32(self) -> Option<u32> {
+        self.0.get(HEADER_LEN..HEAD_LEN).map(|slice| {
+            u32::from_be_bytes([slice[0], slice[1], slice[2], slice[3]])
+        })
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pdu_tracker() {
+        // A Serial Notify, a Cache Reset, and an End of Data PDU.
+        let data = [
+            1, 0, 0, 7, 0, 0, 0, 12, 0, 0, 0, 42,
+            1, 8, 0, 0, 0, 0, 0, 8,
+            1, 7, 0, 7, 0, 0, 0, 24, 0, 0, 0, 43,
+            0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3,
+        ];
+        for chunk_len in 1..data.len() {
+            let mut tracker = PduTracker::default();
+            let mut pdus = Vec::new();
+            for chunk in data.chunks(chunk_len) {
+                tracker.process(chunk, |head| {
+                    pdus.push((
+                        head.version(), head.pdu(), head.session(),
+                        head.body_u32()
+                    ))
+                });
+            }
+            assert_eq!(
+                pdus,
+                [
+                    (1, 0, 7, Some(42)),
+                    (1, 8, 0, None),
+                    (1, 7, 7, Some(43)),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Graceful shutdown of the RTR server.
+//!
+//! When Routinator shuts down, it shouldn’t just drop all RTR connections
+//! on the floor. Instead, the listeners stop accepting new connections and
+//! every connection finishes the response it is currently sending. It then
+//! tells the client that it is going away and closes the connection
+//! cleanly.
+//!
+//! When upgrading in place, the same happens once the new process has
+//! taken over the listening sockets and is ready to serve.
+//!
+//! This is coordinated through an [`RtrShutdown`] handle. Listeners and
+//! connections register with the handle and check it whenever they are
+//! polled. Since this happens a lot, the check only looks at an atomic
+//! flag and the registration’s own waker.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::task::Context;
+use std::time::Duration;
+use futures::task::AtomicWaker;
+use log::{info, warn};
+use tokio::sync::Notify;
+use crate::utils::sync::Mutex;
+
+
+//------------ RtrShutdown ---------------------------------------------------
+
+/// A handle for shutting down the RTR server.
+#[derive(Clone, Debug, Default)]
+pub struct RtrShutdown(Arc<ShutdownState>);
+
+/// The state shared between the handle and its registrations.
+#[derive(Debug, Default)]
+struct ShutdownState {
+    /// Has shutdown started?
+    active: AtomicBool,
+
+    /// The identifier for the next registration.
+    next_id: AtomicU64,
+
+    /// The wakers of all current registrations.
+    ///
+    /// The mutex is only taken when registrations come and go and when
+    /// shutdown starts.
+    wakers: Mutex<HashMap<u64, Arc<AtomicWaker>>>,
+
+    /// Notification when a registration goes away.
+    dropped: Notify,
+}
+
+impl RtrShutdown {
+    /// Creates a new shutdown handle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shuts down the RTR server.
+    ///
+    /// Stops all listeners and asks all connections to close once they
+    /// have finished their current response. Waits for this to happen for
+    /// at most `grace`.
+    pub async fn shutdown(&self, grace: Duration) {
+        self.0.active.store(true, Ordering::SeqCst);
+        self.0.wakers.lock().values().for_each(|waker| waker.wake());
+        let drained = tokio::time::timeout(grace, async {
+            loop {
+                if self.0.wakers.lock().is_empty() {
+                    break
+                }
+                self.0.dropped.notified().await
+            }
+        }).await;
+        match drained {
+            Ok(()) => info!("All RTR connections closed."),
+            Err(_) => {
+                warn!(
+                    "Closing {} RTR connections after grace period.",
+                    self.0.wakers.lock().len()
+                );
+            }
+        }
+    }
+
+    /// Registers a new listener or connection.
+    pub fn register(&self) -> ShutdownToken {
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        let waker = Arc::new(AtomicWaker::new());
+        self.0.wakers.lock().insert(id, waker.clone());
+        ShutdownToken { state: self.0.clone(), id, waker }
+    }
+}
+
+
+//------------ ShutdownToken -------------------------------------------------
+
+/// The registration of a listener or connection for shutdown.
+///
+/// The registration ends when the value is dropped.
+#[derive(Debug)]
+pub struct ShutdownToken {
+    /// The shared state.
+    state: Arc<ShutdownState>,
+
+    /// The identifier of this registration.
+    id: u64,
+
+    /// The waker to wake when shutdown starts.
+    waker: Arc<AtomicWaker>,
+}
+
+impl ShutdownToken {
+    /// Returns whether shutdown has started.
+    ///
+    /// If it hasn’t, the waker of `cx` will be woken once it does.
+    pub fn poll_active(&self, cx: &Context) -> bool {
+        if self.state.active.load(Ordering::SeqCst) {
+            return true
+        }
+        // Register before checking again so we can’t miss the wake-up if
+        // shutdown starts in between.
+        self.waker.register(cx.waker());
+        self.state.active.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for ShutdownToken {
+    fn drop(&mut self) {
+        self.state.wakers.lock().remove(&self.id);
+        self.state.dropped.notify_one();
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::task::noop_waker_ref;
+
+    #[tokio::test]
+    async fn shutdown() {
+        let shutdown = RtrShutdown::new();
+        let token = shutdown.register();
+        let cx = Context::from_waker(noop_waker_ref());
+        assert!(!token.poll_active(&cx));
+        let handle = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.shutdown(Duration::from_secs(60)).await }
+        });
+        while !token.poll_active(&cx) {
+            tokio::task::yield_now().await;
+        }
+        drop(token);
+        handle.await.unwrap();
+    }
+}
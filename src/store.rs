@@ -27,10 +27,11 @@
 //! The store uses the file system to store its data. It has its dedicated
 //! directory within the RPKI repository directory, normally named `stored`
 //! (this is because an earlier version used `store` already). Within this
-//! directory are four sub-directories: `rrdp` and `rsync` contain the data
+//! directory are five sub-directories: `rrdp` and `rsync` contain the data
 //! for each stored publication point; `ta` contains the downloaded trust
-//! anchor certificates; and `tmp` is a directory for storing files as they
-//! are constructed.
+//! anchor certificates; `tal` contains the accepted trust anchor
+//! certificate for each TAL; and `tmp` is a directory for storing files as
+//! they are constructed.
 //!
 //! All publication points that do not support RRDP are stored under `rsync`.
 //! Each has a file stored at a path and file name derived from the
@@ -57,6 +58,10 @@
 //! level is the authority (i.e., hostname), and the third is the SHA-256
 //! hash of the full URI with an extension of `.cer` added.
 //!
+//! The accepted trust anchor certificate of each TAL is stored under `tal`
+//! in a file named after the TAL with an extension of `.ta` added. It
+//! contains a serialized [`StoredTaCert`].
+//!
 //! Finally, the `tmp` directory is used to build the publication point files
 //! in so they can be constructed without yet knowing whether the update is
 //! actually complete and correct. File names here are named using eight
@@ -66,6 +71,7 @@ use std::{fs, io};
 use std::fs::File;
 use std::io::{Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use bytes::Bytes;
 use log::{debug, error, info, warn};
 use rand::random;
@@ -75,6 +81,7 @@ use rpki::repository::manifest::{ManifestContent, ManifestHash};
 use rpki::repository::tal::TalUri;
 use rpki::repository::x509::{Serial, Time};
 use rpki::uri;
+use tempfile::TempDir;
 use crate::collector;
 use crate::config::Config;
 use crate::engine::CaCert;
@@ -108,6 +115,12 @@ use crate::utils::uri::UriExt;
 pub struct Store {
     /// The base path for the store.
     path: PathBuf,
+
+    /// The temporary directory of a temporary store.
+    ///
+    /// We only keep this so the directory is removed when the last copy
+    /// of the store is dropped.
+    _temporary: Option<Arc<TempDir>>,
 }
 
 impl Store {
@@ -142,6 +155,28 @@ impl Store {
     pub fn new(config: &Config) -> Result<Self, Failed> {
         Ok(Store {
             path: Self::create_base_dir(config)?,
+            _temporary: None,
+        })
+    }
+
+    /// Creates a new, empty store in a temporary directory.
+    ///
+    /// The directory is created in the repository directory and removed
+    /// again when the store is dropped. Data stored by earlier runs is
+    /// thus neither used nor touched.
+    pub fn temporary(config: &Config) -> Result<Self, Failed> {
+        let dir = tempfile::Builder::new().prefix(
+            "stored-"
+        ).tempdir_in(&config.cache_dir).map_err(|err| {
+            error!(
+                "Failed to create temporary store directory in {}: {}",
+                config.cache_dir.display(), err
+            );
+            Failed
+        })?;
+        Ok(Store {
+            path: dir.path().into(),
+            _temporary: Some(Arc::new(dir)),
         })
     }
 
@@ -157,6 +192,24 @@ impl Store {
         Run::new(self)
     }
 
+    /// Accepts the pending trust anchor certificate of a TAL.
+    ///
+    /// Returns whether there was a pending certificate.
+    pub fn accept_ta_cert(&self, tal: &str) -> Result<bool, Failed> {
+        let run = self.start();
+        let mut stored = match run.load_ta_cert(tal)? {
+            Some(stored) => stored,
+            None => return Ok(false)
+        };
+        let pending = match stored.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(false)
+        };
+        stored.accept(pending);
+        run.update_ta_cert(tal, &stored)?;
+        Ok(true)
+    }
+
     /// Dumps the content of the store.
     pub fn dump(&self, dir: &Path) -> Result<(), Failed> {
         self.dump_ta_certs(dir)?;
@@ -332,6 +385,91 @@ impl Store {
         )
     }
 
+    /// Returns all objects stored for an RRDP repository.
+    ///
+    /// The objects are returned as pairs of their rsync URI and content,
+    /// including the manifest and CRL of each publication point. Points
+    /// whose file cannot be read are skipped.
+    pub fn rrdp_objects(
+        &self,
+        rpki_notify: &uri::Https,
+    ) -> Result<Vec<(uri::Rsync, Bytes)>, Failed> {
+        let mut res = Vec::new();
+        self.collect_tree(&self.rrdp_repository_path(rpki_notify), &mut res)?;
+        Ok(res)
+    }
+
+    /// Collects the objects of all stored points found under `path`.
+    fn collect_tree(
+        &self,
+        path: &Path,
+        res: &mut Vec<(uri::Rsync, Bytes)>,
+    ) -> Result<(), Failed> {
+        let dir = match fatal::read_existing_dir(path)? {
+            Some(dir) => dir,
+            None => return Ok(())
+        };
+        for entry in dir {
+            let entry = entry?;
+            if entry.is_dir() {
+                self.collect_tree(entry.path(), res)?;
+            }
+            else if entry.is_file() {
+                self.collect_point(entry.path(), res)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the objects of a single stored publication point.
+    fn collect_point(
+        &self,
+        path: &Path,
+        res: &mut Vec<(uri::Rsync, Bytes)>,
+    ) -> Result<(), Failed> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) => {
+                error!(
+                    "Fatal: failed to open file {}: {}",
+                    path.display(), err
+                );
+                return Err(Failed)
+            }
+        };
+        let manifest = match StoredManifest::read(&mut file) {
+            Ok(some) => some,
+            Err(err) => {
+                warn!(
+                    "Skipping {}: failed to read file: {}",
+                    path.display(), err
+                );
+                return Ok(())
+            }
+        };
+        let mut objects = vec![
+            (manifest.manifest_uri, manifest.manifest),
+            (manifest.crl_uri, manifest.crl),
+        ];
+        loop {
+            match StoredObject::read(&mut file) {
+                Ok(Some(object)) => {
+                    objects.push((object.uri, object.content))
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    warn!(
+                        "Skipping {}: failed to read file: {}",
+                        path.display(), err
+                    );
+                    return Ok(())
+                }
+            }
+        }
+        res.append(&mut objects);
+        Ok(())
+    }
+
     /// Returns the path to use for the trust anchor at the given URI.
     fn ta_path(&self, uri: &TalUri) -> PathBuf {
         match *uri {
@@ -348,6 +486,11 @@ impl Store {
         }
     }
 
+    /// Returns the path for the accepted trust anchor certificate of a TAL.
+    fn ta_cert_path(&self, tal: &str) -> PathBuf {
+        self.path.join("tal").join(format!("{}.ta", tal))
+    }
+
     /// The name of the directory where all the RRDP repositories go.
     const RRDP_BASE: &'static str = "rrdp";
 
@@ -464,6 +607,49 @@ impl<'a> Run<'a> {
         fatal::write_file(&path, content)
     }
 
+    /// Loads the accepted trust anchor certificate of a TAL.
+    ///
+    /// Returns `Ok(None)` if there is no certificate for the TAL yet or if
+    /// the stored data is broken.
+    pub fn load_ta_cert(
+        &self, tal: &str
+    ) -> Result<Option<StoredTaCert>, Failed> {
+        let path = self.store.ta_cert_path(tal);
+        let data = match fatal::read_existing_file(&path)? {
+            Some(data) => data,
+            None => return Ok(None)
+        };
+        match StoredTaCert::read(&mut data.as_slice()) {
+            Ok(stored) => Ok(Some(stored)),
+            Err(err) => {
+                warn!(
+                    "Ignoring broken trust anchor state {}: {}",
+                    path.display(), err
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Updates or inserts the accepted trust anchor certificate of a TAL.
+    pub fn update_ta_cert(
+        &self, tal: &str, stored: &StoredTaCert,
+    ) -> Result<(), Failed> {
+        let path = self.store.ta_cert_path(tal);
+        if let Some(dir) = path.parent() {
+            fatal::create_dir_all(dir)?;
+        }
+        let mut data = Vec::new();
+        if let Err(err) = stored.write(&mut data) {
+            error!(
+                "Fatal: failed to encode trust anchor state {}: {}",
+                path.display(), err
+            );
+            return Err(Failed)
+        }
+        fatal::write_file(&path, &data)
+    }
+
     /// Accesses the repository for the provided RPKI CA.
     ///
     /// If the CA’s rpkiNotify URI is present, the RRDP repository identified
@@ -550,6 +736,7 @@ impl<'a> Run<'a> {
                 &mut fatal::open_file(path)?
             ) {
                 if stored.retain() {
+                    retain.add_erik_host(&stored.manifest_uri);
                     if let Some(uri) = stored.rpki_notify.as_ref() {
                         retain.add_rrdp_repository(uri)
                     }
@@ -1023,6 +1210,97 @@ impl StoredManifest {
 }
 
 
+//------------ StoredTaCert --------------------------------------------------
+
+/// The trust anchor certificate accepted for a TAL.
+///
+/// This keeps the raw bytes of the certificate last accepted for a TAL
+/// together with the time it was accepted. In addition, a certificate that
+/// has been refused and waits for an operator to accept it can be kept.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StoredTaCert {
+    /// The raw content of the accepted certificate.
+    accepted: Bytes,
+
+    /// The time the certificate was accepted.
+    since: Time,
+
+    /// The raw content of a refused certificate waiting to be accepted.
+    pending: Option<Bytes>,
+}
+
+impl StoredTaCert {
+    /// The version of the type.
+    const VERSION: u8 = 0;
+
+    /// Creates a new value accepting the given certificate now.
+    pub fn new(accepted: Bytes) -> Self {
+        StoredTaCert {
+            accepted,
+            since: Time::now(),
+            pending: None,
+        }
+    }
+
+    /// Reads the value from an IO reader.
+    pub fn read(reader: &mut impl io::Read) -> Result<Self, ParseError> {
+        let version = u8::parse(reader)?;
+        if version != Self::VERSION {
+            return Err(ParseError::format(
+                    format!("unexpected version {}", version)
+            ))
+        }
+        Ok(StoredTaCert {
+            accepted: Parse::parse(reader)?,
+            since: Parse::parse(reader)?,
+            pending: Parse::parse(reader)?,
+        })
+    }
+
+    /// Appends the value to a writer.
+    pub fn write(
+        &self, writer: &mut impl io::Write
+    ) -> Result<(), io::Error> {
+        Self::VERSION.compose(writer)?;
+
+        self.accepted.compose(writer)?;
+        self.since.compose(writer)?;
+        self.pending.compose(writer)?;
+
+        Ok(())
+    }
+
+    /// Returns the raw content of the accepted certificate.
+    pub fn accepted(&self) -> &Bytes {
+        &self.accepted
+    }
+
+    /// Returns the time the accepted certificate was accepted.
+    pub fn since(&self) -> Time {
+        self.since
+    }
+
+    /// Returns the raw content of the refused certificate if there is one.
+    pub fn pending(&self) -> Option<&Bytes> {
+        self.pending.as_ref()
+    }
+
+    /// Accepts a new certificate now.
+    ///
+    /// This also drops a pending certificate.
+    pub fn accept(&mut self, cert: Bytes) {
+        self.accepted = cert;
+        self.since = Time::now();
+        self.pending = None;
+    }
+
+    /// Sets or clears the refused certificate.
+    pub fn set_pending(&mut self, cert: Option<Bytes>) {
+        self.pending = cert;
+    }
+}
+
+
 //------------ StoredObject --------------------------------------------------
 
 /// The content of an object placed in the store.
@@ -1279,6 +1557,25 @@ mod test {
         assert_eq!(orig, decoded);
     }
 
+    #[test]
+    fn write_read_stored_ta_cert() {
+        let mut orig = StoredTaCert {
+            accepted: Bytes::from(b"foobar".as_ref()),
+            since: Time::utc(2024, 6, 1, 12, 0, 0),
+            pending: None,
+        };
+        let mut written = Vec::new();
+        orig.write(&mut written).unwrap();
+        let decoded = StoredTaCert::read(&mut written.as_slice()).unwrap();
+        assert_eq!(orig, decoded);
+
+        orig.set_pending(Some(Bytes::from(b"blablubb".as_ref())));
+        let mut written = Vec::new();
+        orig.write(&mut written).unwrap();
+        let decoded = StoredTaCert::read(&mut written.as_slice()).unwrap();
+        assert_eq!(orig, decoded);
+    }
+
     #[test]
     fn write_read_stored_object() {
         let orig = StoredObject::new(
@@ -127,6 +127,38 @@ impl<R: io::Read> Parse<R> for Option<i64> {
 }
 
 
+//------------ Option<u64> ---------------------------------------------------
+//
+// Encoded the same way as `Option<i64>`.
+
+impl<W: io::Write> Compose<W> for Option<u64> {
+    fn compose(&self, target: &mut W) -> Result<(), io::Error> {
+        match *self {
+            Some(value) => {
+                1u8.compose(target)?;
+                value.compose(target)
+            }
+            None => {
+                0u8.compose(target)
+            }
+        }
+    }
+}
+
+impl<R: io::Read> Parse<R> for Option<u64> {
+    fn parse(source: &mut R) -> Result<Self, ParseError> {
+        match u8::parse(source)? {
+            0 => return Ok(None),
+            1 => { },
+            _ => {
+                return Err(ParseError::format("illegally encoded Option<u64>"))
+            }
+        };
+        Ok(Some(u64::parse(source)?))
+    }
+}
+
+
 //----------- uri::Rsync -----------------------------------------------------
 //
 // Encoded as a u32 for the length and then that many bytes. If the length
@@ -11,5 +11,6 @@ pub mod net;
 pub mod str;
 pub mod sync;
 pub mod tls;
+#[cfg(test)] pub mod testrepo;
 pub mod uri;
 
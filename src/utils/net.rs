@@ -5,6 +5,8 @@ use log::error;
 use crate::error::ExitError;
 
 
+//------------ bind ----------------------------------------------------------
+
 pub fn bind(addr: &SocketAddr) -> Result<StdListener, ExitError> {
     let listener = match StdListener::bind(addr) {
         Ok(listener) => listener,
@@ -20,3 +22,90 @@ pub fn bind(addr: &SocketAddr) -> Result<StdListener, ExitError> {
     Ok(listener)
 }
 
+
+//------------ ListenSockets -------------------------------------------------
+
+/// The listening sockets of the server.
+///
+/// When upgrading, the server hands its listening sockets over to the new
+/// process so that the listeners stay up during the upgrade. This type
+/// takes over the sockets handed to us for the addresses we are asked to
+/// listen on and keeps a copy of every socket for handing it over later.
+#[derive(Debug, Default)]
+pub struct ListenSockets {
+    /// The sockets handed over to us that haven’t been claimed yet.
+    inherited: Vec<StdListener>,
+
+    /// Copies of all the sockets we are listening on.
+    bound: Vec<StdListener>,
+}
+
+impl ListenSockets {
+    /// Creates a new value from the sockets handed over to us.
+    pub fn new(inherited: Vec<StdListener>) -> Self {
+        ListenSockets { inherited, bound: Vec::new() }
+    }
+
+    /// Returns a non-blocking listener for `addr`.
+    ///
+    /// Uses the socket handed over to us for the address if there is one
+    /// or binds a new socket otherwise.
+    pub fn bind(
+        &mut self, addr: &SocketAddr
+    ) -> Result<StdListener, ExitError> {
+        let inherited = self.inherited.iter().position(|sock| {
+            sock.local_addr().ok().as_ref() == Some(addr)
+        });
+        let listener = match inherited {
+            Some(pos) => self.inherited.swap_remove(pos),
+            None => bind(addr)?
+        };
+        match listener.try_clone() {
+            Ok(copy) => self.bound.push(copy),
+            Err(err) => {
+                error!("Fatal: failed to duplicate socket for {}: {}",
+                    addr, err
+                );
+                return Err(ExitError::Generic)
+            }
+        }
+        Ok(listener)
+    }
+
+    /// Closes the sockets handed over to us that we don’t listen on.
+    ///
+    /// This should be called once all listeners have been created.
+    pub fn close_unclaimed(&mut self) {
+        self.inherited.clear()
+    }
+
+    /// Returns the sockets we are listening on.
+    pub fn bound(&self) -> &[StdListener] {
+        &self.bound
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn inherited() {
+        let any = SocketAddr::from(([127, 0, 0, 1], 0));
+        let first = bind(&any).unwrap();
+        let second = bind(&any).unwrap();
+        let addr = first.local_addr().unwrap();
+        let mut sockets = ListenSockets::new(vec![second, first]);
+
+        // Binding again would fail, so we must get the inherited socket.
+        let listener = sockets.bind(&addr).unwrap();
+        assert_eq!(listener.local_addr().unwrap(), addr);
+        assert_eq!(sockets.bound().len(), 1);
+        assert_eq!(sockets.inherited.len(), 1);
+        sockets.close_unclaimed();
+        assert!(sockets.inherited.is_empty());
+    }
+}
@@ -0,0 +1,481 @@
+//! Building RPKI repositories for testing.
+//!
+//! The types in this module create a small repository under a single
+//! trust anchor and place it in the rsync working directory of a cache
+//! directory. Together with a configuration that uses a no-op rsync
+//! command, this allows running the validation engine over the repository
+//! without any network access.
+//!
+//! All objects are signed with a fixed set of RSA keys kept in `test/keys`
+//! as DER-encoded PKCS#1 private keys.
+
+use std::{fs, io};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use bytes::Bytes;
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use rpki::uri;
+use rpki::crypto::{
+    DigestAlgorithm, PublicKey, PublicKeyFormat, RpkiSignatureAlgorithm,
+    Signature, SignatureAlgorithm,
+};
+use rpki::crypto::signer::{KeyError, Signer, SigningError};
+use rpki::repository::cert::{KeyUsage, Overclaim, TbsCert};
+use rpki::repository::crl::{CrlEntry, TbsCertList};
+use rpki::repository::manifest::{FileAndHash, ManifestContent};
+use rpki::repository::resources::{
+    AsBlocks, AsResources, Asn, IpBlocks, IpResources,
+};
+use rpki::repository::roa::RoaBuilder;
+use rpki::repository::sigobj::SignedObjectBuilder;
+use rpki::repository::x509::{Serial, Time, Validity};
+use rpki::util::base64;
+use tempfile::TempDir;
+use crate::config::Config;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The key of the trust anchor.
+pub const TA_KEY: usize = 0;
+
+/// The key of the CA below the trust anchor.
+pub const CA_KEY: usize = 1;
+
+/// Another key, also used for all EE certificates.
+pub const EE_KEY: usize = 2;
+
+/// The rsync URI all objects are published under.
+const BASE_URI: &str = "rsync://example.test/repo/";
+
+
+//------------ TestSigner ----------------------------------------------------
+
+/// A signer using the fixed test keys.
+pub struct TestSigner {
+    /// The key pairs, indexed by key ID.
+    keys: Vec<RsaKeyPair>,
+
+    /// The random number generator.
+    rng: SystemRandom,
+}
+
+impl TestSigner {
+    /// Creates the signer.
+    pub fn new() -> Self {
+        TestSigner {
+            keys: [
+                &include_bytes!("../../test/keys/ta.der")[..],
+                &include_bytes!("../../test/keys/ca.der")[..],
+                &include_bytes!("../../test/keys/ee.der")[..],
+            ].into_iter().map(|der| {
+                RsaKeyPair::from_der(der).unwrap()
+            }).collect(),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Returns the public key of the given key.
+    pub fn public_key(&self, key: usize) -> PublicKey {
+        PublicKey::rsa_from_bits_bytes(
+            Bytes::copy_from_slice(self.keys[key].public().as_ref())
+        ).unwrap()
+    }
+
+    /// Signs the data with the given key pair.
+    fn sign_with(
+        &self, key: &RsaKeyPair, data: &[u8]
+    ) -> Result<Bytes, ring::error::Unspecified> {
+        let mut sig = vec![0; key.public().modulus_len()];
+        key.sign(&RSA_PKCS1_SHA256, &self.rng, data, &mut sig)?;
+        Ok(sig.into())
+    }
+}
+
+impl Default for TestSigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Signer for TestSigner {
+    type KeyId = usize;
+    type Error = ring::error::Unspecified;
+
+    fn create_key(
+        &self, _algorithm: PublicKeyFormat
+    ) -> Result<Self::KeyId, Self::Error> {
+        Err(ring::error::Unspecified)
+    }
+
+    fn get_key_info(
+        &self, key: &Self::KeyId
+    ) -> Result<PublicKey, KeyError<Self::Error>> {
+        if *key >= self.keys.len() {
+            return Err(KeyError::KeyNotFound)
+        }
+        Ok(self.public_key(*key))
+    }
+
+    fn destroy_key(
+        &self, _key: &Self::KeyId
+    ) -> Result<(), KeyError<Self::Error>> {
+        Ok(())
+    }
+
+    fn sign<Alg: SignatureAlgorithm, D: AsRef<[u8]> + ?Sized>(
+        &self, key: &Self::KeyId, algorithm: Alg, data: &D
+    ) -> Result<Signature<Alg>, SigningError<Self::Error>> {
+        let pair = self.keys.get(*key).ok_or(SigningError::KeyNotFound)?;
+        let sig = self.sign_with(pair, data.as_ref()).map_err(
+            SigningError::Signer
+        )?;
+        Ok(Signature::new(algorithm, sig))
+    }
+
+    fn sign_one_off<Alg: SignatureAlgorithm, D: AsRef<[u8]> + ?Sized>(
+        &self, algorithm: Alg, data: &D
+    ) -> Result<(Signature<Alg>, PublicKey), Self::Error> {
+        let sig = self.sign_with(&self.keys[EE_KEY], data.as_ref())?;
+        Ok((Signature::new(algorithm, sig), self.public_key(EE_KEY)))
+    }
+
+    fn rand(&self, target: &mut [u8]) -> Result<(), Self::Error> {
+        ring::rand::SecureRandom::fill(&self.rng, target)
+    }
+}
+
+
+//------------ TestCa --------------------------------------------------------
+
+/// A CA of the test repository.
+#[derive(Clone, Debug)]
+pub struct TestCa {
+    /// The key of the CA.
+    pub key: usize,
+
+    /// The name of the CA.
+    ///
+    /// This is used as the directory of the CA’s publication point.
+    pub name: String,
+
+    /// The URI of the CA’s certificate.
+    pub cert_uri: uri::Rsync,
+}
+
+impl TestCa {
+    /// Returns the URI of the CA’s publication point.
+    pub fn repository(&self) -> uri::Rsync {
+        self.uri("")
+    }
+
+    /// Returns the URI of the CA’s manifest.
+    pub fn manifest(&self) -> uri::Rsync {
+        self.uri("manifest.mft")
+    }
+
+    /// Returns the URI of the CA’s CRL.
+    pub fn crl(&self) -> uri::Rsync {
+        self.uri("revoked.crl")
+    }
+
+    /// Returns the URI of an object published by the CA.
+    pub fn uri(&self, file: &str) -> uri::Rsync {
+        uri::Rsync::from_string(
+            format!("{}{}/{}", BASE_URI, self.name, file)
+        ).unwrap()
+    }
+}
+
+
+//------------ TestRepo ------------------------------------------------------
+
+/// A repository for testing.
+pub struct TestRepo {
+    /// The directory holding the cache and the TAL.
+    dir: TempDir,
+
+    /// The signer.
+    signer: TestSigner,
+
+    /// The next serial number to use.
+    serial: AtomicU64,
+
+    /// The time all validity periods are based on.
+    now: Time,
+}
+
+impl TestRepo {
+    /// Creates a new, empty repository and writes the TAL.
+    pub fn new() -> Self {
+        Self::new_at(Time::now())
+    }
+
+    /// Creates a new repository with objects published at the given time.
+    ///
+    /// All certificates, manifests, and CRLs will be valid at `now` rather
+    /// than the current time.
+    pub fn new_at(now: Time) -> Self {
+        let res = TestRepo {
+            dir: tempfile::tempdir().unwrap(),
+            signer: TestSigner::new(),
+            serial: AtomicU64::new(1),
+            now,
+        };
+        fs::create_dir_all(res.tal_dir()).unwrap();
+        fs::write(
+            res.tal_dir().join("test.tal"),
+            format!(
+                "{}\n\n{}\n",
+                res.ta().cert_uri,
+                base64::Xml.encode(
+                    &res.signer.public_key(TA_KEY).to_info_bytes()
+                ),
+            )
+        ).unwrap();
+        res
+    }
+
+    /// Returns a configuration for validating the repository.
+    ///
+    /// The configuration only uses rsync with a command that does nothing
+    /// and only uses the test TAL.
+    pub fn config(&self) -> Config {
+        let mut config = Config::default_with_paths(
+            Default::default(), self.dir.path().join("cache")
+        );
+        config.no_rir_tals = true;
+        config.extra_tals_dir = Some(self.tal_dir());
+        config.disable_rrdp = true;
+        config.rsync_command = "true".into();
+        config.rsync_args = Some(Vec::new());
+        config
+    }
+
+    /// Returns the signer.
+    pub fn signer(&self) -> &TestSigner {
+        &self.signer
+    }
+
+    /// Returns a new serial number.
+    pub fn next_serial(&self) -> Serial {
+        self.serial.fetch_add(1, Ordering::Relaxed).into()
+    }
+
+    /// Returns the trust anchor CA.
+    pub fn ta(&self) -> TestCa {
+        TestCa {
+            key: TA_KEY,
+            name: "ta".into(),
+            cert_uri: uri::Rsync::from_string(
+                format!("{}ta.cer", BASE_URI)
+            ).unwrap(),
+        }
+    }
+
+    /// Returns a CA issued by `issuer`.
+    ///
+    /// The certificate of the CA is published in the issuer’s publication
+    /// point as `<name>.cer`.
+    pub fn ca(&self, issuer: &TestCa, name: &str, key: usize) -> TestCa {
+        TestCa {
+            key,
+            name: name.into(),
+            cert_uri: issuer.uri(&format!("{}.cer", name)),
+        }
+    }
+
+    /// Creates and writes the trust anchor certificate.
+    ///
+    /// The resources are given as strings of comma-separated AS and IPv4
+    /// blocks, respectively.
+    pub fn ta_cert(&self, asns: &str, v4: &str) -> Bytes {
+        let ta = self.ta();
+        let public = self.signer.public_key(ta.key);
+        let mut cert = TbsCert::new(
+            self.next_serial(), public.to_subject_name(),
+            self.validity(), None, public,
+            KeyUsage::Ca, Overclaim::Refuse,
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_ca_repository(Some(ta.repository()));
+        cert.set_rpki_manifest(Some(ta.manifest()));
+        Self::set_resources(&mut cert, asns, v4);
+        let cert = cert.into_cert(&self.signer, &ta.key).unwrap();
+        let bytes = cert.to_captured().into_bytes();
+        self.write(&ta.cert_uri, &bytes);
+        bytes
+    }
+
+    /// Creates and writes the certificate of a CA.
+    ///
+    /// The certificate is not added to the issuer’s manifest. This has to
+    /// happen via [`publish`][Self::publish] with the returned data.
+    pub fn ca_cert(
+        &self, issuer: &TestCa, ca: &TestCa, asns: &str, v4: &str,
+    ) -> Bytes {
+        let public = self.signer.public_key(ca.key);
+        let mut cert = TbsCert::new(
+            self.next_serial(),
+            self.signer.public_key(issuer.key).to_subject_name(),
+            self.validity(), None, public,
+            KeyUsage::Ca, Overclaim::Refuse,
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_authority_key_identifier(Some(
+            self.signer.public_key(issuer.key).key_identifier()
+        ));
+        cert.set_crl_uri(Some(issuer.crl()));
+        cert.set_ca_issuer(Some(issuer.cert_uri.clone()));
+        cert.set_ca_repository(Some(ca.repository()));
+        cert.set_rpki_manifest(Some(ca.manifest()));
+        Self::set_resources(&mut cert, asns, v4);
+        let cert = cert.into_cert(&self.signer, &issuer.key).unwrap();
+        let bytes = cert.to_captured().into_bytes();
+        self.write(&ca.cert_uri, &bytes);
+        bytes
+    }
+
+    /// Creates and writes a ROA for a single IPv4 prefix.
+    pub fn roa(
+        &self, ca: &TestCa, name: &str, asn: u32, prefix: &str,
+    ) -> Bytes {
+        self.roa_with_validity(ca, name, asn, prefix, self.validity())
+    }
+
+    /// Creates and writes a ROA with the given EE certificate validity.
+    pub fn roa_with_validity(
+        &self, ca: &TestCa, name: &str, asn: u32, prefix: &str,
+        validity: Validity,
+    ) -> Bytes {
+        let (addr, len) = prefix.split_once('/').unwrap();
+        let mut roa = RoaBuilder::new(Asn::from_u32(asn));
+        roa.push_v4_addr(addr.parse().unwrap(), len.parse().unwrap(), None);
+        let mut sigobj = self.signed_object(ca, &ca.uri(name));
+        sigobj.set_validity(validity);
+        let roa = roa.finalize(sigobj, &self.signer, &ca.key).unwrap();
+        let bytes = roa.to_captured().into_bytes();
+        self.write(&ca.uri(name), &bytes);
+        bytes
+    }
+
+    /// Returns a builder for a signed object issued by a CA.
+    pub fn signed_object(
+        &self, ca: &TestCa, uri: &uri::Rsync
+    ) -> SignedObjectBuilder {
+        SignedObjectBuilder::new(
+            self.next_serial(), self.validity(),
+            ca.crl(), ca.cert_uri.clone(), uri.clone()
+        )
+    }
+
+    /// Writes the manifest and CRL of a CA’s publication point.
+    ///
+    /// The manifest lists the given objects and the CRL. Their content
+    /// needs to have been written already. The CRL lists the given
+    /// revoked serial numbers. The manifest’s thisUpdate is moved forward
+    /// by `number` seconds so that republishing with a greater number
+    /// right away results in a later time.
+    pub fn publish(
+        &self, ca: &TestCa, number: u64, objects: &[(&str, &Bytes)],
+        revoked: &[Serial],
+    ) {
+        let crl = TbsCertList::new(
+            RpkiSignatureAlgorithm::default(),
+            self.signer.public_key(ca.key).to_subject_name(),
+            self.this_update(), self.next_update(),
+            revoked.iter().map(|serial| {
+                CrlEntry::new(*serial, self.now)
+            }).collect::<Vec<_>>(),
+            self.signer.public_key(ca.key).key_identifier(),
+            number.into(),
+        ).into_crl(&self.signer, &ca.key).unwrap();
+        let crl = crl.to_captured().into_bytes();
+        self.write(&ca.crl(), &crl);
+
+        let crl_name = "revoked.crl";
+        let files = objects.iter().copied().chain(
+            Some((crl_name, &crl))
+        ).map(|(name, content)| {
+            FileAndHash::new(
+                Bytes::copy_from_slice(name.as_bytes()),
+                Bytes::copy_from_slice(
+                    DigestAlgorithm::sha256().digest(content).as_ref()
+                ),
+            )
+        }).collect::<Vec<_>>();
+        let manifest = ManifestContent::new(
+            number.into(),
+            self.this_update() + chrono::Duration::seconds(
+                i64::try_from(number).unwrap()
+            ),
+            self.next_update(),
+            DigestAlgorithm::sha256(), files.iter(),
+        ).into_manifest(
+            self.signed_object(ca, &ca.manifest()), &self.signer, &ca.key
+        ).unwrap();
+        self.write(&ca.manifest(), &manifest.to_captured().into_bytes());
+    }
+
+    /// Writes an object to the rsync working directory.
+    pub fn write(&self, uri: &uri::Rsync, content: &[u8]) {
+        let path = self.path(uri);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// Removes an object from the rsync working directory.
+    pub fn remove(&self, uri: &uri::Rsync) -> Result<(), io::Error> {
+        fs::remove_file(self.path(uri))
+    }
+
+    /// Returns the path of an object in the rsync working directory.
+    fn path(&self, uri: &uri::Rsync) -> PathBuf {
+        let mut res = self.dir.path().join("cache/rsync");
+        res.push(uri.canonical_authority().as_ref());
+        res.push(uri.module_name());
+        res.push(uri.path());
+        res
+    }
+
+    /// Returns the directory holding the TAL.
+    fn tal_dir(&self) -> PathBuf {
+        self.dir.path().join("tals")
+    }
+
+    /// Returns the validity used for all certificates.
+    fn validity(&self) -> Validity {
+        Validity::new(
+            self.this_update(), self.now + chrono::TimeDelta::days(365)
+        )
+    }
+
+    /// Returns the this update time used for manifests and CRLs.
+    fn this_update(&self) -> Time {
+        self.now - chrono::TimeDelta::minutes(5)
+    }
+
+    /// Returns the next update time used for manifests and CRLs.
+    fn next_update(&self) -> Time {
+        self.now + chrono::TimeDelta::days(7)
+    }
+
+    /// Sets the resources of a certificate from strings.
+    fn set_resources(cert: &mut TbsCert, asns: &str, v4: &str) {
+        cert.set_as_resources(AsResources::blocks(
+            AsBlocks::from_str(asns).unwrap()
+        ));
+        cert.set_v4_resources(IpResources::blocks(
+            IpBlocks::from_str(v4).unwrap()
+        ));
+    }
+}
+
+impl Default for TestRepo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+